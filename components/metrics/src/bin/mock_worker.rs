@@ -90,6 +90,7 @@ async fn mock_event_publisher(namespace: Namespace) {
 
         let event = KVHitRateEvent {
             worker_id,
+            dp_rank: 0,
             isl_blocks,
             overlap_blocks,
         };
@@ -123,6 +124,7 @@ fn mock_stats_handler(_stats: EndpointStats) -> serde_json::Value {
         num_requests_waiting,
         gpu_cache_usage_perc,
         gpu_prefix_cache_hit_rate,
+        loaded_lora_ids: vec![],
     };
     tracing::info!("Stats: {stats:?}");
     serde_json::to_value(stats).unwrap()
@@ -0,0 +1,126 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Replays a JSONL trace of requests against simulated workers, routing them with the KV router,
+//! and reports TTFT / ITL, KV hit rates and load balance.
+//!
+//! Each line of the trace is a request, e.g.
+//! `{"timestamp": 0, "hash_ids": [0, 1, 2], "input_length": 150, "output_length": 20}`
+//! or `{"timestamp": 5, "token_ids": [1, 2, 3], "output_length": 20}`.
+//!
+//! To evaluate a custom [`WorkerSelector`](dynamo_llm::kv_router::WorkerSelector), pass it to
+//! [`KvRoutingSimulator::new`].
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+
+use dynamo_llm::kv_router::simulator::{load_trace, KvRoutingSimulator, SimulatorConfig};
+use dynamo_llm::kv_router::KvRouterConfig;
+use dynamo_llm::mocker::protocols::MockEngineArgs;
+use dynamo_runtime::{logging, Result};
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// JSONL trace of the requests to replay
+    trace: PathBuf,
+
+    /// Number of simulated workers
+    #[arg(long, default_value_t = 4)]
+    num_workers: usize,
+
+    /// Number of tokens in a KV cache block, for the workers and the router
+    #[arg(long, default_value_t = 64)]
+    block_size: usize,
+
+    /// Number of tokens each hash id of the trace stands for. Default: the block size
+    #[arg(long)]
+    trace_block_size: Option<usize>,
+
+    /// Number of KV cache blocks of each worker
+    #[arg(long, default_value_t = 16384)]
+    num_gpu_blocks: usize,
+
+    /// Maximum number of new tokens prefilled in a single forward pass
+    #[arg(long, default_value_t = 8192)]
+    max_num_batched_tokens: usize,
+
+    /// Divides the simulated prefill time
+    #[arg(long, default_value_t = 1.0)]
+    prefill_speedup_ratio: f64,
+
+    /// Divides the simulated decode time
+    #[arg(long, default_value_t = 1.0)]
+    decode_speedup_ratio: f64,
+
+    /// Weight of the KV cache overlap in worker selection
+    #[arg(long)]
+    overlap_score_weight: Option<f64>,
+
+    /// Weight of the GPU cache usage in worker selection
+    #[arg(long)]
+    gpu_cache_usage_weight: Option<f64>,
+
+    /// Weight of the waiting requests in worker selection
+    #[arg(long)]
+    waiting_requests_weight: Option<f64>,
+
+    /// How often the workers publish their metrics, in milliseconds of simulated time
+    #[arg(long, default_value_t = 100)]
+    metrics_interval_ms: u64,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+// The simulation runs on a virtual clock, a single thread keeps it deterministic
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    logging::init();
+    let args = Args::parse();
+
+    let engine_args = MockEngineArgs::builder()
+        .num_gpu_blocks(args.num_gpu_blocks)
+        .block_size(args.block_size)
+        .max_num_batched_tokens(args.max_num_batched_tokens)
+        .prefill_speedup_ratio(args.prefill_speedup_ratio)
+        .decode_speedup_ratio(args.decode_speedup_ratio)
+        .build()?;
+    let kv_router_config = KvRouterConfig::new(
+        args.overlap_score_weight,
+        args.gpu_cache_usage_weight,
+        args.waiting_requests_weight,
+    );
+    let config = SimulatorConfig::builder()
+        .num_workers(args.num_workers)
+        .engine_args(engine_args)
+        .kv_router_config(kv_router_config)
+        .metrics_interval(Duration::from_millis(args.metrics_interval_ms))
+        .trace_block_size(args.trace_block_size)
+        .build()?;
+
+    let trace = load_trace(&args.trace)?;
+    let report = KvRoutingSimulator::new(config, None).run(trace).await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{report}");
+    }
+    Ok(())
+}
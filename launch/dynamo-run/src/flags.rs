@@ -15,9 +15,13 @@
 
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use clap::ValueEnum;
-use dynamo_llm::kv_router::KvRouterConfig;
+use dynamo_llm::kv_router::{event_stream::KvEventStreamConfig, KvRouterConfig};
+use dynamo_runtime::component::health::HealthConfig;
+use dynamo_runtime::pipeline::network::egress::queue::QueueConfig;
+use dynamo_runtime::pipeline::network::egress::retry::RetryConfig;
 use dynamo_runtime::pipeline::RouterMode as RuntimeRouterMode;
 
 /// Required options depend on the in and out choices
@@ -42,6 +46,37 @@ pub struct Flags {
     #[arg(long, default_value = "8080")]
     pub http_port: u16,
 
+    /// JSON file of static API keys callers must present as a bearer token. `in=http` only
+    /// Example file contents:
+    /// {"keys": [{"id": "team-a", "key": "<secret>", "models": ["Qwen2.5-3B-Instruct"]}]}
+    /// `models` is optional and restricts the key to those models.
+    #[arg(long)]
+    pub http_api_keys: Option<PathBuf>,
+
+    /// JWKS file with the keys of accepted JWT bearer tokens. `in=http` only
+    /// The `sub` claim identifies the caller, the optional `models` claim restricts the models.
+    #[arg(long)]
+    pub http_jwks: Option<PathBuf>,
+
+    /// File holding the HMAC secret of accepted JWT bearer tokens (HS256/HS384/HS512).
+    /// `in=http` only. Claims are read as for `--http-jwks`.
+    #[arg(long)]
+    pub http_jwt_secret_file: Option<PathBuf>,
+
+    /// JSON file of per-tenant rate limits. `in=http` only
+    /// Example file contents:
+    /// {"tenant_header": "x-tenant-id", "default": {"requests_per_minute": 60},
+    ///  "tenants": {"team-a": {"prompt_tokens_per_minute": 100000, "output_tokens_per_minute": 20000}}}
+    /// Tenants are identified by API key id when authentication is enabled.
+    /// Without this flag, dynamic engines follow the limits stored in etcd.
+    #[arg(long)]
+    pub http_rate_limits: Option<PathBuf>,
+
+    /// Send an SSE keep-alive comment on idle streaming responses every this many seconds,
+    /// so that proxies don't drop the connection during long prefills. `in=http` only
+    #[arg(long)]
+    pub http_sse_keep_alive: Option<u64>,
+
     /// The name of the model we are serving
     #[arg(long)]
     pub model_name: Option<String>,
@@ -121,6 +156,86 @@ pub struct Flags {
     #[arg(long)]
     pub kv_waiting_requests_weight: Option<f64>,
 
+    /// KV Router: Weight for a LoRA request's adapter not being loaded on a worker.
+    /// Higher values prefer workers that already have the adapter. Default: 1.0
+    #[arg(long)]
+    pub kv_lora_load_weight: Option<f64>,
+
+    /// KV Router: Directory in which to persist the router's view of the worker KV caches,
+    /// so that a restarted router routes as well as before. Disabled unless set.
+    #[arg(long)]
+    pub kv_router_snapshot_dir: Option<PathBuf>,
+
+    /// KV Router: Directory in which to record the routing decisions (overlap, load and logit of
+    /// each worker, and the chosen one), one JSONL file per model. Disabled unless set.
+    #[arg(long)]
+    pub kv_router_decision_log_dir: Option<PathBuf>,
+
+    /// KV Router: Fraction of the requests whose routing decision is recorded. Default: 1.0
+    #[arg(long)]
+    pub kv_router_decision_log_sample_rate: Option<f64>,
+
+    /// KV Router: Read the KV events from the namespace's durable JetStream stream with this
+    /// consumer name, so that a restarted router replays the events it missed. Each router
+    /// replica needs its own name. Core NATS unless set.
+    #[arg(long)]
+    pub kv_event_stream_consumer: Option<String>,
+
+    /// Admission queue: maximum number of requests in flight to the workers of a model.
+    /// Further requests wait for admission by priority (`x-request-priority` header or
+    /// `nvext.priority`). Disabled unless set. `in=http` only
+    #[arg(long)]
+    pub router_max_inflight: Option<usize>,
+
+    /// Admission queue: maximum number of waiting requests per model, further requests get a 503.
+    #[arg(long, default_value = "1024")]
+    pub router_queue_depth: usize,
+
+    /// Admission queue: seconds a request may wait for admission before it gets a 503.
+    #[arg(long, default_value = "30")]
+    pub router_queue_timeout: u64,
+
+    /// How many times a request which failed before its first token, e.g. because its worker
+    /// went away, is retried on another worker. 0 disables retries. Not used by KV routing.
+    /// `in=http` only
+    #[arg(long, default_value = "3")]
+    pub router_max_retries: u32,
+
+    /// Milliseconds to wait before the first retry of a request, doubled for each further retry
+    /// up to one second.
+    #[arg(long, default_value = "50")]
+    pub router_retry_backoff_ms: u64,
+
+    /// How many times a request whose worker went away mid-stream is moved to another worker,
+    /// which continues its response. 0 disables migration. `in=http` only
+    #[arg(long, default_value = "0")]
+    pub migration_limit: u32,
+
+    /// Track the health of each worker and stop routing to the workers which fail or are too
+    /// slow, until a probe request succeeds. `in=http` only
+    #[arg(long)]
+    pub router_circuit_breaker: bool,
+
+    /// Circuit breaker: share of failed requests over 30 seconds above which a worker is taken
+    /// out of routing, once it got at least 10 requests.
+    #[arg(long, default_value = "0.5")]
+    pub router_circuit_error_rate: f64,
+
+    /// Circuit breaker: average milliseconds to the first token above which a worker is taken
+    /// out of routing. Unbounded unless set.
+    #[arg(long)]
+    pub router_circuit_max_latency_ms: Option<u64>,
+
+    /// Circuit breaker: seconds a worker stays out of routing before a probe request is sent.
+    #[arg(long, default_value = "10")]
+    pub router_circuit_open_secs: u64,
+
+    /// Circuit breaker: seconds between checks that each worker answers its service stats
+    /// requests; a worker missing 3 checks in a row stops getting requests. 0 disables the
+    /// checks.
+    #[arg(long, default_value = "5")]
+    pub router_health_check_interval: u64,
+
     /// Max model context length. Reduce this if you don't have enough VRAM for the full model
     /// context length (e.g. Llama 4).
     /// Defaults to the model's max, which is usually model_max_length in tokenizer_config.json.
@@ -131,6 +246,12 @@ pub struct Flags {
     #[arg(long)]
     pub kv_cache_block_size: Option<usize>,
 
+    /// How to recognize tool calls in the model output, for chat requests that include `tools`.
+    /// One of: json, hermes, llama3_json, mistral, qwen3_coder.
+    /// Defaults to bare JSON objects.
+    #[arg(long)]
+    pub tool_call_parser: Option<String>,
+
     /// Additional engine-specific arguments from a JSON file.
     /// Contains a mapping of parameter names to values.
     #[arg(long)]
@@ -161,6 +282,58 @@ impl Flags {
             self.kv_gpu_cache_usage_weight,
             self.kv_waiting_requests_weight,
         )
+        .with_lora_load_weight(self.kv_lora_load_weight)
+        .with_snapshot_dir(self.kv_router_snapshot_dir.clone())
+        .with_decision_log(
+            self.kv_router_decision_log_dir.clone(),
+            self.kv_router_decision_log_sample_rate,
+        )
+        .with_kv_event_stream(
+            self.kv_event_stream_consumer
+                .as_ref()
+                .map(KvEventStreamConfig::with_consumer_name),
+        )
+    }
+
+    /// Get the admission queue configuration, if enabled
+    pub fn queue_config(&self) -> Option<QueueConfig> {
+        self.router_max_inflight.map(|max_inflight| QueueConfig {
+            max_inflight,
+            max_depth: self.router_queue_depth,
+            max_wait: Duration::from_secs(self.router_queue_timeout),
+        })
+    }
+
+    /// Get the retry configuration, if enabled
+    pub fn retry_config(&self) -> Option<RetryConfig> {
+        if self.router_max_retries == 0 {
+            return None;
+        }
+        Some(RetryConfig {
+            max_retries: self.router_max_retries,
+            initial_backoff: Duration::from_millis(self.router_retry_backoff_ms),
+            ..Default::default()
+        })
+    }
+
+    /// Get the health tracking configuration, if enabled
+    pub fn health_config(&self) -> Option<HealthConfig> {
+        if !self.router_circuit_breaker {
+            return None;
+        }
+        let check_interval = match self.router_health_check_interval {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        Some(HealthConfig {
+            max_error_rate: self.router_circuit_error_rate,
+            max_latency: self
+                .router_circuit_max_latency_ms
+                .map(Duration::from_millis),
+            open_duration: Duration::from_secs(self.router_circuit_open_secs),
+            check_interval,
+            ..Default::default()
+        })
     }
 
     /// Convert the flags back to a command line. Including only the non-null values, but
@@ -237,6 +410,9 @@ pub enum RouterMode {
     #[value(name = "round-robin")]
     RoundRobin,
     Random,
+    /// The instance with the fewest requests in flight
+    #[value(name = "least-loaded")]
+    LeastLoaded,
     #[value(name = "kv")]
     KV,
 }
@@ -246,6 +422,7 @@ impl From<RouterMode> for RuntimeRouterMode {
         match r {
             RouterMode::RoundRobin => RuntimeRouterMode::RoundRobin,
             RouterMode::Random => RuntimeRouterMode::Random,
+            RouterMode::LeastLoaded => RuntimeRouterMode::LeastLoaded,
             RouterMode::KV => RuntimeRouterMode::KV,
         }
     }
@@ -141,6 +141,8 @@ mod tests {
         chat_completions::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse},
         completions::{NvCreateCompletionRequest, NvCreateCompletionResponse},
     };
+    use dynamo_runtime::engine::AsyncEngine;
+    use futures::StreamExt;
 
     const HF_PATH: &str = concat!(
         env!("CARGO_MANIFEST_DIR"),
@@ -166,6 +168,47 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_core_engine_logprobs() -> anyhow::Result<()> {
+        let card = ModelDeploymentCard::load(HF_PATH).await?;
+        let engine = dynamo_llm::engines::make_engine_core();
+        let pipeline =
+            build_pipeline::<NvCreateCompletionRequest, NvCreateCompletionResponse>(&card, engine)
+                .await?;
+
+        let request: NvCreateCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": card.display_name,
+            "prompt": "hello world",
+            "max_tokens": 3,
+            "logprobs": 1,
+        }))?;
+        let responses: Vec<_> = pipeline
+            .generate(Context::new(request))
+            .await?
+            .collect()
+            .await;
+
+        // the logprobs of the echo engine flow through the backend and the preprocessor
+        let logprobs: Vec<_> = responses
+            .iter()
+            .filter_map(|response| response.data.as_ref())
+            .flat_map(|response| &response.inner.choices)
+            .filter_map(|choice| choice.logprobs.as_ref())
+            .collect();
+        assert!(!logprobs.is_empty());
+        for logprobs in logprobs {
+            assert_eq!(
+                logprobs.token_logprobs,
+                vec![Some(0.0); logprobs.tokens.len()]
+            );
+            for top_logprobs in &logprobs.top_logprobs {
+                assert_eq!(top_logprobs.as_object().unwrap().len(), 1);
+            }
+        }
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_build_completions_pipeline_core_engine_succeeds() -> anyhow::Result<()> {
         // Create test model card
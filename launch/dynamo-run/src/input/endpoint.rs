@@ -31,26 +31,21 @@ use dynamo_runtime::engine::AsyncEngineStream;
 use dynamo_runtime::pipeline::{
     network::Ingress, Context, ManyOut, Operator, SegmentSource, ServiceBackend, SingleIn, Source,
 };
-use dynamo_runtime::{protocols::Endpoint as EndpointId, DistributedRuntime};
+use dynamo_runtime::{component::Component, protocols::Endpoint as EndpointId, DistributedRuntime};
 
 use crate::EngineConfig;
 
+/// Serve the engine on `path`. The `component` of that path must have its service created.
 pub async fn run(
     distributed_runtime: DistributedRuntime,
+    component: Component,
     path: String,
     engine_config: EngineConfig,
 ) -> anyhow::Result<()> {
     let cancel_token = distributed_runtime.primary_token().clone();
     let endpoint_id: EndpointId = path.parse()?;
 
-    let component = distributed_runtime
-        .namespace(&endpoint_id.namespace)?
-        .component(&endpoint_id.component)?;
-    let endpoint = component
-        .service_builder()
-        .create()
-        .await?
-        .endpoint(&endpoint_id.name);
+    let endpoint = component.endpoint(&endpoint_id.name);
 
     let (rt_fut, card): (Pin<Box<dyn Future<Output = _> + Send + 'static>>, _) = match engine_config
     {
@@ -108,11 +103,8 @@ pub async fn run(
     }
 
     // Cleanup on shutdown
-    if let Some(mut card) = card {
-        if let Err(err) = card
-            .delete_from_nats(distributed_runtime.nats_client())
-            .await
-        {
+    if let (Some(mut card), Some(nats_client)) = (card, distributed_runtime.try_nats_client()) {
+        if let Err(err) = card.delete_from_nats(nats_client).await {
             tracing::error!(%err, "delete_from_nats error on shutdown");
         }
     }
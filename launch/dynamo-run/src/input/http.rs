@@ -1,7 +1,7 @@
 // SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use crate::input::common;
 use crate::{EngineConfig, Flags};
@@ -9,7 +9,11 @@ use dynamo_llm::kv_router::KvRouterConfig;
 use dynamo_llm::{
     discovery::{ModelManager, ModelWatcher, MODEL_ROOT_PATH},
     engines::StreamingEngineAdapter,
-    http::service::service_v2,
+    http::service::{
+        auth::{AuthConfig, JwtAuthenticator, StaticKeyAuthenticator},
+        rate_limit::{RateLimitConf, RateLimiter},
+        service_v2,
+    },
     request_template::RequestTemplate,
     types::{
         openai::chat_completions::{
@@ -18,6 +22,9 @@ use dynamo_llm::{
         openai::completions::{NvCreateCompletionRequest, NvCreateCompletionResponse},
     },
 };
+use dynamo_runtime::component::health::{HealthConfig, HealthMetrics};
+use dynamo_runtime::pipeline::network::egress::queue::{QueueConfig, QueueMetrics};
+use dynamo_runtime::pipeline::network::egress::retry::{RetryConfig, RetryMetrics};
 use dynamo_runtime::pipeline::RouterMode;
 use dynamo_runtime::transports::etcd;
 use dynamo_runtime::{DistributedRuntime, Runtime};
@@ -29,18 +36,72 @@ pub async fn run(
     engine_config: EngineConfig,
     template: Option<RequestTemplate>,
 ) -> anyhow::Result<()> {
+    let mut auth = AuthConfig::new();
+    if let Some(path) = flags.http_api_keys.as_ref() {
+        auth = auth.with_authenticator(StaticKeyAuthenticator::load(path)?);
+    }
+    if let Some(path) = flags.http_jwks.as_ref() {
+        auth = auth.with_authenticator(JwtAuthenticator::load_jwks(path)?);
+    }
+    if let Some(path) = flags.http_jwt_secret_file.as_ref() {
+        auth = auth.with_authenticator(JwtAuthenticator::load_hmac(path)?);
+    }
+
+    let distributed_runtime = match engine_config {
+        EngineConfig::Dynamic => Some(DistributedRuntime::from_settings(runtime.clone()).await?),
+        _ => None,
+    };
+
+    // Static rate limits take precedence, otherwise follow the limits stored in etcd
+    let rate_limiter = match (flags.http_rate_limits.as_ref(), &distributed_runtime) {
+        (Some(path), _) => Some(RateLimiter::new(RateLimitConf::load(path)?)),
+        (None, Some(drt)) if drt.etcd_client().is_some() => {
+            let (_, conf) = RateLimitConf::from_etcd_with_watcher(Arc::new(drt.clone())).await?;
+            Some(RateLimiter::from_watcher(conf))
+        }
+        _ => None,
+    };
+
     let http_service = service_v2::HttpService::builder()
         .port(flags.http_port)
         .enable_chat_endpoints(true)
         .enable_cmpl_endpoints(true)
         .enable_embeddings_endpoints(true)
         .with_request_template(template)
+        .auth(Some(auth))
+        .rate_limiter(rate_limiter.map(Arc::new))
+        .sse_keep_alive(flags.http_sse_keep_alive.map(Duration::from_secs))
         .build()?;
     match engine_config {
         EngineConfig::Dynamic => {
-            let distributed_runtime = DistributedRuntime::from_settings(runtime.clone()).await?;
+            let distributed_runtime =
+                distributed_runtime.expect("distributed runtime is created for dynamic engines");
             match distributed_runtime.etcd_client() {
                 Some(etcd_client) => {
+                    let admission = match flags.queue_config() {
+                        Some(config) => {
+                            let metrics = Arc::new(QueueMetrics::new("nv_llm"));
+                            metrics.register(http_service.metrics_registry())?;
+                            Some((config, metrics))
+                        }
+                        None => None,
+                    };
+                    let retry = match flags.retry_config() {
+                        Some(config) => {
+                            let metrics = Arc::new(RetryMetrics::new("nv_llm"));
+                            metrics.register(http_service.metrics_registry())?;
+                            Some((config, metrics))
+                        }
+                        None => None,
+                    };
+                    let health = match flags.health_config() {
+                        Some(config) => {
+                            let metrics = Arc::new(HealthMetrics::new("nv_llm"));
+                            metrics.register(http_service.metrics_registry())?;
+                            Some((config, metrics))
+                        }
+                        None => None,
+                    };
                     // Listen for models registering themselves in etcd, add them to HTTP service
                     run_watcher(
                         distributed_runtime,
@@ -49,6 +110,10 @@ pub async fn run(
                         MODEL_ROOT_PATH,
                         flags.router_mode.into(),
                         Some(flags.kv_router_config()),
+                        admission,
+                        retry,
+                        flags.migration_limit,
+                        health,
                     )
                     .await?;
                 }
@@ -99,6 +164,7 @@ pub async fn run(
 
 /// Spawns a task that watches for new models in etcd at network_prefix,
 /// and registers them with the ModelManager so that the HTTP service can use them.
+#[allow(clippy::too_many_arguments)]
 async fn run_watcher(
     runtime: DistributedRuntime,
     model_manager: Arc<ModelManager>,
@@ -106,8 +172,22 @@ async fn run_watcher(
     network_prefix: &str,
     router_mode: RouterMode,
     kv_router_config: Option<KvRouterConfig>,
+    admission: Option<(QueueConfig, Arc<QueueMetrics>)>,
+    retry: Option<(RetryConfig, Arc<RetryMetrics>)>,
+    migration_limit: u32,
+    health: Option<(HealthConfig, Arc<HealthMetrics>)>,
 ) -> anyhow::Result<()> {
-    let watch_obj = ModelWatcher::new(runtime, model_manager, router_mode, kv_router_config);
+    let mut watch_obj = ModelWatcher::new(runtime, model_manager, router_mode, kv_router_config)
+        .with_migration_limit(migration_limit);
+    if let Some((config, metrics)) = admission {
+        watch_obj = watch_obj.with_admission_queue(config, metrics);
+    }
+    if let Some((config, metrics)) = retry {
+        watch_obj = watch_obj.with_retry_policy(config, metrics);
+    }
+    if let Some((config, metrics)) = health {
+        watch_obj = watch_obj.with_health_tracking(config, metrics);
+    }
     tracing::info!("Watching for remote model at {network_prefix}");
     let models_watcher = etcd_client.kv_get_and_watch_prefix(network_prefix).await?;
     let (_prefix, _watcher, receiver) = models_watcher.dissolve();
@@ -5,6 +5,7 @@ use std::{future::Future, pin::Pin};
 use std::{io::Read, sync::Arc, time::Duration};
 
 use anyhow::Context;
+use dynamo_llm::mocker::{engine::make_mocker_engine, protocols::MockEngineArgs};
 use dynamo_llm::{backend::ExecutionContext, engines::StreamingEngine, local_model::LocalModel};
 use dynamo_runtime::protocols::Endpoint as EndpointId;
 use dynamo_runtime::slug::Slug;
@@ -93,6 +94,9 @@ pub async fn run(
     if let Some(context_length) = flags.context_length {
         local_model.set_context_length(context_length);
     }
+    if let Some(tool_call_parser) = flags.tool_call_parser.clone() {
+        local_model.set_tool_call_parser(tool_call_parser);
+    }
     // Always set, there is no engine provided default
     local_model.set_kv_cache_block_size(
         flags
@@ -113,6 +117,23 @@ pub async fn run(
     // We may need it later
     let card = local_model.card().clone();
 
+    // `in=dyn` serves the engine on a component. Create it before the engine, because the
+    // mocker publishes its KV events and load metrics on that component.
+    let mut endpoint_component = match &in_opt {
+        Input::Endpoint(path) => {
+            let distributed_runtime = DistributedRuntime::from_settings(runtime.clone()).await?;
+            let endpoint_id: EndpointId = path.parse()?;
+            let component = distributed_runtime
+                .namespace(&endpoint_id.namespace)?
+                .component(&endpoint_id.component)?
+                .service_builder()
+                .create()
+                .await?;
+            Some((distributed_runtime, component))
+        }
+        _ => None,
+    };
+
     let out_opt = out_opt.unwrap_or_else(|| {
         let default_engine = if card.is_gguf() {
             gguf_default()
@@ -137,6 +158,9 @@ pub async fn run(
             if flags.kv_cache_block_size.is_some() {
                 anyhow::bail!("'--kv-cache-block-size' flag should only be used on the worker node, not on the ingress");
             }
+            if flags.tool_call_parser.is_some() {
+                anyhow::bail!("'--tool-call-parser' flag should only be used on the worker node, not on the ingress");
+            }
             EngineConfig::Dynamic
         }
         Output::EchoFull => EngineConfig::StaticFull {
@@ -155,6 +179,29 @@ pub async fn run(
                 model: Box::new(local_model),
             }
         }
+        Output::Mocker => {
+            if !local_model.card().has_tokenizer() {
+                anyhow::bail!(
+                    "out=mocker need to find the tokenizer. Pass flag --model-path <path>"
+                );
+            };
+            let mut args: MockEngineArgs = match flags.load_extra_engine_args()? {
+                Some(extra) => {
+                    serde_json::from_value(serde_json::Value::Object(extra.into_iter().collect()))
+                        .context("Invalid mocker arguments in --extra-engine-args")?
+                }
+                None => MockEngineArgs::default(),
+            };
+            // The KV router hashes blocks of the model card's block size
+            args.block_size = local_model.card().kv_cache_block_size;
+            let component = endpoint_component
+                .as_ref()
+                .map(|(_, component)| component.clone());
+            EngineConfig::StaticCore {
+                engine: make_mocker_engine(args, component, cancel_token.clone()).await?,
+                model: Box::new(local_model),
+            }
+        }
         #[cfg(feature = "mistralrs")]
         Output::MistralRs => EngineConfig::StaticFull {
             engine: dynamo_engine_mistralrs::make_engine(&local_model).await?,
@@ -280,6 +327,8 @@ pub async fn run(
             }
             let engine =
                 dynamo_engine_llamacpp::make_engine(cancel_token.clone(), &local_model).await?;
+            // llama.cpp samples with the GBNF grammar of the request
+            local_model.set_enforces_guided_grammar(true);
             EngineConfig::StaticCore {
                 engine,
                 model: Box::new(local_model),
@@ -311,8 +360,11 @@ pub async fn run(
                 .await?;
         }
         Input::Endpoint(path) => {
-            let distributed_runtime = DistributedRuntime::from_settings(runtime.clone()).await?;
-            crate::input::endpoint::run(distributed_runtime, path, engine_config).await?;
+            let (distributed_runtime, component) = endpoint_component
+                .take()
+                .context("in=dyn component was not created")?;
+            crate::input::endpoint::run(distributed_runtime, component, path, engine_config)
+                .await?;
         }
     }
 
@@ -87,6 +87,10 @@ pub enum Output {
     /// Accept preprocessed requests, echo the tokens back as the response
     EchoCore,
 
+    /// Accept preprocessed requests, simulate a vllm-like engine's scheduling and KV cache.
+    /// Configure it with `--extra-engine-args`, see `MockEngineArgs`.
+    Mocker,
+
     /// Listen for models on nats/etcd, add/remove dynamically
     Dynamic,
 
@@ -126,6 +130,7 @@ impl TryFrom<&str> for Output {
 
             "echo_full" => Ok(Output::EchoFull),
             "echo_core" => Ok(Output::EchoCore),
+            "mocker" => Ok(Output::Mocker),
 
             "dyn" => Ok(Output::Dynamic),
 
@@ -158,6 +163,7 @@ impl fmt::Display for Output {
 
             Output::EchoFull => "echo_full",
             Output::EchoCore => "echo_core",
+            Output::Mocker => "mocker",
 
             Output::Dynamic => "dyn",
         };
@@ -168,7 +174,11 @@ impl fmt::Display for Output {
 impl Output {
     #[allow(unused_mut)]
     pub fn available_engines() -> Vec<String> {
-        let mut out = vec!["echo_core".to_string(), "echo_full".to_string()];
+        let mut out = vec![
+            "echo_core".to_string(),
+            "echo_full".to_string(),
+            Output::Mocker.to_string(),
+        ];
         #[cfg(feature = "mistralrs")]
         {
             out.push(Output::MistralRs.to_string());
@@ -25,6 +25,7 @@ from vllm.entrypoints.openai.api_server import (
     build_async_engine_client_from_engine_args,
 )
 from vllm.inputs import TokensPrompt
+from vllm.sampling_params import GuidedDecodingParams
 
 from dynamo.llm import ModelType, WorkerMetricsPublisher, register_llm
 from dynamo.runtime import DistributedRuntime, dynamo_worker
@@ -37,6 +38,28 @@ DEFAULT_MODEL = "Qwen/Qwen3-0.6B"
 configure_dynamo_logging()
 
 
+# The fields of vLLM's GuidedDecodingParams for each kind of structured output constraint
+GUIDED_DECODING_FIELDS = {
+    "json_schema": "json",
+    "regex": "regex",
+    "grammar": "grammar",
+    "choice": "choice",
+}
+
+
+def guided_decoding_params(guided_decoding) -> Optional[GuidedDecodingParams]:
+    """
+    vLLM's equivalent of the structured output constraint of a preprocessed request: either
+    "json_object", or a single key naming the kind of constraint.
+    """
+    if guided_decoding is None:
+        return None
+    if guided_decoding == "json_object":
+        return GuidedDecodingParams(json_object=True)
+    ((kind, value),) = guided_decoding.items()
+    return GuidedDecodingParams(**{GUIDED_DECODING_FIELDS[kind]: value})
+
+
 class Config:
     """Command line parameters or defaults"""
 
@@ -105,6 +128,11 @@ class RequestHandler:
         if max_tokens:
             sampling_params.max_tokens = max_tokens
 
+        # Constrain sampling to the requested structured output
+        sampling_params.guided_decoding = guided_decoding_params(
+            request.get("guided_decoding")
+        )
+
         num_output_tokens_so_far = 0
         gen = self.engine_client.generate(prompt, sampling_params, request_id)
         async for res in gen:
@@ -211,6 +239,7 @@ async def init(runtime: DistributedRuntime, config: Config):
             "max_model_len", None
         ),  # if None, takes length from tokenizer
         kv_cache_block_size=arg_map["block_size"],
+        enforces_guided_grammar=True,
     )
     handler = RequestHandler(component, engine_client, default_sampling_params)
     handler.setup_kv_metrics()
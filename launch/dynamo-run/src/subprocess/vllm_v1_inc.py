@@ -26,7 +26,7 @@ from vllm.config import VllmConfig
 from vllm.distributed.kv_events import KVEventsConfig
 from vllm.engine.arg_utils import AsyncEngineArgs
 from vllm.inputs import TokensPrompt
-from vllm.sampling_params import SamplingParams
+from vllm.sampling_params import GuidedDecodingParams, SamplingParams
 from vllm.usage.usage_lib import UsageContext
 from vllm.v1.engine.async_llm import AsyncLLM
 from vllm.v1.metrics.loggers import StatLoggerBase
@@ -47,6 +47,28 @@ DEFAULT_ENDPOINT = "dyn://dynamo.backend.generate"
 DEFAULT_MODEL = "Qwen/Qwen3-0.6B"
 
 configure_dynamo_logging()
+
+
+# The fields of vLLM's GuidedDecodingParams for each kind of structured output constraint
+GUIDED_DECODING_FIELDS = {
+    "json_schema": "json",
+    "regex": "regex",
+    "grammar": "grammar",
+    "choice": "choice",
+}
+
+
+def guided_decoding_params(guided_decoding) -> Optional[GuidedDecodingParams]:
+    """
+    vLLM's equivalent of the structured output constraint of a preprocessed request: either
+    "json_object", or a single key naming the kind of constraint.
+    """
+    if guided_decoding is None:
+        return None
+    if guided_decoding == "json_object":
+        return GuidedDecodingParams(json_object=True)
+    ((kind, value),) = guided_decoding.items()
+    return GuidedDecodingParams(**{GUIDED_DECODING_FIELDS[kind]: value})
 logger = logging.getLogger(__name__)
 
 
@@ -67,9 +89,8 @@ class Config:
 class DynamoStatLoggerPublisher(StatLoggerBase):
     """Stat logger publisher. Wrapper for the WorkerMetricsPublisher to match the StatLoggerBase interface."""
 
-    def __init__(self, component: Component, dp_rank: int) -> None:
-        self.inner = WorkerMetricsPublisher()
-        self.inner.create_endpoint(component)
+    def __init__(self, publisher: WorkerMetricsPublisher, dp_rank: int) -> None:
+        self.inner = publisher
         self.dp_rank = dp_rank
 
     def record(
@@ -85,7 +106,6 @@ class DynamoStatLoggerPublisher(StatLoggerBase):
                 / scheduler_stats.prefix_cache_stats.queries
             )
 
-        # TODO Manage DP Ranks in metrics aggregation.
         self.inner.publish(
             request_active_slots=scheduler_stats.num_running_reqs,
             request_total_slots=0,  # TODO - remove from metrics
@@ -106,9 +126,15 @@ class StatLoggerFactory:
 
     def __init__(self, component: Component) -> None:
         self.component = component
+        self.publisher: Optional[WorkerMetricsPublisher] = None
 
     def create_stat_logger(self, dp_rank: int) -> StatLoggerBase:
-        return DynamoStatLoggerPublisher(self.component, dp_rank)
+        # The data parallel ranks share the worker's metrics endpoint, which serves the
+        # metrics of each rank
+        if self.publisher is None:
+            self.publisher = WorkerMetricsPublisher()
+            self.publisher.create_endpoint(self.component)
+        return DynamoStatLoggerPublisher(self.publisher, dp_rank)
 
     def __call__(self, vllm_config: VllmConfig, dp_rank: int) -> StatLoggerBase:
         return self.create_stat_logger(dp_rank=dp_rank)
@@ -147,8 +173,24 @@ class RequestHandler:
         if max_tokens:
             sampling_params.max_tokens = max_tokens
 
+        # Constrain sampling to the requested structured output
+        sampling_params.guided_decoding = guided_decoding_params(
+            request.get("guided_decoding")
+        )
+
+        # Number of candidate tokens per position; 0 still returns the sampled token's logprob
+        logprobs = (request.get("output_options") or {}).get("logprobs")
+        if logprobs is not None:
+            sampling_params.logprobs = logprobs
+
         num_output_tokens_so_far = 0
-        gen = self.engine_client.generate(prompt, sampling_params, request_id)
+        # The KV router picks the data parallel rank whose KV cache holds the prompt
+        gen = self.engine_client.generate(
+            prompt,
+            sampling_params,
+            request_id,
+            data_parallel_rank=request.get("dp_rank"),
+        )
         async for res in gen:
             # res is vllm's RequestOutput
 
@@ -164,7 +206,27 @@ class RequestHandler:
 
             output = res.outputs[0]
             next_total_toks = len(output.token_ids)
-            out = {"token_ids": output.token_ids[num_output_tokens_so_far:]}
+            new_token_ids = output.token_ids[num_output_tokens_so_far:]
+            out = {"token_ids": new_token_ids}
+            if output.logprobs:
+                # One {token_id: Logprob} dict per position, holding the sampled token and the
+                # most likely candidates. The backend detokenizes the candidates.
+                new_logprobs = output.logprobs[num_output_tokens_so_far:]
+                out["log_probs"] = [
+                    candidates[token_id].logprob
+                    for token_id, candidates in zip(new_token_ids, new_logprobs)
+                ]
+                out["top_logprobs"] = [
+                    [
+                        {
+                            "token_id": candidate_id,
+                            "token": candidate.decoded_token,
+                            "logprob": candidate.logprob,
+                        }
+                        for candidate_id, candidate in candidates.items()
+                    ]
+                    for candidates in new_logprobs
+                ]
             if output.finish_reason:
                 out["finish_reason"] = output.finish_reason
             if output.stop_reason:
@@ -195,6 +257,7 @@ async def init(runtime: DistributedRuntime, config: Config):
         config.model_path,
         config.model_name,
         kv_cache_block_size=config.kv_block_size,
+        enforces_guided_grammar=True,
     )
 
     arg_map = {
@@ -20,7 +20,7 @@ use std::ffi::CStr;
 use std::sync::atomic::{AtomicU32, Ordering};
 
 use dynamo_llm::kv_router::{
-    indexer::compute_block_hash_for_seq, protocols::*, publisher::KvEventPublisher,
+    indexer::compute_block_hash_for_seq_with_lora, protocols::*, publisher::KvEventPublisher,
 };
 use dynamo_runtime::{DistributedRuntime, Worker};
 static WK: OnceCell<Worker> = OnceCell::new();
@@ -159,11 +159,12 @@ fn kv_event_create_stored_block_from_parts(
     token_ids: *const u32,
     num_tokens: usize,
     kv_block_size: usize,
-    _lora_id: u64,
+    lora_id: u64,
 ) -> KvCacheStoredBlockData {
-    let tokens_hash = compute_block_hash_for_seq(
+    let tokens_hash = compute_block_hash_for_seq_with_lora(
         unsafe { std::slice::from_raw_parts(token_ids, num_tokens) },
         kv_block_size,
+        lora_id,
     )[0];
     KvCacheStoredBlockData {
         block_hash: ExternalSequenceBlockHash(block_hash),
@@ -221,6 +222,7 @@ fn kv_event_create_stored_from_parts(
         data: KvCacheEventData::Stored(KvCacheStoreData {
             blocks,
             parent_hash: kv_params.parent_hash.map(ExternalSequenceBlockHash),
+            lora_id: kv_params.lora_id,
         }),
         event_id: kv_params.event_id,
     }
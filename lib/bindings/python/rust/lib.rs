@@ -97,7 +97,7 @@ fn log_message(level: &str, message: &str, module: &str, file: &str, line: u32)
 }
 
 #[pyfunction]
-#[pyo3(signature = (model_type, endpoint, model_path, model_name=None, context_length=None, kv_cache_block_size=None))]
+#[pyo3(signature = (model_type, endpoint, model_path, model_name=None, context_length=None, kv_cache_block_size=None, tool_call_parser=None, enforces_guided_grammar=false))]
 fn register_llm<'p>(
     py: Python<'p>,
     model_type: ModelType,
@@ -106,6 +106,8 @@ fn register_llm<'p>(
     model_name: Option<&str>,
     context_length: Option<usize>,
     kv_cache_block_size: Option<usize>,
+    tool_call_parser: Option<String>,
+    enforces_guided_grammar: bool,
 ) -> PyResult<Bound<'p, PyAny>> {
     let model_type_obj = match model_type {
         ModelType::Chat => llm_rs::model_type::ModelType::Chat,
@@ -128,6 +130,10 @@ fn register_llm<'p>(
         if let Some(kv_cache_block_size) = kv_cache_block_size {
             local_model.set_kv_cache_block_size(kv_cache_block_size);
         }
+        if let Some(tool_call_parser) = tool_call_parser {
+            local_model.set_tool_call_parser(tool_call_parser);
+        }
+        local_model.set_enforces_guided_grammar(enforces_guided_grammar);
 
         // Advertise ourself on etcd so ingress can find us
         local_model
@@ -673,6 +679,33 @@ impl Client {
         })
     }
 
+    /// Send a request to the endpoint with the fewest requests in flight from this client.
+    #[pyo3(signature = (request, annotated=DEFAULT_ANNOTATED_SETTING))]
+    fn least_loaded<'p>(
+        &self,
+        py: Python<'p>,
+        request: PyObject,
+        annotated: Option<bool>,
+    ) -> PyResult<Bound<'p, PyAny>> {
+        let request: serde_json::Value = pythonize::depythonize(&request.into_bound(py))?;
+        let annotated = annotated.unwrap_or(false);
+
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        let client = self.router.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let stream = client
+                .least_loaded(request.into())
+                .await
+                .map_err(to_pyerr)?;
+            tokio::spawn(process_stream(stream, tx));
+            Ok(AsyncResponseStream {
+                rx: Arc::new(Mutex::new(rx)),
+                annotated,
+            })
+        })
+    }
+
     /// Directly send a request to a specific endpoint.
     #[pyo3(signature = (request, instance_id, annotated=DEFAULT_ANNOTATED_SETTING))]
     fn direct<'p>(
@@ -17,7 +17,7 @@ use std::collections::HashMap;
 use std::sync::atomic::AtomicU32;
 
 use super::*;
-use llm_rs::kv_router::indexer::compute_block_hash_for_seq;
+use llm_rs::kv_router::indexer::compute_block_hash_for_seq_with_lora;
 use llm_rs::kv_router::indexer::KvIndexerInterface;
 use rs::traits::events::EventSubscriber;
 use tracing;
@@ -68,12 +68,17 @@ impl KvRouter {
 }
 
 #[pyfunction]
-pub fn compute_block_hash_for_seq_py(tokens: Vec<u32>, kv_block_size: usize) -> PyResult<Vec<u64>> {
+#[pyo3(signature = (tokens, kv_block_size, lora_id = 0))]
+pub fn compute_block_hash_for_seq_py(
+    tokens: Vec<u32>,
+    kv_block_size: usize,
+    lora_id: u64,
+) -> PyResult<Vec<u64>> {
     if kv_block_size == 0 {
         return Err(to_pyerr(anyhow::anyhow!("kv_block_size cannot be 0")));
     }
 
-    let hashes = compute_block_hash_for_seq(&tokens, kv_block_size);
+    let hashes = compute_block_hash_for_seq_with_lora(&tokens, kv_block_size, lora_id);
     Ok(hashes.into_iter().map(|h| h.0).collect())
 }
 
@@ -111,7 +116,7 @@ impl WorkerMetricsPublisher {
     }
 
     #[allow(clippy::too_many_arguments)]
-    #[pyo3(signature = (request_active_slots, request_total_slots, kv_active_blocks, kv_total_blocks, num_requests_waiting, gpu_cache_usage_perc, gpu_prefix_cache_hit_rate, data_parallel_rank = 0))]
+    #[pyo3(signature = (request_active_slots, request_total_slots, kv_active_blocks, kv_total_blocks, num_requests_waiting, gpu_cache_usage_perc, gpu_prefix_cache_hit_rate, data_parallel_rank = 0, loaded_lora_ids = None))]
     fn publish(
         &self,
         _py: Python,
@@ -123,6 +128,7 @@ impl WorkerMetricsPublisher {
         gpu_cache_usage_perc: f32,
         gpu_prefix_cache_hit_rate: f32,
         data_parallel_rank: u32,
+        loaded_lora_ids: Option<Vec<u64>>,
     ) -> PyResult<()> {
         self.inner
             .publish(
@@ -135,6 +141,7 @@ impl WorkerMetricsPublisher {
                     num_requests_waiting,
                     gpu_cache_usage_perc,
                     gpu_prefix_cache_hit_rate,
+                    loaded_lora_ids: loaded_lora_ids.unwrap_or_default(),
                 }
                 .into(),
             )
@@ -153,6 +160,8 @@ pub struct ZmqKvEventPublisherConfig {
     pub zmq_endpoint: String,
     #[pyo3(get, set)]
     pub zmq_topic: String,
+    #[pyo3(get, set)]
+    pub dp_rank: u32,
 }
 
 #[pymethods]
@@ -162,19 +171,22 @@ impl ZmqKvEventPublisherConfig {
         worker_id,
         kv_block_size,
         zmq_endpoint = "tcp://127.0.0.1:5557".to_string(),
-        zmq_topic = "".to_string()
+        zmq_topic = "".to_string(),
+        dp_rank = 0
     ))]
     pub fn new(
         worker_id: i64,
         kv_block_size: usize,
         zmq_endpoint: String,
         zmq_topic: String,
+        dp_rank: u32,
     ) -> Self {
         Self {
             worker_id,
             kv_block_size,
             zmq_endpoint,
             zmq_topic,
+            dp_rank,
         }
     }
 }
@@ -188,9 +200,10 @@ pub(crate) struct ZmqKvEventPublisher {
 impl ZmqKvEventPublisher {
     #[new]
     fn new(component: Component, config: ZmqKvEventPublisherConfig) -> PyResult<Self> {
-        let inner = llm_rs::kv_router::publisher::KvEventPublisher::new(
+        let inner = llm_rs::kv_router::publisher::KvEventPublisher::new_with_dp_rank(
             component.inner,
             config.worker_id,
+            config.dp_rank,
             config.kv_block_size,
             Some(KvEventSourceConfig::Zmq {
                 endpoint: config.zmq_endpoint,
@@ -201,6 +214,19 @@ impl ZmqKvEventPublisher {
         Ok(Self { inner })
     }
 
+    #[pyo3(signature = (component))]
+    fn create_dump_endpoint<'p>(
+        &self,
+        py: Python<'p>,
+        component: Component,
+    ) -> PyResult<Bound<'p, PyAny>> {
+        let dump_endpoint = self.inner.create_dump_endpoint(component.inner.clone());
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            dump_endpoint.await.map_err(to_pyerr)?;
+            Ok(())
+        })
+    }
+
     fn shutdown(&mut self) {
         self.inner.shutdown()
     }
@@ -285,14 +311,21 @@ pub(crate) struct KvEventPublisher {
 #[pymethods]
 impl KvEventPublisher {
     #[new]
-    fn new(component: Component, worker_id: i64, kv_block_size: usize) -> PyResult<Self> {
+    #[pyo3(signature = (component, worker_id, kv_block_size, dp_rank=0))]
+    fn new(
+        component: Component,
+        worker_id: i64,
+        kv_block_size: usize,
+        dp_rank: u32,
+    ) -> PyResult<Self> {
         if kv_block_size == 0 {
             return Err(to_pyerr(anyhow::anyhow!("kv_block_size cannot be 0")));
         }
 
-        let inner = llm_rs::kv_router::publisher::KvEventPublisher::new(
+        let inner = llm_rs::kv_router::publisher::KvEventPublisher::new_with_dp_rank(
             component.inner,
             worker_id,
+            dp_rank,
             kv_block_size,
             None,
         )
@@ -305,6 +338,19 @@ impl KvEventPublisher {
         })
     }
 
+    #[pyo3(signature = (component))]
+    fn create_dump_endpoint<'p>(
+        &self,
+        py: Python<'p>,
+        component: Component,
+    ) -> PyResult<Bound<'p, PyAny>> {
+        let dump_endpoint = self.inner.create_dump_endpoint(component.inner.clone());
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            dump_endpoint.await.map_err(to_pyerr)?;
+            Ok(())
+        })
+    }
+
     #[allow(clippy::too_many_arguments)]
     #[pyo3(signature = (event_id, token_ids, num_block_tokens, block_hashes, lora_id, parent_hash=None))]
     fn publish_stored(
@@ -329,6 +375,7 @@ impl KvEventPublisher {
                     lora_id,
                     &self.warning_count,
                 ),
+                lora_id,
             }),
         };
 
@@ -357,9 +404,24 @@ pub(crate) struct OverlapScores {
 
 #[pymethods]
 impl OverlapScores {
+    /// The best score among the data parallel ranks of each worker
     #[getter]
     fn scores(&self) -> HashMap<llm_rs::kv_router::indexer::WorkerId, u32> {
-        self.inner.scores.clone()
+        let mut scores = HashMap::new();
+        for (worker, score) in &self.inner.scores {
+            let best = scores.entry(worker.worker_id).or_insert(0);
+            *best = (*best).max(*score);
+        }
+        scores
+    }
+
+    #[getter]
+    fn dp_scores(&self) -> HashMap<(llm_rs::kv_router::indexer::WorkerId, u32), u32> {
+        self.inner
+            .scores
+            .iter()
+            .map(|(worker, score)| ((worker.worker_id, worker.dp_rank), *score))
+            .collect()
     }
 
     #[getter]
@@ -402,11 +464,13 @@ impl RadixTree {
         })
     }
 
+    #[pyo3(signature = (worker_id, kv_cache_event_bytes, dp_rank=0))]
     fn apply_event(
         &mut self,
         _py: Python,
         worker_id: i64,
         kv_cache_event_bytes: &[u8],
+        dp_rank: u32,
     ) -> PyResult<()> {
         let kv_cache_event: llm_rs::kv_router::protocols::KvCacheEvent =
             serde_json::from_slice(kv_cache_event_bytes).map_err(|e| {
@@ -416,7 +480,8 @@ impl RadixTree {
                 ))
             })?;
 
-        let router_event = llm_rs::kv_router::indexer::RouterEvent::new(worker_id, kv_cache_event);
+        let router_event = llm_rs::kv_router::indexer::RouterEvent::new(worker_id, kv_cache_event)
+            .with_dp_rank(dp_rank);
         self.inner.apply_event(router_event);
         Ok(())
     }
@@ -426,8 +491,10 @@ impl RadixTree {
         Ok(())
     }
 
-    fn clear_all_blocks(&mut self, _py: Python, worker_id: i64) -> PyResult<()> {
-        self.inner.clear_all_blocks(worker_id);
+    #[pyo3(signature = (worker_id, dp_rank=0))]
+    fn clear_all_blocks(&mut self, _py: Python, worker_id: i64, dp_rank: u32) -> PyResult<()> {
+        self.inner
+            .clear_all_blocks(WorkerWithDpRank::new(worker_id, dp_rank));
         Ok(())
     }
 }
@@ -462,14 +529,16 @@ impl KvIndexer {
             // should have been made to a trait and implemented here? i.e. AsyncEngine style
             tokio::spawn(async move {
                 while let Some(event) = kv_events_rx.next().await {
-                    let event: llm_rs::kv_router::indexer::RouterEvent =
-                        serde_json::from_slice(&event.payload).unwrap();
-                    tracing::debug!("received kv event: {:?}", event);
-                    if let Err(e) = kv_events_tx.send(event).await {
-                        tracing::trace!(
-                            "failed to send kv event to indexer; shutting down: {:?}",
-                            e
-                        );
+                    let events =
+                        llm_rs::kv_router::codec::decode_router_events(&event.payload).unwrap();
+                    for event in events {
+                        tracing::debug!("received kv event: {:?}", event);
+                        if let Err(e) = kv_events_tx.send(event).await {
+                            tracing::trace!(
+                                "failed to send kv event to indexer; shutting down: {:?}",
+                                e
+                            );
+                        }
                     }
                 }
             });
@@ -503,12 +572,12 @@ impl KvIndexer {
         &self,
         py: Python<'p>,
         token_ids: Vec<u32>,
-        _lora_id: u64,
+        lora_id: u64,
     ) -> PyResult<Bound<'p, PyAny>> {
         let indexer = self.inner.clone();
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
             let rs_overlap_scores = indexer
-                .find_matches_for_request(token_ids.as_slice())
+                .find_matches_for_lora_request(token_ids.as_slice(), lora_id)
                 .await
                 .map_err(to_pyerr)?;
             Ok(OverlapScores {
@@ -524,6 +593,8 @@ pub(crate) struct EndpointKvMetrics {
     #[pyo3(get, set)]
     pub worker_id: i64,
     #[pyo3(get, set)]
+    pub dp_rank: u32,
+    #[pyo3(get, set)]
     pub request_active_slots: u64,
     #[pyo3(get, set)]
     pub request_total_slots: u64,
@@ -577,8 +648,9 @@ impl KvMetricsAggregator {
         let endpoint_kv_metrics = endpoints
             .endpoints
             .iter()
-            .map(|(worker_id, x)| EndpointKvMetrics {
-                worker_id: *worker_id,
+            .map(|(worker, x)| EndpointKvMetrics {
+                worker_id: worker.worker_id,
+                dp_rank: worker.dp_rank,
                 request_active_slots: x.data.request_active_slots,
                 request_total_slots: x.data.request_total_slots,
                 kv_active_blocks: x.data.kv_active_blocks,
@@ -651,14 +723,16 @@ impl KvRecorder {
             // Spawn a task to forward events to the recorder
             tokio::spawn(async move {
                 while let Some(event) = kv_events_rx.next().await {
-                    let event: llm_rs::kv_router::indexer::RouterEvent =
-                        serde_json::from_slice(&event.payload).unwrap();
-                    tracing::debug!("KvRecorder received kv event: {:?}", event);
-                    if let Err(e) = event_tx.send(event).await {
-                        tracing::trace!(
-                            "KvRecorder failed to send kv event; shutting down: {:?}",
-                            e
-                        );
+                    let events =
+                        llm_rs::kv_router::codec::decode_router_events(&event.payload).unwrap();
+                    for event in events {
+                        tracing::debug!("KvRecorder received kv event: {:?}", event);
+                        if let Err(e) = event_tx.send(event).await {
+                            tracing::trace!(
+                                "KvRecorder failed to send kv event; shutting down: {:?}",
+                                e
+                            );
+                        }
                     }
                 }
             });
@@ -21,6 +21,7 @@ from typing import (
     Dict,
     List,
     Optional,
+    Tuple,
     Union,
 )
 
@@ -266,6 +267,12 @@ class Client:
         """
         ...
 
+    async def least_loaded(self, request: JsonLike) -> AsyncIterator[JsonLike]:
+        """
+        Pick the instance of the endpoint with the fewest requests in flight from this client
+        """
+        ...
+
     async def direct(self, request: JsonLike, instance: str) -> AsyncIterator[JsonLike]:
         """
         Pick a specific instance of the endpoint
@@ -344,13 +351,16 @@ class DisaggregatedRouter:
         """
         ...
 
-def compute_block_hash_for_seq_py(tokens: List[int], kv_block_size: int) -> List[int]:
+def compute_block_hash_for_seq_py(
+    tokens: List[int], kv_block_size: int, lora_id: int = 0
+) -> List[int]:
     """
     Compute block hashes for a sequence of tokens
 
     Args:
         tokens: List of token IDs
         kv_block_size: Size of each KV cache block
+        lora_id: ID of the LoRA adapter generating the tokens, 0 for the base model
 
     Returns:
         List of block hashes as integers
@@ -385,9 +395,15 @@ class WorkerMetricsPublisher:
         gpu_cache_usage_perc: float,
         gpu_prefix_cache_hit_rate: float,
         data_parallel_rank: int = 0,
+        loaded_lora_ids: Optional[List[int]] = None,
     ) -> None:
         """
-        Update the KV metrics being reported.
+        Update the KV metrics being reported for the data parallel rank `data_parallel_rank`.
+        Workers with several data parallel ranks publish the metrics of each rank on the same
+        publisher, the KV router then routes to each rank separately.
+
+        `loaded_lora_ids` are the LoRA adapters loaded on the worker, the KV router prefers
+        workers that already have the adapter of a request.
         """
         ...
 
@@ -435,12 +451,23 @@ class OverlapScores:
     def scores(self) -> Dict[int, int]:
         """
         Map of worker_id to the score which is the number of matching blocks.
+        For workers with several data parallel ranks, the best score of the ranks.
 
         Returns:
             Dictionary mapping worker IDs to their overlap scores
         """
         ...
 
+    @property
+    def dp_scores(self) -> Dict[Tuple[int, int], int]:
+        """
+        Map of (worker_id, dp_rank) to the score which is the number of matching blocks.
+
+        Returns:
+            Dictionary mapping worker IDs and data parallel ranks to their overlap scores
+        """
+        ...
+
     @property
     def frequencies(self) -> List[int]:
         """
@@ -484,13 +511,16 @@ class RadixTree:
         """
         ...
 
-    def apply_event(self, worker_id: int, kv_cache_event_bytes: bytes) -> None:
+    def apply_event(
+        self, worker_id: int, kv_cache_event_bytes: bytes, dp_rank: int = 0
+    ) -> None:
         """
         Apply a KV cache event to update the RadixTree state.
 
         Args:
             worker_id: ID of the worker that generated the event
             kv_cache_event_bytes: Serialized KV cache event as bytes
+            dp_rank: Data parallel rank of the worker the event is about
 
         Raises:
             ValueError: If the event bytes cannot be deserialized
@@ -499,19 +529,20 @@ class RadixTree:
 
     def remove_worker(self, worker_id: int) -> None:
         """
-        Remove all blocks associated with a specific worker.
+        Remove all blocks associated with a specific worker, on all its data parallel ranks.
 
         Args:
             worker_id: ID of the worker to remove
         """
         ...
 
-    def clear_all_blocks(self, worker_id: int) -> None:
+    def clear_all_blocks(self, worker_id: int, dp_rank: int = 0) -> None:
         """
         Clear all blocks for a specific worker.
 
         Args:
             worker_id: ID of the worker whose blocks should be cleared
+            dp_rank: Data parallel rank of the worker whose blocks should be cleared
         """
         ...
 
@@ -543,7 +574,8 @@ class KvIndexer:
         self, token_ids: List[int], lora_id: int
     ) -> OverlapScores:
         """
-        Return the overlapping scores of workers for the given token ids.
+        Return the overlapping scores of workers for the given token ids, generated with
+        the LoRA adapter `lora_id` (0 for the base model).
         """
         ...
 
@@ -659,10 +691,11 @@ class KvEventPublisher:
     ...
 
     def __init__(
-        self, component: Component, worker_id: int, kv_block_size: int
+        self, component: Component, worker_id: int, kv_block_size: int, dp_rank: int = 0
     ) -> None:
         """
-        Create a `KvEventPublisher` object
+        Create a `KvEventPublisher` object, publishing the events of the KV cache of the
+        data parallel rank `dp_rank` of the worker
         """
 
     def publish_stored(
@@ -686,13 +719,24 @@ class KvEventPublisher:
         """
         ...
 
+    async def create_dump_endpoint(self, component: Component) -> None:
+        """
+        Serve the blocks stored according to the published events on the component's
+        `kv_dump` endpoint, so that the KV router can resync after missing events.
+
+        The endpoint serves every data parallel rank of the worker publishing in this
+        process; only the first publisher of the worker to call it serves the endpoint.
+        """
+        ...
+
 class ZmqKvEventPublisherConfig:
     def __init__(
         self,
         worker_id: int,
         kv_block_size: int,
         zmq_endpoint: str = "tcp://127.0.0.1:5557",
-        zmq_topic: str = ""
+        zmq_topic: str = "",
+        dp_rank: int = 0
     ) -> None:
         """
         Configuration for the ZmqKvEventPublisher.
@@ -701,6 +745,7 @@ class ZmqKvEventPublisherConfig:
         :param kv_block_size: The block size for the key-value store.
         :param zmq_endpoint: The ZeroMQ endpoint. Defaults to "tcp://127.0.0.1:5557".
         :param zmq_topic: The ZeroMQ topic to subscribe to. Defaults to an empty string.
+        :param dp_rank: The data parallel rank whose KV cache the events are about. Defaults to 0.
         """
         ...
 
@@ -714,6 +759,16 @@ class ZmqKvEventPublisher:
         """
         ...
 
+    async def create_dump_endpoint(self, component: Component) -> None:
+        """
+        Serve the blocks stored according to the published events on the component's
+        `kv_dump` endpoint, so that the KV router can resync after missing events.
+
+        The endpoint serves every data parallel rank of the worker publishing in this
+        process; only the first publisher of the worker to call it serves the endpoint.
+        """
+        ...
+
     def shutdown(self) -> None:
         """
         Shuts down the event publisher, stopping any background tasks.
@@ -748,8 +803,13 @@ class ModelType:
     """What type of request this model needs: Chat, Component or Backend (pre-processed)"""
     ...
 
-async def register_llm(model_type: ModelType, endpoint: Endpoint, model_path: str, model_name: Optional[str] = None, context_length: Optional[int] = None, kv_cache_block_size: Optional[int] = None) -> None:
-    """Attach the model at path to the given endpoint, and advertise it as model_type"""
+async def register_llm(model_type: ModelType, endpoint: Endpoint, model_path: str, model_name: Optional[str] = None, context_length: Optional[int] = None, kv_cache_block_size: Optional[int] = None, tool_call_parser: Optional[str] = None, enforces_guided_grammar: bool = False) -> None:
+    """
+    Attach the model at path to the given endpoint, and advertise it as model_type.
+
+    Set enforces_guided_grammar if the engine constrains sampling to the grammar of
+    `guided_grammar` requests; they are rejected otherwise.
+    """
     ...
 
 class NatsQueue:
@@ -23,6 +23,7 @@ use llama_cpp_2::{
     LogOptions,
 };
 
+use dynamo_llm::guided_decoding;
 use dynamo_llm::protocols::common::llm_backend::LLMEngineOutput;
 use dynamo_llm::protocols::common::preprocessor::PreprocessedRequest;
 use dynamo_llm::{backend::ExecutionContext, local_model::LocalModel};
@@ -240,7 +241,31 @@ fn run_request(
         .decode(&mut batch)
         .with_context(|| "llama_decode failed on first pass")?;
 
-    let mut sampler = LlamaSampler::greedy();
+    // Constrain sampling to the requested structured output. Regular expressions have no grammar
+    // translation; those are left to the preprocessor, which validates the finished output.
+    let grammar = match work_request
+        .request
+        .guided_decoding
+        .as_ref()
+        .map(guided_decoding::to_gbnf)
+        .transpose()
+    {
+        Ok(grammar) => grammar.flatten(),
+        Err(err) => {
+            let err_msg = format!("Unsupported structured output request: {err:#}");
+            let _ = work_request
+                .response_channel
+                .blocking_send(Annotated::from_data(LLMEngineOutput::error(err_msg)));
+            return Ok(());
+        }
+    };
+    let mut sampler = match grammar {
+        Some(grammar) => LlamaSampler::chain_simple([
+            LlamaSampler::grammar(LLAMA_MODEL.get().unwrap(), &grammar, "root"),
+            LlamaSampler::greedy(),
+        ]),
+        None => LlamaSampler::greedy(),
+    };
     let mut n_cur = batch.n_tokens() as u32;
 
     let mut used_output_tokens = 0;
@@ -268,6 +293,7 @@ fn run_request(
             //text: if output.text.is_empty() { None } else { Some(output.text) },
             cum_log_probs: None, // TODO output.cumulative_logprob.map(|v| v as f64),
             log_probs: None,     // TODO  output.logprobs
+            top_logprobs: None,
             finish_reason: None,
             index: None,
         };
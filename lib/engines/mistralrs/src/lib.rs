@@ -23,6 +23,7 @@ use dynamo_runtime::pipeline::error as pipeline_error;
 use dynamo_runtime::pipeline::{Error, ManyOut, SingleIn};
 use dynamo_runtime::protocols::annotated::Annotated;
 
+use dynamo_llm::protocols::common::GuidedDecodingProvider;
 use dynamo_llm::protocols::openai::{
     chat_completions::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse},
     completions::{prompt_to_string, NvCreateCompletionRequest, NvCreateCompletionResponse},
@@ -30,6 +31,8 @@ use dynamo_llm::protocols::openai::{
 };
 
 use dynamo_llm::engines::{EngineDispatcher, StreamingEngine};
+use dynamo_llm::guided_decoding::{choice_regex, GuidedDecoding};
+use dynamo_llm::http::service::error::HttpError;
 use dynamo_llm::local_model::LocalModel;
 
 /// How many requests mistral will run at once in the paged attention scheduler.
@@ -273,6 +276,7 @@ impl
         let (request, context) = request.transfer(());
         let ctx = context.context();
         let (tx, mut rx) = channel(10_000);
+        let constraint = to_constraint(request.extract_guided_decoding()?)?;
 
         let mut messages = vec![];
         for m in request.inner.messages {
@@ -349,7 +353,7 @@ impl
             response: tx,
             return_logprobs: request.inner.logprobs.unwrap_or_default(),
             is_streaming: true,
-            constraint: Constraint::None,
+            constraint,
             suffix: None,
             tools: None,
             tool_choice: None,
@@ -435,6 +439,27 @@ impl
     }
 }
 
+/// Translates a structured output request into the equivalent mistral.rs sampling constraint.
+fn to_constraint(guided_decoding: Option<GuidedDecoding>) -> anyhow::Result<Constraint> {
+    let constraint = match guided_decoding {
+        None => Constraint::None,
+        Some(GuidedDecoding::JsonObject) => {
+            Constraint::JsonSchema(serde_json::json!({"type": "object"}))
+        }
+        Some(GuidedDecoding::JsonSchema(schema)) => Constraint::JsonSchema(schema),
+        Some(GuidedDecoding::Regex(regex)) => Constraint::Regex(regex),
+        Some(GuidedDecoding::Choice(choices)) => Constraint::Regex(choice_regex(&choices)),
+        Some(GuidedDecoding::Grammar(_)) => {
+            return Err(HttpError {
+                code: 400,
+                message: "GBNF grammars are not supported by the mistralrs engine".to_string(),
+            }
+            .into());
+        }
+    };
+    Ok(constraint)
+}
+
 /// openai stop tokens to mistralrs stop tokens
 fn to_stop_tokens(t: async_openai::types::Stop) -> StopTokens {
     match t {
@@ -482,6 +507,7 @@ impl
         let ctx = context.context();
         let (tx, mut rx) = channel(10_000);
         let response_generator = request.response_generator();
+        let constraint = to_constraint(request.extract_guided_decoding()?)?;
 
         let messages = RequestMessage::Completion {
             text: prompt_to_string(&request.inner.prompt),
@@ -543,7 +569,7 @@ impl
             response: tx,
             return_logprobs: false,
             is_streaming: true,
-            constraint: Constraint::None,
+            constraint,
             suffix: None,
             tools: None,
             tool_choice: None,
@@ -581,7 +607,7 @@ impl
                             None => None,
                         };
                         #[allow(deprecated)]
-                        let inner = response_generator.create_choice(0, Some(from_assistant), None);
+                        let inner = response_generator.create_choice(0, Some(from_assistant), None, None);
                         let ann = Annotated{
                             id: None,
                             data: Some(inner),
@@ -44,7 +44,9 @@ use dynamo_runtime::{
 
 use crate::protocols::{
     common::{
-        llm_backend::{BackendOutput, FinishReason, LLMEngineOutput, PreprocessedRequest},
+        llm_backend::{
+            BackendOutput, FinishReason, LLMEngineOutput, PreprocessedRequest, TopLogProbs,
+        },
         StopConditions,
     },
     TokenIdType,
@@ -214,14 +216,19 @@ impl
 
         // convert stream of processed Annotated<LLMEngineOutput> to Annotated<BackendOutput>
         //let mdcsum = self.mdcsum.clone();
+        let tokenizer = self.tokenizer.clone();
         let stream = processed_stream.map(move |output| {
             output.map_data(|data| {
+                let top_logprobs = data
+                    .top_logprobs
+                    .map(|top_logprobs| detokenize_top_logprobs(tokenizer.as_ref(), top_logprobs));
                 Ok(BackendOutput {
                     token_ids: data.token_ids,
                     tokens: data.tokens.unwrap_or_default(),
                     text: data.text,
                     cum_log_probs: data.cum_log_probs,
                     log_probs: data.log_probs,
+                    top_logprobs,
                     finish_reason: data.finish_reason,
                     //mdcsum: mdcsum.clone(),
                     index: data.index,
@@ -233,6 +240,24 @@ impl
     }
 }
 
+/// Fills in the text of any candidate token the engine returned without detokenizing.
+fn detokenize_top_logprobs(
+    tokenizer: Option<&Tokenizer>,
+    mut top_logprobs: TopLogProbs,
+) -> TopLogProbs {
+    let Some(tokenizer) = tokenizer else {
+        return top_logprobs;
+    };
+
+    for candidate in top_logprobs.iter_mut().flatten() {
+        if candidate.token.is_none() {
+            candidate.token = tokenizer.decode(&[candidate.token_id], false).ok();
+        }
+    }
+
+    top_logprobs
+}
+
 // todo - add visible stop conditions
 // visible_stop_ids: HashSet<TokenIdType>,
 // visible_stop_sequences: Vec<String>,
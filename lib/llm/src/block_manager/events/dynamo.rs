@@ -165,6 +165,7 @@ async fn progress_engine(state: NatsEventsManagerState) {
                         tokens_hash: event.block_hash,
                     }],
                     parent_hash: event.parent_hash,
+                    lora_id: 0,
                 };
                 let data = KvCacheEventData::Stored(store_data);
                 let event = KvCacheEvent { event_id, data };
@@ -184,6 +185,7 @@ async fn progress_engine(state: NatsEventsManagerState) {
                         })
                         .collect(),
                     parent_hash: event.parent_hash,
+                    lora_id: 0,
                 };
                 let data = KvCacheEventData::Stored(store_data);
                 let event = KvCacheEvent { event_id, data };
@@ -1,11 +1,20 @@
 // SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use dynamo_runtime::component::Component;
+use dynamo_runtime::{
+    component::{
+        health::{HealthTracker, InstanceHealth},
+        Component,
+    },
+    slug::Slug,
+};
 
 use crate::discovery::ModelEntry;
 
-use crate::kv_router::{scheduler::DefaultWorkerSelector, KvRouterConfig};
+use crate::kv_router::{
+    metrics::KvRouterMetrics, scheduler::DefaultWorkerSelector, KvRouterConfig,
+    DEFAULT_SNAPSHOT_INTERVAL,
+};
 use crate::{
     kv_router::KvRouter,
     types::openai::{
@@ -36,9 +45,11 @@ pub struct ModelManager {
     chat_completion_engines: RwLock<ModelEngines<OpenAIChatCompletionsStreamingEngine>>,
     embeddings_engines: RwLock<ModelEngines<OpenAIEmbeddingsStreamingEngine>>,
 
-    // These two are Mutex because we read and write rarely and equally
+    // These are Mutex because we read and write rarely and equally
     entries: Mutex<HashMap<String, ModelEntry>>,
     kv_choosers: Mutex<HashMap<String, Arc<KvRouter>>>,
+    health_trackers: Mutex<HashMap<String, Arc<HealthTracker>>>,
+    kv_router_metrics: Arc<KvRouterMetrics>,
 }
 
 impl Default for ModelManager {
@@ -55,6 +66,8 @@ impl ModelManager {
             embeddings_engines: RwLock::new(ModelEngines::default()),
             entries: Mutex::new(HashMap::new()),
             kv_choosers: Mutex::new(HashMap::new()),
+            health_trackers: Mutex::new(HashMap::new()),
+            kv_router_metrics: Arc::new(KvRouterMetrics::default()),
         }
     }
 
@@ -176,6 +189,33 @@ impl ModelManager {
         self.entries.lock().unwrap().remove(key)
     }
 
+    /// Keep the health tracker of the workers of this model, for the `/health` detail view
+    pub fn add_health_tracker(&self, model: &str, tracker: Arc<HealthTracker>) {
+        self.health_trackers
+            .lock()
+            .unwrap()
+            .insert(model.to_string(), tracker);
+    }
+
+    pub fn remove_health_tracker(&self, model: &str) {
+        self.health_trackers.lock().unwrap().remove(model);
+    }
+
+    /// The health of the workers of each model with a health tracker
+    pub fn instance_health(&self) -> HashMap<String, Vec<InstanceHealth>> {
+        self.health_trackers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(model, tracker)| (model.clone(), tracker.instances()))
+            .collect()
+    }
+
+    /// The Prometheus metrics shared by the KV routers of all the models
+    pub fn kv_router_metrics(&self) -> Arc<KvRouterMetrics> {
+        self.kv_router_metrics.clone()
+    }
+
     pub async fn kv_chooser_for(
         &self,
         model_name: &str,
@@ -212,8 +252,47 @@ impl ModelManager {
         kv_cache_block_size: usize,
         kv_router_config: Option<KvRouterConfig>,
     ) -> anyhow::Result<Arc<KvRouter>> {
+        // Each model has its own indexer, so its own snapshots
+        let (snapshot_dir, snapshot_interval) = match &kv_router_config {
+            Some(config) => (
+                config
+                    .snapshot_dir
+                    .as_ref()
+                    .map(|dir| dir.join(Slug::slugify(model_name).to_string())),
+                config.snapshot_interval,
+            ),
+            None => (None, DEFAULT_SNAPSHOT_INTERVAL),
+        };
+        // ... and its own durable consumer of the KV events
+        let kv_event_stream = kv_router_config
+            .as_ref()
+            .and_then(|config| config.kv_event_stream.clone())
+            .map(|mut stream| {
+                stream.consumer_name =
+                    format!("{}_{}", stream.consumer_name, Slug::slugify(model_name));
+                stream
+            });
+        // ... and its own decision log
+        let decision_log = kv_router_config.as_ref().and_then(|config| {
+            let dir = config.decision_log_dir.as_ref()?;
+            let path = dir.join(format!("{}.jsonl", Slug::slugify(model_name)));
+            Some((path, config.decision_log_sample_rate))
+        });
         let selector = Box::new(DefaultWorkerSelector::new(kv_router_config));
-        let chooser = KvRouter::new(component.clone(), kv_cache_block_size, Some(selector)).await?;
+        let chooser = KvRouter::new_with_snapshots(
+            component.clone(),
+            kv_cache_block_size,
+            Some(selector),
+            snapshot_dir,
+            snapshot_interval,
+            self.kv_router_metrics.clone(),
+            kv_event_stream,
+        )
+        .await?;
+        let chooser = match decision_log {
+            Some((path, sample_rate)) => chooser.with_decision_log(path, sample_rate).await?,
+            None => chooser,
+        };
         let new_kv_chooser = Arc::new(chooser);
         self.kv_choosers
             .lock()
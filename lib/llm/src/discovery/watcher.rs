@@ -7,9 +7,14 @@ use anyhow::Context as _;
 use tokio::sync::{mpsc::Receiver, Notify};
 
 use dynamo_runtime::{
+    component::health::{HealthConfig, HealthMetrics, HealthTracker},
     pipeline::{
-        network::egress::push_router::PushRouter, ManyOut, Operator, RouterMode, SegmentSource,
-        ServiceBackend, SingleIn, Source,
+        network::egress::{
+            push_router::PushRouter,
+            queue::{AdmissionQueue, QueueConfig, QueueMetrics},
+            retry::{RetryConfig, RetryMetrics, RetryPolicy},
+        },
+        ManyOut, Operator, RouterMode, SegmentSource, ServiceBackend, SingleIn, Source,
     },
     protocols::annotated::Annotated,
     transports::etcd::{KeyValue, WatchEvent},
@@ -19,6 +24,7 @@ use dynamo_runtime::{
 use crate::{
     backend::Backend,
     kv_router::{KvPushRouter, KvRouterConfig},
+    migration::Migration,
     model_type::ModelType,
     preprocessor::{OpenAIPreprocessor, PreprocessedRequest},
     protocols::common::llm_backend::LLMEngineOutput,
@@ -37,6 +43,10 @@ pub struct ModelWatcher {
     router_mode: RouterMode,
     notify_on_model: Notify,
     kv_router_config: Option<KvRouterConfig>,
+    admission: Option<(QueueConfig, Arc<QueueMetrics>)>,
+    retry: Option<(RetryConfig, Arc<RetryMetrics>)>,
+    migration_limit: u32,
+    health: Option<(HealthConfig, Arc<HealthMetrics>)>,
 }
 
 impl ModelWatcher {
@@ -52,9 +62,46 @@ impl ModelWatcher {
             router_mode,
             notify_on_model: Notify::new(),
             kv_router_config,
+            admission: None,
+            retry: None,
+            migration_limit: 0,
+            health: None,
         }
     }
 
+    /// Queue the requests to each model, so that at most `config.max_inflight` requests per model
+    /// are in flight to its workers. Disabled by default.
+    pub fn with_admission_queue(mut self, config: QueueConfig, metrics: Arc<QueueMetrics>) -> Self {
+        self.admission = Some((config, metrics));
+        self
+    }
+
+    /// Retry the requests to each model which fail before their first response on another
+    /// worker. Disabled by default. Not used by KV routing, which picks the worker itself.
+    pub fn with_retry_policy(mut self, config: RetryConfig, metrics: Arc<RetryMetrics>) -> Self {
+        self.retry = Some((config, metrics));
+        self
+    }
+
+    /// Move the requests whose worker went away mid-stream to another worker, at most
+    /// `migration_limit` times per request. Only for the workers taking pre-processed requests,
+    /// whose token streams can be continued. Disabled by default.
+    pub fn with_migration_limit(mut self, migration_limit: u32) -> Self {
+        self.migration_limit = migration_limit;
+        self
+    }
+
+    /// Track the health of the workers of each model, keeping those whose circuit is open out of
+    /// routing. Disabled by default.
+    pub fn with_health_tracking(
+        mut self,
+        config: HealthConfig,
+        metrics: Arc<HealthMetrics>,
+    ) -> Self {
+        self.health = Some((config, metrics));
+        self
+    }
+
     /// Wait until we have at least one chat completions model and return it's name.
     pub async fn wait_for_chat_model(&self) -> String {
         // Loop in case it gets added and immediately deleted
@@ -153,6 +200,7 @@ impl ModelWatcher {
         let _ = self.manager.remove_chat_completions_model(&model_name);
         let _ = self.manager.remove_completions_model(&model_name);
         let _ = self.manager.remove_embeddings_model(&model_name);
+        self.manager.remove_health_tracker(&model_name);
 
         Ok(Some(model_name))
     }
@@ -165,7 +213,35 @@ impl ModelWatcher {
             .drt
             .namespace(&endpoint_id.namespace)?
             .component(&endpoint_id.component)?;
-        let client = component.endpoint(&endpoint_id.name).client().await?;
+        let mut client = component.endpoint(&endpoint_id.name).client().await?;
+
+        // one health tracker for all the routers of the model, they share the client
+        if let Some((config, metrics)) = self.health.as_ref() {
+            let tracker = Arc::new(HealthTracker::new(
+                &model_entry.name,
+                config.clone(),
+                metrics.clone(),
+            ));
+            self.manager
+                .add_health_tracker(&model_entry.name, tracker.clone());
+            client = client.with_health(tracker);
+        }
+
+        // one queue for all the routers of the model, they share the workers
+        let queue = self.admission.as_ref().map(|(config, metrics)| {
+            Arc::new(AdmissionQueue::new(
+                &model_entry.name,
+                config.clone(),
+                metrics.clone(),
+            ))
+        });
+        let retry = self.retry.as_ref().map(|(config, metrics)| {
+            Arc::new(RetryPolicy::new(
+                &model_entry.name,
+                config.clone(),
+                metrics.clone(),
+            ))
+        });
 
         let Some(etcd_client) = self.drt.etcd_client() else {
             // Should be impossible because we only get here on an etcd event
@@ -195,7 +271,11 @@ impl ModelWatcher {
                 // This cache_dir is a tempfile::TempDir will be deleted on drop. I _think_
                 // OpenAIPreprocessor::new loads the files, so we can delete them after this
                 // function. Needs checking carefully, possibly we need to store it in state.
-                let _cache_dir = Some(card.move_from_nats(self.drt.nats_client()).await?);
+                let _cache_dir = match self.drt.try_nats_client() {
+                    Some(nats_client) => Some(card.move_from_nats(nats_client).await?),
+                    // On a local runtime, the card points at files of this host
+                    None => None,
+                };
 
                 let frontend = SegmentSource::<
                     SingleIn<NvCreateChatCompletionRequest>,
@@ -203,16 +283,20 @@ impl ModelWatcher {
                 >::new();
                 let preprocessor = OpenAIPreprocessor::new(card.clone()).await?.into_operator();
                 let backend = Backend::from_mdc(card.clone()).await?.into_operator();
+                let migration = Migration::new(self.migration_limit).into_operator();
                 let router =
                     PushRouter::<PreprocessedRequest, Annotated<LLMEngineOutput>>::from_client(
                         client.clone(),
                         self.router_mode,
                     )
-                    .await?;
+                    .await?
+                    .with_admission_queue(queue.clone())
+                    .with_retry_policy(retry.clone());
                 let service_backend = match self.router_mode {
-                    RouterMode::Random | RouterMode::RoundRobin | RouterMode::Direct(_) => {
-                        ServiceBackend::from_engine(Arc::new(router))
-                    }
+                    RouterMode::Random
+                    | RouterMode::RoundRobin
+                    | RouterMode::Direct(_)
+                    | RouterMode::LeastLoaded => ServiceBackend::from_engine(Arc::new(router)),
                     RouterMode::KV => {
                         let chooser = self
                             .manager
@@ -231,7 +315,9 @@ impl ModelWatcher {
                 let chat_engine = frontend
                     .link(preprocessor.forward_edge())?
                     .link(backend.forward_edge())?
+                    .link(migration.forward_edge())?
                     .link(service_backend)?
+                    .link(migration.backward_edge())?
                     .link(backend.backward_edge())?
                     .link(preprocessor.backward_edge())?
                     .link(frontend)?;
@@ -244,16 +330,20 @@ impl ModelWatcher {
                 >::new();
                 let preprocessor = OpenAIPreprocessor::new(card.clone()).await?.into_operator();
                 let backend = Backend::from_mdc(card.clone()).await?.into_operator();
+                let migration = Migration::new(self.migration_limit).into_operator();
                 let router =
                     PushRouter::<PreprocessedRequest, Annotated<LLMEngineOutput>>::from_client(
                         client,
                         self.router_mode,
                     )
-                    .await?;
+                    .await?
+                    .with_admission_queue(queue.clone())
+                    .with_retry_policy(retry.clone());
                 let service_backend = match self.router_mode {
-                    RouterMode::Random | RouterMode::RoundRobin | RouterMode::Direct(_) => {
-                        ServiceBackend::from_engine(Arc::new(router))
-                    }
+                    RouterMode::Random
+                    | RouterMode::RoundRobin
+                    | RouterMode::Direct(_)
+                    | RouterMode::LeastLoaded => ServiceBackend::from_engine(Arc::new(router)),
                     RouterMode::KV => {
                         let chooser = self
                             .manager
@@ -272,7 +362,9 @@ impl ModelWatcher {
                 let completions_engine = frontend
                     .link(preprocessor.forward_edge())?
                     .link(backend.forward_edge())?
+                    .link(migration.forward_edge())?
                     .link(service_backend)?
+                    .link(migration.backward_edge())?
                     .link(backend.backward_edge())?
                     .link(preprocessor.backward_edge())?
                     .link(frontend)?;
@@ -284,7 +376,9 @@ impl ModelWatcher {
                     NvCreateChatCompletionRequest,
                     Annotated<NvCreateChatCompletionStreamResponse>,
                 >::from_client(client, Default::default())
-                .await?;
+                .await?
+                .with_admission_queue(queue)
+                .with_retry_policy(retry);
                 let engine = Arc::new(push_router);
                 self.manager
                     .add_chat_completions_model(&model_entry.name, engine)?;
@@ -294,7 +388,9 @@ impl ModelWatcher {
                     NvCreateCompletionRequest,
                     Annotated<NvCreateCompletionResponse>,
                 >::from_client(client, Default::default())
-                .await?;
+                .await?
+                .with_admission_queue(queue)
+                .with_retry_policy(retry);
                 let engine = Arc::new(push_router);
                 self.manager
                     .add_completions_model(&model_entry.name, engine)?;
@@ -304,7 +400,9 @@ impl ModelWatcher {
                     NvCreateEmbeddingRequest,
                     Annotated<NvCreateEmbeddingResponse>,
                 >::from_client(client, Default::default())
-                .await?;
+                .await?
+                .with_admission_queue(queue)
+                .with_retry_policy(retry);
                 let engine = Arc::new(push_router);
                 self.manager
                     .add_embeddings_model(&model_entry.name, engine)?;
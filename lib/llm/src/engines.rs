@@ -26,8 +26,11 @@ use dynamo_runtime::pipeline::{Error, ManyOut, SingleIn};
 use dynamo_runtime::protocols::annotated::Annotated;
 
 use crate::backend::ExecutionContext;
+use crate::guided_decoding::{GuidedDecoding, GuidedDecodingValidator};
+use crate::http::service::error::HttpError;
 use crate::preprocessor::PreprocessedRequest;
-use crate::protocols::common::llm_backend::LLMEngineOutput;
+use crate::protocols::common::llm_backend::{LLMEngineOutput, TopLogProb};
+use crate::protocols::common::GuidedDecodingProvider;
 use crate::protocols::openai::{
     chat_completions::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse},
     completions::{prompt_to_string, NvCreateCompletionRequest, NvCreateCompletionResponse},
@@ -96,10 +99,11 @@ impl AsyncEngine<SingleIn<PreprocessedRequest>, ManyOut<Annotated<LLMEngineOutpu
         let (request, context) = incoming_request.into_parts();
         let ctx = context.context();
 
+        let logprobs = request.output_options.logprobs.is_some();
         let output = stream! {
             for tok in request.token_ids {
                 tokio::time::sleep(*TOKEN_ECHO_DELAY).await;
-                yield delta_core(tok);
+                yield delta_core(tok, logprobs);
             }
             yield Annotated::from_data(LLMEngineOutput::stop());
         };
@@ -107,13 +111,22 @@ impl AsyncEngine<SingleIn<PreprocessedRequest>, ManyOut<Annotated<LLMEngineOutpu
     }
 }
 
-fn delta_core(tok: u32) -> Annotated<LLMEngineOutput> {
+/// The echoed token is certain, so when log probabilities are requested it has a log probability
+/// of 0 and is its own only candidate.
+fn delta_core(tok: u32, logprobs: bool) -> Annotated<LLMEngineOutput> {
     let delta = LLMEngineOutput {
         token_ids: vec![tok],
         tokens: None,
         text: None,
         cum_log_probs: None,
-        log_probs: None,
+        log_probs: logprobs.then(|| vec![0.0]),
+        top_logprobs: logprobs.then(|| {
+            vec![vec![TopLogProb {
+                token_id: tok,
+                token: None,
+                logprob: 0.0,
+            }]]
+        }),
         finish_reason: None,
         index: None,
     };
@@ -124,6 +137,27 @@ fn delta_core(tok: u32) -> Annotated<LLMEngineOutput> {
 /// Useful for testing ingress such as service-http.
 struct EchoEngineFull {}
 
+/// The echo engine cannot steer its output, so a structured output request is only accepted if
+/// the echoed prompt already satisfies it. [`EchoEngineCore`] is covered by the preprocessor,
+/// which validates the output of engines that do not enforce the constraint.
+fn validate_guided_decoding(
+    guided_decoding: Option<GuidedDecoding>,
+    echo: &str,
+) -> Result<(), Error> {
+    if let Some(guided_decoding) = guided_decoding {
+        GuidedDecodingValidator::new(&guided_decoding)
+            .map_err(|err| HttpError {
+                code: 400,
+                message: format!("{err:#}"),
+            })?
+            .validate(echo)
+            .map_err(|err| {
+                anyhow::anyhow!("Echoed prompt violates the structured output constraint: {err:#}")
+            })?;
+    }
+    Ok(())
+}
+
 /// Engine that dispatches requests to either OpenAICompletions
 //or OpenAIChatCompletions engine
 pub struct EngineDispatcher<E> {
@@ -180,6 +214,7 @@ impl
         let (request, context) = incoming_request.transfer(());
         let deltas = request.response_generator();
         let ctx = context.context();
+        let guided_decoding = request.extract_guided_decoding()?;
         let req = request.inner.messages.into_iter().next_back().unwrap();
 
         let prompt = match req {
@@ -193,6 +228,7 @@ impl
             }
             _ => anyhow::bail!("Invalid request type, expected User message"),
         };
+        validate_guided_decoding(guided_decoding, &prompt)?;
 
         let output = stream! {
             let mut id = 1;
@@ -234,15 +270,16 @@ impl
         let deltas = request.response_generator();
         let ctx = context.context();
         let chars_string = prompt_to_string(&request.inner.prompt);
+        validate_guided_decoding(request.extract_guided_decoding()?, &chars_string)?;
         let output = stream! {
             let mut id = 1;
             for c in chars_string.chars() {
                 tokio::time::sleep(*TOKEN_ECHO_DELAY).await;
-                let response = deltas.create_choice(0, Some(c.to_string()), None);
+                let response = deltas.create_choice(0, Some(c.to_string()), None, None);
                 yield Annotated{ id: Some(id.to_string()), data: Some(response), event: None, comment: None };
                 id += 1;
             }
-            let response = deltas.create_choice(0, None, Some(async_openai::types::CompletionFinishReason::Stop));
+            let response = deltas.create_choice(0, None, Some(async_openai::types::CompletionFinishReason::Stop), None);
             yield Annotated { id: Some(id.to_string()), data: Some(response), event: None, comment: None };
 
         };
@@ -0,0 +1,930 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Guided Decoding
+//!
+//! Structured output requests (`response_format` and the `guided_*` [`NvExt`] fields) are
+//! normalized into a [`GuidedDecoding`] constraint which travels to the engine as part of the
+//! [`PreprocessedRequest`].
+//!
+//! Engines which can constrain sampling translate the constraint into their native token-level
+//! form; [`to_gbnf`] compiles it into a GBNF grammar for llama.cpp style grammar samplers and
+//! [`choice_regex`] turns a list of choices into a regular expression. Engines which cannot
+//! enforce the constraint are covered by the [`GuidedDecodingValidator`], which the preprocessor
+//! applies to the generated text once each choice completes.
+//!
+//! The validator only supports the JSON schema keywords listed in [`SUPPORTED_KEYWORDS`] and
+//! rejects schemas using any other, and it cannot check text against a grammar, so grammars are
+//! only accepted by engines which sample with them.
+//!
+//! [`NvExt`]: crate::protocols::openai::nvext::NvExt
+//! [`PreprocessedRequest`]: crate::protocols::common::preprocessor::PreprocessedRequest
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde_json::Value;
+
+pub use crate::protocols::common::GuidedDecoding;
+
+/// Generic JSON grammar, following llama.cpp's `json.gbnf`.
+const JSON_GBNF: &str = r#"value ::= object | array | string | number | ("true" | "false" | "null") ws
+object ::= "{" ws ( string ":" ws value ( "," ws string ":" ws value )* )? "}" ws
+array ::= "[" ws ( value ( "," ws value )* )? "]" ws
+string ::= "\"" ( [^"\\\x7F\x00-\x1F] | "\\" ( ["\\bfnrt] | "u" [0-9a-fA-F]{4} ) )* "\"" ws
+number ::= ( "-"? ( [0-9] | [1-9] [0-9]{0,15} ) ) ( "." [0-9]+ )? ( [eE] [-+]? [0-9] [1-9]{0,15} )? ws
+integer ::= ( "-"? ( [0-9] | [1-9] [0-9]{0,15} ) ) ws
+boolean ::= ( "true" | "false" ) ws
+null ::= "null" ws
+ws ::= | " " | "\n" [ \t]{0,20}
+"#;
+
+/// JSON schema keywords the [`GuidedDecodingValidator`] supports.
+pub const SUPPORTED_KEYWORDS: &[&str] = &[
+    "$defs",
+    "definitions",
+    "type",
+    "enum",
+    "const",
+    "$ref",
+    "allOf",
+    "anyOf",
+    "oneOf",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "minItems",
+    "maxItems",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+];
+
+/// JSON schema keywords which annotate the schema without constraining the value. `format` is
+/// an annotation unless the format assertion vocabulary is enabled.
+const ANNOTATION_KEYWORDS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+    "format",
+];
+
+/// The JSON schema types.
+const JSON_TYPES: &[&str] = &[
+    "object", "array", "string", "number", "integer", "boolean", "null",
+];
+
+/// Checks that generated text satisfies a [`GuidedDecoding`] constraint.
+#[derive(Debug, Clone)]
+pub struct GuidedDecodingValidator {
+    constraint: Constraint,
+}
+
+#[derive(Debug, Clone)]
+enum Constraint {
+    JsonObject,
+    JsonSchema(Value),
+    Regex(Regex),
+    Choice(Vec<String>),
+}
+
+impl GuidedDecodingValidator {
+    /// Compiles the constraint, failing if it is malformed or cannot be validated.
+    pub fn new(guided_decoding: &GuidedDecoding) -> Result<Self> {
+        let constraint = match guided_decoding {
+            GuidedDecoding::JsonObject => Constraint::JsonObject,
+            GuidedDecoding::JsonSchema(schema) => {
+                check_json_schema(schema, schema, "$")?;
+                Constraint::JsonSchema(schema.clone())
+            }
+            GuidedDecoding::Regex(pattern) => Constraint::Regex(
+                Regex::new(&format!("^(?:{pattern})$"))
+                    .with_context(|| format!("Invalid guided decoding regex: {pattern}"))?,
+            ),
+            GuidedDecoding::Choice(choices) => {
+                if choices.is_empty() {
+                    anyhow::bail!("guided_choice must contain at least one choice");
+                }
+                Constraint::Choice(choices.clone())
+            }
+            GuidedDecoding::Grammar(_) => {
+                anyhow::bail!(
+                    "guided_grammar is not supported by this engine, the output cannot be checked against a grammar"
+                );
+            }
+        };
+        Ok(Self { constraint })
+    }
+
+    /// Returns an error describing why `text` does not satisfy the constraint.
+    pub fn validate(&self, text: &str) -> Result<()> {
+        match &self.constraint {
+            Constraint::JsonObject => {
+                let value: Value =
+                    serde_json::from_str(text.trim()).context("Output is not valid JSON")?;
+                if !value.is_object() {
+                    anyhow::bail!("Output is not a JSON object");
+                }
+            }
+            Constraint::JsonSchema(schema) => {
+                let value: Value =
+                    serde_json::from_str(text.trim()).context("Output is not valid JSON")?;
+                validate_json_schema(schema, schema, &value, "$")
+                    .context("Output does not match the JSON schema")?;
+            }
+            Constraint::Regex(regex) => {
+                if !regex.is_match(text) {
+                    anyhow::bail!("Output does not match the regex {}", regex.as_str());
+                }
+            }
+            Constraint::Choice(choices) => {
+                if !choices.iter().any(|choice| choice == text) {
+                    anyhow::bail!("Output is not one of the choices {:?}", choices);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds a regular expression matching exactly one of `choices`.
+pub fn choice_regex(choices: &[String]) -> String {
+    choices
+        .iter()
+        .map(|choice| regex::escape(choice))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Compiles the constraint into a GBNF grammar with a `root` rule.
+///
+/// Returns `None` for regular expressions, which have no GBNF translation. JSON schema keywords
+/// which cannot be expressed in the grammar (string patterns, numeric ranges, ...) are relaxed;
+/// the [`GuidedDecodingValidator`] catches any output violating them.
+pub fn to_gbnf(guided_decoding: &GuidedDecoding) -> Result<Option<String>> {
+    let grammar = match guided_decoding {
+        GuidedDecoding::JsonObject => format!("root ::= object\n{JSON_GBNF}"),
+        GuidedDecoding::JsonSchema(schema) => {
+            let mut builder = GbnfBuilder::new(schema);
+            let root = builder.visit(schema, "root-value")?;
+            let root = builder.add_rule("root-value", root);
+            builder.finish(&root)
+        }
+        GuidedDecoding::Choice(choices) => {
+            let choices = choices
+                .iter()
+                .map(|choice| gbnf_literal(choice))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            format!("root ::= {choices}\n")
+        }
+        GuidedDecoding::Grammar(grammar) => grammar.clone(),
+        GuidedDecoding::Regex(_) => return Ok(None),
+    };
+    Ok(Some(grammar))
+}
+
+/// Translates a JSON schema into GBNF rules, one rule per (sub)schema.
+struct GbnfBuilder<'a> {
+    root_schema: &'a Value,
+    rules: Vec<(String, String)>,
+    refs: HashMap<String, String>,
+}
+
+impl<'a> GbnfBuilder<'a> {
+    fn new(root_schema: &'a Value) -> Self {
+        Self {
+            root_schema,
+            rules: Vec::new(),
+            refs: HashMap::new(),
+        }
+    }
+
+    fn finish(self, root: &str) -> String {
+        let mut grammar = format!("root ::= {root}\n");
+        for (name, body) in self.rules {
+            grammar.push_str(&format!("{name} ::= {body}\n"));
+        }
+        grammar.push_str(JSON_GBNF);
+        grammar
+    }
+
+    /// Adds a rule for `body`, returning its (unique) name.
+    fn add_rule(&mut self, name: &str, body: String) -> String {
+        let name = self.unique_name(name);
+        self.rules.push((name.clone(), body));
+        name
+    }
+
+    fn unique_name(&self, name: &str) -> String {
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let taken = |candidate: &str| {
+            JSON_GBNF.contains(&format!("\n{candidate} ::="))
+                || JSON_GBNF.starts_with(&format!("{candidate} ::="))
+                || self.rules.iter().any(|(rule, _)| rule == candidate)
+                || self.refs.values().any(|rule| rule == candidate)
+        };
+        (0..)
+            .map(|i| {
+                if i == 0 {
+                    name.clone()
+                } else {
+                    format!("{name}-{i}")
+                }
+            })
+            .find(|candidate| !taken(candidate))
+            .unwrap()
+    }
+
+    /// Returns a GBNF expression matching `schema`.
+    fn visit(&mut self, schema: &Value, name: &str) -> Result<String> {
+        let schema = match schema {
+            Value::Bool(true) => return Ok("value".to_string()),
+            Value::Bool(false) => anyhow::bail!("JSON schema `false` matches nothing"),
+            Value::Object(schema) => schema,
+            _ => anyhow::bail!("JSON schema must be an object or a boolean"),
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            if let Some(rule) = self.refs.get(reference) {
+                return Ok(rule.clone());
+            }
+            let target = resolve_ref(self.root_schema, reference)?;
+            let rule = self.unique_name(&format!("ref-{}", reference.trim_start_matches("#/")));
+            // register the rule before visiting the target so recursive schemas terminate
+            self.refs.insert(reference.to_string(), rule.clone());
+            let body = self.visit(target, &rule)?;
+            self.rules.push((rule.clone(), body));
+            return Ok(rule);
+        }
+
+        if let Some(value) = schema.get("const") {
+            return Ok(json_literal(value));
+        }
+
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            let values = values.iter().map(json_literal).collect::<Vec<_>>();
+            return Ok(format!("( {} )", values.join(" | ")));
+        }
+
+        if let Some(variants) = schema
+            .get("anyOf")
+            .or_else(|| schema.get("oneOf"))
+            .and_then(Value::as_array)
+        {
+            let mut alternatives = Vec::with_capacity(variants.len());
+            for (i, variant) in variants.iter().enumerate() {
+                let expression = self.visit(variant, &format!("{name}-{i}"))?;
+                alternatives.push(self.add_rule(&format!("{name}-{i}"), expression));
+            }
+            return Ok(format!("( {} )", alternatives.join(" | ")));
+        }
+
+        if let Some(variants) = schema.get("allOf").and_then(Value::as_array) {
+            if let [variant] = variants.as_slice() {
+                return self.visit(variant, name);
+            }
+            // intersections are left to the validator
+            return Ok("value".to_string());
+        }
+
+        match schema.get("type") {
+            Some(Value::String(ty)) => self.visit_type(schema, ty, name),
+            Some(Value::Array(types)) => {
+                let mut alternatives = Vec::with_capacity(types.len());
+                for ty in types.iter().filter_map(Value::as_str) {
+                    alternatives.push(self.visit_type(schema, ty, &format!("{name}-{ty}"))?);
+                }
+                Ok(format!("( {} )", alternatives.join(" | ")))
+            }
+            _ if schema.contains_key("properties") => self.visit_type(schema, "object", name),
+            _ if schema.contains_key("items") => self.visit_type(schema, "array", name),
+            _ => Ok("value".to_string()),
+        }
+    }
+
+    fn visit_type(
+        &mut self,
+        schema: &serde_json::Map<String, Value>,
+        ty: &str,
+        name: &str,
+    ) -> Result<String> {
+        let expression = match ty {
+            "object" => self.visit_object(schema, name)?,
+            "array" => self.visit_array(schema, name)?,
+            "string" => "string".to_string(),
+            "number" => "number".to_string(),
+            "integer" => "integer".to_string(),
+            "boolean" => "boolean".to_string(),
+            "null" => "null".to_string(),
+            _ => anyhow::bail!("Unsupported JSON schema type: {ty}"),
+        };
+        Ok(expression)
+    }
+
+    fn visit_object(
+        &mut self,
+        schema: &serde_json::Map<String, Value>,
+        name: &str,
+    ) -> Result<String> {
+        let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+            return match schema.get("additionalProperties") {
+                Some(additional) if additional.is_object() => {
+                    let value = self.visit(additional, &format!("{name}-additional"))?;
+                    let value = self.add_rule(&format!("{name}-additional"), value);
+                    Ok(format!(
+                        r#""{{" ws ( string ":" ws {value} ( "," ws string ":" ws {value} )* )? "}}" ws"#
+                    ))
+                }
+                _ => Ok("object".to_string()),
+            };
+        };
+
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|required| required.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        let mut required_members = Vec::new();
+        let mut optional_members = Vec::new();
+        for (property, property_schema) in properties {
+            let rule_name = format!("{name}-{property}");
+            let value = self.visit(property_schema, &rule_name)?;
+            let value = self.add_rule(&rule_name, value);
+            let member = format!(r#"{} ":" ws {value}"#, gbnf_literal(&json_string(property)));
+            if required.contains(&property.as_str()) {
+                required_members.push(member);
+            } else {
+                optional_members.push(member);
+            }
+        }
+
+        let members = if !required_members.is_empty() {
+            // required members in order, each optional member may follow
+            let mut members = required_members.join(r#" "," ws "#);
+            for member in &optional_members {
+                members.push_str(&format!(r#" ( "," ws {member} )?"#));
+            }
+            members
+        } else if !optional_members.is_empty() {
+            // any of the optional members may come first, followed by any of the later ones
+            let alternatives = (0..optional_members.len())
+                .map(|first| {
+                    let mut members = optional_members[first].clone();
+                    for member in &optional_members[first + 1..] {
+                        members.push_str(&format!(r#" ( "," ws {member} )?"#));
+                    }
+                    members
+                })
+                .collect::<Vec<_>>();
+            format!("( ( {} ) )?", alternatives.join(" ) | ( "))
+        } else {
+            String::new()
+        };
+
+        Ok(format!(r#""{{" ws {members} "}}" ws"#))
+    }
+
+    fn visit_array(
+        &mut self,
+        schema: &serde_json::Map<String, Value>,
+        name: &str,
+    ) -> Result<String> {
+        let item = match schema.get("items") {
+            Some(items) => {
+                let item = self.visit(items, &format!("{name}-item"))?;
+                self.add_rule(&format!("{name}-item"), item)
+            }
+            None => "value".to_string(),
+        };
+
+        let min_items = schema.get("minItems").and_then(Value::as_u64).unwrap_or(0);
+        if min_items > 0 {
+            Ok(format!(r#""[" ws {item} ( "," ws {item} )* "]" ws"#))
+        } else {
+            Ok(format!(r#""[" ws ( {item} ( "," ws {item} )* )? "]" ws"#))
+        }
+    }
+}
+
+/// Resolves a local JSON pointer reference such as `#/$defs/Item`.
+fn resolve_ref<'a>(root: &'a Value, reference: &str) -> Result<&'a Value> {
+    let pointer = reference
+        .strip_prefix('#')
+        .with_context(|| format!("Only local JSON schema references are supported: {reference}"))?;
+    root.pointer(pointer)
+        .with_context(|| format!("Unresolved JSON schema reference: {reference}"))
+}
+
+/// GBNF expression matching the JSON serialization of `value`.
+fn json_literal(value: &Value) -> String {
+    format!("{} ws", gbnf_literal(&value.to_string()))
+}
+
+fn json_string(value: &str) -> String {
+    Value::String(value.to_string()).to_string()
+}
+
+/// Quotes `text` as a GBNF string literal.
+fn gbnf_literal(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Checks that `schema` only uses the [`SUPPORTED_KEYWORDS`], with well-formed values, so that
+/// the validator does not silently ignore a constraint.
+fn check_json_schema(root: &Value, schema: &Value, path: &str) -> Result<()> {
+    let schema = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(schema) => schema,
+        _ => anyhow::bail!("{path}: JSON schema must be an object or a boolean"),
+    };
+
+    for (keyword, value) in schema {
+        let keyword_path = format!("{path}.{keyword}");
+        match keyword.as_str() {
+            "properties" | "$defs" | "definitions" => {
+                let subschemas = value
+                    .as_object()
+                    .with_context(|| format!("{keyword_path}: expected an object of schemas"))?;
+                for (name, subschema) in subschemas {
+                    check_json_schema(root, subschema, &format!("{keyword_path}.{name}"))?;
+                }
+            }
+            "items" | "additionalProperties" => check_json_schema(root, value, &keyword_path)?,
+            "allOf" | "anyOf" | "oneOf" => {
+                let variants = value
+                    .as_array()
+                    .filter(|variants| !variants.is_empty())
+                    .with_context(|| format!("{keyword_path}: expected a non-empty array"))?;
+                for (i, variant) in variants.iter().enumerate() {
+                    check_json_schema(root, variant, &format!("{keyword_path}[{i}]"))?;
+                }
+            }
+            "$ref" => {
+                let reference = value
+                    .as_str()
+                    .with_context(|| format!("{keyword_path}: expected a string"))?;
+                resolve_ref(root, reference)?;
+            }
+            "type" => {
+                let types = match value {
+                    Value::String(ty) => vec![ty.as_str()],
+                    Value::Array(types) => types
+                        .iter()
+                        .map(Value::as_str)
+                        .collect::<Option<_>>()
+                        .with_context(|| {
+                        format!("{keyword_path}: expected an array of types")
+                    })?,
+                    _ => anyhow::bail!("{keyword_path}: expected a type or an array of types"),
+                };
+                if let Some(ty) = types.iter().find(|ty| !JSON_TYPES.contains(ty)) {
+                    anyhow::bail!("{keyword_path}: unknown type {ty}");
+                }
+            }
+            "enum" if !value.is_array() => anyhow::bail!("{keyword_path}: expected an array"),
+            "required"
+                if !value
+                    .as_array()
+                    .is_some_and(|required| required.iter().all(Value::is_string)) =>
+            {
+                anyhow::bail!("{keyword_path}: expected an array of property names")
+            }
+            "minItems" | "maxItems" | "minLength" | "maxLength" if !value.is_u64() => {
+                anyhow::bail!("{keyword_path}: expected a non-negative integer")
+            }
+            "minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum"
+                if !value.is_number() =>
+            {
+                anyhow::bail!("{keyword_path}: expected a number")
+            }
+            "pattern" => {
+                let pattern = value
+                    .as_str()
+                    .with_context(|| format!("{keyword_path}: expected a string"))?;
+                Regex::new(pattern)
+                    .with_context(|| format!("{keyword_path}: invalid pattern {pattern}"))?;
+            }
+            keyword
+                if SUPPORTED_KEYWORDS.contains(&keyword)
+                    || ANNOTATION_KEYWORDS.contains(&keyword) => {}
+            _ => anyhow::bail!("{path}: unsupported JSON schema keyword {keyword}"),
+        }
+    }
+    Ok(())
+}
+
+/// Validates `value` against the subset of JSON schema checked by [`check_json_schema`].
+fn validate_json_schema(root: &Value, schema: &Value, value: &Value, path: &str) -> Result<()> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => anyhow::bail!("{path}: no value is allowed"),
+        Value::Object(schema) => schema,
+        _ => anyhow::bail!("{path}: invalid schema"),
+    };
+
+    // the keywords next to a reference apply as well
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        validate_json_schema(root, resolve_ref(root, reference)?, value, path)?;
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            anyhow::bail!("{path}: expected {expected}");
+        }
+    }
+
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        if !values.contains(value) {
+            anyhow::bail!(
+                "{path}: {value} is not one of {}",
+                Value::from(values.clone())
+            );
+        }
+    }
+
+    match schema.get("type") {
+        Some(Value::String(ty)) if !type_matches(ty, value) => {
+            anyhow::bail!("{path}: expected type {ty}");
+        }
+        Some(Value::Array(types))
+            if !types
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|ty| type_matches(ty, value)) =>
+        {
+            anyhow::bail!(
+                "{path}: expected one of the types {}",
+                Value::from(types.clone())
+            );
+        }
+        _ => {}
+    }
+
+    if let Some(variants) = schema.get("allOf").and_then(Value::as_array) {
+        for variant in variants {
+            validate_json_schema(root, variant, value, path)?;
+        }
+    }
+    if let Some(variants) = schema.get("anyOf").and_then(Value::as_array) {
+        if !variants
+            .iter()
+            .any(|variant| validate_json_schema(root, variant, value, path).is_ok())
+        {
+            anyhow::bail!("{path}: does not match any of the anyOf schemas");
+        }
+    }
+    if let Some(variants) = schema.get("oneOf").and_then(Value::as_array) {
+        let matches = variants
+            .iter()
+            .filter(|variant| validate_json_schema(root, variant, value, path).is_ok())
+            .count();
+        if matches != 1 {
+            anyhow::bail!("{path}: matches {matches} of the oneOf schemas, expected exactly 1");
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for property in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(property) {
+                        anyhow::bail!("{path}: missing required property {property}");
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (property, property_value) in object {
+                let property_path = format!("{path}.{property}");
+                match properties.and_then(|properties| properties.get(property)) {
+                    Some(property_schema) => {
+                        validate_json_schema(root, property_schema, property_value, &property_path)?
+                    }
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            anyhow::bail!("{path}: unexpected property {property}")
+                        }
+                        Some(additional) => {
+                            validate_json_schema(root, additional, property_value, &property_path)?
+                        }
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < min {
+                    anyhow::bail!("{path}: expected at least {min} items");
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if (items.len() as u64) > max {
+                    anyhow::bail!("{path}: expected at most {max} items");
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_json_schema(root, item_schema, item, &format!("{path}[{i}]"))?;
+                }
+            }
+        }
+        Value::String(string) => {
+            let length = string.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if length < min {
+                    anyhow::bail!("{path}: expected at least {min} characters");
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if length > max {
+                    anyhow::bail!("{path}: expected at most {max} characters");
+                }
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                let regex = Regex::new(pattern)
+                    .with_context(|| format!("{path}: invalid pattern {pattern}"))?;
+                if !regex.is_match(string) {
+                    anyhow::bail!("{path}: does not match the pattern {pattern}");
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+            if bound("minimum").is_some_and(|min| number < min)
+                || bound("exclusiveMinimum").is_some_and(|min| number <= min)
+                || bound("maximum").is_some_and(|max| number > max)
+                || bound("exclusiveMaximum").is_some_and(|max| number >= max)
+            {
+                anyhow::bail!("{path}: {number} is out of range");
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn type_matches(ty: &str, value: &Value) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64()
+                || value.is_u64()
+                || value.as_f64().is_some_and(|number| number.fract() == 0.0)
+        }
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn person_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"$ref": "#/$defs/tag"}}
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false,
+            "$defs": {
+                "tag": {"enum": ["a", "b"]}
+            }
+        })
+    }
+
+    #[test]
+    fn test_json_schema_validation() {
+        let validator =
+            GuidedDecodingValidator::new(&GuidedDecoding::JsonSchema(person_schema())).unwrap();
+
+        assert!(validator
+            .validate(r#"{"name": "Ada", "age": 36, "tags": ["a"]}"#)
+            .is_ok());
+        assert!(validator.validate(r#"{"name": "Ada"}"#).is_err());
+        assert!(validator.validate(r#"{"name": "Ada", "age": -1}"#).is_err());
+        assert!(validator
+            .validate(r#"{"name": "Ada", "age": 1, "tags": ["c"]}"#)
+            .is_err());
+        assert!(validator
+            .validate(r#"{"name": "Ada", "age": 1, "extra": true}"#)
+            .is_err());
+        assert!(validator.validate("not json").is_err());
+    }
+
+    #[test]
+    fn test_json_schema_refs() {
+        let schema = json!({
+            "$ref": "#/$defs/node",
+            "$defs": {
+                "node": {
+                    "type": "object",
+                    "properties": {
+                        "name": {"$ref": "#/$defs/name", "maxLength": 3},
+                        "children": {"type": "array", "items": {"$ref": "#/$defs/node"}}
+                    },
+                    "required": ["name"]
+                },
+                "name": {"type": "string", "minLength": 1}
+            }
+        });
+        let validator = GuidedDecodingValidator::new(&GuidedDecoding::JsonSchema(schema)).unwrap();
+
+        assert!(validator
+            .validate(r#"{"name": "a", "children": [{"name": "b", "children": []}]}"#)
+            .is_ok());
+        assert!(validator
+            .validate(r#"{"name": "a", "children": [{"children": []}]}"#)
+            .is_err());
+        assert!(validator
+            .validate(r#"{"name": "a", "children": [{"name": ""}]}"#)
+            .is_err());
+        // keywords next to the reference apply as well
+        assert!(validator.validate(r#"{"name": "abcd"}"#).is_err());
+    }
+
+    #[test]
+    fn test_json_schema_any_of_and_one_of() {
+        let schema = json!({
+            "anyOf": [{"type": "string"}, {"type": "integer", "minimum": 10}]
+        });
+        let validator = GuidedDecodingValidator::new(&GuidedDecoding::JsonSchema(schema)).unwrap();
+        assert!(validator.validate(r#""a""#).is_ok());
+        assert!(validator.validate("12").is_ok());
+        assert!(validator.validate("5").is_err());
+        assert!(validator.validate("null").is_err());
+
+        let schema = json!({
+            "oneOf": [{"type": "integer"}, {"type": "number", "maximum": 5}]
+        });
+        let validator = GuidedDecodingValidator::new(&GuidedDecoding::JsonSchema(schema)).unwrap();
+        assert!(validator.validate("7").is_ok());
+        assert!(validator.validate("4.5").is_ok());
+        // matches both variants
+        assert!(validator.validate("3").is_err());
+        assert!(validator.validate("7.5").is_err());
+    }
+
+    #[test]
+    fn test_json_schema_additional_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": {"id": {"type": "integer"}},
+            "additionalProperties": {"type": "string"}
+        });
+        let validator = GuidedDecodingValidator::new(&GuidedDecoding::JsonSchema(schema)).unwrap();
+        assert!(validator
+            .validate(r#"{"id": 1, "a": "x", "b": "y"}"#)
+            .is_ok());
+        assert!(validator.validate(r#"{"id": 1, "a": 2}"#).is_err());
+        assert!(validator.validate(r#"{"id": "1"}"#).is_err());
+
+        let schema = json!({"type": "object", "additionalProperties": false});
+        let validator = GuidedDecodingValidator::new(&GuidedDecoding::JsonSchema(schema)).unwrap();
+        assert!(validator.validate("{}").is_ok());
+        assert!(validator.validate(r#"{"a": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_json_schema() {
+        let unsupported = [
+            json!({"type": "object", "patternProperties": {"^a": {"type": "string"}}}),
+            json!({"properties": {"a": {"type": "string", "contentEncoding": "base64"}}}),
+            json!({"items": {"$ref": "#/$defs/missing"}}),
+            json!({"$ref": "https://example.com/schema.json"}),
+            json!({"type": "decimal"}),
+            json!({"type": "number", "exclusiveMinimum": true}),
+            json!({"anyOf": []}),
+            json!(["not", "a", "schema"]),
+        ];
+        for schema in unsupported {
+            assert!(
+                GuidedDecodingValidator::new(&GuidedDecoding::JsonSchema(schema.clone())).is_err(),
+                "{schema} should be rejected"
+            );
+        }
+
+        // annotations are accepted
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "Event",
+            "type": "string",
+            "format": "date-time",
+            "description": "When it happens"
+        });
+        assert!(GuidedDecodingValidator::new(&GuidedDecoding::JsonSchema(schema)).is_ok());
+    }
+
+    #[test]
+    fn test_grammar_cannot_be_validated() {
+        let grammar = GuidedDecoding::Grammar(r#"root ::= "yes" | "no""#.to_string());
+        assert!(GuidedDecodingValidator::new(&grammar).is_err());
+    }
+
+    #[test]
+    fn test_json_object_validation() {
+        let validator = GuidedDecodingValidator::new(&GuidedDecoding::JsonObject).unwrap();
+        assert!(validator.validate(" {\"a\": 1}\n").is_ok());
+        assert!(validator.validate("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn test_regex_and_choice_validation() {
+        let validator =
+            GuidedDecodingValidator::new(&GuidedDecoding::Regex("[0-9]+".to_string())).unwrap();
+        assert!(validator.validate("123").is_ok());
+        assert!(validator.validate("123a").is_err());
+
+        assert!(GuidedDecodingValidator::new(&GuidedDecoding::Regex("(".to_string())).is_err());
+
+        let choices = vec!["yes".to_string(), "no".to_string()];
+        let validator = GuidedDecodingValidator::new(&GuidedDecoding::Choice(choices)).unwrap();
+        assert!(validator.validate("yes").is_ok());
+        assert!(validator.validate("maybe").is_err());
+    }
+
+    #[test]
+    fn test_choice_regex() {
+        let regex = choice_regex(&["a.b".to_string(), "c".to_string()]);
+        assert_eq!(regex, r"a\.b|c");
+    }
+
+    #[test]
+    fn test_json_schema_to_gbnf() {
+        let grammar = to_gbnf(&GuidedDecoding::JsonSchema(person_schema()))
+            .unwrap()
+            .unwrap();
+
+        assert!(grammar.starts_with("root ::= root-value\n"));
+        assert!(grammar.contains(r#""\"name\"" ":" ws root-value-name"#));
+        assert!(grammar.contains(r#"( "," ws "\"tags\"" ":" ws root-value-tags )?"#));
+        assert!(grammar.contains("root-value-age ::= integer\n"));
+        assert!(grammar.contains(r#"ref--defs-tag ::= ( "\"a\"" ws | "\"b\"" ws )"#));
+        assert!(grammar.contains("\nvalue ::= "));
+    }
+
+    #[test]
+    fn test_choice_to_gbnf() {
+        let choices = vec!["yes".to_string(), "say \"no\"".to_string()];
+        let grammar = to_gbnf(&GuidedDecoding::Choice(choices)).unwrap().unwrap();
+        assert_eq!(grammar, "root ::= \"yes\" | \"say \\\"no\\\"\"\n");
+
+        assert!(to_gbnf(&GuidedDecoding::Regex("a+".to_string()))
+            .unwrap()
+            .is_none());
+    }
+}
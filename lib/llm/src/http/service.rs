@@ -20,9 +20,11 @@
 
 mod openai;
 
+pub mod auth;
 pub mod error;
 pub mod health;
 pub mod metrics;
+pub mod rate_limit;
 pub mod service_v2;
 
 pub use axum;
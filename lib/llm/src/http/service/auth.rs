@@ -0,0 +1,615 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Authentication and per-key authorization for the HTTP service
+//!
+//! Callers present a credential as `Authorization: Bearer <token>`, the scheme being
+//! case-insensitive. The token is checked by the
+//! configured [`Authenticator`]s in order; the first one that recognizes it produces the
+//! [`AuthContext`] of the caller, which is attached to the request as an extension. Handlers use
+//! the context to enforce the per-key model allow-list and to label metrics with the key id.
+//!
+//! Two authenticators are provided:
+//! - [`StaticKeyAuthenticator`]: static bearer keys loaded from a JSON file
+//! - [`JwtAuthenticator`]: JWTs signed with an HMAC secret or a key from a JWKS file
+//!
+//! A token rejected by one authenticator is still tried by the next, so e.g. JWTs signed with a
+//! rotated-out secret can be accepted by a second [`JwtAuthenticator`] during the rotation.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{Context as _, Result};
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use jsonwebtoken::{
+    jwk::{AlgorithmParameters, Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::Deserialize;
+
+use super::{metrics::Metrics, openai::ErrorResponse};
+
+/// Value for the `reason` label of the auth failure counter when no valid credential was presented
+pub const AUTH_FAILURE_UNAUTHORIZED: &str = "unauthorized";
+
+/// Value for the `reason` label of the auth failure counter when the key may not use the model
+pub const AUTH_FAILURE_FORBIDDEN: &str = "forbidden";
+
+/// Value for the `key_id` label when the caller could not be identified
+const UNKNOWN_KEY_ID: &str = "unknown";
+
+/// The identity of an authenticated caller
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthContext {
+    /// Identifier of the key, used in logs and as the `key_id` metrics label.
+    /// This is never the secret itself.
+    pub key_id: String,
+
+    /// Models the key may use; `None` allows every model
+    pub allowed_models: Option<HashSet<String>>,
+}
+
+impl AuthContext {
+    pub fn is_model_allowed(&self, model: &str) -> bool {
+        self.allowed_models
+            .as_ref()
+            .map(|models| models.contains(model))
+            .unwrap_or(true)
+    }
+}
+
+/// Verifies a bearer token
+pub trait Authenticator: Send + Sync {
+    /// Returns `Ok(None)` if the token is not a credential this authenticator handles, and an
+    /// error if the token looks like one but is not valid. In both cases the next authenticator
+    /// is tried.
+    fn authenticate(&self, token: &str) -> Result<Option<AuthContext>>;
+}
+
+/// Authentication configuration of the [`super::service_v2::HttpService`]
+#[derive(Clone, Default)]
+pub struct AuthConfig {
+    authenticators: Vec<Arc<dyn Authenticator>>,
+}
+
+impl AuthConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an authenticator; authenticators are tried in the order they were added
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticators.push(Arc::new(authenticator));
+        self
+    }
+
+    /// Returns true if at least one authenticator is configured
+    pub fn is_enabled(&self) -> bool {
+        !self.authenticators.is_empty()
+    }
+
+    /// Authenticates a bearer token against the configured authenticators. If none accepts the
+    /// token, the error of the first one that rejected it is returned.
+    pub fn authenticate(&self, token: &str) -> Result<AuthContext> {
+        let mut rejection = None;
+        for authenticator in &self.authenticators {
+            match authenticator.authenticate(token) {
+                Ok(Some(context)) => return Ok(context),
+                Ok(None) => {}
+                Err(err) => {
+                    rejection.get_or_insert(err);
+                }
+            }
+        }
+        Err(rejection.unwrap_or_else(|| anyhow::anyhow!("Incorrect API key provided")))
+    }
+}
+
+/// A static key entry of the keys file
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKey {
+    /// Identifier of the key, e.g. the team or tenant it was issued to
+    pub id: String,
+
+    /// The bearer secret
+    pub key: String,
+
+    /// Models the key may use; every model if absent
+    #[serde(default)]
+    pub models: Option<HashSet<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiKeysFile {
+    keys: Vec<ApiKey>,
+}
+
+/// Authenticates static bearer keys.
+///
+/// Keys are indexed by their blake3 digest so that secrets are not kept in memory and lookups
+/// do not compare secrets byte by byte.
+pub struct StaticKeyAuthenticator {
+    keys: HashMap<blake3::Hash, AuthContext>,
+}
+
+impl StaticKeyAuthenticator {
+    pub fn new(keys: Vec<ApiKey>) -> Result<Self> {
+        let mut index = HashMap::with_capacity(keys.len());
+        let mut ids = HashSet::with_capacity(keys.len());
+        for key in keys {
+            if key.key.is_empty() {
+                anyhow::bail!("API key '{}' has an empty secret", key.id);
+            }
+            if !ids.insert(key.id.clone()) {
+                anyhow::bail!("Duplicate API key id '{}'", key.id);
+            }
+            let context = AuthContext {
+                key_id: key.id,
+                allowed_models: key.models,
+            };
+            if index
+                .insert(blake3::hash(key.key.as_bytes()), context)
+                .is_some()
+            {
+                anyhow::bail!("Duplicate API key secret");
+            }
+        }
+        Ok(Self { keys: index })
+    }
+
+    /// Loads keys from a JSON file of the form
+    /// `{"keys": [{"id": "team-a", "key": "<secret>", "models": ["llama-3-8b"]}]}`
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read API keys file {}", path.display()))?;
+        let file: ApiKeysFile = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse API keys file {}", path.display()))?;
+        Self::new(file.keys)
+    }
+}
+
+impl Authenticator for StaticKeyAuthenticator {
+    fn authenticate(&self, token: &str) -> Result<Option<AuthContext>> {
+        Ok(self.keys.get(&blake3::hash(token.as_bytes())).cloned())
+    }
+}
+
+/// Claims read from a JWT
+#[derive(Debug, Deserialize)]
+struct Claims {
+    /// Used as the key id of the caller
+    sub: Option<String>,
+
+    /// Models the token may use; every model if absent
+    #[serde(default)]
+    models: Option<HashSet<String>>,
+}
+
+enum JwtKeys {
+    Hmac(DecodingKey),
+    /// Keys by `kid`, with the algorithms each key may be used with
+    Jwks(HashMap<String, (DecodingKey, Vec<Algorithm>)>),
+}
+
+/// Authenticates JWTs signed with an HMAC secret or with a key from a JWKS file.
+///
+/// The `sub` claim becomes the key id and the optional `models` claim the model allow-list.
+/// `exp` is required; `iss` and `aud` are checked when configured.
+pub struct JwtAuthenticator {
+    keys: JwtKeys,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+impl JwtAuthenticator {
+    /// Verifies HS256/HS384/HS512 tokens signed with `secret`
+    pub fn hmac(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            keys: JwtKeys::Hmac(DecodingKey::from_secret(secret.as_ref())),
+            issuer: None,
+            audience: None,
+        }
+    }
+
+    /// Verifies HS256/HS384/HS512 tokens signed with the secret stored in a file. Surrounding
+    /// whitespace, like a trailing newline, is not part of the secret.
+    pub fn load_hmac(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read JWT secret file {}", path.display()))?;
+        let secret = contents.trim();
+        if secret.is_empty() {
+            anyhow::bail!("JWT secret file {} is empty", path.display());
+        }
+        Ok(Self::hmac(secret))
+    }
+
+    /// Verifies tokens signed with the keys of a JWKS file; tokens must name their key in `kid`
+    pub fn load_jwks(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read JWKS file {}", path.display()))?;
+        let jwks: JwkSet = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse JWKS file {}", path.display()))?;
+
+        let mut keys = HashMap::new();
+        for jwk in &jwks.keys {
+            let Some(kid) = jwk.common.key_id.clone() else {
+                tracing::warn!("Skipping JWK without a kid in {}", path.display());
+                continue;
+            };
+            let key = DecodingKey::from_jwk(jwk)
+                .with_context(|| format!("Invalid JWK '{kid}' in {}", path.display()))?;
+            let algorithms = match &jwk.common.key_algorithm {
+                // KeyAlgorithm and Algorithm share their JWA names
+                Some(algorithm) => {
+                    let name = serde_json::to_value(algorithm)?;
+                    let algorithm =
+                        serde_json::from_value::<Algorithm>(name).with_context(|| {
+                            format!(
+                                "Unsupported algorithm for JWK '{kid}' in {}",
+                                path.display()
+                            )
+                        })?;
+                    vec![algorithm]
+                }
+                None => key_type_algorithms(jwk),
+            };
+            keys.insert(kid, (key, algorithms));
+        }
+
+        if keys.is_empty() {
+            anyhow::bail!("JWKS file {} contains no usable keys", path.display());
+        }
+
+        Ok(Self {
+            keys: JwtKeys::Jwks(keys),
+            issuer: None,
+            audience: None,
+        })
+    }
+
+    /// Requires the `iss` claim to match
+    pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Requires the `aud` claim to contain the audience
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+}
+
+/// Algorithms usable with the type of key of `jwk`, used when the JWK does not pin its `alg`
+fn key_type_algorithms(jwk: &Jwk) -> Vec<Algorithm> {
+    match &jwk.algorithm {
+        AlgorithmParameters::OctetKey(_) => {
+            vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512]
+        }
+        AlgorithmParameters::RSA(_) => vec![
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+        ],
+        AlgorithmParameters::EllipticCurve(_) => vec![Algorithm::ES256, Algorithm::ES384],
+        AlgorithmParameters::OctetKeyPair(_) => vec![Algorithm::EdDSA],
+    }
+}
+
+impl Authenticator for JwtAuthenticator {
+    fn authenticate(&self, token: &str) -> Result<Option<AuthContext>> {
+        // anything that is not a compact JWS is left to the other authenticators
+        if token.split('.').count() != 3 {
+            return Ok(None);
+        }
+
+        let header = jsonwebtoken::decode_header(token).context("Invalid token header")?;
+
+        let (key, algorithms) = match &self.keys {
+            JwtKeys::Hmac(key) => (
+                key,
+                vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512],
+            ),
+            JwtKeys::Jwks(keys) => {
+                let kid = header.kid.as_deref().context("Token has no kid")?;
+                let (key, algorithms) = keys
+                    .get(kid)
+                    .with_context(|| format!("Unknown signing key '{kid}'"))?;
+                (key, algorithms.clone())
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = algorithms;
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let claims = jsonwebtoken::decode::<Claims>(token, key, &validation)
+            .context("Invalid token")?
+            .claims;
+
+        let key_id = claims.sub.context("Token has no sub claim")?;
+        Ok(Some(AuthContext {
+            key_id,
+            allowed_models: claims.models,
+        }))
+    }
+}
+
+/// The token of a `Bearer` authorization header; the scheme is case-insensitive
+fn bearer_token(value: &str) -> Option<&str> {
+    let (scheme, token) = value.trim_start().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let token = token.trim();
+    (!token.is_empty()).then_some(token)
+}
+
+/// State of the [`auth_middleware`]
+#[derive(Clone)]
+pub(crate) struct AuthState {
+    pub(crate) config: AuthConfig,
+    pub(crate) metrics: Arc<Metrics>,
+}
+
+/// Axum middleware authenticating the bearer token of every request, attaching the
+/// [`AuthContext`] of the caller to the request extensions. Requests without a valid
+/// credential are rejected with a 401.
+pub(crate) async fn auth_middleware(
+    State(state): State<AuthState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token);
+
+    let Some(token) = token else {
+        state
+            .metrics
+            .inc_auth_failure(UNKNOWN_KEY_ID, AUTH_FAILURE_UNAUTHORIZED);
+        return Err(ErrorResponse::unauthorized(
+            "Missing bearer token in the Authorization header",
+        ));
+    };
+
+    match state.config.authenticate(token) {
+        Ok(context) => {
+            request.extensions_mut().insert(context);
+            Ok(next.run(request).await)
+        }
+        Err(err) => {
+            tracing::debug!("Rejected request: {err:#}");
+            state
+                .metrics
+                .inc_auth_failure(UNKNOWN_KEY_ID, AUTH_FAILURE_UNAUTHORIZED);
+            Err(ErrorResponse::unauthorized(&format!("{err}")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    fn static_keys() -> StaticKeyAuthenticator {
+        StaticKeyAuthenticator::new(vec![
+            ApiKey {
+                id: "team-a".to_string(),
+                key: "secret-a".to_string(),
+                models: Some(HashSet::from(["llama".to_string()])),
+            },
+            ApiKey {
+                id: "admin".to_string(),
+                key: "secret-admin".to_string(),
+                models: None,
+            },
+        ])
+        .unwrap()
+    }
+
+    fn jwt(claims: serde_json::Value, secret: &[u8]) -> String {
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap()
+    }
+
+    fn exp() -> u64 {
+        jsonwebtoken::get_current_timestamp() + 600
+    }
+
+    #[test]
+    fn test_static_keys() {
+        let auth = static_keys();
+
+        let context = auth.authenticate("secret-a").unwrap().unwrap();
+        assert_eq!(context.key_id, "team-a");
+        assert!(context.is_model_allowed("llama"));
+        assert!(!context.is_model_allowed("mistral"));
+
+        let context = auth.authenticate("secret-admin").unwrap().unwrap();
+        assert!(context.is_model_allowed("mistral"));
+
+        assert!(auth.authenticate("secret-b").unwrap().is_none());
+
+        // ids must be unique
+        assert!(StaticKeyAuthenticator::new(vec![
+            ApiKey {
+                id: "a".to_string(),
+                key: "1".to_string(),
+                models: None,
+            },
+            ApiKey {
+                id: "a".to_string(),
+                key: "2".to_string(),
+                models: None,
+            },
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_load_static_keys() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(
+            &mut file,
+            br#"{"keys": [{"id": "team-a", "key": "secret-a", "models": ["llama"]}]}"#,
+        )
+        .unwrap();
+
+        let auth = StaticKeyAuthenticator::load(file.path()).unwrap();
+        let context = auth.authenticate("secret-a").unwrap().unwrap();
+        assert_eq!(context.key_id, "team-a");
+    }
+
+    #[test]
+    fn test_hmac_jwt() {
+        let auth = JwtAuthenticator::hmac(b"jwt-secret").with_audience("dynamo");
+
+        let token = jwt(
+            serde_json::json!({"sub": "team-b", "exp": exp(), "aud": "dynamo", "models": ["llama"]}),
+            b"jwt-secret",
+        );
+        let context = auth.authenticate(&token).unwrap().unwrap();
+        assert_eq!(context.key_id, "team-b");
+        assert!(context.is_model_allowed("llama"));
+        assert!(!context.is_model_allowed("mistral"));
+
+        // wrong secret
+        let token = jwt(
+            serde_json::json!({"sub": "team-b", "exp": exp(), "aud": "dynamo"}),
+            b"other-secret",
+        );
+        assert!(auth.authenticate(&token).is_err());
+
+        // wrong audience
+        let token = jwt(
+            serde_json::json!({"sub": "team-b", "exp": exp(), "aud": "other"}),
+            b"jwt-secret",
+        );
+        assert!(auth.authenticate(&token).is_err());
+
+        // expired
+        let token = jwt(
+            serde_json::json!({"sub": "team-b", "exp": 1, "aud": "dynamo"}),
+            b"jwt-secret",
+        );
+        assert!(auth.authenticate(&token).is_err());
+
+        // not a jwt
+        assert!(auth.authenticate("secret-a").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_load_hmac_secret() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"jwt-secret\n").unwrap();
+        let auth = JwtAuthenticator::load_hmac(file.path()).unwrap();
+
+        let token = jwt(
+            serde_json::json!({"sub": "team-b", "exp": exp()}),
+            b"jwt-secret",
+        );
+        assert_eq!(auth.authenticate(&token).unwrap().unwrap().key_id, "team-b");
+    }
+
+    #[test]
+    fn test_jwks() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        // "jwks-secret" base64url encoded
+        std::io::Write::write_all(
+            &mut file,
+            br#"{"keys": [{"kty": "oct", "kid": "k1", "alg": "HS256", "k": "andrcy1zZWNyZXQ"}]}"#,
+        )
+        .unwrap();
+        let auth = JwtAuthenticator::load_jwks(file.path()).unwrap();
+
+        let claims = serde_json::json!({"sub": "team-c", "exp": exp()});
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("k1".to_string());
+        let token = encode(&header, &claims, &EncodingKey::from_secret(b"jwks-secret")).unwrap();
+        let context = auth.authenticate(&token).unwrap().unwrap();
+        assert_eq!(context.key_id, "team-c");
+
+        // unknown kid
+        header.kid = Some("k2".to_string());
+        let token = encode(&header, &claims, &EncodingKey::from_secret(b"jwks-secret")).unwrap();
+        assert!(auth.authenticate(&token).is_err());
+    }
+
+    #[test]
+    fn test_auth_config_chain() {
+        let config = AuthConfig::new()
+            .with_authenticator(static_keys())
+            .with_authenticator(JwtAuthenticator::hmac(b"jwt-secret"));
+        assert!(config.is_enabled());
+
+        assert_eq!(config.authenticate("secret-a").unwrap().key_id, "team-a");
+
+        let token = jwt(
+            serde_json::json!({"sub": "team-b", "exp": exp()}),
+            b"jwt-secret",
+        );
+        assert_eq!(config.authenticate(&token).unwrap().key_id, "team-b");
+
+        assert!(config.authenticate("nope").is_err());
+        assert!(!AuthConfig::new().is_enabled());
+
+        // a token rejected by an authenticator is still tried by the next one
+        let config = AuthConfig::new()
+            .with_authenticator(JwtAuthenticator::hmac(b"old-secret"))
+            .with_authenticator(JwtAuthenticator::hmac(b"jwt-secret"));
+        assert_eq!(config.authenticate(&token).unwrap().key_id, "team-b");
+        let token = jwt(
+            serde_json::json!({"sub": "team-b", "exp": exp()}),
+            b"other-secret",
+        );
+        assert!(config.authenticate(&token).is_err());
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer abc"), Some("abc"));
+        assert_eq!(bearer_token("bearer abc"), Some("abc"));
+        assert_eq!(bearer_token("BEARER  abc "), Some("abc"));
+        assert_eq!(bearer_token("Basic abc"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+        assert_eq!(bearer_token("Bearerabc"), None);
+    }
+}
@@ -29,10 +29,21 @@
 // limitations under the License.
 
 use super::{service_v2, RouteDoc};
-use axum::{http::Method, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use axum::{
+    extract::Query, http::Method, http::StatusCode, response::IntoResponse, routing::get, Json,
+    Router,
+};
+use serde::Deserialize;
 use serde_json::json;
 use std::sync::Arc;
 
+#[derive(Debug, Default, Deserialize)]
+struct HealthQuery {
+    /// Add the health of the workers of each model, if tracked
+    #[serde(default)]
+    detail: bool,
+}
+
 pub fn health_check_router(
     state: Arc<service_v2::State>,
     path: Option<String>,
@@ -50,6 +61,7 @@ pub fn health_check_router(
 
 async fn health_handler(
     axum::extract::State(state): axum::extract::State<Arc<service_v2::State>>,
+    Query(query): Query<HealthQuery>,
 ) -> impl IntoResponse {
     let model_entries = state.manager().get_model_entries();
 
@@ -66,12 +78,13 @@ async fn health_handler(
             .iter()
             .map(|entry| entry.endpoint.as_url())
             .collect();
-        (
-            StatusCode::OK,
-            Json(json!({
-                "status": "healthy",
-                "endpoints": endpoints
-            })),
-        )
+        let mut body = json!({
+            "status": "healthy",
+            "endpoints": endpoints
+        });
+        if query.detail {
+            body["instances"] = json!(state.manager().instance_health());
+        }
+        (StatusCode::OK, Json(body))
     }
 }
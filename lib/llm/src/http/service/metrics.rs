@@ -26,6 +26,10 @@ pub const REQUEST_TYPE_UNARY: &str = "unary";
 
 pub struct Metrics {
     request_counter: IntCounterVec,
+    key_request_counter: IntCounterVec,
+    auth_failure_counter: IntCounterVec,
+    rate_limited_counter: IntCounterVec,
+    cancelled_counter: IntCounterVec,
     inflight_gauge: IntGaugeVec,
     request_duration: HistogramVec,
     input_sequence_length: HistogramVec,
@@ -45,6 +49,7 @@ pub struct InflightGuard {
     request_type: RequestType,
     status: Status,
     timer: Instant,
+    key_id: Option<String>,
 }
 
 /// Requests will be logged by the type of endpoint hit
@@ -58,6 +63,9 @@ pub enum Endpoint {
 
     /// OAI Embeddings
     Embeddings,
+
+    /// OAI Responses
+    Responses,
 }
 
 /// Metrics for the HTTP service
@@ -99,6 +107,10 @@ impl Metrics {
     /// Create Metrics with the given prefix
     /// The following metrics will be created:
     /// - `{prefix}_http_service_requests_total` - IntCounterVec for the total number of requests processed
+    /// - `{prefix}_http_service_key_requests_total` - IntCounterVec for the requests processed per API key
+    /// - `{prefix}_http_service_auth_failures_total` - IntCounterVec for the requests rejected by authentication or authorization
+    /// - `{prefix}_http_service_rate_limited_total` - IntCounterVec for the requests rejected by rate limiting
+    /// - `{prefix}_http_service_cancelled_requests_total` - IntCounterVec for the streaming requests cancelled by a client disconnect
     /// - `{prefix}_http_service_inflight_requests` - IntGaugeVec for the number of inflight requests
     /// - `{prefix}_http_service_request_duration_seconds` - HistogramVec for the duration of requests
     /// - `{prefix}_http_service_input_sequence_tokens` - HistogramVec for input sequence length in tokens
@@ -115,6 +127,42 @@ impl Metrics {
         )
         .unwrap();
 
+        let key_request_counter = IntCounterVec::new(
+            Opts::new(
+                format!("{}_http_service_key_requests_total", prefix),
+                "Total number of LLM requests processed per API key",
+            ),
+            &["key_id", "model", "endpoint", "status"],
+        )
+        .unwrap();
+
+        let auth_failure_counter = IntCounterVec::new(
+            Opts::new(
+                format!("{}_http_service_auth_failures_total", prefix),
+                "Total number of requests rejected by authentication or authorization",
+            ),
+            &["key_id", "reason"],
+        )
+        .unwrap();
+
+        let rate_limited_counter = IntCounterVec::new(
+            Opts::new(
+                format!("{}_http_service_rate_limited_total", prefix),
+                "Total number of requests rejected by rate limiting",
+            ),
+            &["tenant", "limit"],
+        )
+        .unwrap();
+
+        let cancelled_counter = IntCounterVec::new(
+            Opts::new(
+                format!("{}_http_service_cancelled_requests_total", prefix),
+                "Total number of streaming requests cancelled because the client disconnected",
+            ),
+            &["model", "endpoint"],
+        )
+        .unwrap();
+
         let inflight_gauge = IntGaugeVec::new(
             Opts::new(
                 format!("{}_http_service_inflight_requests", prefix),
@@ -188,6 +236,10 @@ impl Metrics {
 
         Metrics {
             request_counter,
+            key_request_counter,
+            auth_failure_counter,
+            rate_limited_counter,
+            cancelled_counter,
             inflight_gauge,
             request_duration,
             input_sequence_length,
@@ -241,6 +293,59 @@ impl Metrics {
             .inc()
     }
 
+    /// Get the number of requests for the given API key and dimensions:
+    /// - model
+    /// - endpoint
+    /// - status (success/error)
+    pub fn get_key_request_counter(
+        &self,
+        key_id: &str,
+        model: &str,
+        endpoint: &Endpoint,
+        status: &Status,
+    ) -> u64 {
+        self.key_request_counter
+            .with_label_values(&[key_id, model, endpoint.as_str(), status.as_str()])
+            .get()
+    }
+
+    /// Get the number of requests rejected for the given key id and reason
+    /// (see [`super::auth::AUTH_FAILURE_UNAUTHORIZED`] and [`super::auth::AUTH_FAILURE_FORBIDDEN`])
+    pub fn get_auth_failure_counter(&self, key_id: &str, reason: &str) -> u64 {
+        self.auth_failure_counter
+            .with_label_values(&[key_id, reason])
+            .get()
+    }
+
+    pub(crate) fn inc_auth_failure(&self, key_id: &str, reason: &str) {
+        self.auth_failure_counter
+            .with_label_values(&[key_id, reason])
+            .inc()
+    }
+
+    /// Get the number of requests rejected by rate limiting for the given tenant and limit
+    /// (see [`super::rate_limit::LIMIT_REQUESTS`], [`super::rate_limit::LIMIT_PROMPT_TOKENS`] and
+    /// [`super::rate_limit::LIMIT_OUTPUT_TOKENS`])
+    pub fn get_rate_limited_counter(&self, tenant: &str, limit: &str) -> u64 {
+        self.rate_limited_counter
+            .with_label_values(&[tenant, limit])
+            .get()
+    }
+
+    pub(crate) fn inc_rate_limited(&self, tenant: &str, limit: &str) {
+        self.rate_limited_counter
+            .with_label_values(&[tenant, limit])
+            .inc()
+    }
+
+    /// Get the number of requests for the given model and endpoint that were cancelled because
+    /// the client disconnected before the response completed
+    pub fn get_cancelled_counter(&self, model: &str, endpoint: &Endpoint) -> u64 {
+        self.cancelled_counter
+            .with_label_values(&[model, endpoint.as_str()])
+            .get()
+    }
+
     /// Get the number if inflight requests for the given model
     pub fn get_inflight_count(&self, model: &str) -> i64 {
         self.inflight_gauge.with_label_values(&[model]).get()
@@ -256,6 +361,10 @@ impl Metrics {
 
     pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
         registry.register(Box::new(self.request_counter.clone()))?;
+        registry.register(Box::new(self.key_request_counter.clone()))?;
+        registry.register(Box::new(self.auth_failure_counter.clone()))?;
+        registry.register(Box::new(self.rate_limited_counter.clone()))?;
+        registry.register(Box::new(self.cancelled_counter.clone()))?;
         registry.register(Box::new(self.inflight_gauge.clone()))?;
         registry.register(Box::new(self.request_duration.clone()))?;
         registry.register(Box::new(self.input_sequence_length.clone()))?;
@@ -317,12 +426,27 @@ impl InflightGuard {
             request_type,
             status: Status::Error,
             timer,
+            key_id: None,
         }
     }
 
     pub(crate) fn mark_ok(&mut self) {
         self.status = Status::Success;
     }
+
+    /// Attribute the request to an API key; the per-key request counter is incremented on drop
+    pub(crate) fn set_key_id(&mut self, key_id: &str) {
+        self.key_id = Some(key_id.to_string());
+    }
+
+    /// The client disconnected before the response completed; the request is still counted
+    /// with an error status on drop
+    pub(crate) fn mark_cancelled(&mut self) {
+        self.metrics
+            .cancelled_counter
+            .with_label_values(&[&self.model, self.endpoint.as_str()])
+            .inc();
+    }
 }
 
 impl Drop for InflightGuard {
@@ -340,6 +464,18 @@ impl Drop for InflightGuard {
             &self.status,
         );
 
+        if let Some(key_id) = &self.key_id {
+            self.metrics
+                .key_request_counter
+                .with_label_values(&[
+                    key_id.as_str(),
+                    &self.model,
+                    self.endpoint.as_str(),
+                    self.status.as_str(),
+                ])
+                .inc();
+        }
+
         // Record the duration of the request
         self.metrics
             .request_duration
@@ -354,6 +490,7 @@ impl std::fmt::Display for Endpoint {
             Endpoint::Completions => write!(f, "completions"),
             Endpoint::ChatCompletions => write!(f, "chat_completions"),
             Endpoint::Embeddings => write!(f, "embeddings"),
+            Endpoint::Responses => write!(f, "responses"),
         }
     }
 }
@@ -364,6 +501,7 @@ impl Endpoint {
             Endpoint::Completions => "completions",
             Endpoint::ChatCompletions => "chat_completions",
             Endpoint::Embeddings => "embeddings",
+            Endpoint::Responses => "responses",
         }
     }
 }
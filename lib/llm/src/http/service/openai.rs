@@ -3,13 +3,13 @@
 
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{
         sse::{Event, KeepAlive, Sse},
         IntoResponse, Response,
     },
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
 use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
@@ -20,17 +20,22 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 use tokio_stream::wrappers::ReceiverStream;
+use tracing::Instrument;
 
 use super::{
+    auth::{AuthContext, AUTH_FAILURE_FORBIDDEN},
     error::HttpError,
     metrics::{Endpoint, InflightGuard, ResponseMetricCollector},
+    rate_limit::{RateLimited, TenantUsage, TENANT_USAGE_KEY},
     service_v2, RouteDoc,
 };
 
 use crate::preprocessor::LLMMetricAnnotation;
 use crate::protocols::openai::embeddings::{NvCreateEmbeddingRequest, NvCreateEmbeddingResponse};
 use crate::protocols::openai::{
-    chat_completions::NvCreateChatCompletionResponse, completions::NvCreateCompletionResponse,
+    chat_completions::NvCreateChatCompletionResponse,
+    completions::NvCreateCompletionResponse,
+    responses::{response_event_stream, NvCreateResponse, NvResponse, ResponseEventGenerator},
 };
 use crate::request_template::RequestTemplate;
 use crate::types::{
@@ -40,7 +45,13 @@ use crate::types::{
     Annotated,
 };
 
+use crate::protocols::openai::nvext::NvExt;
+use dynamo_runtime::pipeline::network::egress::queue::{Priority, QueueError, PRIORITY_KEY};
 use dynamo_runtime::pipeline::{AsyncEngineContext, Context};
+use dynamo_runtime::trace::{self, TraceParent, TRACEPARENT_HEADER};
+
+/// Header carrying the admission priority of a request: high, normal or low
+const PRIORITY_HEADER: &str = "x-request-priority";
 
 #[derive(Serialize, Deserialize)]
 pub(crate) struct ErrorResponse {
@@ -58,6 +69,39 @@ impl ErrorResponse {
         )
     }
 
+    /// Unauthorized
+    /// This is returned when the request has no valid credential.
+    pub fn unauthorized(msg: &str) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: msg.to_string(),
+            }),
+        )
+    }
+
+    /// Forbidden
+    /// This is returned when the credential is valid, but may not access the requested model.
+    pub fn forbidden(msg: &str) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: msg.to_string(),
+            }),
+        )
+    }
+
+    /// Too Many Requests
+    /// This is returned when the tenant of the request exceeded its rate limits.
+    pub fn rate_limited(msg: &str) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse {
+                error: msg.to_string(),
+            }),
+        )
+    }
+
     /// Service Unavailable
     /// This is returned when the service is live, but not ready.
     pub fn _service_unavailable() -> (StatusCode, Json<ErrorResponse>) {
@@ -88,12 +132,32 @@ impl ErrorResponse {
     /// If successful, it will return the [`HttpError`] as an [`ErrorResponse::internal_server_error`]
     /// with the details of the error.
     pub fn from_anyhow(err: anyhow::Error, alt_msg: &str) -> (StatusCode, Json<ErrorResponse>) {
-        match err.downcast::<HttpError>() {
-            Ok(http_error) => ErrorResponse::from_http_error(http_error),
+        let err = match err.downcast::<HttpError>() {
+            Ok(http_error) => return ErrorResponse::from_http_error(http_error),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<RateLimited>() {
+            // the rate limit middleware completes the response
+            Ok(rejection) => return ErrorResponse::rate_limited(&rejection.to_string()),
+            Err(err) => err,
+        };
+        match err.downcast::<QueueError>() {
+            Ok(queue_error) => ErrorResponse::from_queue_error(queue_error),
             Err(err) => ErrorResponse::internal_server_error(&format!("{alt_msg}: {err}")),
         }
     }
 
+    /// The request was not admitted by the admission queue in front of the workers; all of them
+    /// are busy.
+    pub fn from_queue_error(err: QueueError) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                error: err.to_string(),
+            }),
+        )
+    }
+
     /// Implementers should only be able to throw 400-499 errors.
     pub fn from_http_error(err: HttpError) -> (StatusCode, Json<ErrorResponse>) {
         if err.code < 400 || err.code >= 500 {
@@ -120,9 +184,12 @@ impl From<HttpError> for ErrorResponse {
 ///
 /// Note: For all requests, streaming or non-streaming, we always call the engine with streaming enabled. For
 /// non-streaming requests, we will fold the stream into a single response as part of this handler.
-#[tracing::instrument(skip_all)]
+#[tracing::instrument(skip_all, fields(traceparent = traceparent_field(&headers)))]
 async fn completions(
     State(state): State<Arc<service_v2::State>>,
+    auth: Option<Extension<AuthContext>>,
+    usage: Option<Extension<TenantUsage>>,
+    headers: HeaderMap,
     Json(request): Json<NvCreateCompletionRequest>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     // return a 503 if the service is not ready
@@ -149,6 +216,11 @@ async fn completions(
     // todo - when optional, if none, apply a default
     let model = &request.inner.model;
 
+    // return a 403 if the API key may not use the model
+    check_model_access(&state, &auth, model)?;
+
+    let priority = request_priority(&headers, request.nvext.as_ref())?;
+
     // todo - error handling should be more robust
     let engine = state
         .manager()
@@ -159,12 +231,23 @@ async fn completions(
         state
             .metrics_clone()
             .create_inflight_guard(model, Endpoint::Completions, streaming);
+    if let Some(Extension(auth)) = &auth {
+        inflight_guard.set_key_id(&auth.key_id);
+    }
 
     let mut response_collector = state.metrics_clone().create_response_collector(model);
 
     // setup context
     // todo - inherit request_id from distributed trace details
-    let request = Context::with_id(request, request_id.clone());
+    let mut request = Context::with_id(request, request_id.clone());
+    request.set_traceparent(Some(request_traceparent(&headers)));
+    if let Some(priority) = priority {
+        request.insert(PRIORITY_KEY, priority);
+    }
+    // the preprocessor admits the prompt against the limits of the tenant
+    if let Some(Extension(usage)) = &usage {
+        request.insert(TENANT_USAGE_KEY, usage.clone());
+    }
 
     // issue the generate call on the engine
     let stream = engine
@@ -177,6 +260,11 @@ async fn completions(
 
     // todo - tap the stream and propagate request level metrics
     // note - we might do this as part of the post processing set to make it more generic
+    let mut usage = usage.map(|Extension(usage)| usage);
+    let stream = stream.map(move |response| {
+        observe_tenant_usage(&response, &mut usage);
+        response
+    });
 
     if streaming {
         let stream = stream.map(move |response| {
@@ -193,7 +281,7 @@ async fn completions(
         Ok(sse_stream.into_response())
     } else {
         // TODO: report ISL/OSL for non-streaming requests
-        let response = NvCreateCompletionResponse::from_annotated_stream(stream.into())
+        let response = NvCreateCompletionResponse::from_annotated_stream(Box::pin(stream))
             .await
             .map_err(|e| {
                 tracing::error!(
@@ -209,9 +297,12 @@ async fn completions(
     }
 }
 
-#[tracing::instrument(skip_all)]
+#[tracing::instrument(skip_all, fields(traceparent = traceparent_field(&headers)))]
 async fn embeddings(
     State(state): State<Arc<service_v2::State>>,
+    auth: Option<Extension<AuthContext>>,
+    usage: Option<Extension<TenantUsage>>,
+    headers: HeaderMap,
     Json(request): Json<NvCreateEmbeddingRequest>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     // return a 503 if the service is not ready
@@ -227,6 +318,11 @@ async fn embeddings(
     // todo - when optional, if none, apply a default
     let model = &request.inner.model;
 
+    // return a 403 if the API key may not use the model
+    check_model_access(&state, &auth, model)?;
+
+    let priority = request_priority(&headers, request.nvext.as_ref())?;
+
     // todo - error handling should be more robust
     let engine = state
         .manager()
@@ -238,10 +334,17 @@ async fn embeddings(
         state
             .metrics_clone()
             .create_inflight_guard(model, Endpoint::Embeddings, streaming);
+    if let Some(Extension(auth)) = &auth {
+        inflight.set_key_id(&auth.key_id);
+    }
 
     // setup context
     // todo - inherit request_id from distributed trace details
-    let request = Context::with_id(request, request_id.clone());
+    let mut request = Context::with_id(request, request_id.clone());
+    request.set_traceparent(Some(request_traceparent(&headers)));
+    if let Some(priority) = priority {
+        request.insert(PRIORITY_KEY, priority);
+    }
 
     // issue the generate call on the engine
     let stream = engine
@@ -262,6 +365,11 @@ async fn embeddings(
             ErrorResponse::internal_server_error("Failed to fold embeddings stream")
         })?;
 
+    // the prompt tokens of embeddings are only known from their response
+    if let Some(Extension(mut usage)) = usage {
+        usage.charge(response.inner.usage.prompt_tokens as usize, 0);
+    }
+
     inflight.mark_ok();
     Ok(Json(response).into_response())
 }
@@ -274,9 +382,12 @@ async fn embeddings(
 ///
 /// Note: For all requests, streaming or non-streaming, we always call the engine with streaming enabled. For
 /// non-streaming requests, we will fold the stream into a single response as part of this handler.
-#[tracing::instrument(skip_all)]
+#[tracing::instrument(skip_all, fields(traceparent = traceparent_field(&headers)))]
 async fn chat_completions(
     State((state, template)): State<(Arc<service_v2::State>, Option<RequestTemplate>)>,
+    auth: Option<Extension<AuthContext>>,
+    usage: Option<Extension<TenantUsage>>,
+    headers: HeaderMap,
     Json(mut request): Json<NvCreateChatCompletionRequest>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     // return a 503 if the service is not ready
@@ -318,6 +429,11 @@ async fn chat_completions(
     let model = &request.inner.model;
 
     // todo - determine the proper error code for when a request model is not present
+    // return a 403 if the API key may not use the model
+    check_model_access(&state, &auth, model)?;
+
+    let priority = request_priority(&headers, request.nvext.as_ref())?;
+
     tracing::trace!("Getting chat completions engine for model: {}", model);
 
     let engine = state
@@ -329,12 +445,23 @@ async fn chat_completions(
         state
             .metrics_clone()
             .create_inflight_guard(model, Endpoint::ChatCompletions, streaming);
+    if let Some(Extension(auth)) = &auth {
+        inflight_guard.set_key_id(&auth.key_id);
+    }
 
     let mut response_collector = state.metrics_clone().create_response_collector(model);
 
     // setup context
     // todo - inherit request_id from distributed trace details
-    let request = Context::with_id(request, request_id.clone());
+    let mut request = Context::with_id(request, request_id.clone());
+    request.set_traceparent(Some(request_traceparent(&headers)));
+    if let Some(priority) = priority {
+        request.insert(PRIORITY_KEY, priority);
+    }
+    // the preprocessor admits the prompt against the limits of the tenant
+    if let Some(Extension(usage)) = &usage {
+        request.insert(TENANT_USAGE_KEY, usage.clone());
+    }
 
     tracing::trace!("Issuing generate call for chat completions");
 
@@ -349,6 +476,11 @@ async fn chat_completions(
 
     // todo - tap the stream and propagate request level metrics
     // note - we might do this as part of the post processing set to make it more generic
+    let mut usage = usage.map(|Extension(usage)| usage);
+    let stream = stream.map(move |response| {
+        observe_tenant_usage(&response, &mut usage);
+        response
+    });
 
     if streaming {
         let stream = stream.map(move |response| {
@@ -365,7 +497,7 @@ async fn chat_completions(
         Ok(sse_stream.into_response())
     } else {
         // TODO: report ISL/OSL for non-streaming requests
-        let response = NvCreateChatCompletionResponse::from_annotated_stream(stream.into())
+        let response = NvCreateChatCompletionResponse::from_annotated_stream(Box::pin(stream))
             .await
             .map_err(|e| {
                 tracing::error!(
@@ -384,6 +516,174 @@ async fn chat_completions(
     }
 }
 
+/// OpenAI Responses Request Handler
+///
+/// The request is translated onto a chat completions request and served by the chat completions
+/// engine registered for the model. The chat completion stream is converted back into the
+/// Responses API output items; when streaming, into its semantic SSE events.
+#[tracing::instrument(skip_all, fields(traceparent = traceparent_field(&headers)))]
+async fn responses(
+    State((state, template)): State<(Arc<service_v2::State>, Option<RequestTemplate>)>,
+    auth: Option<Extension<AuthContext>>,
+    usage: Option<Extension<TenantUsage>>,
+    headers: HeaderMap,
+    Json(mut request): Json<NvCreateResponse>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    // return a 503 if the service is not ready
+    check_ready(&state)?;
+
+    // Apply template values if present
+    if let Some(template) = template {
+        if request.inner.model.is_empty() {
+            request.inner.model = template.model.clone();
+        }
+        if request.inner.temperature.unwrap_or(0.0) == 0.0 {
+            request.inner.temperature = Some(template.temperature);
+        }
+        if request.inner.max_output_tokens.unwrap_or(0) == 0 {
+            request.inner.max_output_tokens = Some(template.max_completion_tokens);
+        }
+    }
+    tracing::trace!("Received responses request: {:?}", request.inner);
+
+    // todo - extract distributed tracing id and context id from headers
+    let request_id = uuid::Uuid::new_v4().to_string();
+
+    let streaming = request.inner.stream.unwrap_or(false);
+
+    let generator = ResponseEventGenerator::new(NvResponse::new(&request.inner, &request_id));
+
+    let mut request = NvCreateChatCompletionRequest::try_from(request).map_err(|e| {
+        ErrorResponse::from_http_error(HttpError {
+            code: 400,
+            message: e.to_string(),
+        })
+    })?;
+
+    // update the request to always stream
+    request.inner.stream = Some(true);
+
+    let model = &request.inner.model;
+
+    // return a 403 if the API key may not use the model
+    check_model_access(&state, &auth, model)?;
+
+    let priority = request_priority(&headers, request.nvext.as_ref())?;
+
+    tracing::trace!("Getting chat completions engine for model: {}", model);
+
+    let engine = state
+        .manager()
+        .get_chat_completions_engine(model)
+        .map_err(|_| ErrorResponse::model_not_found())?;
+
+    let mut inflight_guard =
+        state
+            .metrics_clone()
+            .create_inflight_guard(model, Endpoint::Responses, streaming);
+    if let Some(Extension(auth)) = &auth {
+        inflight_guard.set_key_id(&auth.key_id);
+    }
+
+    let mut response_collector = state.metrics_clone().create_response_collector(model);
+
+    let mut request = Context::with_id(request, request_id.clone());
+    request.set_traceparent(Some(request_traceparent(&headers)));
+    if let Some(priority) = priority {
+        request.insert(PRIORITY_KEY, priority);
+    }
+    // the preprocessor admits the prompt against the limits of the tenant
+    if let Some(Extension(usage)) = &usage {
+        request.insert(TENANT_USAGE_KEY, usage.clone());
+    }
+
+    tracing::trace!("Issuing generate call for responses");
+
+    let stream = engine
+        .generate(request)
+        .await
+        .map_err(|e| ErrorResponse::from_anyhow(e, "Failed to generate response"))?;
+
+    // capture the context to cancel the stream if the client disconnects
+    let ctx = stream.context();
+
+    let mut usage = usage.map(|Extension(usage)| usage);
+    let stream = stream.map(move |response| {
+        observe_llm_metrics(&response, &mut response_collector);
+        observe_tenant_usage(&response, &mut usage);
+        response
+    });
+
+    if streaming {
+        let stream = response_event_stream(stream, generator)
+            .map(|event| Event::default().event(event.event_type()).json_data(event));
+        let stream = monitor_for_disconnects(stream.boxed(), ctx, inflight_guard).await;
+
+        let mut sse_stream = Sse::new(stream);
+
+        if let Some(keep_alive) = state.sse_keep_alive() {
+            sse_stream = sse_stream.keep_alive(KeepAlive::default().interval(keep_alive));
+        }
+
+        Ok(sse_stream.into_response())
+    } else {
+        let response = NvResponse::from_annotated_stream(stream, generator)
+            .await
+            .map_err(|e| {
+                tracing::error!(request_id, "Failed to fold responses stream for: {:?}", e);
+                ErrorResponse::internal_server_error(&format!(
+                    "Failed to fold responses stream: {}",
+                    e
+                ))
+            })?;
+
+        inflight_guard.mark_ok();
+        Ok(Json(response).into_response())
+    }
+}
+
+/// The traceparent header of the request as sent, or empty. Recorded on the span of the handler,
+/// which joins the trace of the caller through it.
+fn traceparent_field(headers: &HeaderMap) -> &str {
+    headers
+        .get(TRACEPARENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+}
+
+/// The trace context carried by the request through the distributed runtime: that of the
+/// handler span when traces are exported, else the traceparent header of the caller, else a new
+/// trace.
+fn request_traceparent(headers: &HeaderMap) -> TraceParent {
+    trace::current()
+        .or_else(|| traceparent_field(headers).parse().ok())
+        .unwrap_or_else(TraceParent::new_root)
+}
+
+/// The admission priority of the request, from its [`NvExt`] or else the [`PRIORITY_HEADER`].
+fn request_priority(
+    headers: &HeaderMap,
+    nvext: Option<&NvExt>,
+) -> Result<Option<Priority>, (StatusCode, Json<ErrorResponse>)> {
+    if let Some(priority) = nvext.and_then(|nvext| nvext.priority) {
+        return Ok(Some(priority));
+    }
+    let Some(value) = headers.get(PRIORITY_HEADER) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .map_err(anyhow::Error::from)
+        .and_then(|value| value.parse::<Priority>())
+        .map(Some)
+        .map_err(|e| {
+            ErrorResponse::from_http_error(HttpError {
+                code: 400,
+                message: format!("Invalid {PRIORITY_HEADER} header: {e}"),
+            })
+        })
+}
+
 // todo - abstract this to the top level lib.rs to be reused
 // todo - move the service_observer to its own state/arc
 fn check_ready(_state: &Arc<service_v2::State>) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
@@ -408,6 +708,7 @@ fn check_ready(_state: &Arc<service_v2::State>) -> Result<(), (StatusCode, Json<
 /// }
 async fn list_models_openai(
     State(state): State<Arc<service_v2::State>>,
+    auth: Option<Extension<AuthContext>>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     check_ready(&state)?;
 
@@ -419,6 +720,12 @@ async fn list_models_openai(
 
     let models: HashSet<String> = state.manager().model_display_names();
     for model_name in models {
+        // only list the models the API key may use
+        if let Some(Extension(auth)) = &auth {
+            if !auth.is_model_allowed(&model_name) {
+                continue;
+            }
+        }
         data.push(ModelListing {
             id: model_name.clone(),
             object: "object",
@@ -453,7 +760,8 @@ struct ModelListing {
 /// how we can monitor for disconnects and stop the generation of completions.
 ///
 /// If a disconnect is detected, then the context will issue a `stop_generating` call to the context which will
-/// propagate the cancellation signal to the backend.
+/// propagate the cancellation signal to the backend. The disconnect is detected as soon as the client goes away,
+/// even while the engine has not produced the next event yet, e.g. during a long prefill.
 async fn monitor_for_disconnects(
     stream: Pin<
         Box<dyn Stream<Item = Result<axum::response::sse::Event, axum::Error>> + std::marker::Send>,
@@ -463,18 +771,34 @@ async fn monitor_for_disconnects(
 ) -> ReceiverStream<Result<Event, axum::Error>> {
     let (tx, rx) = tokio::sync::mpsc::channel(8);
 
-    tokio::spawn(async move {
+    let forward = async move {
         let mut stream = stream;
-        while let Some(event) = stream.next().await {
+        loop {
+            let event = tokio::select! {
+                biased;
+                _ = tx.closed() => None,
+                event = stream.next() => match event {
+                    Some(event) => Some(event),
+                    None => break,
+                },
+            };
+
             let event = match event {
-                Ok(event) => Ok(event),
-                Err(err) => Ok(Event::default().event("error").comment(err.to_string())),
+                Some(Ok(event)) => Ok(event),
+                Some(Err(err)) => Ok(Event::default().event("error").comment(err.to_string())),
+                None => {
+                    tracing::trace!("Forwarding SSE stream was dropped; breaking loop");
+                    inflight_guard.mark_cancelled();
+                    context.stop_generating();
+                    return;
+                }
             };
 
             if (tx.send(event).await).is_err() {
                 tracing::trace!("Forwarding SSE stream was dropped; breaking loop");
+                inflight_guard.mark_cancelled();
                 context.stop_generating();
-                break;
+                return;
             }
         }
 
@@ -482,7 +806,9 @@ async fn monitor_for_disconnects(
         if tx.send(Ok(Event::default().data("[DONE]"))).await.is_ok() {
             inflight_guard.mark_ok();
         }
-    });
+    };
+    // the request span lasts until the stream is done
+    tokio::spawn(forward.instrument(tracing::Span::current()));
 
     ReceiverStream::new(rx)
 }
@@ -502,10 +828,7 @@ fn process_event_converter<T: Serialize>(
     let mut annotated = annotated.0;
 
     // update metrics
-    if let Ok(Some(metrics)) = LLMMetricAnnotation::from_annotation(&annotated) {
-        response_collector.observe_current_osl(metrics.output_tokens);
-        response_collector.observe_response(metrics.input_tokens, metrics.chunk_tokens);
-
+    if observe_llm_metrics(&annotated, response_collector) {
         // Chomp the LLMMetricAnnotation so it's not returned in the response stream
         // TODO: add a flag to control what is returned in the SSE stream
         if annotated.event.as_deref() == Some(crate::preprocessor::ANNOTATION_LLM_METRICS) {
@@ -539,6 +862,49 @@ fn process_event_converter<T: Serialize>(
     Ok(event)
 }
 
+/// Returns a 403 if the request was authenticated with an API key that may not use `model`
+fn check_model_access(
+    state: &Arc<service_v2::State>,
+    auth: &Option<Extension<AuthContext>>,
+    model: &str,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if let Some(Extension(auth)) = auth {
+        if !auth.is_model_allowed(model) {
+            state
+                .metrics_clone()
+                .inc_auth_failure(&auth.key_id, AUTH_FAILURE_FORBIDDEN);
+            return Err(ErrorResponse::forbidden(&format!(
+                "API key '{}' is not allowed to use model '{}'",
+                auth.key_id, model
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Records the [`LLMMetricAnnotation`] carried by the response, if any.
+/// Returns true if the response carried the annotation.
+fn observe_llm_metrics<T>(
+    annotated: &Annotated<T>,
+    response_collector: &mut ResponseMetricCollector,
+) -> bool {
+    match LLMMetricAnnotation::from_annotation(annotated) {
+        Ok(Some(metrics)) => {
+            response_collector.observe_current_osl(metrics.output_tokens);
+            response_collector.observe_response(metrics.input_tokens, metrics.chunk_tokens);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Charges the tokens reported by the response to the tenant of a rate limited request.
+fn observe_tenant_usage<T>(annotated: &Annotated<T>, usage: &mut Option<TenantUsage>) {
+    if let Some(usage) = usage.as_mut() {
+        usage.observe(annotated);
+    }
+}
+
 /// Create an Axum [`Router`] for the OpenAI API Completions endpoint
 /// If not path is provided, the default path is `/v1/completions`
 pub fn completions_router(
@@ -568,6 +934,21 @@ pub fn chat_completions_router(
     (vec![doc], router)
 }
 
+/// Create an Axum [`Router`] for the OpenAI API Responses endpoint
+/// If not path is provided, the default path is `/v1/responses`
+pub fn responses_router(
+    state: Arc<service_v2::State>,
+    template: Option<RequestTemplate>,
+    path: Option<String>,
+) -> (Vec<RouteDoc>, Router) {
+    let path = path.unwrap_or("/v1/responses".to_string());
+    let doc = RouteDoc::new(axum::http::Method::POST, &path);
+    let router = Router::new()
+        .route(&path, post(responses))
+        .with_state((state, template));
+    (vec![doc], router)
+}
+
 /// Create an Axum [`Router`] for the OpenAI API Embeddings endpoint
 /// If not path is provided, the default path is `/v1/embeddings`
 pub fn embeddings_router(
@@ -0,0 +1,719 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-tenant rate limiting for the HTTP service
+//!
+//! Each tenant has up to three token buckets, sized from its [`RateLimits`]: requests per minute,
+//! prompt tokens per minute and output tokens per minute. A tenant is identified by the key id of
+//! its [`AuthContext`] when authentication is enabled, otherwise by the value of the configured
+//! tenant header.
+//!
+//! The prompt of a request is admitted by the preprocessor, once it computed the input sequence
+//! length: the request gets the [`TenantUsage`] of its tenant in its context under
+//! [`TENANT_USAGE_KEY`]. Output tokens are only known as the request generates, so they are
+//! charged as the [`LLMMetricAnnotation`]s of the response stream are observed and may take the
+//! bucket into debt; a tenant in debt is not admitted again until its buckets have refilled.
+//! Rejected requests get a 429 with a `Retry-After` header.
+//!
+//! Tenants named by a header are chosen by the clients, so only the [`MAX_TRACKED_TENANTS`] most
+//! recently seen tenants are tracked.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, Result};
+use axum::{
+    extract::{Request, State},
+    http::header::RETRY_AFTER,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use dynamo_runtime::transports::etcd::WatchEvent;
+use dynamo_runtime::DistributedRuntime;
+
+use super::{auth::AuthContext, metrics::Metrics, openai::ErrorResponse};
+use crate::preprocessor::LLMMetricAnnotation;
+use crate::types::Annotated;
+
+/// etcd key holding the JSON encoded [`RateLimitConf`]
+pub const RATE_LIMIT_CONF_ETCD_KEY: &str = "public/components/http/rate_limits";
+
+/// Value for the `limit` label of the rate limited counter for the requests per minute limit
+pub const LIMIT_REQUESTS: &str = "requests";
+
+/// Value for the `limit` label of the rate limited counter for the prompt tokens per minute limit
+pub const LIMIT_PROMPT_TOKENS: &str = "prompt_tokens";
+
+/// Value for the `limit` label of the rate limited counter for the output tokens per minute limit
+pub const LIMIT_OUTPUT_TOKENS: &str = "output_tokens";
+
+/// Tenant of requests that are neither authenticated nor carry the tenant header
+pub const ANONYMOUS_TENANT: &str = "anonymous";
+
+/// Key of the [`TenantUsage`] of a request in its context
+pub const TENANT_USAGE_KEY: &str = "tenant_usage";
+
+/// The least recently seen tenant is forgotten beyond this many tenants
+pub const MAX_TRACKED_TENANTS: usize = 10_000;
+
+/// Limits of a tenant; `None` leaves the dimension unlimited
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RateLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens_per_minute: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_tokens_per_minute: Option<u64>,
+}
+
+/// Rate limit configuration of the HTTP service
+///
+/// Example:
+/// ```json
+/// {
+///     "tenant_header": "x-tenant-id",
+///     "default": {"requests_per_minute": 60},
+///     "tenants": {"team-a": {"requests_per_minute": 600, "output_tokens_per_minute": 100000}}
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RateLimitConf {
+    /// Limits of tenants without an entry in `tenants`
+    #[serde(default)]
+    pub default: RateLimits,
+
+    /// Limits by tenant
+    #[serde(default)]
+    pub tenants: HashMap<String, RateLimits>,
+
+    /// Header identifying the tenant of unauthenticated requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_header: Option<String>,
+}
+
+impl RateLimitConf {
+    pub fn load(path: &Path) -> Result<Self> {
+        let conf = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rate limits file {}", path.display()))?;
+        let conf: Self = serde_json::from_str(&conf)
+            .with_context(|| format!("Failed to parse rate limits file {}", path.display()))?;
+        Ok(conf)
+    }
+
+    /// Limits of `tenant`
+    pub fn limits(&self, tenant: &str) -> &RateLimits {
+        self.tenants.get(tenant).unwrap_or(&self.default)
+    }
+
+    /// Reads the configuration from [`RATE_LIMIT_CONF_ETCD_KEY`] and watches the key for updates.
+    /// A missing or deleted key disables rate limiting.
+    pub async fn from_etcd_with_watcher(
+        drt: Arc<DistributedRuntime>,
+    ) -> Result<(Self, watch::Receiver<Self>)> {
+        let etcd_key = RATE_LIMIT_CONF_ETCD_KEY;
+
+        let Some(etcd_client) = drt.etcd_client() else {
+            anyhow::bail!("Static components don't have an etcd client");
+        };
+
+        let initial_config = match etcd_client.kv_get_prefix(etcd_key).await {
+            Ok(kvs) => match kvs.first() {
+                Some(kv) => serde_json::from_slice::<RateLimitConf>(kv.value())
+                    .inspect(|config| {
+                        tracing::debug!("Found initial rate limits for key {etcd_key}: {config:?}")
+                    })
+                    .unwrap_or_else(|e| {
+                        tracing::warn!(
+                            "Failed to parse initial rate limits for key {etcd_key}: {e}"
+                        );
+                        RateLimitConf::default()
+                    }),
+                None => {
+                    tracing::debug!("No rate limits found for key {etcd_key}, using default");
+                    RateLimitConf::default()
+                }
+            },
+            Err(e) => {
+                tracing::warn!("Error fetching rate limits for key {etcd_key}: {e}");
+                RateLimitConf::default()
+            }
+        };
+
+        let (watch_tx, watch_rx) = watch::channel(initial_config.clone());
+
+        let prefix_watcher = etcd_client.kv_get_and_watch_prefix(etcd_key).await?;
+        let (key, _watcher, mut kv_event_rx) = prefix_watcher.dissolve();
+
+        drt.runtime().secondary().spawn(async move {
+            tracing::info!("Starting rate limits watcher for key: {}", key);
+
+            loop {
+                let kv_event = tokio::select! {
+                    _ = watch_tx.closed() => break,
+                    kv_event = kv_event_rx.recv() => match kv_event {
+                        Some(kv_event) => kv_event,
+                        None => break,
+                    },
+                };
+
+                let config = match kv_event {
+                    WatchEvent::Put(kv) => {
+                        match serde_json::from_slice::<RateLimitConf>(kv.value()) {
+                            Ok(config) => config,
+                            Err(e) => {
+                                // keep enforcing the last valid limits
+                                tracing::error!("Unable to parse rate limits for key {key}: {e}");
+                                continue;
+                            }
+                        }
+                    }
+                    WatchEvent::Delete(_) => {
+                        tracing::warn!("Rate limits key was deleted: {}", key);
+                        RateLimitConf::default()
+                    }
+                };
+
+                tracing::info!("Rate limits updated for key {}: {:?}", key, config);
+                if watch_tx.send(config).is_err() {
+                    break;
+                }
+            }
+
+            tracing::debug!("Completed rate limits watcher for key: {}", key);
+        });
+
+        Ok((initial_config, watch_rx))
+    }
+}
+
+/// A token bucket refilling at `capacity` tokens per minute. Charges may take the bucket into
+/// debt, which has to be paid back by refilling before the bucket admits again.
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(per_minute: u64, now: Instant) -> Self {
+        Self {
+            capacity: per_minute as f64,
+            tokens: per_minute as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.capacity / 60.0).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until the bucket holds `needed` tokens
+    fn wait_time(&self, needed: f64) -> Duration {
+        if self.tokens >= needed {
+            return Duration::ZERO;
+        }
+        if self.capacity <= 0.0 {
+            return Duration::MAX;
+        }
+        Duration::from_secs_f64((needed - self.tokens) * 60.0 / self.capacity)
+    }
+
+    fn charge(&mut self, amount: f64) {
+        self.tokens -= amount;
+    }
+}
+
+#[derive(Debug)]
+struct TenantBuckets {
+    limits: RateLimits,
+    requests: Option<TokenBucket>,
+    prompt_tokens: Option<TokenBucket>,
+    output_tokens: Option<TokenBucket>,
+}
+
+impl TenantBuckets {
+    fn new(limits: &RateLimits, now: Instant) -> Self {
+        let bucket = |limit: Option<u64>| limit.map(|limit| TokenBucket::new(limit, now));
+        Self {
+            limits: limits.clone(),
+            requests: bucket(limits.requests_per_minute),
+            prompt_tokens: bucket(limits.prompt_tokens_per_minute),
+            output_tokens: bucket(limits.output_tokens_per_minute),
+        }
+    }
+
+    /// Apply updated limits. The buckets keep the tokens they hold, up to their new capacity,
+    /// so that an update does not let the tenants burst.
+    fn update(&mut self, limits: &RateLimits, now: Instant) {
+        let update = |bucket: &mut Option<TokenBucket>, limit: Option<u64>| {
+            *bucket = match (bucket.take(), limit) {
+                (_, None) => None,
+                (Some(mut bucket), Some(limit)) => {
+                    bucket.refill(now);
+                    bucket.capacity = limit as f64;
+                    bucket.tokens = bucket.tokens.min(bucket.capacity);
+                    Some(bucket)
+                }
+                (None, Some(limit)) => Some(TokenBucket::new(limit, now)),
+            };
+        };
+        update(&mut self.requests, limits.requests_per_minute);
+        update(&mut self.prompt_tokens, limits.prompt_tokens_per_minute);
+        update(&mut self.output_tokens, limits.output_tokens_per_minute);
+        self.limits = limits.clone();
+    }
+
+    fn buckets_mut(&mut self) -> impl Iterator<Item = &mut TokenBucket> {
+        [
+            self.requests.as_mut(),
+            self.prompt_tokens.as_mut(),
+            self.output_tokens.as_mut(),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// The buckets of the most recently seen tenants
+#[derive(Debug, Default)]
+struct Tenants {
+    /// Buckets by tenant, with the sequence number of the last request of the tenant
+    buckets: HashMap<String, (u64, TenantBuckets)>,
+    /// Tenants by the sequence number of their last request, least recent first
+    recency: BTreeMap<u64, String>,
+    last_seq: u64,
+}
+
+impl Tenants {
+    /// The buckets of `tenant` for a new request, created from `limits` if the tenant is not
+    /// tracked. Forgets the least recently seen tenant if there are too many.
+    fn admit(&mut self, tenant: &str, limits: &RateLimits, now: Instant) -> &mut TenantBuckets {
+        self.last_seq += 1;
+        let seq = self.last_seq;
+        match self.buckets.get_mut(tenant) {
+            Some((last_seq, _)) => {
+                self.recency.remove(last_seq);
+                *last_seq = seq;
+            }
+            None => {
+                if self.buckets.len() >= MAX_TRACKED_TENANTS {
+                    if let Some((_, oldest)) = self.recency.pop_first() {
+                        self.buckets.remove(&oldest);
+                    }
+                }
+                self.buckets
+                    .insert(tenant.to_string(), (seq, TenantBuckets::new(limits, now)));
+            }
+        }
+        self.recency.insert(seq, tenant.to_string());
+        &mut self.buckets.get_mut(tenant).unwrap().1
+    }
+
+    fn get_mut(&mut self, tenant: &str) -> Option<&mut TenantBuckets> {
+        self.buckets.get_mut(tenant).map(|(_, buckets)| buckets)
+    }
+}
+
+/// A request rejected by the [`RateLimiter`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("rate limit exceeded: {limit} per minute")]
+pub struct RateLimited {
+    /// Which limit was exceeded, see [`LIMIT_REQUESTS`], [`LIMIT_PROMPT_TOKENS`] and
+    /// [`LIMIT_OUTPUT_TOKENS`]
+    pub limit: &'static str,
+
+    /// Time until the request would be admitted
+    pub retry_after: Duration,
+}
+
+/// Admission control of the HTTP service, keeping token buckets per tenant
+pub struct RateLimiter {
+    conf: watch::Receiver<RateLimitConf>,
+    tenants: Mutex<Tenants>,
+}
+
+impl RateLimiter {
+    /// Rate limiter with a static configuration
+    pub fn new(conf: RateLimitConf) -> Self {
+        // the receiver keeps the last value once the sender is dropped
+        let (_, conf) = watch::channel(conf);
+        Self::from_watcher(conf)
+    }
+
+    /// Rate limiter following a configuration that can be updated at runtime, e.g. the
+    /// receiver of [`RateLimitConf::from_etcd_with_watcher`]
+    pub fn from_watcher(conf: watch::Receiver<RateLimitConf>) -> Self {
+        Self {
+            conf,
+            tenants: Mutex::new(Tenants::default()),
+        }
+    }
+
+    /// The header identifying the tenant of unauthenticated requests
+    pub fn tenant_header(&self) -> Option<String> {
+        self.conf.borrow().tenant_header.clone()
+    }
+
+    /// Admits one request of `tenant`, charging its request bucket. Requests are rejected while
+    /// any bucket of the tenant is exhausted or in debt.
+    pub fn admit(self: &Arc<Self>, tenant: &str) -> Result<TenantUsage, RateLimited> {
+        self.admit_at(tenant, Instant::now())?;
+        Ok(TenantUsage {
+            limiter: self.clone(),
+            tenant: tenant.to_string(),
+            prompt_charged: Arc::new(AtomicBool::new(false)),
+            rejection: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn admit_at(&self, tenant: &str, now: Instant) -> Result<(), RateLimited> {
+        let conf = self.conf.borrow();
+        let limits = conf.limits(tenant);
+
+        let mut tenants = self.tenants.lock().unwrap();
+        let buckets = tenants.admit(tenant, limits, now);
+        if buckets.limits != *limits {
+            buckets.update(limits, now);
+        }
+
+        buckets.buckets_mut().for_each(|bucket| bucket.refill(now));
+
+        let checks = [
+            (LIMIT_REQUESTS, &buckets.requests, 1.0),
+            (LIMIT_PROMPT_TOKENS, &buckets.prompt_tokens, 0.0),
+            (LIMIT_OUTPUT_TOKENS, &buckets.output_tokens, 0.0),
+        ];
+        let rejection = checks
+            .into_iter()
+            .filter_map(|(limit, bucket, needed)| {
+                let wait = bucket.as_ref()?.wait_time(needed);
+                (!wait.is_zero()).then_some((limit, wait))
+            })
+            .max_by_key(|(_, wait)| *wait);
+
+        if let Some((limit, retry_after)) = rejection {
+            return Err(RateLimited { limit, retry_after });
+        }
+
+        if let Some(bucket) = buckets.requests.as_mut() {
+            bucket.charge(1.0);
+        }
+        Ok(())
+    }
+
+    /// Admits a prompt of `prompt_tokens` tokens of an admitted request of `tenant`, charging
+    /// its prompt bucket. A prompt larger than the bucket is admitted once the bucket is full.
+    fn admit_prompt_at(
+        &self,
+        tenant: &str,
+        prompt_tokens: usize,
+        now: Instant,
+    ) -> Result<(), RateLimited> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let Some(bucket) = tenants
+            .get_mut(tenant)
+            .and_then(|buckets| buckets.prompt_tokens.as_mut())
+        else {
+            return Ok(());
+        };
+        bucket.refill(now);
+        let needed = (prompt_tokens as f64).min(bucket.capacity);
+        let retry_after = bucket.wait_time(needed);
+        if !retry_after.is_zero() {
+            return Err(RateLimited {
+                limit: LIMIT_PROMPT_TOKENS,
+                retry_after,
+            });
+        }
+        bucket.charge(prompt_tokens as f64);
+        Ok(())
+    }
+
+    fn charge(&self, tenant: &str, prompt_tokens: usize, output_tokens: usize) {
+        let now = Instant::now();
+        let mut tenants = self.tenants.lock().unwrap();
+        let Some(buckets) = tenants.get_mut(tenant) else {
+            return;
+        };
+        if let Some(bucket) = buckets.prompt_tokens.as_mut() {
+            bucket.refill(now);
+            bucket.charge(prompt_tokens as f64);
+        }
+        if let Some(bucket) = buckets.output_tokens.as_mut() {
+            bucket.refill(now);
+            bucket.charge(output_tokens as f64);
+        }
+    }
+}
+
+/// Charges the token usage of an admitted request to its tenant. The clones of a usage are
+/// those of the same request.
+#[derive(Clone)]
+pub struct TenantUsage {
+    limiter: Arc<RateLimiter>,
+    tenant: String,
+    prompt_charged: Arc<AtomicBool>,
+    /// Why the prompt of the request was rejected, if it was
+    rejection: Arc<Mutex<Option<RateLimited>>>,
+}
+
+impl TenantUsage {
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    /// Admits the prompt of the request, of `prompt_tokens` tokens, against the prompt tokens
+    /// limit of the tenant, and charges it
+    pub fn admit_prompt(&self, prompt_tokens: usize) -> Result<(), RateLimited> {
+        if self.prompt_charged.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+        self.limiter
+            .admit_prompt_at(&self.tenant, prompt_tokens, Instant::now())
+            .inspect_err(|rejection| {
+                *self.rejection.lock().unwrap() = Some(rejection.clone());
+            })
+    }
+
+    /// Why the prompt of the request was rejected, if it was
+    fn take_rejection(&self) -> Option<RateLimited> {
+        self.rejection.lock().unwrap().take()
+    }
+
+    /// Charges the tokens reported by the [`LLMMetricAnnotation`] of a response, if any.
+    /// The prompt is charged once, with the first annotation.
+    pub fn observe<T>(&mut self, annotated: &Annotated<T>) {
+        if let Ok(Some(metrics)) = LLMMetricAnnotation::from_annotation(annotated) {
+            self.charge(metrics.input_tokens, metrics.chunk_tokens);
+        }
+    }
+
+    pub fn charge(&mut self, prompt_tokens: usize, output_tokens: usize) {
+        let prompt_tokens = match self.prompt_charged.swap(true, Ordering::Relaxed) {
+            true => 0,
+            false => prompt_tokens,
+        };
+        self.limiter
+            .charge(&self.tenant, prompt_tokens, output_tokens);
+    }
+}
+
+/// State of the [`rate_limit_middleware`]
+#[derive(Clone)]
+pub(crate) struct RateLimitState {
+    pub(crate) limiter: Arc<RateLimiter>,
+    pub(crate) metrics: Arc<Metrics>,
+}
+
+/// Axum middleware admitting requests through the [`RateLimiter`]. Admitted requests carry their
+/// [`TenantUsage`] as an extension; rejected requests get a 429 with a `Retry-After` header.
+///
+/// Must run after the authentication middleware so that tenants are identified by key id.
+pub(crate) async fn rate_limit_middleware(
+    State(state): State<RateLimitState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let tenant = match request.extensions().get::<AuthContext>() {
+        Some(auth) => auth.key_id.clone(),
+        None => state
+            .limiter
+            .tenant_header()
+            .and_then(|header| request.headers().get(header.as_str()).cloned())
+            .and_then(|value| value.to_str().ok().map(str::to_string))
+            .unwrap_or_else(|| ANONYMOUS_TENANT.to_string()),
+    };
+
+    match state.limiter.admit(&tenant) {
+        Ok(usage) => {
+            request.extensions_mut().insert(usage.clone());
+            let response = next.run(request).await;
+            // the preprocessor rejected the prompt
+            match usage.take_rejection() {
+                Some(rejection) => rate_limited(&state.metrics, &tenant, rejection),
+                None => response,
+            }
+        }
+        Err(rejection) => rate_limited(&state.metrics, &tenant, rejection),
+    }
+}
+
+/// The 429 response of a rejected request
+fn rate_limited(metrics: &Metrics, tenant: &str, rejection: RateLimited) -> Response {
+    metrics.inc_rate_limited(tenant, rejection.limit);
+    let retry_after = rejection.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    tracing::debug!(
+        tenant,
+        limit = rejection.limit,
+        retry_after,
+        "Rate limited request"
+    );
+    let mut response = ErrorResponse::rate_limited(&format!(
+        "Rate limit exceeded for {}: {} per minute. Retry after {} seconds",
+        tenant, rejection.limit, retry_after
+    ))
+    .into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, retry_after.into());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(conf: serde_json::Value) -> RateLimiter {
+        RateLimiter::new(serde_json::from_value(conf).unwrap())
+    }
+
+    #[test]
+    fn test_requests_per_minute() {
+        let limiter = limiter(serde_json::json!({
+            "default": {"requests_per_minute": 2},
+            "tenants": {"team-a": {"requests_per_minute": 60}},
+        }));
+        let now = Instant::now();
+
+        assert!(limiter.admit_at("anonymous", now).is_ok());
+        assert!(limiter.admit_at("anonymous", now).is_ok());
+        let rejection = limiter.admit_at("anonymous", now).unwrap_err();
+        assert_eq!(rejection.limit, LIMIT_REQUESTS);
+        assert_eq!(rejection.retry_after, Duration::from_secs(30));
+
+        // one request refills every 30 seconds
+        assert!(limiter
+            .admit_at("anonymous", now + Duration::from_secs(30))
+            .is_ok());
+
+        // tenants have their own buckets
+        for _ in 0..60 {
+            assert!(limiter.admit_at("team-a", now).is_ok());
+        }
+        assert!(limiter.admit_at("team-a", now).is_err());
+    }
+
+    #[test]
+    fn test_token_debt() {
+        let limiter = Arc::new(limiter(serde_json::json!({
+            "default": {"prompt_tokens_per_minute": 1000, "output_tokens_per_minute": 600},
+        })));
+
+        let mut usage = limiter.admit("team-b").unwrap();
+        usage.charge(800, 10);
+        // the prompt is only charged once
+        usage.charge(800, 10);
+        assert!(limiter.admit("team-b").is_ok());
+
+        // a large request takes the output bucket into debt
+        let mut usage = limiter.admit("team-b").unwrap();
+        usage.charge(0, 1180);
+        let rejection = limiter.admit("team-b").err().unwrap();
+        assert_eq!(rejection.limit, LIMIT_OUTPUT_TOKENS);
+        // 600 tokens of debt at 10 tokens per second
+        assert!(rejection.retry_after > Duration::from_secs(59));
+        assert!(rejection.retry_after <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_updated_limits() {
+        let (tx, rx) = watch::channel(RateLimitConf {
+            default: RateLimits {
+                requests_per_minute: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let limiter = RateLimiter::from_watcher(rx);
+        let now = Instant::now();
+
+        assert!(limiter.admit_at("team-c", now).is_ok());
+        assert!(limiter.admit_at("team-c", now).is_err());
+
+        // raising the limit does not refill the bucket
+        let raised = RateLimitConf {
+            default: RateLimits {
+                requests_per_minute: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        tx.send(raised).unwrap();
+        let rejection = limiter.admit_at("team-c", now).unwrap_err();
+        assert_eq!(rejection.retry_after, Duration::from_secs(30));
+
+        // removing the limit admits right away
+        tx.send(RateLimitConf::default()).unwrap();
+        assert!(limiter.admit_at("team-c", now).is_ok());
+    }
+
+    #[test]
+    fn test_prompt_admission() {
+        let limiter = Arc::new(limiter(serde_json::json!({
+            "default": {"prompt_tokens_per_minute": 1000},
+        })));
+
+        let usage = limiter.admit("team-d").unwrap();
+        assert!(usage.admit_prompt(700).is_ok());
+        // the prompt is admitted once per request
+        assert!(usage.admit_prompt(700).is_ok());
+
+        // the next prompt does not fit in the 300 tokens left
+        let usage = limiter.admit("team-d").unwrap();
+        let rejection = usage.admit_prompt(400).unwrap_err();
+        assert_eq!(rejection.limit, LIMIT_PROMPT_TOKENS);
+        assert_eq!(usage.take_rejection(), Some(rejection));
+
+        // a smaller one does
+        let mut usage = limiter.admit("team-d").unwrap();
+        assert!(usage.admit_prompt(300).is_ok());
+        // and is not charged again with the response
+        usage.charge(300, 0);
+        assert!(limiter.admit("team-d").unwrap().admit_prompt(100).is_err());
+    }
+
+    #[test]
+    fn test_tracked_tenants() {
+        let limiter = limiter(serde_json::json!({
+            "default": {"requests_per_minute": 1},
+        }));
+        let now = Instant::now();
+
+        assert!(limiter.admit_at("first", now).is_ok());
+        for i in 0..MAX_TRACKED_TENANTS {
+            assert!(limiter.admit_at(&format!("tenant-{i}"), now).is_ok());
+        }
+        let tenants = limiter.tenants.lock().unwrap();
+        assert_eq!(tenants.buckets.len(), MAX_TRACKED_TENANTS);
+        assert_eq!(tenants.recency.len(), MAX_TRACKED_TENANTS);
+        assert!(!tenants.buckets.contains_key("first"));
+    }
+}
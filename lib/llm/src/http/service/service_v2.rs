@@ -4,7 +4,9 @@
 use std::sync::Arc;
 use std::time::Duration;
 
+use super::auth::{self, AuthConfig};
 use super::metrics;
+use super::rate_limit::{self, RateLimiter};
 use super::Metrics;
 use super::RouteDoc;
 use crate::discovery::ModelManager;
@@ -18,6 +20,7 @@ use tokio_util::sync::CancellationToken;
 pub struct State {
     metrics: Arc<Metrics>,
     manager: Arc<ModelManager>,
+    sse_keep_alive: Option<Duration>,
 }
 
 impl State {
@@ -25,9 +28,16 @@ impl State {
         Self {
             manager,
             metrics: Arc::new(Metrics::default()),
+            sse_keep_alive: None,
         }
     }
 
+    /// Send an SSE comment on streaming responses whenever no event was sent for `interval`
+    pub fn with_sse_keep_alive(mut self, interval: Option<Duration>) -> Self {
+        self.sse_keep_alive = interval;
+        self
+    }
+
     /// Get the Prometheus [`Metrics`] object which tracks request counts and inflight requests
     pub fn metrics_clone(&self) -> Arc<Metrics> {
         self.metrics.clone()
@@ -41,9 +51,9 @@ impl State {
         self.manager.clone()
     }
 
-    // TODO
+    /// Interval of the SSE keep-alive comments, if enabled
     pub fn sse_keep_alive(&self) -> Option<Duration> {
-        None
+        self.sse_keep_alive
     }
 }
 
@@ -53,6 +63,7 @@ pub struct HttpService {
     state: Arc<State>,
 
     router: axum::Router,
+    registry: metrics::Registry,
     port: u16,
     host: String,
     route_docs: Vec<RouteDoc>,
@@ -78,8 +89,24 @@ pub struct HttpServiceConfig {
     #[builder(default = "true")]
     enable_embeddings_endpoints: bool,
 
+    #[builder(default = "true")]
+    enable_responses_endpoints: bool,
+
     #[builder(default = "None")]
     request_template: Option<RequestTemplate>,
+
+    /// Require callers of the OpenAI endpoints to authenticate; disabled by default
+    #[builder(default = "None")]
+    auth: Option<AuthConfig>,
+
+    /// Per-tenant rate limits of the inference endpoints; disabled by default
+    #[builder(default = "None")]
+    rate_limiter: Option<Arc<RateLimiter>>,
+
+    /// Interval of the SSE keep-alive comments sent on idle streaming responses, so that
+    /// proxies and load balancers don't time out during long prefills; disabled by default
+    #[builder(default = "None")]
+    sse_keep_alive: Option<Duration>,
 }
 
 impl HttpService {
@@ -123,6 +150,12 @@ impl HttpService {
         Ok(())
     }
 
+    /// The Prometheus registry served on the metrics endpoint. Other parts of the frontend, e.g.
+    /// the admission queues of the routers, register their metrics here.
+    pub fn metrics_registry(&self) -> &metrics::Registry {
+        &self.registry
+    }
+
     /// Documentation of exposed HTTP endpoints
     pub fn route_docs(&self) -> &[RouteDoc] {
         &self.route_docs
@@ -134,24 +167,38 @@ impl HttpServiceConfigBuilder {
         let config: HttpServiceConfig = self.build_internal()?;
 
         let model_manager = Arc::new(ModelManager::new());
-        let state = Arc::new(State::new(model_manager));
+        let state = Arc::new(State::new(model_manager).with_sse_keep_alive(config.sse_keep_alive));
 
         // enable prometheus metrics
         let registry = metrics::Registry::new();
         state.metrics_clone().register(&registry)?;
+        state.manager().kv_router_metrics().register(&registry)?;
 
         let mut router = axum::Router::new();
 
         let mut all_docs = Vec::new();
 
         let mut routes = vec![
-            metrics::router(registry, None),
-            super::openai::list_models_router(state.clone(), None),
+            metrics::router(registry.clone(), None),
             super::health::health_check_router(state.clone(), None),
         ];
 
+        // routes serving models, which require authentication when enabled
+        let mut model_routes = vec![super::openai::list_models_router(state.clone(), None)];
+
+        // routes issuing inference requests, which are rate limited when enabled
+        let mut inference_routes = Vec::new();
+
         if config.enable_chat_endpoints {
-            routes.push(super::openai::chat_completions_router(
+            inference_routes.push(super::openai::chat_completions_router(
+                state.clone(),
+                config.request_template.clone(),
+                None,
+            ));
+        }
+
+        if config.enable_responses_endpoints {
+            inference_routes.push(super::openai::responses_router(
                 state.clone(),
                 config.request_template,
                 None,
@@ -159,11 +206,46 @@ impl HttpServiceConfigBuilder {
         }
 
         if config.enable_cmpl_endpoints {
-            routes.push(super::openai::completions_router(state.clone(), None));
+            inference_routes.push(super::openai::completions_router(state.clone(), None));
         }
 
         if config.enable_embeddings_endpoints {
-            routes.push(super::openai::embeddings_router(state.clone(), None));
+            inference_routes.push(super::openai::embeddings_router(state.clone(), None));
+        }
+
+        match config.rate_limiter {
+            Some(limiter) => {
+                let rate_limit_state = rate_limit::RateLimitState {
+                    limiter,
+                    metrics: state.metrics_clone(),
+                };
+                model_routes.extend(inference_routes.into_iter().map(|(docs, router)| {
+                    let layer = axum::middleware::from_fn_with_state(
+                        rate_limit_state.clone(),
+                        rate_limit::rate_limit_middleware,
+                    );
+                    (docs, router.layer(layer))
+                }));
+            }
+            None => model_routes.extend(inference_routes),
+        }
+
+        // the authentication layer wraps the rate limit layer, so tenants are identified by key id
+        match config.auth.filter(|auth| auth.is_enabled()) {
+            Some(auth) => {
+                let auth_state = auth::AuthState {
+                    config: auth,
+                    metrics: state.metrics_clone(),
+                };
+                routes.extend(model_routes.into_iter().map(|(docs, router)| {
+                    let layer = axum::middleware::from_fn_with_state(
+                        auth_state.clone(),
+                        auth::auth_middleware,
+                    );
+                    (docs, router.layer(layer))
+                }));
+            }
+            None => routes.extend(model_routes),
         }
 
         // for (route_docs, route) in routes.into_iter().chain(self.routes.into_iter()) {
@@ -179,6 +261,7 @@ impl HttpServiceConfigBuilder {
         Ok(HttpService {
             state,
             router,
+            registry,
             port: config.port,
             host: config.host,
             route_docs: all_docs,
@@ -1,7 +1,12 @@
 // SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::Result;
 use dynamo_runtime::{
@@ -14,35 +19,60 @@ use dynamo_runtime::{
     protocols::annotated::Annotated,
 };
 use futures::stream::{self, StreamExt};
+use rand::Rng;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
+pub mod codec;
+pub mod event_stream;
 pub mod indexer;
+pub mod metrics;
 pub mod metrics_aggregator;
 pub mod protocols;
 pub mod publisher;
 pub mod recorder;
 pub mod scheduler;
 pub mod scoring;
+pub mod simulator;
+pub mod snapshot;
 
 use crate::{
     kv_router::{
-        indexer::{KvIndexer, KvIndexerInterface, RouterEvent},
+        codec::decode_router_events,
+        event_stream::{KvEventStreamConfig, KvEventSubscriber},
+        indexer::{compute_block_hash_for_seq_with_lora, EventGap, KvIndexer, KvIndexerInterface},
+        metrics::{KvRouterMetrics, RESYNC_FAILURE, RESYNC_SUCCESS},
         metrics_aggregator::KvMetricsAggregator,
-        protocols::{LocalBlockHash, RouterRequest, RouterResponse, WorkerSelectionResult},
+        protocols::{
+            KvCacheDump, KvDumpRequest, RouterRequest, RouterResponse, RoutingDecision,
+            WorkerSelectionResult, WorkerWithDpRank,
+        },
         scheduler::{KvScheduler, KvSchedulerError, SchedulingRequest},
         scoring::ProcessedEndpoints,
+        snapshot::KvSnapshotter,
     },
     preprocessor::PreprocessedRequest,
     protocols::common::llm_backend::LLMEngineOutput,
-    tokens::TokenBlockSequence,
+    recorder::Recorder,
 };
 
-use dynamo_runtime::traits::events::EventSubscriber;
-
 // [gluo TODO] shouldn't need to be public
 // this should be discovered from the component
 pub const KV_EVENT_SUBJECT: &str = "kv_events";
 pub const KV_HIT_RATE_SUBJECT: &str = "kv-hit-rate";
 pub const KV_METRICS_ENDPOINT: &str = "load_metrics";
+/// Workers serve the blocks they have stored on this endpoint, see [`protocols::KvCacheDump`]
+pub const KV_DUMP_ENDPOINT: &str = "kv_dump";
+
+/// How often the KV router snapshots its indexer when snapshots are enabled
+pub const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long the KV router waits for a worker's [`KV_DUMP_ENDPOINT`] when resyncing it
+const KV_DUMP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Requests with this annotation get the [`RoutingDecision`] of the KV router as the first
+/// event of their response stream
+pub const ANNOTATION_ROUTING_DECISION: &str = "routing_decision";
 
 /// A trait that users can implement to define custom selection logic
 pub trait WorkerSelector {
@@ -68,6 +98,29 @@ pub struct KvRouterConfig {
     /// Weight for waiting requests in worker selection.
     /// Higher values avoid workers with queued requests. Default: 1.0
     pub waiting_requests_weight: f64,
+
+    /// Weight for the adapter of a LoRA request not being loaded on a worker.
+    /// Higher values prefer workers that already have it loaded. Default: 1.0
+    pub lora_load_weight: f64,
+
+    /// Directory in which the router persists its view of the worker caches, so that it
+    /// restarts warm. Each model gets a sub-directory. Default: no persistence
+    pub snapshot_dir: Option<PathBuf>,
+
+    /// How often the router snapshots its view of the worker caches. Default: 30s
+    pub snapshot_interval: Duration,
+
+    /// Directory in which the router records its routing decisions, one JSONL file per model,
+    /// see [`RoutingDecision`]. Default: no audit log
+    pub decision_log_dir: Option<PathBuf>,
+
+    /// Fraction of the requests whose routing decision is recorded. Default: 1.0
+    pub decision_log_sample_rate: f64,
+
+    /// Read the KV events from the namespace's durable JetStream stream, so that the router
+    /// replays the events it missed while it was down. Default: core NATS, events published
+    /// while the router is down are lost
+    pub kv_event_stream: Option<KvEventStreamConfig>,
 }
 
 impl Default for KvRouterConfig {
@@ -76,6 +129,12 @@ impl Default for KvRouterConfig {
             overlap_score_weight: 1.0,
             gpu_cache_usage_weight: 1.0,
             waiting_requests_weight: 1.0,
+            lora_load_weight: 1.0,
+            snapshot_dir: None,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            decision_log_dir: None,
+            decision_log_sample_rate: 1.0,
+            kv_event_stream: None,
         }
     }
 }
@@ -95,16 +154,72 @@ impl KvRouterConfig {
                 .unwrap_or(default.gpu_cache_usage_weight),
             waiting_requests_weight: waiting_requests_weight
                 .unwrap_or(default.waiting_requests_weight),
+            ..default
+        }
+    }
+
+    /// Set the weight for the adapter of a LoRA request not being loaded on a worker.
+    /// If the weight is None, the default value will be used.
+    pub fn with_lora_load_weight(mut self, lora_load_weight: Option<f64>) -> Self {
+        if let Some(lora_load_weight) = lora_load_weight {
+            self.lora_load_weight = lora_load_weight;
+        }
+        self
+    }
+
+    /// Persist the router's view of the worker caches in `dir`
+    pub fn with_snapshot_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.snapshot_dir = dir;
+        self
+    }
+
+    /// Record the routing decisions of a `sample_rate` fraction of the requests in `dir`.
+    /// If the rate is None, the default value will be used.
+    pub fn with_decision_log(mut self, dir: Option<PathBuf>, sample_rate: Option<f64>) -> Self {
+        self.decision_log_dir = dir;
+        if let Some(sample_rate) = sample_rate {
+            self.decision_log_sample_rate = sample_rate;
         }
+        self
+    }
+
+    /// Read the KV events from the namespace's durable stream, see [`event_stream`]
+    pub fn with_kv_event_stream(mut self, kv_event_stream: Option<KvEventStreamConfig>) -> Self {
+        self.kv_event_stream = kv_event_stream;
+        self
     }
 }
 
 /// A KvRouter only decides which worker you should use. It doesn't send you there.
 /// TODO: Rename this to indicate it only selects a worker, it does not route.
 pub struct KvRouter {
-    indexer: KvIndexer,
+    indexer: Arc<KvIndexer>,
     scheduler: KvScheduler,
     block_size: usize,
+    cancellation_token: CancellationToken,
+    decision_log: Option<DecisionLog>,
+}
+
+/// The audit log of the routing decisions of a [`KvRouter`]
+struct DecisionLog {
+    // Keeps recording until the router's cancellation token is cancelled
+    _recorder: Recorder<RoutingDecision>,
+    decision_tx: mpsc::Sender<RoutingDecision>,
+    sample_rate: f64,
+}
+
+impl DecisionLog {
+    /// Whether to record the decision of the next request
+    fn sample(&self) -> bool {
+        self.sample_rate >= 1.0 || rand::rng().random::<f64>() < self.sample_rate
+    }
+
+    /// Record a decision, dropping it if the recorder is behind rather than delaying the request
+    fn record(&self, decision: RoutingDecision) {
+        if let Err(err) = self.decision_tx.try_send(decision) {
+            tracing::debug!(%err, "Dropped a routing decision from the audit log");
+        }
+    }
 }
 
 impl KvRouter {
@@ -112,6 +227,36 @@ impl KvRouter {
         component: Component,
         block_size: usize,
         selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+    ) -> Result<Self> {
+        Self::new_with_snapshots(
+            component,
+            block_size,
+            selector,
+            None,
+            DEFAULT_SNAPSHOT_INTERVAL,
+            Arc::new(KvRouterMetrics::default()),
+            None,
+        )
+        .await
+    }
+
+    /// Create a KvRouter that restores its indexer from `snapshot_dir` on start, and keeps
+    /// snapshotting it there every `snapshot_interval`. See [`KvSnapshotter`].
+    ///
+    /// The gaps detected in the KV events of the workers and their resyncs are counted in
+    /// `metrics`.
+    ///
+    /// With `kv_event_stream`, the KV events are read with a durable consumer of the namespace's
+    /// stream. A router restored from a snapshot resumes after the last event it applied, one
+    /// without replays the events the stream retains.
+    pub async fn new_with_snapshots(
+        component: Component,
+        block_size: usize,
+        selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+        snapshot_dir: Option<PathBuf>,
+        snapshot_interval: Duration,
+        metrics: Arc<KvRouterMetrics>,
+        kv_event_stream: Option<KvEventStreamConfig>,
     ) -> Result<Self> {
         let cancellation_token = component
             .drt()
@@ -121,7 +266,13 @@ impl KvRouter {
         tracing::info!("KV Routing initialized");
         let metrics_aggregator =
             KvMetricsAggregator::new(component.clone(), cancellation_token.clone()).await;
-        let indexer = KvIndexer::new(cancellation_token.clone(), block_size);
+        let indexer = Arc::new(KvIndexer::new(cancellation_token.clone(), block_size));
+        let mut snapshotter = match snapshot_dir {
+            Some(dir) => {
+                Some(KvSnapshotter::restore(dir, &indexer, cancellation_token.clone()).await?)
+            }
+            None => None,
+        };
         let scheduler = KvScheduler::start(
             component.namespace().clone(),
             block_size,
@@ -130,24 +281,66 @@ impl KvRouter {
         )
         .await?;
 
+        let gaps_rx = indexer.event_gaps().await?;
+        tokio::spawn(resync_workers(
+            component.clone(),
+            indexer.clone(),
+            gaps_rx,
+            metrics,
+        ));
+
         // [gluo TODO] try subscribe_with_type::<RouterEvent>,
         // error checking below will be different.
-        let mut kv_events_rx = component.subscribe(KV_EVENT_SUBJECT).await?;
+        let mut kv_events_rx = KvEventSubscriber::subscribe(
+            &component,
+            kv_event_stream.as_ref(),
+            snapshotter.is_some(),
+        )
+        .await?;
         let kv_events_tx = indexer.event_sender();
+        let snapshot_indexer = indexer.clone();
 
         tokio::spawn(async move {
-            while let Some(event) = kv_events_rx.next().await {
-                let event: RouterEvent = match serde_json::from_slice(&event.payload) {
-                    Ok(event) => event,
-                    Err(e) => {
-                        tracing::warn!("Failed to deserialize RouterEvent: {:?}", e);
-                        // Choosing warn and continue to process other events from other workers
-                        // A bad event likely signals a problem with a worker, but potentially other workers are still healthy
-                        continue;
+            let mut snapshot_ticker = tokio::time::interval(snapshot_interval);
+            snapshot_ticker.reset();
+            loop {
+                tokio::select! {
+                    event = kv_events_rx.next() => {
+                        let Some(event) = event else {
+                            break;
+                        };
+                        // Workers publish either format during a rollout of the binary one
+                        let events = match decode_router_events(event.payload()) {
+                            Ok(events) => events,
+                            Err(e) => {
+                                tracing::warn!("Failed to deserialize RouterEvent: {:?}", e);
+                                // Choosing warn and continue to process other events from other workers
+                                // A bad event likely signals a problem with a worker, but potentially other workers are still healthy
+                                // Redelivering it would not help
+                                event.ack().await;
+                                continue;
+                            }
+                        };
+                        for event in events {
+                            if let Some(snapshotter) = snapshotter.as_mut() {
+                                snapshotter.record(event.clone()).await;
+                            }
+                            if let Err(e) = kv_events_tx.send(event).await {
+                                tracing::debug!("failed to send kv event to indexer; shutting down: {:?}", e);
+                            }
+                        }
+                        event.ack().await;
+                    }
+
+                    // Events are recorded and sent to the indexer in the same order by this task,
+                    // so the snapshot matches the recorded event count.
+                    _ = snapshot_ticker.tick(), if snapshotter.is_some() => {
+                        if let Some(snapshotter) = snapshotter.as_mut() {
+                            if let Err(err) = snapshotter.snapshot(&snapshot_indexer).await {
+                                tracing::warn!(%err, "Failed to snapshot the KV router indexer");
+                            }
+                        }
                     }
-                };
-                if let Err(e) = kv_events_tx.send(event).await {
-                    tracing::debug!("failed to send kv event to indexer; shutting down: {:?}", e);
                 }
             }
         });
@@ -156,43 +349,78 @@ impl KvRouter {
             scheduler,
             indexer,
             block_size,
+            cancellation_token,
+            decision_log: None,
         })
     }
 
-    // [TODO] indexer needs to take 'lora_id' as parameter
-    pub async fn schedule(&self, token_ids: &Vec<u32>, _lora_id: u64) -> Result<i64> {
-        // Extracting part of the code in KvRouter::generate() for only
-        // the decision making part, routing is done by the caller
-        let isl_tokens = token_ids.len();
-        let overlap_scores = self
-            .indexer
-            .find_matches_for_request(token_ids.as_slice())
+    /// Record the [`RoutingDecision`]s of a `sample_rate` fraction of the requests in the JSONL
+    /// file at `path`, which is truncated first. The records are buffered, the file is complete
+    /// once the router shuts down.
+    pub async fn with_decision_log(
+        mut self,
+        path: impl AsRef<Path>,
+        sample_rate: f64,
+    ) -> Result<Self> {
+        let recorder =
+            Recorder::new(self.cancellation_token.clone(), path, None, None, None).await?;
+        self.decision_log = Some(DecisionLog {
+            decision_tx: recorder.event_sender(),
+            _recorder: recorder,
+            sample_rate,
+        });
+        Ok(self)
+    }
+
+    /// Select the worker for these tokens, generated with the LoRA adapter `lora_id`
+    /// (0 for the base model).
+    pub async fn schedule(&self, token_ids: &Vec<u32>, lora_id: u64) -> Result<i64> {
+        // The decision making part of KvRouter::generate(), routing is done by the caller
+        let (worker, _, _) = self
+            .find_best_match(token_ids, lora_id, None, false, HashSet::new())
             .await?;
-        tracing::debug!("KV router overlap_scores: {:?}", overlap_scores);
-        let worker_id = self.scheduler.schedule(overlap_scores, isl_tokens).await?;
-        Ok(worker_id)
+        Ok(worker.worker_id)
     }
 
-    /// Give these tokens, find the worker with the best match in it's KV cache.
+    /// Give these tokens, find the worker and data parallel rank with the best match in its KV
+    /// cache. Only blocks of the same LoRA adapter match, `lora_id` is 0 for the base model.
     /// Returned overlap amount is in number of blocks.
-    async fn find_best_match(&self, tokens: &[u32]) -> anyhow::Result<(i64, u32)> {
+    ///
+    /// The decision behind the choice is returned if `explain` is set, and recorded in the
+    /// decision log if the request is sampled, both tagged with `request_id`. The `unavailable`
+    /// workers are only chosen if no other worker is left.
+    async fn find_best_match(
+        &self,
+        tokens: &[u32],
+        lora_id: u64,
+        request_id: Option<&str>,
+        explain: bool,
+        unavailable: HashSet<i64>,
+    ) -> anyhow::Result<(WorkerWithDpRank, u32, Option<RoutingDecision>)> {
         let isl_tokens = tokens.len();
-        let block_size = self.block_size;
-
-        let (complete_blocks, _partial_block) =
-            TokenBlockSequence::split_tokens(tokens, block_size, 1337_u64);
-
-        let local_block_hashes = complete_blocks
-            .into_iter()
-            .map(|block| LocalBlockHash(block.block_hash()))
-            .collect();
-        let overlap_scores = self.indexer.find_matches(local_block_hashes).await?;
-        let worker_id = self
+        let block_hashes = compute_block_hash_for_seq_with_lora(tokens, self.block_size, lora_id);
+        let overlap_scores = self.indexer.find_matches(block_hashes.clone()).await?;
+        tracing::debug!("KV router overlap_scores: {:?}", overlap_scores);
+        let decision_log = self.decision_log.as_ref().filter(|log| log.sample());
+        let (worker, mut decision) = self
             .scheduler
-            .schedule(overlap_scores.clone(), isl_tokens)
+            .schedule(
+                overlap_scores.clone(),
+                block_hashes,
+                isl_tokens,
+                lora_id,
+                explain || decision_log.is_some(),
+                unavailable,
+            )
             .await?;
-        let overlap_amount = overlap_scores.scores.get(&worker_id).copied().unwrap_or(0);
-        Ok((worker_id, overlap_amount))
+        if let Some(decision) = decision.as_mut() {
+            decision.request_id = request_id.map(str::to_string);
+        }
+        if let (Some(decision_log), Some(decision)) = (decision_log, decision.as_ref()) {
+            decision_log.record(decision.clone());
+        }
+        let overlap_amount = overlap_scores.scores.get(&worker).copied().unwrap_or(0);
+        Ok((worker, overlap_amount, decision.filter(|_| explain)))
     }
 
     /// Get the block size this router was configured with
@@ -201,6 +429,96 @@ impl KvRouter {
     }
 }
 
+/// Resync the blocks of the workers the indexer missed KV events of, from the dump each worker
+/// serves on its [`KV_DUMP_ENDPOINT`].
+async fn resync_workers(
+    component: Component,
+    indexer: Arc<KvIndexer>,
+    mut gaps_rx: mpsc::Receiver<EventGap>,
+    metrics: Arc<KvRouterMetrics>,
+) {
+    let router = match component.endpoint(KV_DUMP_ENDPOINT).client().await {
+        Ok(client) => {
+            PushRouter::<KvDumpRequest, Annotated<KvCacheDump>>::from_client(
+                client,
+                Default::default(),
+            )
+            .await
+        }
+        Err(err) => Err(err),
+    };
+    let router = match router {
+        Ok(router) => Arc::new(router),
+        Err(err) => {
+            tracing::error!(%err, "Failed to create the {KV_DUMP_ENDPOINT} client; workers will not be resynced");
+            return;
+        }
+    };
+
+    while let Some(gap) = gaps_rx.recv().await {
+        metrics.inc_event_gaps();
+        let router = router.clone();
+        let indexer = indexer.clone();
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let worker = gap.worker;
+            let request = request_dump(&router, worker);
+            let dump = match tokio::time::timeout(KV_DUMP_TIMEOUT, request).await {
+                Ok(Ok(dump)) if dump.dp_rank == worker.dp_rank => Some(dump),
+                Ok(Ok(dump)) => {
+                    tracing::warn!(
+                        %worker,
+                        dump_dp_rank = dump.dp_rank,
+                        "The worker served the KV blocks of another data parallel rank"
+                    );
+                    None
+                }
+                Ok(Err(err)) => {
+                    tracing::warn!(%worker, %err, "Failed to get the KV blocks of the worker");
+                    None
+                }
+                Err(_) => {
+                    tracing::warn!(%worker, "Timed out getting the KV blocks of the worker");
+                    None
+                }
+            };
+            if let Some(dump) = dump.as_ref() {
+                tracing::info!(
+                    %worker,
+                    blocks = dump.events.len(),
+                    "Resynced the KV blocks of the worker"
+                );
+            }
+            metrics.inc_resyncs(if dump.is_some() {
+                RESYNC_SUCCESS
+            } else {
+                RESYNC_FAILURE
+            });
+            if let Err(err) = indexer.finish_resync(worker, dump).await {
+                tracing::debug!(%err, "Failed to finish the resync of worker {worker}");
+            }
+        });
+    }
+}
+
+/// Request the blocks stored by a worker on one of its data parallel ranks
+async fn request_dump(
+    router: &PushRouter<KvDumpRequest, Annotated<KvCacheDump>>,
+    worker: WorkerWithDpRank,
+) -> Result<KvCacheDump> {
+    let request = KvDumpRequest {
+        dp_rank: worker.dp_rank,
+    };
+    let mut stream = router.direct(request.into(), worker.worker_id).await?;
+    let response = stream
+        .next()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("Empty response from {KV_DUMP_ENDPOINT}"))?;
+    response
+        .into_result()?
+        .ok_or_else(|| anyhow::anyhow!("No data in the response from {KV_DUMP_ENDPOINT}"))
+}
+
 #[async_trait]
 impl AsyncEngine<SingleIn<RouterRequest>, ManyOut<Annotated<RouterResponse>>, Error> for KvRouter {
     async fn generate(
@@ -208,9 +526,13 @@ impl AsyncEngine<SingleIn<RouterRequest>, ManyOut<Annotated<RouterResponse>>, Er
         request: SingleIn<RouterRequest>,
     ) -> Result<ManyOut<Annotated<RouterResponse>>> {
         let (request, ctx) = request.into_parts();
-        let (worker_id, _) = self.find_best_match(&request.tokens).await?;
+        let (worker, _, _) = self
+            .find_best_match(&request.tokens, 0, Some(ctx.id()), false, HashSet::new())
+            .await?;
 
-        let response = RouterResponse { worker_id };
+        let response = RouterResponse {
+            worker_id: worker.worker_id,
+        };
         let response = Annotated::from_data(response);
         let stream = stream::iter(vec![response]);
         Ok(ResponseStream::new(Box::pin(stream), ctx.context()))
@@ -242,13 +564,54 @@ impl AsyncEngine<SingleIn<PreprocessedRequest>, ManyOut<Annotated<LLMEngineOutpu
         match self.inner.client.instance_source.as_ref() {
             InstanceSource::Static => self.inner.r#static(request).await,
             InstanceSource::Dynamic(_) => {
-                let (instance_id, overlap_amount) =
-                    self.chooser.find_best_match(&request.token_ids).await?;
-                // Update the request with the estimated prefix hit blocks
+                // Wait for admission before choosing a worker, so the choice reflects the load
+                // when the request is sent
+                let permit = self.inner.admit(&request).await?;
+                // Keep the workers the client would not send to, e.g. with an open circuit, out
+                // of the choice
+                let available: HashSet<i64> = self
+                    .inner
+                    .client
+                    .instances_avail()
+                    .await
+                    .iter()
+                    .map(|instance| instance.id())
+                    .collect();
+                let unavailable = self
+                    .inner
+                    .client
+                    .instance_ids()
+                    .into_iter()
+                    .filter(|instance_id| !available.contains(instance_id))
+                    .collect();
+                let (worker, overlap_amount, decision) = self
+                    .chooser
+                    .find_best_match(
+                        &request.token_ids,
+                        request.lora_id.unwrap_or(0),
+                        Some(request.id()),
+                        request.has_annotation(ANNOTATION_ROUTING_DECISION),
+                        unavailable,
+                    )
+                    .await?;
+                // Update the request with the estimated prefix hit blocks, and the data parallel
+                // rank whose cache they are in
                 let (mut backend_input, context) = request.into_parts();
                 backend_input.estimated_prefix_hit_num_blocks = Some(overlap_amount);
+                backend_input.dp_rank = Some(worker.dp_rank);
                 let updated_request = context.map(|_| backend_input);
-                self.inner.direct(updated_request, instance_id).await
+                let response = self
+                    .inner
+                    .direct_admitted(updated_request, worker.worker_id, permit)
+                    .await?;
+                let Some(decision) = decision else {
+                    return Ok(response);
+                };
+                let annotation =
+                    Annotated::from_annotation(ANNOTATION_ROUTING_DECISION, &decision)?;
+                let context = response.context();
+                let stream = stream::iter([annotation]).chain(response);
+                Ok(ResponseStream::new(Box::pin(stream), context))
             }
         }
     }
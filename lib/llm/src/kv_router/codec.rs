@@ -0,0 +1,251 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Wire formats of the KV events published on [`KV_EVENT_SUBJECT`](super::KV_EVENT_SUBJECT).
+//!
+//! - [`KvEventEncoding::Json`]: one JSON [`RouterEvent`] per message, the original format.
+//! - [`KvEventEncoding::Binary`]: a batch of events of one worker per message, msgpack encoded
+//!   after a two byte header, [`BINARY_MAGIC`] and the format version.
+//!
+//! A JSON message starts with `{`, never with [`BINARY_MAGIC`], so [`decode_router_events`]
+//! accepts both formats. Routers are upgraded first, then publishers switch to the binary
+//! format with [`KV_EVENT_ENCODING_ENV`].
+
+use std::str::FromStr;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::kv_router::indexer::{RouterEvent, WorkerId};
+use crate::kv_router::protocols::{KvCacheEvent, WorkerWithDpRank};
+
+/// Environment variable selecting the [`KvEventEncoding`] of the KV event publishers
+pub const KV_EVENT_ENCODING_ENV: &str = "DYN_KV_EVENT_ENCODING";
+
+/// First byte of a binary KV event message
+pub const BINARY_MAGIC: u8 = 0xD7;
+
+/// Version of the binary format, the second byte of a binary KV event message
+pub const BINARY_VERSION: u8 = 1;
+
+/// How KV events are encoded on the event plane
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KvEventEncoding {
+    /// One JSON [`RouterEvent`] per message, understood by every router
+    #[default]
+    Json,
+    /// Batches of msgpack encoded events, understood by routers that decode both formats
+    Binary,
+}
+
+impl KvEventEncoding {
+    /// The encoding set in [`KV_EVENT_ENCODING_ENV`], JSON if unset or invalid
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var(KV_EVENT_ENCODING_ENV) else {
+            return Self::default();
+        };
+        value.parse().unwrap_or_else(|err| {
+            tracing::warn!(%err, "Invalid {KV_EVENT_ENCODING_ENV}, publishing KV events as JSON");
+            Self::default()
+        })
+    }
+}
+
+impl FromStr for KvEventEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "binary" | "msgpack" => Ok(Self::Binary),
+            other => anyhow::bail!("unknown KV event encoding '{other}', expected json or binary"),
+        }
+    }
+}
+
+/// The events of a worker in a binary message
+#[derive(Debug, Serialize, Deserialize)]
+struct RouterEventBatch {
+    worker_id: WorkerId,
+    dp_rank: u32,
+    events: Vec<KvCacheEvent>,
+}
+
+/// Encode the events of a worker as the messages to publish: one per event in JSON, a single
+/// one for the batch in binary.
+pub fn encode_router_events(
+    worker: WorkerWithDpRank,
+    events: Vec<KvCacheEvent>,
+    encoding: KvEventEncoding,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    match encoding {
+        KvEventEncoding::Json => events
+            .into_iter()
+            .map(|event| {
+                let event = RouterEvent::new(worker.worker_id, event).with_dp_rank(worker.dp_rank);
+                serde_json::to_vec(&event).context("Failed to encode KV event as JSON")
+            })
+            .collect(),
+        KvEventEncoding::Binary => {
+            let batch = RouterEventBatch {
+                worker_id: worker.worker_id,
+                dp_rank: worker.dp_rank,
+                events,
+            };
+            let mut message = vec![BINARY_MAGIC, BINARY_VERSION];
+            rmp_serde::encode::write(&mut message, &batch)
+                .context("Failed to encode KV events as msgpack")?;
+            Ok(vec![message])
+        }
+    }
+}
+
+/// Decode a KV event message in either format
+pub fn decode_router_events(payload: &[u8]) -> anyhow::Result<Vec<RouterEvent>> {
+    let Some((&BINARY_MAGIC, rest)) = payload.split_first() else {
+        let event: RouterEvent =
+            serde_json::from_slice(payload).context("Failed to decode JSON KV event")?;
+        return Ok(vec![event]);
+    };
+    let Some((&version, body)) = rest.split_first() else {
+        anyhow::bail!("Truncated binary KV event message");
+    };
+    anyhow::ensure!(
+        version == BINARY_VERSION,
+        "Unsupported binary KV event version {version}, this router decodes version {BINARY_VERSION}"
+    );
+    let batch: RouterEventBatch =
+        rmp_serde::from_slice(body).context("Failed to decode binary KV events")?;
+    Ok(batch
+        .events
+        .into_iter()
+        .map(|event| RouterEvent::new(batch.worker_id, event).with_dp_rank(batch.dp_rank))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv_router::protocols::{
+        ExternalSequenceBlockHash, KvCacheEventData, KvCacheRemoveData, KvCacheStoreData,
+        KvCacheStoredBlockData, LocalBlockHash,
+    };
+
+    fn events() -> Vec<KvCacheEvent> {
+        vec![
+            KvCacheEvent {
+                event_id: 0,
+                data: KvCacheEventData::Stored(KvCacheStoreData {
+                    parent_hash: None,
+                    blocks: (0..4)
+                        .map(|i| KvCacheStoredBlockData {
+                            block_hash: ExternalSequenceBlockHash(i),
+                            tokens_hash: LocalBlockHash(100 + i),
+                        })
+                        .collect(),
+                    lora_id: 7,
+                }),
+            },
+            KvCacheEvent {
+                event_id: 1,
+                data: KvCacheEventData::Removed(KvCacheRemoveData {
+                    block_hashes: vec![ExternalSequenceBlockHash(3)],
+                }),
+            },
+            KvCacheEvent {
+                event_id: 2,
+                data: KvCacheEventData::Cleared,
+            },
+        ]
+    }
+
+    fn roundtrip(encoding: KvEventEncoding) -> (usize, Vec<RouterEvent>) {
+        let worker = WorkerWithDpRank::new(42, 3);
+        let messages = encode_router_events(worker, events(), encoding).unwrap();
+        let decoded = messages
+            .iter()
+            .flat_map(|message| decode_router_events(message).unwrap())
+            .collect();
+        (messages.len(), decoded)
+    }
+
+    #[test]
+    fn test_roundtrip_both_encodings() {
+        let expected = serde_json::to_string(
+            &events()
+                .into_iter()
+                .map(|event| RouterEvent::new(42, event).with_dp_rank(3))
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        let (num_messages, decoded) = roundtrip(KvEventEncoding::Json);
+        assert_eq!(num_messages, 3);
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), expected);
+
+        let (num_messages, decoded) = roundtrip(KvEventEncoding::Binary);
+        assert_eq!(num_messages, 1);
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_binary_is_smaller() {
+        let worker = WorkerWithDpRank::from(1);
+        let json: usize = encode_router_events(worker, events(), KvEventEncoding::Json)
+            .unwrap()
+            .iter()
+            .map(Vec::len)
+            .sum();
+        let binary: usize = encode_router_events(worker, events(), KvEventEncoding::Binary)
+            .unwrap()
+            .iter()
+            .map(Vec::len)
+            .sum();
+        assert!(
+            binary < json / 2,
+            "binary {binary} bytes, json {json} bytes"
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_version() {
+        let mut message =
+            encode_router_events(WorkerWithDpRank::from(1), events(), KvEventEncoding::Binary)
+                .unwrap()
+                .remove(0);
+        message[1] = BINARY_VERSION + 1;
+        assert!(decode_router_events(&message).is_err());
+        assert!(decode_router_events(&[BINARY_MAGIC]).is_err());
+        assert!(decode_router_events(b"not json").is_err());
+    }
+
+    #[test]
+    fn test_encoding_from_str() {
+        assert_eq!(
+            "json".parse::<KvEventEncoding>().unwrap(),
+            KvEventEncoding::Json
+        );
+        assert_eq!(
+            "Binary".parse::<KvEventEncoding>().unwrap(),
+            KvEventEncoding::Binary
+        );
+        assert_eq!(
+            "msgpack".parse::<KvEventEncoding>().unwrap(),
+            KvEventEncoding::Binary
+        );
+        assert!("protobuf".parse::<KvEventEncoding>().is_err());
+    }
+}
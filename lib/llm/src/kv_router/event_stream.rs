@@ -0,0 +1,288 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Durable KV events, retained in a NATS JetStream stream per namespace.
+//!
+//! The stream captures the [`KV_EVENT_SUBJECT`] of every component of the namespace, whether
+//! the publishers publish through JetStream ([`KvEventStreamPublisher`]) or on core NATS, and
+//! core subscribers still receive the events published through JetStream. Publishers and
+//! routers can therefore switch to the stream independently.
+//!
+//! A router reads the events of its component with a durable consumer and acknowledges them
+//! once applied, see [`KvEventSubscriber`]. A router restarted from a snapshot resumes after the
+//! last event it acknowledged; without a snapshot it replays everything the stream retains.
+
+use std::time::Duration;
+
+use anyhow::Context as _;
+use async_nats::jetstream::{self, consumer};
+use async_trait::async_trait;
+use dynamo_runtime::component::{Component, Namespace};
+use dynamo_runtime::slug::Slug;
+use dynamo_runtime::traits::events::{EventPublisher, EventStream, EventSubscriber};
+use dynamo_runtime::traits::DistributedRuntimeProvider;
+use dynamo_runtime::Result;
+use futures::StreamExt;
+use serde::Serialize;
+
+use crate::kv_router::KV_EVENT_SUBJECT;
+
+/// Environment variable enabling [`KvEventStreamPublisher`] in the KV event publishers
+pub const KV_EVENT_STREAM_ENV: &str = "DYN_KV_EVENT_STREAM";
+
+/// Default name of the durable consumer of a router
+pub const DEFAULT_CONSUMER_NAME: &str = "kv_router";
+
+/// Retention of the KV event stream of a namespace, and the consumer a router reads it with
+#[derive(Debug, Clone, PartialEq)]
+pub struct KvEventStreamConfig {
+    /// Name of the router's durable consumer. Each router replica needs its own, replicas
+    /// sharing a consumer would each see a part of the events. Default: "kv_router"
+    pub consumer_name: String,
+
+    /// How long the stream retains an event. Default: 1h
+    pub max_age: Duration,
+
+    /// Maximum size of the stream, the oldest events are dropped first. Default: 1 GiB
+    pub max_bytes: i64,
+}
+
+impl Default for KvEventStreamConfig {
+    fn default() -> Self {
+        Self {
+            consumer_name: DEFAULT_CONSUMER_NAME.to_string(),
+            max_age: Duration::from_secs(60 * 60),
+            max_bytes: 1 << 30,
+        }
+    }
+}
+
+impl KvEventStreamConfig {
+    /// The default config with the durable consumer `consumer_name`
+    pub fn with_consumer_name(consumer_name: impl Into<String>) -> Self {
+        Self {
+            consumer_name: consumer_name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The default config if [`KV_EVENT_STREAM_ENV`] is set to `1` or `true`
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(KV_EVENT_STREAM_ENV).ok()?;
+        matches!(value.to_ascii_lowercase().as_str(), "1" | "true").then(Self::default)
+    }
+}
+
+/// Name of the KV event stream of a namespace
+pub fn stream_name(namespace: &str) -> String {
+    Slug::slugify(&format!("{namespace}_{KV_EVENT_SUBJECT}")).to_string()
+}
+
+/// Subjects captured by the KV event stream of a namespace: the KV events of all its components
+fn stream_subject(namespace: &Namespace) -> String {
+    format!("{}.component.*.{KV_EVENT_SUBJECT}", namespace.subject())
+}
+
+/// Name of the durable consumer of a router for the KV events of `component`
+fn consumer_name(config: &KvEventStreamConfig, component: &Component) -> String {
+    Slug::slugify(&format!("{}_{}", config.consumer_name, component.name())).to_string()
+}
+
+/// The JetStream context of the component's runtime, which local runtimes do not have
+fn jetstream(component: &Component) -> Result<jetstream::Context> {
+    let nats_client = component
+        .drt()
+        .try_nats_client()
+        .ok_or_else(|| anyhow::anyhow!("The KV event stream requires NATS JetStream"))?;
+    Ok(nats_client.jetstream().clone())
+}
+
+/// Get the KV event stream of the namespace, creating it if it does not exist yet. An
+/// existing stream keeps its retention limits.
+async fn ensure_stream(
+    js: &jetstream::Context,
+    namespace: &Namespace,
+    config: &KvEventStreamConfig,
+) -> Result<jetstream::stream::Stream> {
+    let name = stream_name(&namespace.name());
+    js.get_or_create_stream(jetstream::stream::Config {
+        name: name.clone(),
+        subjects: vec![stream_subject(namespace)],
+        max_age: config.max_age,
+        max_bytes: config.max_bytes,
+        ..Default::default()
+    })
+    .await
+    .map_err(|err| anyhow::anyhow!("Failed to get or create the KV event stream {name}: {err}"))
+}
+
+/// Publishes the events of a component through JetStream, waiting for the stream to store each
+/// one
+pub struct KvEventStreamPublisher {
+    subject: String,
+    js: jetstream::Context,
+}
+
+impl KvEventStreamPublisher {
+    pub async fn new(component: &Component, config: &KvEventStreamConfig) -> Result<Self> {
+        let js = jetstream(component)?;
+        ensure_stream(&js, component.namespace(), config).await?;
+        Ok(Self {
+            subject: component.subject(),
+            js,
+        })
+    }
+}
+
+#[async_trait]
+impl EventPublisher for KvEventStreamPublisher {
+    fn subject(&self) -> String {
+        self.subject.clone()
+    }
+
+    async fn publish(
+        &self,
+        event_name: impl AsRef<str> + Send + Sync,
+        event: &(impl Serialize + Send + Sync),
+    ) -> Result<()> {
+        let bytes = serde_json::to_vec(event)?;
+        self.publish_bytes(event_name, bytes).await
+    }
+
+    async fn publish_bytes(
+        &self,
+        event_name: impl AsRef<str> + Send + Sync,
+        bytes: Vec<u8>,
+    ) -> Result<()> {
+        let subject = format!("{}.{}", self.subject, event_name.as_ref());
+        self.js.publish(subject, bytes.into()).await?.await?;
+        Ok(())
+    }
+}
+
+/// The KV events of a component, on core NATS or from the router's durable consumer
+pub enum KvEventSubscriber {
+    Core(EventStream),
+    Durable(consumer::pull::Stream),
+}
+
+/// A message of a [`KvEventSubscriber`]
+pub enum KvEventMessage {
+    Core(async_nats::Message),
+    Durable(jetstream::Message),
+}
+
+impl KvEventMessage {
+    pub fn payload(&self) -> &[u8] {
+        match self {
+            Self::Core(message) => &message.payload,
+            Self::Durable(message) => &message.payload,
+        }
+    }
+
+    /// Acknowledge a durable message, so that the consumer resumes after it
+    pub async fn ack(&self) {
+        if let Self::Durable(message) = self {
+            if let Err(err) = message.ack().await {
+                tracing::warn!(%err, "Failed to acknowledge a KV event");
+            }
+        }
+    }
+}
+
+impl KvEventSubscriber {
+    /// Subscribe to the KV events of `component`, from the namespace's KV event stream if
+    /// `stream` is set. With `resume`, the durable consumer continues after the last event it
+    /// acknowledged, otherwise it is recreated to replay everything the stream retains.
+    pub async fn subscribe(
+        component: &Component,
+        stream: Option<&KvEventStreamConfig>,
+        resume: bool,
+    ) -> Result<Self> {
+        let Some(config) = stream else {
+            return Ok(Self::Core(component.subscribe(KV_EVENT_SUBJECT).await?));
+        };
+        let js = jetstream(component)?;
+        let stream = ensure_stream(&js, component.namespace(), config).await?;
+
+        let name = consumer_name(config, component);
+        if !resume {
+            // A missing consumer is the common case
+            let _ = stream.delete_consumer(&name).await;
+        }
+        let consumer: consumer::PullConsumer = stream
+            .get_or_create_consumer(
+                &name,
+                consumer::pull::Config {
+                    durable_name: Some(name.clone()),
+                    filter_subject: format!("{}.{KV_EVENT_SUBJECT}", component.subject()),
+                    deliver_policy: consumer::DeliverPolicy::All,
+                    ack_policy: consumer::AckPolicy::Explicit,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|err| {
+                anyhow::anyhow!("Failed to create the KV event consumer {name}: {err}")
+            })?;
+        let messages = consumer
+            .messages()
+            .await
+            .with_context(|| format!("Failed to consume the KV events of {name}"))?;
+        tracing::info!(consumer = %name, resume, "Consuming the durable KV event stream");
+        Ok(Self::Durable(messages))
+    }
+
+    /// The next message, `None` once the subscription ends
+    pub async fn next(&mut self) -> Option<KvEventMessage> {
+        match self {
+            Self::Core(subscriber) => subscriber.next().await.map(KvEventMessage::Core),
+            Self::Durable(messages) => loop {
+                match messages.next().await? {
+                    Ok(message) => return Some(KvEventMessage::Durable(message)),
+                    // e.g. missed heartbeats while the server is unreachable, the stream
+                    // recovers once it is back
+                    Err(err) => {
+                        tracing::warn!(%err, "Failed to receive a KV event from the stream")
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_name_is_valid() {
+        assert_eq!(stream_name("dynamo"), "dynamo_kv_events");
+        // Stream names cannot contain dots, wildcards or whitespace
+        assert_eq!(stream_name("my.ns *"), "my_ns___kv_events");
+    }
+
+    #[test]
+    fn test_stream_config_defaults() {
+        let config = KvEventStreamConfig::with_consumer_name("router-1");
+        assert_eq!(config.consumer_name, "router-1");
+        assert_eq!(config.max_age, Duration::from_secs(3600));
+        assert_eq!(config.max_bytes, 1 << 30);
+        assert_eq!(
+            KvEventStreamConfig::default().consumer_name,
+            DEFAULT_CONSUMER_NAME
+        );
+    }
+}
@@ -83,6 +83,9 @@ pub type WorkerId = i64;
 /// A shared reference to a [`RadixBlock`].
 type SharedRadixBlock = Rc<RefCell<RadixBlock>>;
 
+/// Maximum number of events of a worker buffered while its blocks are resynced
+const MAX_RESYNC_BUFFERED_EVENTS: usize = 16384;
+
 pub fn compute_hash(data: &[u8]) -> u64 {
     xxh3::xxh3_64_with_seed(data, XXH3_SEED)
 }
@@ -100,16 +103,6 @@ pub fn compute_block_hash(data: &[u8]) -> LocalBlockHash {
     LocalBlockHash(compute_hash(data))
 }
 
-// /// Updated version of the `compute_block_hash` function that included the lora_id
-// pub fn compute_block_hash_v2(token_id: &[u32], lora_id: u64) {
-//     let mut bytes = Vec::new();
-//     for token in token_id {
-//         bytes.extend_from_slice(&token.to_le_bytes());
-//     }
-//     bytes.extend_from_slice(&lora_id.to_le_bytes());
-//     let hash = xxh3::xxh3_64_with_seed(&bytes, XXH3_SEED);
-// }
-
 /// Compute the hash for a sequence of tokens.
 ///
 /// ### Arguments
@@ -120,13 +113,37 @@ pub fn compute_block_hash(data: &[u8]) -> LocalBlockHash {
 ///
 /// A vector of `LocalBlockHash` representing the computed hashes for each chunk of tokens.
 pub fn compute_block_hash_for_seq(tokens: &[u32], kv_block_size: usize) -> Vec<LocalBlockHash> {
+    compute_block_hash_for_seq_with_lora(tokens, kv_block_size, 0)
+}
+
+/// Compute the hash for a sequence of tokens generated with a LoRA adapter.
+///
+/// The adapter is part of the hash, so that the blocks of different adapters never match. The
+/// base model, `lora_id` 0, hashes like [`compute_block_hash_for_seq`].
+///
+/// ### Arguments
+///
+/// * `tokens` - A vector of `u32` tokens.
+/// * `lora_id` - The ID of the LoRA adapter, 0 for the base model.
+///
+/// ### Returns
+///
+/// A vector of `LocalBlockHash` representing the computed hashes for each chunk of tokens.
+pub fn compute_block_hash_for_seq_with_lora(
+    tokens: &[u32],
+    kv_block_size: usize,
+    lora_id: u64,
+) -> Vec<LocalBlockHash> {
     tokens
         .chunks_exact(kv_block_size) // Split into chunks of kv_block_size elements
         .map(|chunk| {
-            let bytes: Vec<u8> = chunk
+            let mut bytes: Vec<u8> = chunk
                 .iter()
                 .flat_map(|&num| num.to_le_bytes()) // Convert each i32 to its little-endian bytes
                 .collect();
+            if lora_id != 0 {
+                bytes.extend_from_slice(&lora_id.to_le_bytes());
+            }
 
             compute_block_hash(&Bytes::from(bytes)) // Convert the byte Vec to Bytes
         })
@@ -138,6 +155,9 @@ pub fn compute_block_hash_for_seq(tokens: &[u32], kv_block_size: usize) -> Vec<L
 pub struct RouterEvent {
     /// The ID of the worker emitting the event.
     worker_id: WorkerId,
+    /// The data parallel rank of the worker whose cache the event is about.
+    #[serde(default)]
+    dp_rank: u32,
     /// The cache event associated with the worker.
     event: KvCacheEvent,
 }
@@ -154,8 +174,34 @@ impl RouterEvent {
     ///
     /// A new `RouterEvent`.
     pub fn new(worker_id: WorkerId, event: KvCacheEvent) -> Self {
-        Self { worker_id, event }
+        Self {
+            worker_id,
+            dp_rank: 0,
+            event,
+        }
+    }
+
+    /// Set the data parallel rank of the worker whose cache the event is about, 0 by default.
+    pub fn with_dp_rank(mut self, dp_rank: u32) -> Self {
+        self.dp_rank = dp_rank;
+        self
     }
+
+    /// The worker and data parallel rank the event is about.
+    pub fn worker(&self) -> WorkerWithDpRank {
+        WorkerWithDpRank::new(self.worker_id, self.dp_rank)
+    }
+}
+
+/// Events of a worker that never reached the indexer, detected from the event IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventGap {
+    /// The worker and data parallel rank that emitted the missing events.
+    pub worker: WorkerWithDpRank,
+    /// The ID of the last event received before the gap.
+    pub last_event_id: u64,
+    /// The ID of the event received after the gap.
+    pub event_id: u64,
 }
 
 /// A block in the Radix Tree.
@@ -163,10 +209,12 @@ impl RouterEvent {
 struct RadixBlock {
     /// A map of child blocks, keyed by their local block hash.
     children: HashMap<LocalBlockHash, SharedRadixBlock>,
-    /// A set of worker IDs associated with this block.
-    workers: HashSet<WorkerId>,
+    /// A set of workers associated with this block.
+    workers: HashSet<WorkerWithDpRank>,
     /// A buffer of times that this block was last traversed
     recent_uses: VecDeque<Instant>,
+    /// The ID of the LoRA adapter the block was generated with, 0 for the base model.
+    lora_id: u64,
 }
 
 impl RadixBlock {
@@ -180,6 +228,7 @@ impl RadixBlock {
             children: HashMap::new(),
             workers: HashSet::new(),
             recent_uses: VecDeque::new(),
+            lora_id: 0,
         }
     }
 }
@@ -197,9 +246,13 @@ pub struct RadixTree {
     /// Transitioning to a radix tree only would require a change in the messaging structure
     /// as the entire prefix would need to be sent. Alternatively, we could use block_depth
     /// integers to indicate how many blocks to skip and use a radix/prefix tree at each level.
-    lookup: HashMap<WorkerId, HashMap<ExternalSequenceBlockHash, SharedRadixBlock>>,
+    lookup: HashMap<WorkerWithDpRank, HashMap<ExternalSequenceBlockHash, SharedRadixBlock>>,
     /// The time buffer the radix tree should check when considering frequence of block accesses
     expiration_duration: Option<Duration>,
+    /// The ID of the last event received from each worker
+    last_event_ids: HashMap<WorkerWithDpRank, u64>,
+    /// The events received from each worker being resynced, see [`RadixTree::start_resync`]
+    resyncs: HashMap<WorkerWithDpRank, Vec<RouterEvent>>,
 }
 
 impl Default for RadixTree {
@@ -219,6 +272,8 @@ impl RadixTree {
             root: Rc::new(RefCell::new(RadixBlock::new())),
             lookup: HashMap::new(),
             expiration_duration,
+            last_event_ids: HashMap::new(),
+            resyncs: HashMap::new(),
         }
     }
 
@@ -280,8 +335,51 @@ impl RadixTree {
     /// ### Arguments
     ///
     /// * `event` - The `RouterEvent` to apply.
-    pub fn apply_event(&mut self, event: RouterEvent) {
-        let (worker_id, event) = (event.worker_id, event.event);
+    ///
+    /// ### Returns
+    ///
+    /// An [`EventGap`] if events of the worker were missed before this one. The event is applied
+    /// anyway; the tree is only accurate for this worker again once it is resynced.
+    pub fn apply_event(&mut self, event: RouterEvent) -> Option<EventGap> {
+        let gap = self.track_event(&event);
+        self.apply_cache_event(event);
+        gap
+    }
+
+    /// Check the event follows the last one of its worker. Events with an ID that was already
+    /// received are expected, a batch of events can share its ID.
+    fn track_event(&mut self, event: &RouterEvent) -> Option<EventGap> {
+        let (worker, event_id) = (event.worker(), event.event.event_id);
+
+        if let Some(buffered) = self.resyncs.get_mut(&worker) {
+            if buffered.len() < MAX_RESYNC_BUFFERED_EVENTS {
+                buffered.push(event.clone());
+            } else {
+                tracing::warn!(
+                    %worker,
+                    "Too many events during the resync of the worker; abandoning it"
+                );
+                self.resyncs.remove(&worker);
+            }
+        }
+
+        let gap = match self.last_event_ids.get(&worker) {
+            Some(&last_event_id) if event_id > last_event_id.saturating_add(1) => Some(EventGap {
+                worker,
+                last_event_id,
+                event_id,
+            }),
+            _ => None,
+        };
+        let last_event_id = self.last_event_ids.entry(worker).or_insert(event_id);
+        *last_event_id = (*last_event_id).max(event_id);
+
+        // A resync in progress also covers this gap
+        gap.filter(|_| !self.resyncs.contains_key(&worker))
+    }
+
+    fn apply_cache_event(&mut self, event: RouterEvent) {
+        let (worker_id, event) = (event.worker(), event.event);
         let (id, op) = (event.event_id, event.data);
         tracing::trace!(id, "Store operation: {:?}", op);
 
@@ -310,6 +408,7 @@ impl RadixTree {
                     }
                 };
 
+                let lora_id = op.lora_id;
                 for block_id in op.blocks {
                     let mut inner = current.borrow_mut();
                     let block = match inner.children.get(&block_id.tokens_hash) {
@@ -319,7 +418,12 @@ impl RadixTree {
                             let new_block = worker_lookup
                                 .get(&block_id.block_hash)
                                 .cloned()
-                                .unwrap_or_else(|| Rc::new(RefCell::new(RadixBlock::new())));
+                                .unwrap_or_else(|| {
+                                    Rc::new(RefCell::new(RadixBlock {
+                                        lora_id,
+                                        ..RadixBlock::new()
+                                    }))
+                                });
 
                             // insert into radix tree
                             inner
@@ -379,15 +483,113 @@ impl RadixTree {
         }
     }
 
-    pub fn remove_worker(&mut self, worker: WorkerId) {
-        if let Some((_, blocks)) = self.lookup.remove_entry(&worker) {
-            blocks.iter().for_each(|(_, block)| {
-                block.borrow_mut().workers.remove(&worker);
-            });
+    /// Describe the tree as the `Stored` events that rebuild it, one per block and worker, with
+    /// parents before their children. Applying them to an empty tree restores the blocks of
+    /// every worker, including the per-worker lookup tables.
+    pub fn dump_events(&self) -> Vec<RouterEvent> {
+        let mut events = Vec::new();
+        for (worker, blocks) in &self.lookup {
+            let block_hashes: HashMap<*const RefCell<RadixBlock>, ExternalSequenceBlockHash> =
+                blocks
+                    .iter()
+                    .map(|(block_hash, block)| (Rc::as_ptr(block), *block_hash))
+                    .collect();
+
+            // The restored tree then expects the next event of the worker
+            let event_id = self.last_event_ids.get(worker).copied().unwrap_or_default();
+
+            // Only follow the blocks of this worker, a block is only reachable through its parent
+            let mut stack = vec![(self.root.clone(), None)];
+            while let Some((block, parent_hash)) = stack.pop() {
+                for (tokens_hash, child) in block.borrow().children.iter() {
+                    let Some(block_hash) = block_hashes.get(&Rc::as_ptr(child)).copied() else {
+                        continue;
+                    };
+                    events.push(
+                        RouterEvent::new(
+                            worker.worker_id,
+                            KvCacheEvent {
+                                event_id,
+                                data: KvCacheEventData::Stored(KvCacheStoreData {
+                                    parent_hash,
+                                    blocks: vec![KvCacheStoredBlockData {
+                                        block_hash,
+                                        tokens_hash: *tokens_hash,
+                                    }],
+                                    lora_id: child.borrow().lora_id,
+                                }),
+                            },
+                        )
+                        .with_dp_rank(worker.dp_rank),
+                    );
+                    stack.push((child.clone(), Some(block_hash)));
+                }
+            }
         }
+        events
     }
 
-    pub fn clear_all_blocks(&mut self, worker: WorkerId) {
+    /// Remove every data parallel rank of the worker.
+    pub fn remove_worker(&mut self, worker_id: WorkerId) {
+        let ranks: Vec<WorkerWithDpRank> = self
+            .lookup
+            .keys()
+            .filter(|worker| worker.worker_id == worker_id)
+            .copied()
+            .collect();
+        for worker in ranks {
+            if let Some(blocks) = self.lookup.remove(&worker) {
+                blocks.iter().for_each(|(_, block)| {
+                    block.borrow_mut().workers.remove(&worker);
+                });
+            }
+        }
+        self.last_event_ids
+            .retain(|worker, _| worker.worker_id != worker_id);
+        self.resyncs
+            .retain(|worker, _| worker.worker_id != worker_id);
+    }
+
+    /// Start buffering the events of `worker` while a dump of its blocks is requested, so that
+    /// the events the dump misses are applied again by [`RadixTree::finish_resync`].
+    pub fn start_resync(&mut self, worker: WorkerWithDpRank) {
+        self.resyncs.insert(worker, Vec::new());
+    }
+
+    /// Replace the blocks of `worker` by the ones in `dump`, then apply the events received
+    /// since the dump was requested that it may not include. Applying an event twice is harmless.
+    ///
+    /// Without a dump, e.g. when the worker could not provide one, the resync is abandoned.
+    pub fn finish_resync(&mut self, worker: WorkerWithDpRank, dump: Option<KvCacheDump>) {
+        let Some(buffered) = self.resyncs.remove(&worker) else {
+            tracing::warn!(%worker, "No resync in progress for the worker");
+            return;
+        };
+        let Some(dump) = dump else {
+            return;
+        };
+
+        self.clear_all_blocks(worker);
+        for event in dump.events {
+            self.apply_cache_event(
+                RouterEvent::new(worker.worker_id, event).with_dp_rank(worker.dp_rank),
+            );
+        }
+        for event in buffered {
+            if dump
+                .last_event_id
+                .is_none_or(|last_event_id| event.event.event_id >= last_event_id)
+            {
+                self.apply_cache_event(event);
+            }
+        }
+        if let Some(last_event_id) = dump.last_event_id {
+            let tracked = self.last_event_ids.entry(worker).or_insert(last_event_id);
+            *tracked = (*tracked).max(last_event_id);
+        }
+    }
+
+    pub fn clear_all_blocks(&mut self, worker: WorkerWithDpRank) {
         // Check if the worker has any blocks to clear
         if let Some(blocks) = self.lookup.get(&worker) {
             let blocks_to_clear: Vec<_> = blocks.values().collect();
@@ -408,8 +610,8 @@ impl RadixTree {
 /// Scores representing the overlap of workers.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OverlapScores {
-    // map of worker_id to score
-    pub scores: HashMap<WorkerId, u32>,
+    // map of worker and data parallel rank to score
+    pub scores: HashMap<WorkerWithDpRank, u32>,
     // List of frequencies that the blocks have been accessed. Entries with value 0 are omitted.
     pub frequencies: Vec<usize>,
 }
@@ -437,8 +639,8 @@ impl OverlapScores {
     ///
     /// ### Arguments
     ///
-    /// * `workers` - A reference to a `HashSet` of `WorkerId`s.
-    pub fn update_scores(&mut self, workers: &HashSet<WorkerId>) {
+    /// * `workers` - A reference to a `HashSet` of `WorkerWithDpRank`s.
+    pub fn update_scores(&mut self, workers: &HashSet<WorkerWithDpRank>) {
         for worker in workers {
             let score = self.scores.entry(*worker).or_insert(0);
             *score += 1;
@@ -494,6 +696,25 @@ pub trait KvIndexerInterface {
     async fn find_matches_for_request(
         &self,
         tokens: &[u32],
+    ) -> Result<OverlapScores, KvRouterError> {
+        self.find_matches_for_lora_request(tokens, 0).await
+    }
+
+    /// Find matches for a given sequence of tokens generated with a LoRA adapter. Only the
+    /// blocks of the same adapter match.
+    ///
+    /// ### Arguments
+    ///
+    /// * `tokens` - A vector of `u32` tokens.
+    /// * `lora_id` - The ID of the LoRA adapter, 0 for the base model.
+    ///
+    /// ### Returns
+    ///
+    /// An `OverlapScores` representing the match scores.
+    async fn find_matches_for_lora_request(
+        &self,
+        tokens: &[u32],
+        lora_id: u64,
     ) -> Result<OverlapScores, KvRouterError>;
 
     /// Apply a `RouterEvent` to the KV store.
@@ -524,6 +745,14 @@ pub struct KvIndexer {
     match_tx: mpsc::Sender<MatchRequest>,
     /// A sender for remove worker requests.
     remove_worker_tx: mpsc::Sender<WorkerId>,
+    /// A sender for requests to dump the tree as events.
+    dump_tx: mpsc::Sender<oneshot::Sender<Vec<RouterEvent>>>,
+    /// A sender for requests to wait until the events already sent are applied.
+    flush_tx: mpsc::Sender<oneshot::Sender<()>>,
+    /// A sender for the channel to notify event gaps on.
+    gaps_tx: mpsc::Sender<mpsc::Sender<EventGap>>,
+    /// A sender for the dumps finishing worker resyncs.
+    resync_tx: mpsc::Sender<(WorkerWithDpRank, Option<KvCacheDump>)>,
     /// A handle to the background task managing the KV store.
     task: OnceLock<std::thread::JoinHandle<()>>,
     /// The size of the KV block this indexer can handle.
@@ -549,6 +778,10 @@ impl KvIndexer {
         let (event_tx, event_rx) = mpsc::channel::<RouterEvent>(2048);
         let (match_tx, match_rx) = mpsc::channel::<MatchRequest>(128);
         let (remove_worker_tx, remove_worker_rx) = mpsc::channel::<WorkerId>(16);
+        let (dump_tx, dump_rx) = mpsc::channel::<oneshot::Sender<Vec<RouterEvent>>>(16);
+        let (flush_tx, flush_rx) = mpsc::channel::<oneshot::Sender<()>>(16);
+        let (gaps_tx, gaps_rx) = mpsc::channel::<mpsc::Sender<EventGap>>(1);
+        let (resync_tx, resync_rx) = mpsc::channel::<(WorkerWithDpRank, Option<KvCacheDump>)>(16);
         let cancel_clone = token.clone();
         let task = std::thread::spawn(move || {
             // create a new tokio runtime which will only perform work on a single thread
@@ -566,6 +799,11 @@ impl KvIndexer {
                     let mut match_rx = match_rx;
                     let mut event_rx = event_rx;
                     let mut remove_worker_rx = remove_worker_rx;
+                    let mut dump_rx = dump_rx;
+                    let mut flush_rx = flush_rx;
+                    let mut gaps_rx = gaps_rx;
+                    let mut resync_rx = resync_rx;
+                    let mut gap_tx: Option<mpsc::Sender<EventGap>> = None;
                     let mut trie = RadixTree::new_with_frequency(expiration_duration);
                     loop {
                         tokio::select! {
@@ -575,6 +813,14 @@ impl KvIndexer {
                                 trie.remove_worker(worker);
                             }
 
+                            Some((worker, dump)) = resync_rx.recv() => {
+                                trie.finish_resync(worker, dump);
+                            }
+
+                            Some(tx) = gaps_rx.recv() => {
+                                gap_tx = Some(tx);
+                            }
+
                             Some(req) = match_rx.recv() => {
                                 let matches = trie.find_matches(req.sequence, req.early_exit);
                                 let _ = req.resp.send(matches);
@@ -586,7 +832,28 @@ impl KvIndexer {
                             }
 
                             Some(event) = event_rx.recv() => {
-                                trie.apply_event(event);
+                                let Some(gap) = trie.apply_event(event) else {
+                                    continue;
+                                };
+                                tracing::warn!(
+                                    worker = %gap.worker,
+                                    last_event_id = gap.last_event_id,
+                                    event_id = gap.event_id,
+                                    "Missed KV events of the worker"
+                                );
+                                // Only resync when someone requests the dump
+                                if gap_tx.as_ref().is_some_and(|tx| tx.try_send(gap).is_ok()) {
+                                    trie.start_resync(gap.worker);
+                                }
+                            }
+
+                            // Polled last, so that every event already sent is in the dump
+                            Some(resp) = dump_rx.recv() => {
+                                let _ = resp.send(trie.dump_events());
+                            }
+
+                            Some(resp) = flush_rx.recv() => {
+                                let _ = resp.send(());
                             }
                         }
                     }
@@ -606,6 +873,10 @@ impl KvIndexer {
             event_tx,
             match_tx,
             remove_worker_tx,
+            dump_tx,
+            flush_tx,
+            gaps_tx,
+            resync_tx,
             task: once,
             kv_block_size,
         }
@@ -627,6 +898,54 @@ impl KvIndexer {
     pub fn event_sender(&self) -> mpsc::Sender<RouterEvent> {
         self.event_tx.clone()
     }
+
+    /// Dump the tree as the events that rebuild it, see [`RadixTree::dump_events`].
+    /// Events sent to the indexer before this call are included.
+    pub async fn dump_events(&self) -> Result<Vec<RouterEvent>, KvRouterError> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        if self.dump_tx.send(resp_tx).await.is_err() {
+            return Err(KvRouterError::IndexerOffline);
+        }
+        resp_rx
+            .await
+            .map_err(|_| KvRouterError::IndexerDroppedRequest)
+    }
+
+    /// Wait until the events sent to the indexer before this call are applied, so that the next
+    /// match requests see them.
+    pub async fn flush(&self) -> Result<(), KvRouterError> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        if self.flush_tx.send(resp_tx).await.is_err() {
+            return Err(KvRouterError::IndexerOffline);
+        }
+        resp_rx
+            .await
+            .map_err(|_| KvRouterError::IndexerDroppedRequest)
+    }
+
+    /// Get notified of the [`EventGap`]s the indexer detects, replacing any previous receiver.
+    ///
+    /// The indexer buffers the events of a worker from the notification until
+    /// [`KvIndexer::finish_resync`] is called for it, which the receiver must always do.
+    pub async fn event_gaps(&self) -> Result<mpsc::Receiver<EventGap>, KvRouterError> {
+        let (gap_tx, gap_rx) = mpsc::channel(64);
+        if self.gaps_tx.send(gap_tx).await.is_err() {
+            return Err(KvRouterError::IndexerOffline);
+        }
+        Ok(gap_rx)
+    }
+
+    /// Finish the resync of a worker after an [`EventGap`], see [`RadixTree::finish_resync`].
+    pub async fn finish_resync(
+        &self,
+        worker: WorkerWithDpRank,
+        dump: Option<KvCacheDump>,
+    ) -> Result<(), KvRouterError> {
+        self.resync_tx
+            .send((worker, dump))
+            .await
+            .map_err(|_| KvRouterError::IndexerOffline)
+    }
 }
 
 #[async_trait]
@@ -655,16 +974,18 @@ impl KvIndexerInterface for KvIndexer {
             .map_err(|_| KvRouterError::IndexerDroppedRequest)
     }
 
-    async fn find_matches_for_request(
+    async fn find_matches_for_lora_request(
         &self,
         tokens: &[u32],
+        lora_id: u64,
     ) -> Result<OverlapScores, KvRouterError> {
         tracing::debug!(
+            lora_id,
             "Finding matches for request tokens: {:?} / len: {}",
             tokens,
             tokens.len()
         );
-        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size);
+        let sequence = compute_block_hash_for_seq_with_lora(tokens, self.kv_block_size, lora_id);
         tracing::debug!("Computed sequence: {:?}", sequence);
         self.find_matches(sequence).await
     }
@@ -860,11 +1181,12 @@ impl KvIndexerInterface for KvIndexerSharded {
         }
     }
 
-    async fn find_matches_for_request(
+    async fn find_matches_for_lora_request(
         &self,
         tokens: &[u32],
+        lora_id: u64,
     ) -> Result<OverlapScores, KvRouterError> {
-        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size);
+        let sequence = compute_block_hash_for_seq_with_lora(tokens, self.kv_block_size, lora_id);
         self.find_matches(sequence).await
     }
 
@@ -937,6 +1259,7 @@ mod tests {
         KvCacheEventData::Stored(KvCacheStoreData {
             parent_hash,
             blocks: make_blocks(hashes),
+            lora_id: 0,
         })
     }
 
@@ -946,19 +1269,19 @@ mod tests {
         hashes: Vec<u64>,
         parent: Option<ExternalSequenceBlockHash>,
     ) -> RouterEvent {
-        RouterEvent {
+        RouterEvent::new(
             worker_id,
-            event: KvCacheEvent {
+            KvCacheEvent {
                 event_id,
                 data: add_blocks(hashes, parent),
             },
-        }
+        )
     }
 
     fn create_remove_event(worker_id: WorkerId, event_id: u64, hashes: Vec<u64>) -> RouterEvent {
-        RouterEvent {
+        RouterEvent::new(
             worker_id,
-            event: KvCacheEvent {
+            KvCacheEvent {
                 event_id,
                 data: KvCacheEventData::Removed(KvCacheRemoveData {
                     block_hashes: hashes
@@ -967,7 +1290,7 @@ mod tests {
                         .collect(),
                 }),
             },
-        }
+        )
     }
 
     #[test]
@@ -976,10 +1299,15 @@ mod tests {
 
         let mut trie = RadixTree::new();
 
-        let worker_1 = 0;
-        let worker_2 = 1;
+        let worker_1 = WorkerWithDpRank::from(0);
+        let worker_2 = WorkerWithDpRank::from(1);
 
-        trie.apply_event(create_store_event(worker_1, 1, vec![1, 2, 3], None));
+        trie.apply_event(create_store_event(
+            worker_1.worker_id,
+            1,
+            vec![1, 2, 3],
+            None,
+        ));
 
         let scores = trie.find_matches(
             vec![LocalBlockHash(1), LocalBlockHash(2), LocalBlockHash(3)],
@@ -1014,7 +1342,12 @@ mod tests {
             1
         );
 
-        trie.apply_event(create_store_event(worker_2, 1, vec![1, 4, 5], None));
+        trie.apply_event(create_store_event(
+            worker_2.worker_id,
+            1,
+            vec![1, 4, 5],
+            None,
+        ));
 
         let scores = trie.find_matches(
             vec![LocalBlockHash(1), LocalBlockHash(2), LocalBlockHash(3)],
@@ -1051,7 +1384,7 @@ mod tests {
             2
         );
 
-        trie.apply_event(create_remove_event(worker_2, 2, vec![5]));
+        trie.apply_event(create_remove_event(worker_2.worker_id, 2, vec![5]));
         assert_eq!(trie.lookup.len(), 2);
         assert_eq!(trie.lookup.get(&worker_1).unwrap().len(), 3);
         assert_eq!(trie.lookup.get(&worker_2).unwrap().len(), 2);
@@ -1080,7 +1413,7 @@ mod tests {
             2
         );
 
-        trie.apply_event(create_remove_event(worker_2, 3, vec![4]));
+        trie.apply_event(create_remove_event(worker_2.worker_id, 3, vec![4]));
 
         assert_eq!(trie.lookup.len(), 2);
         assert_eq!(trie.lookup.get(&worker_1).unwrap().len(), 3);
@@ -1111,7 +1444,7 @@ mod tests {
         );
 
         trie.apply_event(create_store_event(
-            worker_2,
+            worker_2.worker_id,
             4,
             vec![2, 6, 7],
             Some(ExternalSequenceBlockHash(100)),
@@ -1180,32 +1513,207 @@ mod tests {
         setup();
         let mut trie = RadixTree::new();
 
-        let worker_0 = 0;
-        let worker_1 = 1;
+        let worker_0 = WorkerWithDpRank::from(0);
+        let worker_1 = WorkerWithDpRank::from(1);
 
         assert!(trie
             .find_matches(vec![LocalBlockHash(0)], false)
             .scores
             .is_empty());
 
-        trie.apply_event(create_store_event(worker_0, 0, vec![0], None));
-        trie.apply_event(create_store_event(worker_1, 0, vec![0], None));
+        trie.apply_event(create_store_event(worker_0.worker_id, 0, vec![0], None));
+        trie.apply_event(create_store_event(worker_1.worker_id, 0, vec![0], None));
 
         let result = trie.find_matches(vec![LocalBlockHash(0)], false).scores;
         assert!(result.len() == 2 && result[&worker_0] == 1 && result[&worker_1] == 1);
 
-        trie.remove_worker(worker_0);
+        trie.remove_worker(worker_0.worker_id);
 
         let result = trie.find_matches(vec![LocalBlockHash(0)], false).scores;
         assert!(result.len() == 1 && result[&worker_1] == 1);
     }
 
+    #[test]
+    fn test_data_parallel_ranks() {
+        setup();
+        let mut trie = RadixTree::new();
+        let rank_0 = WorkerWithDpRank::new(0, 0);
+        let rank_1 = WorkerWithDpRank::new(0, 1);
+
+        // The ranks of a worker have their own caches, and their own event IDs
+        trie.apply_event(create_store_event(0, 0, vec![0, 1], None));
+        assert!(trie
+            .apply_event(create_store_event(0, 0, vec![0], None).with_dp_rank(1))
+            .is_none());
+
+        let result = trie
+            .find_matches(vec![LocalBlockHash(0), LocalBlockHash(1)], false)
+            .scores;
+        assert!(result.len() == 2 && result[&rank_0] == 2 && result[&rank_1] == 1);
+
+        let gap = trie.apply_event(create_remove_event(0, 2, vec![0]).with_dp_rank(1));
+        assert_eq!(gap.map(|gap| gap.worker), Some(rank_1));
+
+        // The dump keeps the ranks apart
+        let mut restored = RadixTree::new();
+        for event in trie.dump_events() {
+            restored.apply_event(event);
+        }
+        let result = restored
+            .find_matches(vec![LocalBlockHash(0), LocalBlockHash(1)], false)
+            .scores;
+        assert!(result.len() == 1 && result[&rank_0] == 2);
+
+        // Removing the worker removes all its ranks
+        trie.apply_event(create_store_event(0, 3, vec![0], None).with_dp_rank(1));
+        trie.remove_worker(0);
+        assert!(trie.lookup.is_empty());
+        assert!(trie
+            .find_matches(vec![LocalBlockHash(0)], false)
+            .scores
+            .is_empty());
+    }
+
+    #[test]
+    fn test_dump_events_restores_tree() {
+        setup();
+        let mut trie = RadixTree::new();
+
+        trie.apply_event(create_store_event(0, 0, vec![0, 1, 3], None));
+        trie.apply_event(create_store_event(1, 0, vec![0, 2], None));
+        trie.apply_event(create_store_event(
+            1,
+            1,
+            vec![4],
+            Some(ExternalSequenceBlockHash(200)),
+        ));
+        trie.apply_event(create_store_event(2, 0, vec![5, 6], None));
+        trie.apply_event(create_remove_event(2, 1, vec![6]));
+
+        let mut restored = RadixTree::new();
+        for event in trie.dump_events() {
+            restored.apply_event(event);
+        }
+
+        for sequence in [vec![0, 1, 3], vec![0, 2, 4], vec![5, 6]] {
+            let sequence: Vec<_> = sequence.into_iter().map(LocalBlockHash).collect();
+            assert_eq!(
+                restored.find_matches(sequence.clone(), false).scores,
+                trie.find_matches(sequence, false).scores
+            );
+        }
+        for worker in 0..3 {
+            let worker = WorkerWithDpRank::from(worker);
+            let mut expected: Vec<_> = trie.lookup[&worker].keys().copied().collect();
+            let mut actual: Vec<_> = restored.lookup[&worker].keys().copied().collect();
+            expected.sort();
+            actual.sort();
+            assert_eq!(actual, expected);
+        }
+
+        // The restored tree keeps following events
+        restored.apply_event(create_remove_event(0, 1, vec![3]));
+        let result = restored
+            .find_matches(
+                vec![LocalBlockHash(0), LocalBlockHash(1), LocalBlockHash(3)],
+                false,
+            )
+            .scores;
+        assert_eq!(result[&WorkerWithDpRank::from(0)], 2);
+    }
+
+    #[test]
+    fn test_event_gap_and_resync() {
+        setup();
+        let mut trie = RadixTree::new();
+        let worker = WorkerWithDpRank::from(0);
+        let matches = |trie: &RadixTree, sequence: Vec<u64>| {
+            let sequence = sequence.into_iter().map(LocalBlockHash).collect();
+            trie.find_matches(sequence, false)
+                .scores
+                .get(&worker)
+                .copied()
+                .unwrap_or(0)
+        };
+
+        assert!(trie
+            .apply_event(create_store_event(worker.worker_id, 0, vec![1, 2], None))
+            .is_none());
+        // A batch of events can share its ID
+        assert!(trie
+            .apply_event(create_store_event(worker.worker_id, 0, vec![3], None))
+            .is_none());
+
+        // Event 1, removing block 3, is lost
+        let gap = trie.apply_event(create_store_event(
+            worker.worker_id,
+            2,
+            vec![4],
+            Some(ExternalSequenceBlockHash(200)),
+        ));
+        assert_eq!(
+            gap,
+            Some(EventGap {
+                worker,
+                last_event_id: 0,
+                event_id: 2,
+            })
+        );
+        assert_eq!(matches(&trie, vec![3]), 1);
+
+        // The worker keeps storing blocks while its dump is requested, the gaps in between are
+        // covered by the resync
+        trie.start_resync(worker);
+        assert!(trie
+            .apply_event(create_store_event(
+                worker.worker_id,
+                4,
+                vec![5],
+                Some(ExternalSequenceBlockHash(100)),
+            ))
+            .is_none());
+
+        // The dump was taken after event 2
+        trie.finish_resync(
+            worker,
+            Some(KvCacheDump {
+                dp_rank: 0,
+                last_event_id: Some(2),
+                events: vec![
+                    KvCacheEvent {
+                        event_id: 2,
+                        data: add_blocks(vec![1, 2], None),
+                    },
+                    KvCacheEvent {
+                        event_id: 2,
+                        data: add_blocks(vec![4], Some(ExternalSequenceBlockHash(200))),
+                    },
+                ],
+            }),
+        );
+        assert_eq!(matches(&trie, vec![1, 2, 4]), 3);
+        assert_eq!(matches(&trie, vec![3]), 0);
+        assert_eq!(matches(&trie, vec![1, 5]), 2);
+        assert!(trie
+            .apply_event(create_remove_event(worker.worker_id, 5, vec![5]))
+            .is_none());
+
+        // A failed resync leaves the tree as it is
+        assert!(trie
+            .apply_event(create_remove_event(worker.worker_id, 7, vec![4]))
+            .is_some());
+        trie.start_resync(worker);
+        trie.finish_resync(worker, None);
+        assert_eq!(matches(&trie, vec![1, 2, 4]), 2);
+        assert!(trie.resyncs.is_empty());
+    }
+
     #[test]
     fn test_clear_all_blocks() {
         let mut trie = RadixTree::new();
 
-        let worker_0 = 0;
-        let worker_1 = 1;
+        let worker_0 = WorkerWithDpRank::from(0);
+        let worker_1 = WorkerWithDpRank::from(1);
 
         assert!(trie
             .find_matches(vec![LocalBlockHash(0)], false)
@@ -1217,8 +1725,18 @@ mod tests {
         assert!(!trie.lookup.contains_key(&worker_0));
 
         // Test clearing a worker with shared blocks
-        trie.apply_event(create_store_event(worker_0, 0, vec![0, 1, 3], None));
-        trie.apply_event(create_store_event(worker_1, 0, vec![0, 2, 3], None));
+        trie.apply_event(create_store_event(
+            worker_0.worker_id,
+            0,
+            vec![0, 1, 3],
+            None,
+        ));
+        trie.apply_event(create_store_event(
+            worker_1.worker_id,
+            0,
+            vec![0, 2, 3],
+            None,
+        ));
 
         let result = trie.find_matches(vec![LocalBlockHash(0)], false).scores;
         assert!(result.len() == 2 && result[&worker_0] == 1 && result[&worker_1] == 1);
@@ -1242,7 +1760,7 @@ mod tests {
         assert_eq!(result[&worker_1], 1);
 
         // Test re-adding blocks after clearing worker
-        trie.apply_event(create_store_event(worker_0, 0, vec![4, 5], None));
+        trie.apply_event(create_store_event(worker_0.worker_id, 0, vec![4, 5], None));
         let result = trie
             .find_matches(vec![LocalBlockHash(4), LocalBlockHash(5)], false)
             .scores;
@@ -1262,9 +1780,9 @@ mod tests {
         assert!(trie.lookup.get(&worker_1).unwrap().is_empty());
 
         // Test clearing a worker that has been removed
-        trie.apply_event(create_store_event(worker_0, 0, vec![6], None));
-        trie.apply_event(create_store_event(worker_1, 0, vec![6], None));
-        trie.remove_worker(worker_0);
+        trie.apply_event(create_store_event(worker_0.worker_id, 0, vec![6], None));
+        trie.apply_event(create_store_event(worker_1.worker_id, 0, vec![6], None));
+        trie.remove_worker(worker_0.worker_id);
         trie.clear_all_blocks(worker_0);
         assert!(!trie.lookup.contains_key(&worker_0));
         let result = trie.find_matches(vec![LocalBlockHash(6)], false).scores;
@@ -1272,7 +1790,7 @@ mod tests {
         assert_eq!(result[&worker_1], 1);
 
         // Test clearing a worker that doesn't exist
-        let worker_fake = 2;
+        let worker_fake = WorkerWithDpRank::from(2);
         assert!(!trie.lookup.contains_key(&worker_fake));
         trie.clear_all_blocks(worker_fake);
         assert!(!trie.lookup.contains_key(&worker_fake));
@@ -1287,11 +1805,16 @@ mod tests {
         setup();
         let mut trie = RadixTree::new();
 
-        let worker_0 = 0;
-        let worker_1 = 1;
+        let worker_0 = WorkerWithDpRank::from(0);
+        let worker_1 = WorkerWithDpRank::from(1);
 
-        trie.apply_event(create_store_event(worker_0, 0, vec![0, 1, 2], None));
-        trie.apply_event(create_store_event(worker_1, 0, vec![0], None));
+        trie.apply_event(create_store_event(
+            worker_0.worker_id,
+            0,
+            vec![0, 1, 2],
+            None,
+        ));
+        trie.apply_event(create_store_event(worker_1.worker_id, 0, vec![0], None));
 
         let result = trie
             .find_matches(
@@ -1518,6 +2041,148 @@ mod tests {
         assert_eq!(overlap.frequencies, vec![3, 3, 3, 2]);
     }
 
+    #[tokio::test]
+    async fn test_kv_indexer_event_gaps() {
+        setup();
+        let token = CancellationToken::new();
+        let kv_indexer = KvIndexer::new(token.clone(), 4);
+        let mut gaps = kv_indexer.event_gaps().await.unwrap();
+        let event_tx = kv_indexer.event_sender();
+
+        event_tx
+            .send(create_store_event(0, 0, vec![1], None))
+            .await
+            .unwrap();
+        event_tx
+            .send(create_store_event(0, 3, vec![2], None))
+            .await
+            .unwrap();
+        let gap = gaps.recv().await.unwrap();
+        assert_eq!(
+            (gap.worker, gap.last_event_id, gap.event_id),
+            (WorkerWithDpRank::from(0), 0, 3)
+        );
+
+        kv_indexer
+            .finish_resync(
+                gap.worker,
+                Some(KvCacheDump {
+                    dp_rank: 0,
+                    last_event_id: Some(3),
+                    events: vec![KvCacheEvent {
+                        event_id: 3,
+                        data: add_blocks(vec![2], None),
+                    }],
+                }),
+            )
+            .await
+            .unwrap();
+        let scores = kv_indexer
+            .find_matches(vec![LocalBlockHash(1)])
+            .await
+            .unwrap();
+        assert!(scores.scores.is_empty());
+        let scores = kv_indexer
+            .find_matches(vec![LocalBlockHash(2)])
+            .await
+            .unwrap();
+        assert_eq!(scores.scores.get(&WorkerWithDpRank::from(0)), Some(&1));
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_kv_indexer_flush() {
+        setup();
+        let token = CancellationToken::new();
+        let kv_indexer = KvIndexer::new(token.clone(), 4);
+        let event_tx = kv_indexer.event_sender();
+
+        for event_id in 0..100 {
+            event_tx
+                .send(create_store_event(0, event_id, vec![event_id], None))
+                .await
+                .unwrap();
+        }
+        kv_indexer.flush().await.unwrap();
+
+        let scores = kv_indexer
+            .find_matches(vec![LocalBlockHash(99)])
+            .await
+            .unwrap();
+        assert_eq!(scores.scores.get(&WorkerWithDpRank::from(0)), Some(&1));
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_find_matches_for_lora_request() {
+        setup();
+        let token = CancellationToken::new();
+        let kv_indexer = KvIndexer::new(token.clone(), 4);
+        let tokens: Vec<u32> = (1..=8).collect();
+
+        // The base model hashes are unchanged
+        assert_eq!(
+            compute_block_hash_for_seq_with_lora(&tokens, 4, 0),
+            compute_block_hash_for_seq(&tokens, 4)
+        );
+
+        // Worker 0 cached the prompt with the base model, worker 1 with adapter 7
+        for (worker_id, lora_id) in [(0, 0), (1, 7)] {
+            let blocks = compute_block_hash_for_seq_with_lora(&tokens, 4, lora_id)
+                .into_iter()
+                .enumerate()
+                .map(|(i, tokens_hash)| KvCacheStoredBlockData {
+                    block_hash: ExternalSequenceBlockHash(i as u64),
+                    tokens_hash,
+                })
+                .collect();
+            let event = RouterEvent::new(
+                worker_id,
+                KvCacheEvent {
+                    event_id: 0,
+                    data: KvCacheEventData::Stored(KvCacheStoreData {
+                        parent_hash: None,
+                        blocks,
+                        lora_id,
+                    }),
+                },
+            );
+            kv_indexer.event_sender().send(event).await.unwrap();
+        }
+
+        for (lora_id, worker_id) in [(0, 0), (7, 1)] {
+            let scores = kv_indexer
+                .find_matches_for_lora_request(&tokens, lora_id)
+                .await
+                .unwrap();
+            assert_eq!(scores.scores.len(), 1);
+            assert_eq!(
+                scores.scores.get(&WorkerWithDpRank::from(worker_id)),
+                Some(&2)
+            );
+        }
+        let scores = kv_indexer
+            .find_matches_for_lora_request(&tokens, 3)
+            .await
+            .unwrap();
+        assert!(scores.scores.is_empty());
+
+        // The adapter of the blocks survives a dump
+        let lora_ids: Vec<u64> = kv_indexer
+            .dump_events()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|event| event.worker_id == 1)
+            .map(|event| match event.event.data {
+                KvCacheEventData::Stored(store) => store.lora_id,
+                _ => panic!("Expected KvCacheEventData::Stored variant"),
+            })
+            .collect();
+        assert_eq!(lora_ids, vec![7, 7]);
+        token.cancel();
+    }
+
     #[test]
     fn test_router_event_new() {
         setup();
@@ -1530,6 +2195,7 @@ mod tests {
                     block_hash: ExternalSequenceBlockHash(0),
                     tokens_hash: LocalBlockHash(13226331709069118873),
                 }],
+                lora_id: 0,
             }),
         };
         let router_event = RouterEvent::new(worker_id, kv_cache_event);
@@ -0,0 +1,87 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use prometheus::{IntCounter, IntCounterVec, Opts, Registry};
+
+/// Value of the `result` label of a resync that replaced the worker's blocks
+pub const RESYNC_SUCCESS: &str = "success";
+
+/// Value of the `result` label of a resync for which the worker did not provide its blocks
+pub const RESYNC_FAILURE: &str = "failure";
+
+/// Prometheus metrics of the KV routers
+pub struct KvRouterMetrics {
+    event_gaps: IntCounter,
+    resyncs: IntCounterVec,
+}
+
+impl Default for KvRouterMetrics {
+    fn default() -> Self {
+        Self::new("nv_llm")
+    }
+}
+
+impl KvRouterMetrics {
+    /// Create KvRouterMetrics with the given prefix
+    /// The following metrics will be created:
+    /// - `{prefix}_kv_router_event_gaps_total` - IntCounter for the gaps detected in the KV events of the workers
+    /// - `{prefix}_kv_router_resyncs_total` - IntCounterVec for the resyncs of a worker's blocks by result
+    pub fn new(prefix: &str) -> Self {
+        let event_gaps = IntCounter::new(
+            format!("{}_kv_router_event_gaps_total", prefix),
+            "Total number of gaps detected in the KV events of the workers",
+        )
+        .unwrap();
+
+        let resyncs = IntCounterVec::new(
+            Opts::new(
+                format!("{}_kv_router_resyncs_total", prefix),
+                "Total number of resyncs of a worker's KV blocks after a gap in its events",
+            ),
+            &["result"],
+        )
+        .unwrap();
+
+        KvRouterMetrics {
+            event_gaps,
+            resyncs,
+        }
+    }
+
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.event_gaps.clone()))?;
+        registry.register(Box::new(self.resyncs.clone()))?;
+        Ok(())
+    }
+
+    /// Get the number of gaps detected in the KV events of the workers
+    pub fn get_event_gaps(&self) -> u64 {
+        self.event_gaps.get()
+    }
+
+    /// Get the number of resyncs with the given result
+    /// (see [`RESYNC_SUCCESS`] and [`RESYNC_FAILURE`])
+    pub fn get_resyncs(&self, result: &str) -> u64 {
+        self.resyncs.with_label_values(&[result]).get()
+    }
+
+    pub(crate) fn inc_event_gaps(&self) {
+        self.event_gaps.inc();
+    }
+
+    pub(crate) fn inc_resyncs(&self, result: &str) {
+        self.resyncs.with_label_values(&[result]).inc();
+    }
+}
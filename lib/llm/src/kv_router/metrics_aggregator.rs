@@ -115,22 +115,28 @@ pub async fn collect_endpoints_task(
                             continue;
                         }
                     };
+                // Workers with several data parallel ranks publish the metrics of each rank
                 let endpoints: Vec<Endpoint> = unfiltered_endpoints
                     .into_iter()
                     .filter(|s| s.data.is_some())
-                    .filter_map(|s|
-                        match s.data.unwrap().decode::<ForwardPassMetrics>() {
-                            Ok(data) => Some(Endpoint {
-                                name: s.name,
-                                subject: s.subject,
-                                data,
-                            }),
-                            Err(e) => {
-                                tracing::debug!("skip endpoint data that can't be parsed as ForwardPassMetrics: {:?}", e);
-                                None
-                            }
-                        }
-                    )
+                    .flat_map(|s| {
+                        let data = s.data.unwrap();
+                        let ranks = match data.clone().decode::<ForwardPassMetrics>() {
+                            Ok(metrics) => vec![metrics],
+                            Err(_) => match data.decode::<Vec<ForwardPassMetrics>>() {
+                                Ok(ranks) => ranks,
+                                Err(e) => {
+                                    tracing::debug!("skip endpoint data that can't be parsed as ForwardPassMetrics: {:?}", e);
+                                    vec![]
+                                }
+                            },
+                        };
+                        ranks.into_iter().map(move |data| Endpoint {
+                            name: s.name.clone(),
+                            subject: s.subject.clone(),
+                            data,
+                        })
+                    })
                     .collect();
                 tracing::trace!("Found {} endpoints for service: {service_subject}", endpoints.len());
 
@@ -26,10 +26,47 @@ pub struct RouterResponse {
     pub worker_id: i64,
 }
 
+/// A worker, or one of its data parallel ranks, as addressed by the KV router.
+///
+/// Data parallel ranks (DP attention, SGLang/DeepSeek style) have their own KV cache and queue,
+/// so the router routes to them as separate workers. Workers without data parallelism only
+/// have rank 0.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Ord, PartialOrd, Serialize, Deserialize,
+)]
+pub struct WorkerWithDpRank {
+    pub worker_id: i64,
+    pub dp_rank: u32,
+}
+
+impl WorkerWithDpRank {
+    pub fn new(worker_id: i64, dp_rank: u32) -> Self {
+        Self { worker_id, dp_rank }
+    }
+}
+
+impl From<i64> for WorkerWithDpRank {
+    /// Rank 0 of the worker
+    fn from(worker_id: i64) -> Self {
+        Self::new(worker_id, 0)
+    }
+}
+
+impl std::fmt::Display for WorkerWithDpRank {
+    /// The worker ID, followed by the rank for ranks other than 0
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.dp_rank == 0 {
+            write!(f, "{}", self.worker_id)
+        } else {
+            write!(f, "{}:{}", self.worker_id, self.dp_rank)
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct WorkerSelectionResult {
-    /// The worker id of the selected worker
-    pub worker_id: i64,
+    /// The selected worker and data parallel rank
+    pub worker: WorkerWithDpRank,
 
     /// The total number of blocks required to prefill the request
     pub required_blocks: u64,
@@ -37,6 +74,68 @@ pub struct WorkerSelectionResult {
     /// The number of blocks that the selected worker may already have cached.
     /// This is not a guarantee, but an estimate.
     pub overlap_blocks: usize,
+
+    /// Why the worker was selected, if the request asked for it, see
+    /// [`SchedulingRequest::explain`](super::scheduler::SchedulingRequest::explain)
+    pub decision: Option<RoutingDecision>,
+}
+
+/// A worker the KV router considered for a request, see [`RoutingDecision`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerCandidate {
+    pub worker: WorkerWithDpRank,
+
+    /// Blocks of the request the worker has cached, or is about to with the requests in flight
+    pub overlap_blocks: u32,
+
+    /// Blocks the worker would have to prefill, as a fraction of its KV cache
+    pub normalized_new_blocks: f64,
+
+    /// Fraction of the KV cache of the worker in use
+    pub gpu_cache_usage: f64,
+
+    /// Requests waiting on the worker, including the ones in flight to it
+    pub num_requests_waiting: u64,
+
+    /// The worker would have to load the LoRA adapter of the request first
+    pub lora_not_loaded: bool,
+
+    /// Cost of the worker, the weighted sum of the above. Lower is better
+    pub logit: f64,
+
+    /// Probability of sampling the worker
+    pub probability: f64,
+}
+
+/// Why the KV router chose a worker for a request: the inputs and the outcome of the selection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingDecision {
+    /// ID of the request, when routed by a [`KvPushRouter`](super::KvPushRouter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+
+    /// The chosen worker
+    pub worker: WorkerWithDpRank,
+
+    /// Number of blocks of the request
+    pub request_blocks: u64,
+
+    /// The LoRA adapter of the request, 0 for the base model
+    pub lora_id: u64,
+
+    pub overlap_score_weight: f64,
+    pub gpu_cache_usage_weight: f64,
+    pub waiting_requests_weight: f64,
+    pub lora_load_weight: f64,
+
+    /// Temperature of the softmax the worker is sampled with
+    pub temperature: f64,
+
+    /// All logits were zero, the worker was picked uniformly at random
+    pub random_fallback: bool,
+
+    /// The workers considered, ordered by logit
+    pub candidates: Vec<WorkerCandidate>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -54,6 +153,9 @@ pub struct ForwardPassMetrics {
     pub gpu_cache_usage_perc: f32,
     // percentage represented as a float from 0 to 1
     pub gpu_prefix_cache_hit_rate: f32,
+    // IDs of the LoRA adapters currently loaded on the worker
+    #[serde(default)]
+    pub loaded_lora_ids: Vec<u64>,
 }
 
 /// A [`LocalBlockHash`] is a hash computed from the tokens_ids, extra_token_ids and the optional
@@ -119,6 +221,10 @@ pub struct KvCacheStoreData {
     pub parent_hash: Option<ExternalSequenceBlockHash>,
     /// A list of stored blocked data.
     pub blocks: Vec<KvCacheStoredBlockData>,
+    /// The ID of the LoRA adapter the blocks were generated with, 0 for the base model.
+    /// The adapter is already part of the `tokens_hash` of each block.
+    #[serde(default)]
+    pub lora_id: u64,
 }
 
 /// Represents data for a stored block.
@@ -137,6 +243,28 @@ pub struct KvCacheRemoveData {
     pub block_hashes: Vec<ExternalSequenceBlockHash>,
 }
 
+/// The request for the blocks a worker has stored on one of its data parallel ranks, sent to
+/// its `kv_dump` endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KvDumpRequest {
+    /// The data parallel rank to dump the blocks of.
+    pub dp_rank: u32,
+}
+
+/// All the blocks a worker has stored, as served on its `kv_dump` endpoint.
+///
+/// Used by the router to resync its view of a worker after missing some of its events.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct KvCacheDump {
+    /// The data parallel rank of the worker the blocks are stored on.
+    #[serde(default)]
+    pub dp_rank: u32,
+    /// The ID of the last event the dump includes, `None` if the worker has not published any.
+    pub last_event_id: Option<u64>,
+    /// `Stored` events rebuilding the blocks, parents before their children.
+    pub events: Vec<KvCacheEvent>,
+}
+
 impl Serialize for LocalBlockHash {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -208,6 +336,7 @@ mod tests {
                 block_hash: ExternalSequenceBlockHash(2),
                 tokens_hash: LocalBlockHash(3),
             }],
+            lora_id: 0,
         });
 
         let event = KvCacheEvent {
@@ -14,9 +14,11 @@
 // limitations under the License.
 
 use crate::kv_router::{
-    indexer::{compute_block_hash_for_seq, RouterEvent},
+    codec::{encode_router_events, KvEventEncoding},
+    event_stream::{KvEventStreamConfig, KvEventStreamPublisher},
+    indexer::compute_block_hash_for_seq_with_lora,
     protocols::*,
-    KV_EVENT_SUBJECT, KV_METRICS_ENDPOINT,
+    KV_DUMP_ENDPOINT, KV_EVENT_SUBJECT, KV_METRICS_ENDPOINT,
 };
 use async_trait::async_trait;
 use dynamo_runtime::traits::{events::EventPublisher, DistributedRuntimeProvider};
@@ -30,7 +32,9 @@ use dynamo_runtime::{
     Error, Result,
 };
 use futures::stream;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, LazyLock, Mutex};
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
@@ -95,6 +99,144 @@ impl KvEventSource {
     }
 }
 
+/// How a [`KvEventPublisher`] encodes and batches its events
+#[derive(Debug, Clone)]
+pub struct KvEventPublishConfig {
+    /// Wire format of the events
+    pub encoding: KvEventEncoding,
+    /// Maximum number of events in a binary message
+    pub max_batch_events: usize,
+    /// How long a binary message waits for more events after its first one. With zero, a
+    /// message batches the events already queued.
+    pub batch_window: Duration,
+    /// Publish through the namespace's durable KV event stream, waiting for it to store each
+    /// message, see [`event_stream`](super::event_stream)
+    pub stream: Option<KvEventStreamConfig>,
+}
+
+impl Default for KvEventPublishConfig {
+    fn default() -> Self {
+        Self {
+            encoding: KvEventEncoding::default(),
+            max_batch_events: 128,
+            batch_window: Duration::ZERO,
+            stream: None,
+        }
+    }
+}
+
+impl KvEventPublishConfig {
+    /// The default config, with the encoding and the stream set in the environment, see
+    /// [`KvEventEncoding::from_env`] and [`KvEventStreamConfig::from_env`]
+    pub fn from_env() -> Self {
+        Self {
+            encoding: KvEventEncoding::from_env(),
+            stream: KvEventStreamConfig::from_env(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A block in [`StoredBlocks`]
+#[derive(Debug)]
+struct StoredBlock {
+    /// Order in which the blocks were stored, so that parents are dumped before their children
+    order: u64,
+    parent_hash: Option<ExternalSequenceBlockHash>,
+    tokens_hash: LocalBlockHash,
+    lora_id: u64,
+}
+
+/// The blocks a worker has stored, following the KV events it publishes.
+/// Served on the [`KV_DUMP_ENDPOINT`] so that the router can resync after missing events.
+#[derive(Debug, Default)]
+struct StoredBlocks {
+    /// The data parallel rank the blocks are stored on
+    dp_rank: u32,
+    /// The ID of the last published event
+    last_event_id: Option<u64>,
+    next_order: u64,
+    blocks: HashMap<ExternalSequenceBlockHash, StoredBlock>,
+}
+
+impl StoredBlocks {
+    /// Give the event the next ID of this publisher, and apply it.
+    ///
+    /// The publisher numbers its events itself, the IDs of the engine are not always contiguous,
+    /// e.g. when it emits events we don't publish, or one ID per batch of events.
+    fn sequence(&mut self, event: &mut KvCacheEvent) {
+        event.event_id = self.last_event_id.map_or(0, |id| id + 1);
+        self.last_event_id = Some(event.event_id);
+
+        match &event.data {
+            KvCacheEventData::Stored(store) => {
+                let mut parent_hash = store.parent_hash;
+                for block in &store.blocks {
+                    let order = self.next_order;
+                    self.next_order += 1;
+                    self.blocks.entry(block.block_hash).or_insert(StoredBlock {
+                        order,
+                        parent_hash,
+                        tokens_hash: block.tokens_hash,
+                        lora_id: store.lora_id,
+                    });
+                    parent_hash = Some(block.block_hash);
+                }
+            }
+            KvCacheEventData::Removed(remove) => {
+                for block_hash in &remove.block_hashes {
+                    self.blocks.remove(block_hash);
+                }
+            }
+            KvCacheEventData::Cleared => {
+                self.blocks.clear();
+            }
+        }
+    }
+
+    /// The stored blocks as one `Stored` event per block, parents first
+    fn dump(&self) -> KvCacheDump {
+        let mut blocks: Vec<_> = self.blocks.iter().collect();
+        blocks.sort_by_key(|(_, block)| block.order);
+        let event_id = self.last_event_id.unwrap_or_default();
+        let events = blocks
+            .into_iter()
+            .map(|(block_hash, block)| KvCacheEvent {
+                event_id,
+                data: KvCacheEventData::Stored(KvCacheStoreData {
+                    parent_hash: block.parent_hash,
+                    blocks: vec![KvCacheStoredBlockData {
+                        block_hash: *block_hash,
+                        tokens_hash: block.tokens_hash,
+                    }],
+                    lora_id: block.lora_id,
+                }),
+            })
+            .collect();
+        KvCacheDump {
+            dp_rank: self.dp_rank,
+            last_event_id: self.last_event_id,
+            events,
+        }
+    }
+}
+
+/// The stored blocks of each data parallel rank of a worker
+type RankBlocks = HashMap<u32, Arc<Mutex<StoredBlocks>>>;
+
+/// The publishers of a worker in this process, keyed by data parallel rank
+#[derive(Default)]
+struct WorkerBlocks {
+    ranks: Arc<Mutex<RankBlocks>>,
+    /// Whether the worker serves its [`KV_DUMP_ENDPOINT`] already
+    serving: bool,
+}
+
+/// The workers of this process, keyed by component path and worker ID. All the data parallel
+/// ranks of a worker share its [`KV_DUMP_ENDPOINT`], which serves the rank requested.
+static WORKERS: LazyLock<Mutex<HashMap<(String, i64), WorkerBlocks>>> =
+    LazyLock::new(Default::default);
+
 /// A publisher of KV events.
 pub struct KvEventPublisher {
     /// The size of the KV block.
@@ -106,6 +248,10 @@ pub struct KvEventPublisher {
     cancellation_token: CancellationToken,
     /// The channel to send events to.
     tx: mpsc::UnboundedSender<KvCacheEvent>,
+    /// The blocks stored according to the published events.
+    stored_blocks: Arc<Mutex<StoredBlocks>>,
+    /// The component path and worker ID the publisher is registered under in [`WORKERS`].
+    worker_key: (String, i64),
 }
 
 impl KvEventPublisher {
@@ -114,6 +260,37 @@ impl KvEventPublisher {
         worker_id: i64,
         kv_block_size: usize,
         source_config: Option<KvEventSourceConfig>,
+    ) -> Result<Self> {
+        Self::new_with_dp_rank(component, worker_id, 0, kv_block_size, source_config)
+    }
+
+    /// A publisher for the KV cache of one data parallel rank of the worker. Each rank with its
+    /// own KV cache publishes its events on its own publisher.
+    pub fn new_with_dp_rank(
+        component: Component,
+        worker_id: i64,
+        dp_rank: u32,
+        kv_block_size: usize,
+        source_config: Option<KvEventSourceConfig>,
+    ) -> Result<Self> {
+        Self::new_with_publish_config(
+            component,
+            worker_id,
+            dp_rank,
+            kv_block_size,
+            source_config,
+            KvEventPublishConfig::from_env(),
+        )
+    }
+
+    /// A publisher encoding and batching its events according to `publish_config`
+    pub fn new_with_publish_config(
+        component: Component,
+        worker_id: i64,
+        dp_rank: u32,
+        kv_block_size: usize,
+        source_config: Option<KvEventSourceConfig>,
+        publish_config: KvEventPublishConfig,
     ) -> Result<Self> {
         let cancellation_token = CancellationToken::new();
 
@@ -131,25 +308,76 @@ impl KvEventPublisher {
             )?);
         }
 
+        let stored_blocks = Arc::new(Mutex::new(StoredBlocks {
+            dp_rank,
+            ..Default::default()
+        }));
+        let worker_key = (component.path(), worker_id);
+        WORKERS
+            .lock()
+            .unwrap()
+            .entry(worker_key.clone())
+            .or_default()
+            .ranks
+            .lock()
+            .unwrap()
+            .insert(dp_rank, stored_blocks.clone());
+        let worker = WorkerWithDpRank::new(worker_id, dp_rank);
+        let processor_token = cancellation_token.clone();
+        let processor_blocks = stored_blocks.clone();
         component
             .drt()
             .runtime()
             .secondary()
-            .spawn(start_event_processor(
-                component,
-                worker_id,
-                cancellation_token.clone(),
-                rx,
-            ));
+            .spawn(async move {
+                let stream_publisher = match publish_config.stream.as_ref() {
+                    Some(stream) => match KvEventStreamPublisher::new(&component, stream).await {
+                        Ok(publisher) => Some(publisher),
+                        Err(err) => {
+                            tracing::error!(%err, "Failed to set up the KV event stream, publishing on core NATS");
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                match stream_publisher {
+                    Some(publisher) => {
+                        start_event_processor(
+                            publisher,
+                            worker,
+                            processor_token,
+                            rx,
+                            processor_blocks,
+                            publish_config,
+                        )
+                        .await
+                    }
+                    None => {
+                        start_event_processor(
+                            component,
+                            worker,
+                            processor_token,
+                            rx,
+                            processor_blocks,
+                            publish_config,
+                        )
+                        .await
+                    }
+                }
+            });
 
         Ok(Self {
             kv_block_size,
             source,
             cancellation_token,
             tx,
+            stored_blocks,
+            worker_key,
         })
     }
 
+    /// Publish an event. Its `event_id` is replaced by the next ID of this publisher, so that
+    /// the router can detect missed events.
     pub fn publish(&self, event: KvCacheEvent) -> Result<(), mpsc::error::SendError<KvCacheEvent>> {
         tracing::trace!("Publish event: {:?}", event);
         self.tx.send(event)
@@ -159,6 +387,40 @@ impl KvEventPublisher {
         self.kv_block_size
     }
 
+    /// Serve the blocks stored according to the published events on the component's
+    /// [`KV_DUMP_ENDPOINT`]. The KV router calls it when it detects that it missed some events.
+    ///
+    /// The endpoint serves the blocks of every data parallel rank the worker publishes the events
+    /// of in this process, so the first publisher of the worker to call it serves the endpoint
+    /// and the future returned to the others completes right away.
+    ///
+    /// The returned future does not borrow the publisher, it serves until the endpoint stops.
+    pub fn create_dump_endpoint(
+        &self,
+        component: Component,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        let ranks = {
+            let mut workers = WORKERS.lock().unwrap();
+            let worker = workers.entry(self.worker_key.clone()).or_default();
+            let serving = std::mem::replace(&mut worker.serving, true);
+            (!serving).then(|| worker.ranks.clone())
+        };
+        async move {
+            let Some(ranks) = ranks else {
+                return Ok(());
+            };
+            let handler = Arc::new(KvDumpEndpointHandler { ranks });
+            let handler = Ingress::for_engine(handler)?;
+
+            component
+                .endpoint(KV_DUMP_ENDPOINT)
+                .endpoint_builder()
+                .handler(handler)
+                .start()
+                .await
+        }
+    }
+
     pub fn shutdown(&mut self) {
         if !self.cancellation_token.is_cancelled() {
             self.cancellation_token.cancel();
@@ -167,6 +429,17 @@ impl KvEventPublisher {
         if let Some(source) = self.source.take() {
             source.shutdown();
         }
+
+        let dp_rank = self.stored_blocks.lock().unwrap().dp_rank;
+        if let Some(worker) = WORKERS.lock().unwrap().get(&self.worker_key) {
+            let mut ranks = worker.ranks.lock().unwrap();
+            if ranks
+                .get(&dp_rank)
+                .is_some_and(|blocks| Arc::ptr_eq(blocks, &self.stored_blocks))
+            {
+                ranks.remove(&dp_rank);
+            }
+        }
     }
 }
 
@@ -178,9 +451,11 @@ impl Drop for KvEventPublisher {
 
 async fn start_event_processor<P: EventPublisher + Send + Sync + 'static>(
     publisher: P,
-    worker_id: i64,
+    worker: WorkerWithDpRank,
     cancellation_token: CancellationToken,
     mut rx: mpsc::UnboundedReceiver<KvCacheEvent>,
+    stored_blocks: Arc<Mutex<StoredBlocks>>,
+    config: KvEventPublishConfig,
 ) {
     loop {
         tokio::select! {
@@ -193,17 +468,92 @@ async fn start_event_processor<P: EventPublisher + Send + Sync + 'static>(
                     tracing::debug!("Event processor channel closed.");
                     break;
                 };
+                let mut events = vec![event];
+                if config.encoding == KvEventEncoding::Binary {
+                    collect_batch(&mut rx, &mut events, &config).await;
+                }
 
-                // Encapsulate in a router event and publish.
-                let router_event = RouterEvent::new(worker_id, event);
-                if let Err(e) = publisher.publish(KV_EVENT_SUBJECT, &router_event).await {
-                    tracing::error!("Failed to publish event: {}", e);
+                {
+                    let mut stored_blocks = stored_blocks.lock().unwrap();
+                    for event in &mut events {
+                        stored_blocks.sequence(event);
+                    }
+                }
+
+                // Encapsulate in router events and publish.
+                let messages = match encode_router_events(worker, events, config.encoding) {
+                    Ok(messages) => messages,
+                    Err(e) => {
+                        tracing::error!("Failed to encode events: {}", e);
+                        continue;
+                    }
+                };
+                for message in messages {
+                    if let Err(e) = publisher.publish_bytes(KV_EVENT_SUBJECT, message).await {
+                        tracing::error!("Failed to publish event: {}", e);
+                    }
                 }
             }
         }
     }
 }
 
+/// Add the events queued behind the first one of a batch, waiting up to the batch window for
+/// more, until the batch is full
+async fn collect_batch(
+    rx: &mut mpsc::UnboundedReceiver<KvCacheEvent>,
+    events: &mut Vec<KvCacheEvent>,
+    config: &KvEventPublishConfig,
+) {
+    let deadline = tokio::time::Instant::now() + config.batch_window;
+    while events.len() < config.max_batch_events {
+        match rx.try_recv() {
+            Ok(event) => {
+                events.push(event);
+                continue;
+            }
+            Err(mpsc::error::TryRecvError::Disconnected) => return,
+            Err(mpsc::error::TryRecvError::Empty) => {}
+        }
+        if config.batch_window.is_zero() {
+            return;
+        }
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(event)) => events.push(event),
+            _ => return,
+        }
+    }
+}
+
+/// Serves the stored blocks of the data parallel rank requested
+struct KvDumpEndpointHandler {
+    ranks: Arc<Mutex<RankBlocks>>,
+}
+
+#[async_trait]
+impl AsyncEngine<SingleIn<KvDumpRequest>, ManyOut<Annotated<KvCacheDump>>, Error>
+    for KvDumpEndpointHandler
+{
+    async fn generate(
+        &self,
+        request: SingleIn<KvDumpRequest>,
+    ) -> Result<ManyOut<Annotated<KvCacheDump>>> {
+        let (request, context) = request.into_parts();
+        let stored_blocks = self
+            .ranks
+            .lock()
+            .unwrap()
+            .get(&request.dp_rank)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow::anyhow!("No KV events published for dp_rank {}", request.dp_rank)
+            })?;
+        let dump = stored_blocks.lock().unwrap().dump();
+        let stream = stream::iter(vec![Annotated::from_data(dump)]);
+        Ok(ResponseStream::new(Box::pin(stream), context.context()))
+    }
+}
+
 // Error handling configuration for ZMQ operations
 const INITIAL_BACKOFF_MS: u64 = 10;
 const MAX_BACKOFF_MS: u64 = 5000;
@@ -359,6 +709,7 @@ fn convert_event(
                         lora_id.unwrap_or(0),
                         warning_count,
                     ),
+                    lora_id: lora_id.unwrap_or(0),
                 }),
             }
         }
@@ -385,9 +736,9 @@ pub fn create_stored_block_from_parts(
     kv_block_size: usize,
     block_hash: i64,
     token_ids: &[u32],
-    _lora_id: u64,
+    lora_id: u64,
 ) -> KvCacheStoredBlockData {
-    let tokens_hash = compute_block_hash_for_seq(token_ids, kv_block_size)[0];
+    let tokens_hash = compute_block_hash_for_seq_with_lora(token_ids, kv_block_size, lora_id)[0];
     KvCacheStoredBlockData {
         block_hash: ExternalSequenceBlockHash::from(block_hash),
         tokens_hash,
@@ -460,25 +811,37 @@ enum RawKvEvent {
 // Metrics Publishers ------------------------------------------------------
 // -------------------------------------------------------------------------
 
+/// The latest metrics of each data parallel rank of a worker
+type DpRankMetrics = BTreeMap<u32, Arc<ForwardPassMetrics>>;
+
 pub struct WorkerMetricsPublisher {
-    tx: tokio::sync::watch::Sender<Arc<ForwardPassMetrics>>,
-    rx: tokio::sync::watch::Receiver<Arc<ForwardPassMetrics>>,
+    tx: tokio::sync::watch::Sender<DpRankMetrics>,
+    rx: tokio::sync::watch::Receiver<DpRankMetrics>,
 }
 
 impl WorkerMetricsPublisher {
     pub fn new() -> Result<Self> {
-        let (tx, rx) = tokio::sync::watch::channel(Arc::new(ForwardPassMetrics::default()));
+        let (tx, rx) = tokio::sync::watch::channel(DpRankMetrics::new());
         Ok(WorkerMetricsPublisher { tx, rx })
     }
 
+    /// Publish the metrics of the data parallel rank they are about, replacing the previous
+    /// metrics of that rank.
     pub fn publish(
         &self,
         metrics: Arc<ForwardPassMetrics>,
     ) -> Result<(), tokio::sync::watch::error::SendError<Arc<ForwardPassMetrics>>> {
         tracing::trace!("Publish metrics: {metrics:?}");
-        self.tx.send(metrics)
+        let dp_rank = metrics.data_parallel_rank.unwrap_or(0);
+        self.tx.send_modify(|ranks| {
+            ranks.insert(dp_rank, metrics);
+        });
+        Ok(())
     }
 
+    /// Serve the metrics on the component's [`KV_METRICS_ENDPOINT`]. The stats of the endpoint
+    /// are the [`ForwardPassMetrics`] of the worker, or a list of them when several data parallel
+    /// ranks published theirs.
     pub async fn create_endpoint(&self, component: Component) -> Result<()> {
         let mut metrics_rx = self.rx.clone();
         let handler = Arc::new(KvLoadEndpoingHander::new(metrics_rx.clone()));
@@ -488,8 +851,8 @@ impl WorkerMetricsPublisher {
             .endpoint(KV_METRICS_ENDPOINT)
             .endpoint_builder()
             .stats_handler(move |_| {
-                let metrics = metrics_rx.borrow_and_update().clone();
-                serde_json::to_value(&*metrics).unwrap()
+                let ranks = metrics_rx.borrow_and_update().clone();
+                dp_rank_stats(&ranks)
             })
             .handler(handler)
             .start()
@@ -497,12 +860,24 @@ impl WorkerMetricsPublisher {
     }
 }
 
+/// The stats of the [`KV_METRICS_ENDPOINT`], a single [`ForwardPassMetrics`] unless several
+/// data parallel ranks published metrics
+fn dp_rank_stats(ranks: &DpRankMetrics) -> serde_json::Value {
+    if ranks.len() > 1 {
+        let metrics: Vec<&ForwardPassMetrics> = ranks.values().map(|m| m.as_ref()).collect();
+        serde_json::to_value(metrics).unwrap()
+    } else {
+        let metrics = ranks.values().next().cloned().unwrap_or_default();
+        serde_json::to_value(&*metrics).unwrap()
+    }
+}
+
 struct KvLoadEndpoingHander {
-    metrics_rx: tokio::sync::watch::Receiver<Arc<ForwardPassMetrics>>,
+    metrics_rx: tokio::sync::watch::Receiver<DpRankMetrics>,
 }
 
 impl KvLoadEndpoingHander {
-    pub fn new(metrics_rx: tokio::sync::watch::Receiver<Arc<ForwardPassMetrics>>) -> Self {
+    pub fn new(metrics_rx: tokio::sync::watch::Receiver<DpRankMetrics>) -> Self {
         Self { metrics_rx }
     }
 }
@@ -516,8 +891,14 @@ impl AsyncEngine<SingleIn<()>, ManyOut<Annotated<ForwardPassMetrics>>, Error>
         request: SingleIn<()>,
     ) -> Result<ManyOut<Annotated<ForwardPassMetrics>>> {
         let context = request.context();
-        let metrics = self.metrics_rx.borrow().clone();
-        let metrics = (*metrics).clone();
+        // The metrics of the lowest rank, rank 0 unless the worker only publishes other ranks
+        let metrics = self
+            .metrics_rx
+            .borrow()
+            .values()
+            .next()
+            .map(|metrics| (**metrics).clone())
+            .unwrap_or_default();
         let stream = stream::iter(vec![Annotated::from_data(metrics)]);
         Ok(ResponseStream::new(Box::pin(stream), context))
     }
@@ -546,6 +927,12 @@ mod test_event_processing {
         assert_eq!(stored.block_hash.0, blk_hash as u64);
         let expected_hash = compute_block_hash_for_seq(&token_ids, 4)[0];
         assert_eq!(stored.tokens_hash, expected_hash);
+
+        // The blocks of a LoRA adapter don't match the blocks of the base model
+        let stored = create_stored_block_from_parts(kv_block_size, blk_hash, &token_ids, 7);
+        assert_ne!(stored.tokens_hash, expected_hash);
+        let expected_hash = compute_block_hash_for_seq_with_lora(&token_ids, 4, 7)[0];
+        assert_eq!(stored.tokens_hash, expected_hash);
     }
 
     // ---------------------------------------------------------------------
@@ -632,11 +1019,86 @@ mod test_event_processing {
         let out = convert_event(raw_evt, 1, kv_block_size, &Arc::new(AtomicU32::new(0)));
         assert!(matches!(out.data, KvCacheEventData::Cleared));
     }
+
+    // ---------------------------------------------------------------------
+    // StoredBlocks ---------------------------------------------------------
+    // ---------------------------------------------------------------------
+    #[test]
+    fn test_stored_blocks_sequence_and_dump() {
+        let mut stored_blocks = StoredBlocks::default();
+        assert!(stored_blocks.dump().last_event_id.is_none());
+
+        // ZMQ batches share their sequence number as event ID
+        let raw_events = [
+            RawKvEvent::BlockStored {
+                block_hashes: vec![10, 11, 12],
+                parent_block_hash: None,
+                token_ids: (0..12).collect(),
+                block_size: 4,
+                lora_id: None,
+            },
+            RawKvEvent::BlockRemoved {
+                block_hashes: vec![12],
+            },
+        ];
+        for raw_event in raw_events {
+            let mut event = convert_event(raw_event, 7, 4, &Arc::new(AtomicU32::new(0)));
+            stored_blocks.sequence(&mut event);
+        }
+
+        let dump = stored_blocks.dump();
+        assert_eq!(dump.last_event_id, Some(1));
+        let blocks: Vec<_> = dump
+            .events
+            .iter()
+            .map(|event| {
+                let KvCacheEventData::Stored(store) = &event.data else {
+                    panic!("expected KvCacheStoreData");
+                };
+                (store.parent_hash.map(|h| h.0), store.blocks[0].block_hash.0)
+            })
+            .collect();
+        assert_eq!(blocks, vec![(None, 10), (Some(10), 11)]);
+
+        let mut event = convert_event(
+            RawKvEvent::AllBlocksCleared,
+            8,
+            4,
+            &Arc::new(AtomicU32::new(0)),
+        );
+        stored_blocks.sequence(&mut event);
+        assert_eq!(event.event_id, 2);
+        assert!(stored_blocks.dump().events.is_empty());
+    }
+
+    #[test]
+    fn test_dp_rank_stats() {
+        let mut ranks = DpRankMetrics::new();
+        let stats = dp_rank_stats(&ranks);
+        assert!(serde_json::from_value::<ForwardPassMetrics>(stats).is_ok());
+
+        for dp_rank in [0, 1] {
+            ranks.insert(
+                dp_rank,
+                Arc::new(ForwardPassMetrics {
+                    data_parallel_rank: Some(dp_rank),
+                    kv_active_blocks: dp_rank as u64,
+                    ..Default::default()
+                }),
+            );
+        }
+        let stats: Vec<ForwardPassMetrics> = serde_json::from_value(dp_rank_stats(&ranks)).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[1].data_parallel_rank, Some(1));
+        assert_eq!(stats[1].kv_active_blocks, 1);
+    }
 }
 
 #[cfg(test)]
 mod tests_startup_helpers {
     use super::*;
+    use crate::kv_router::codec::decode_router_events;
+    use crate::kv_router::indexer::RouterEvent;
     use crate::kv_router::protocols::ExternalSequenceBlockHash;
     use async_trait;
     use bytes::Bytes;
@@ -698,6 +1160,85 @@ mod tests_startup_helpers {
         }
     }
 
+    //--------------------------------------------------------------------
+    // Test KvDumpEndpointHandler
+    //--------------------------------------------------------------------
+    #[tokio::test]
+    async fn test_dump_endpoint_resyncs_requested_dp_rank() {
+        use crate::kv_router::indexer::{KvIndexer, KvIndexerInterface};
+        use futures::StreamExt;
+
+        let mut ranks = RankBlocks::new();
+        for dp_rank in [0, 1] {
+            let mut stored_blocks = StoredBlocks {
+                dp_rank,
+                ..Default::default()
+            };
+            let mut event = KvCacheEvent {
+                event_id: 0,
+                data: KvCacheEventData::Stored(KvCacheStoreData {
+                    parent_hash: None,
+                    blocks: vec![KvCacheStoredBlockData {
+                        block_hash: ExternalSequenceBlockHash(10 + dp_rank as u64),
+                        tokens_hash: LocalBlockHash(dp_rank as u64),
+                    }],
+                    lora_id: 0,
+                }),
+            };
+            stored_blocks.sequence(&mut event);
+            ranks.insert(dp_rank, Arc::new(Mutex::new(stored_blocks)));
+        }
+        let handler = KvDumpEndpointHandler {
+            ranks: Arc::new(Mutex::new(ranks)),
+        };
+
+        let mut stream = handler
+            .generate(KvDumpRequest { dp_rank: 1 }.into())
+            .await
+            .unwrap();
+        let dump = stream.next().await.unwrap().data.unwrap();
+        assert_eq!(dump.dp_rank, 1);
+        assert!(handler
+            .generate(KvDumpRequest { dp_rank: 2 }.into())
+            .await
+            .is_err());
+
+        // Rank 1 missed an event, the dump of rank 1 resyncs it
+        let token = CancellationToken::new();
+        let kv_indexer = KvIndexer::new(token.clone(), 4);
+        let mut gaps = kv_indexer.event_gaps().await.unwrap();
+        let worker = WorkerWithDpRank::new(0, 1);
+        let removed = |event_id| {
+            RouterEvent::new(
+                0,
+                KvCacheEvent {
+                    event_id,
+                    data: KvCacheEventData::Removed(KvCacheRemoveData {
+                        block_hashes: vec![ExternalSequenceBlockHash(99)],
+                    }),
+                },
+            )
+            .with_dp_rank(1)
+        };
+        kv_indexer.event_sender().send(removed(0)).await.unwrap();
+        kv_indexer.event_sender().send(removed(2)).await.unwrap();
+        let gap = gaps.recv().await.unwrap();
+        assert_eq!(gap.worker, worker);
+
+        kv_indexer.finish_resync(worker, Some(dump)).await.unwrap();
+        let scores = kv_indexer
+            .find_matches(vec![LocalBlockHash(1)])
+            .await
+            .unwrap();
+        assert_eq!(scores.scores.get(&worker), Some(&1));
+        let scores = kv_indexer
+            .find_matches(vec![LocalBlockHash(0)])
+            .await
+            .unwrap();
+        assert!(scores.scores.is_empty());
+        token.cancel();
+    }
+
     //--------------------------------------------------------------------
     // Test start_event_processor
     //--------------------------------------------------------------------
@@ -717,7 +1258,15 @@ mod tests_startup_helpers {
         tx.send(event).unwrap();
         drop(tx);
 
-        let handle = tokio::spawn(start_event_processor(component, 1, token, rx));
+        let stored_blocks = Arc::new(Mutex::new(StoredBlocks::default()));
+        let handle = tokio::spawn(start_event_processor(
+            component,
+            WorkerWithDpRank::from(1),
+            token,
+            rx,
+            stored_blocks.clone(),
+            KvEventPublishConfig::default(),
+        ));
 
         tokio::time::timeout(tokio::time::Duration::from_secs(1), handle)
             .await
@@ -728,6 +1277,58 @@ mod tests_startup_helpers {
         assert_eq!(published.len(), 1);
         let (subject, _) = &published[0];
         assert_eq!(subject, &KV_EVENT_SUBJECT.to_string());
+        // Events are numbered by the publisher
+        assert_eq!(stored_blocks.lock().unwrap().last_event_id, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_start_event_processor_batches_binary_events() {
+        let (component, published) = MockComponent::new();
+
+        let (tx, rx) = mpsc::unbounded_channel::<KvCacheEvent>();
+        for event_id in 0..5 {
+            tx.send(KvCacheEvent {
+                event_id: 100 + event_id,
+                data: KvCacheEventData::Removed(KvCacheRemoveData {
+                    block_hashes: vec![ExternalSequenceBlockHash(event_id)],
+                }),
+            })
+            .unwrap();
+        }
+        drop(tx);
+
+        let config = KvEventPublishConfig {
+            encoding: KvEventEncoding::Binary,
+            max_batch_events: 3,
+            ..Default::default()
+        };
+        let stored_blocks = Arc::new(Mutex::new(StoredBlocks::default()));
+        let handle = tokio::spawn(start_event_processor(
+            component,
+            WorkerWithDpRank::new(1, 2),
+            CancellationToken::new(),
+            rx,
+            stored_blocks.clone(),
+            config,
+        ));
+
+        tokio::time::timeout(tokio::time::Duration::from_secs(1), handle)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // 5 events in batches of at most 3
+        let published = published.lock().unwrap();
+        assert_eq!(published.len(), 2);
+        let events: Vec<RouterEvent> = published
+            .iter()
+            .flat_map(|(_, payload)| decode_router_events(payload).unwrap())
+            .collect();
+        assert_eq!(events.len(), 5);
+        assert!(events
+            .iter()
+            .all(|event| event.worker() == WorkerWithDpRank::new(1, 2)));
+        assert_eq!(stored_blocks.lock().unwrap().last_event_id, Some(4));
     }
 
     //--------------------------------------------------------------------
@@ -795,6 +1396,7 @@ mod tests_startup_helpers {
         let KvCacheEventData::Stored(KvCacheStoreData {
             parent_hash,
             blocks,
+            ..
         }) = event.data
         else {
             panic!("expected KvCacheStoreData");
@@ -47,6 +47,7 @@ mod tests {
         KvCacheEventData::Stored(KvCacheStoreData {
             parent_hash,
             blocks: make_blocks(hashes),
+            lora_id: 0,
         })
     }
 
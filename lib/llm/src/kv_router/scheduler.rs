@@ -17,20 +17,27 @@ use dynamo_runtime::component::Namespace;
 use dynamo_runtime::traits::events::EventPublisher;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::borrow::BorrowMut;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
 
-use super::protocols::WorkerSelectionResult;
+use super::protocols::{RoutingDecision, WorkerCandidate, WorkerSelectionResult, WorkerWithDpRank};
 use super::WorkerSelector;
 use crate::kv_router::indexer::OverlapScores;
 pub use crate::kv_router::protocols::ForwardPassMetrics;
+use crate::kv_router::protocols::LocalBlockHash;
 use crate::kv_router::scoring::ProcessedEndpoints;
 use crate::kv_router::KvRouterConfig;
 use crate::kv_router::KV_HIT_RATE_SUBJECT;
 
+/// How long a dispatched request counts as in flight on its worker, if the worker's metrics
+/// don't reflect it sooner
+const PENDING_PREFILL_TTL: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KVHitRateEvent {
     pub worker_id: i64,
+    #[serde(default)]
+    pub dp_rank: u32,
     pub isl_blocks: usize,
     pub overlap_blocks: usize,
 }
@@ -69,22 +76,126 @@ impl Endpoint {
         )
         .expect("invalid worker id")
     }
+
+    /// The worker and data parallel rank the metrics are about
+    pub fn worker(&self) -> WorkerWithDpRank {
+        WorkerWithDpRank::new(self.worker_id(), self.data.data_parallel_rank.unwrap_or(0))
+    }
 }
 
 pub struct SchedulingRequest {
     pub isl_tokens: usize,
     pub overlap: OverlapScores,
-    resp_tx: tokio::sync::oneshot::Sender<i64>,
+    /// The LoRA adapter of the request, 0 for the base model
+    pub lora_id: u64,
+    /// The hashes of the complete blocks of the request
+    pub block_hashes: Vec<LocalBlockHash>,
+    /// Ask the selector for a [`RoutingDecision`] explaining its choice
+    pub explain: bool,
+    /// Workers to leave out of the candidates, e.g. because their circuit is open
+    pub unavailable: HashSet<i64>,
+    resp_tx: tokio::sync::oneshot::Sender<(WorkerWithDpRank, Option<RoutingDecision>)>,
 }
 
 impl SchedulingRequest {
-    pub fn respond(self, worker_id: i64) {
-        if self.resp_tx.send(worker_id).is_err() {
+    pub fn respond(self, worker: WorkerWithDpRank, decision: Option<RoutingDecision>) {
+        if self.resp_tx.send((worker, decision)).is_err() {
             tracing::trace!("failed to send response to requestor");
         }
     }
 }
 
+/// A request dispatched to a worker, that the worker's metrics and KV events may not reflect yet
+#[derive(Debug)]
+struct PendingPrefill {
+    worker: WorkerWithDpRank,
+    block_hashes: Vec<LocalBlockHash>,
+    new_blocks: u64,
+    dispatched: Instant,
+}
+
+/// The scheduler's prediction of the requests the workers are about to prefill, and of the
+/// blocks they are about to hold.
+///
+/// Worker metrics and KV events lag behind the requests the scheduler dispatches, so without it
+/// a burst of requests sharing a prefix would all land on the worker that has the prefix cached.
+/// A prediction is dropped once the worker's metrics had the time to reflect it, or after
+/// [`PENDING_PREFILL_TTL`]. Once KV events report the blocks stored, their overlap catches up with
+/// the predicted one.
+#[derive(Debug, Default)]
+struct PendingPrefills {
+    /// Oldest first
+    prefills: VecDeque<PendingPrefill>,
+    last_metrics_update: Option<Instant>,
+}
+
+impl PendingPrefills {
+    /// Record a request dispatched to a worker, that needs `new_blocks` blocks on top of the
+    /// ones the worker has cached
+    fn add(
+        &mut self,
+        worker: WorkerWithDpRank,
+        block_hashes: Vec<LocalBlockHash>,
+        new_blocks: u64,
+        now: Instant,
+    ) {
+        self.prefills.push_back(PendingPrefill {
+            worker,
+            block_hashes,
+            new_blocks,
+            dispatched: now,
+        });
+    }
+
+    /// The metrics of the workers were refreshed. The requests dispatched before the previous
+    /// refresh had a whole metrics interval to show up in them.
+    fn metrics_updated(&mut self, now: Instant) {
+        if let Some(last_update) = self.last_metrics_update {
+            self.prefills
+                .retain(|prefill| prefill.dispatched >= last_update);
+        }
+        self.last_metrics_update = Some(now);
+    }
+
+    /// Drop the predictions older than [`PENDING_PREFILL_TTL`]
+    fn expire(&mut self, now: Instant) {
+        while self
+            .prefills
+            .front()
+            .is_some_and(|prefill| now.duration_since(prefill.dispatched) >= PENDING_PREFILL_TTL)
+        {
+            self.prefills.pop_front();
+        }
+    }
+
+    /// The state of the workers including the predictions: each pending request is waiting and
+    /// holds its new blocks, and the request overlaps the pending requests it shares a prefix with.
+    fn apply(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &mut SchedulingRequest,
+    ) -> ProcessedEndpoints {
+        let mut workers = workers.clone();
+        for prefill in &self.prefills {
+            let Some(ep) = workers.endpoints.get_mut(&prefill.worker) else {
+                continue;
+            };
+            ep.data.num_requests_waiting += 1;
+            ep.data.kv_active_blocks += prefill.new_blocks;
+
+            let overlap = prefill
+                .block_hashes
+                .iter()
+                .zip(&request.block_hashes)
+                .take_while(|(pending, requested)| pending == requested)
+                .count() as u32;
+            let score = request.overlap.scores.entry(prefill.worker).or_default();
+            *score = (*score).max(overlap);
+        }
+        workers
+    }
+}
+
 pub struct KvScheduler {
     request_tx: tokio::sync::mpsc::Sender<SchedulingRequest>,
 }
@@ -96,10 +207,6 @@ impl KvScheduler {
         endpoints_rx: tokio::sync::watch::Receiver<ProcessedEndpoints>,
         selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
     ) -> Result<Self, KvSchedulerError> {
-        let selector = selector.unwrap_or(Box::new(DefaultWorkerSelector::default()));
-        let mut endpoints_rx = endpoints_rx;
-        let mut endpoints: ProcessedEndpoints = endpoints_rx.borrow_and_update().clone();
-
         let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel::<KVHitRateEvent>();
         tokio::spawn(async move {
             let mut event_rx = event_rx;
@@ -110,12 +217,28 @@ impl KvScheduler {
             }
         });
 
+        Self::start_with_event_sink(block_size, endpoints_rx, selector, event_tx).await
+    }
+
+    /// Start a scheduler that sends its [`KVHitRateEvent`]s on `event_tx` instead of publishing
+    /// them on a namespace, e.g. to run it outside of a distributed runtime.
+    pub async fn start_with_event_sink(
+        block_size: usize,
+        endpoints_rx: tokio::sync::watch::Receiver<ProcessedEndpoints>,
+        selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+        event_tx: tokio::sync::mpsc::UnboundedSender<KVHitRateEvent>,
+    ) -> Result<Self, KvSchedulerError> {
+        let selector = selector.unwrap_or(Box::new(DefaultWorkerSelector::default()));
+        let mut endpoints_rx = endpoints_rx;
+        let mut endpoints: ProcessedEndpoints = endpoints_rx.borrow_and_update().clone();
+
         // Channel to accept new scheduling requests
         let (request_tx, request_rx) = tokio::sync::mpsc::channel::<SchedulingRequest>(1024);
         // Background task to handle scheduling requests
         tokio::spawn(async move {
             let mut request: SchedulingRequest;
             let mut request_rx = request_rx;
+            let mut pending = PendingPrefills::default();
             tracing::trace!("scheduler background task started");
 
             'outer: loop {
@@ -137,18 +260,24 @@ impl KvScheduler {
 
                     _ = endpoints_rx.changed() => {
                         endpoints = endpoints_rx.borrow_and_update().clone();
+                        pending.metrics_updated(Instant::now());
                         continue 'outer;
                     }
                 };
                 loop {
-                    match selector.select_worker(&endpoints, &request, block_size) {
-                        Ok(selection) => {
-                            let worker_id = process_worker_selection(
-                                endpoints.borrow_mut(),
+                    pending.expire(Instant::now());
+                    let mut predicted = pending.apply(&endpoints, &mut request);
+                    retain_available(&mut predicted, &request.unavailable);
+                    match selector.select_worker(&predicted, &request, block_size) {
+                        Ok(mut selection) => {
+                            let decision = selection.decision.take();
+                            let worker = process_worker_selection(
+                                &mut pending,
                                 selection,
+                                std::mem::take(&mut request.block_hashes),
                                 &event_tx,
                             );
-                            request.respond(worker_id);
+                            request.respond(worker, decision);
                             continue 'outer;
                         }
                         Err(KvSchedulerError::AllWorkersBusy) => {
@@ -161,6 +290,7 @@ impl KvScheduler {
                                 }
                             };
                             endpoints = endpoints_rx.borrow_and_update().clone();
+                            pending.metrics_updated(Instant::now());
                         }
                         Err(e) => {
                             tracing::error!("error scheduling request: {:?}", e);
@@ -176,15 +306,26 @@ impl KvScheduler {
         Ok(KvScheduler { request_tx })
     }
 
+    /// Select the worker for a request, with the [`RoutingDecision`] behind the choice if
+    /// `explain` is set and the selector explains its choices. The `unavailable` workers are
+    /// only picked if no other worker is left.
     pub async fn schedule(
         &self,
         overlap: OverlapScores,
+        block_hashes: Vec<LocalBlockHash>,
         isl_tokens: usize,
-    ) -> Result<i64, KvSchedulerError> {
+        lora_id: u64,
+        explain: bool,
+        unavailable: HashSet<i64>,
+    ) -> Result<(WorkerWithDpRank, Option<RoutingDecision>), KvSchedulerError> {
         let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
         let request = SchedulingRequest {
             isl_tokens,
             overlap,
+            lora_id,
+            block_hashes,
+            explain,
+            unavailable,
             resp_tx,
         };
         self.request_tx
@@ -198,37 +339,75 @@ impl KvScheduler {
     }
 }
 
+/// Leave the unavailable workers out of the candidates, unless none would be left: the request
+/// then goes to one of them and fails there, rather than holding up the scheduler.
+fn retain_available(workers: &mut ProcessedEndpoints, unavailable: &HashSet<i64>) {
+    if workers
+        .endpoints
+        .keys()
+        .any(|worker| !unavailable.contains(&worker.worker_id))
+    {
+        workers
+            .endpoints
+            .retain(|worker, _| !unavailable.contains(&worker.worker_id));
+    }
+}
+
 // This becomes the driver function that handles the selection result
-pub fn process_worker_selection(
-    workers: &mut ProcessedEndpoints,
+fn process_worker_selection(
+    pending: &mut PendingPrefills,
     selection: WorkerSelectionResult,
+    block_hashes: Vec<LocalBlockHash>,
     event_tx: &tokio::sync::mpsc::UnboundedSender<KVHitRateEvent>,
-) -> i64 {
-    let worker = workers
-        .endpoints
-        .get_mut(&selection.worker_id)
-        .expect("worker not found");
-
-    // Update worker state predictively
-    // Will be overwritten on next polling of metrics
-    worker.data.kv_active_blocks += selection
-        .required_blocks
-        .saturating_sub(selection.overlap_blocks as u64);
+) -> WorkerWithDpRank {
+    // Update worker state predictively, until its metrics reflect the request
+    pending.add(
+        selection.worker,
+        block_hashes,
+        selection
+            .required_blocks
+            .saturating_sub(selection.overlap_blocks as u64),
+        Instant::now(),
+    );
 
     // Emit event
     if let Err(e) = event_tx.send(KVHitRateEvent {
-        worker_id: selection.worker_id,
+        worker_id: selection.worker.worker_id,
+        dp_rank: selection.worker.dp_rank,
         isl_blocks: selection.required_blocks as usize,
         overlap_blocks: selection.overlap_blocks,
     }) {
         tracing::warn!("Failed to send KV hit rate event: {:?}", e);
     }
 
-    selection.worker_id
+    selection.worker
 }
 
 // Helper function for softmax sampling
-fn softmax_sample(logits: &HashMap<i64, f64>, temperature: f64) -> i64 {
+fn softmax_sample(logits: &HashMap<WorkerWithDpRank, f64>, temperature: f64) -> WorkerWithDpRank {
+    let (keys, probabilities) = softmax_probabilities(logits, temperature);
+
+    // Sample from the probability distribution
+    let mut rng = rand::rng();
+    let sample: f64 = rng.random();
+
+    let mut cumsum = 0.0;
+    for (i, &prob) in probabilities.iter().enumerate() {
+        cumsum += prob;
+        if sample <= cumsum {
+            return keys[i];
+        }
+    }
+
+    // Fallback to last key (shouldn't normally reach here)
+    keys[keys.len() - 1]
+}
+
+/// The probability of [`softmax_sample`] picking each worker
+fn softmax_probabilities(
+    logits: &HashMap<WorkerWithDpRank, f64>,
+    temperature: f64,
+) -> (Vec<WorkerWithDpRank>, Vec<f64>) {
     if logits.is_empty() {
         panic!("Empty logits for softmax sampling");
     }
@@ -264,20 +443,7 @@ fn softmax_sample(logits: &HashMap<i64, f64>, temperature: f64) -> i64 {
         exp_values.iter().map(|&v| v / sum_exp).collect()
     };
 
-    // Sample from the probability distribution
-    let mut rng = rand::rng();
-    let sample: f64 = rng.random();
-
-    let mut cumsum = 0.0;
-    for (i, &prob) in probabilities.iter().enumerate() {
-        cumsum += prob;
-        if sample <= cumsum {
-            return keys[i];
-        }
-    }
-
-    // Fallback to last key (shouldn't normally reach here)
-    keys[keys.len() - 1]
+    (keys, probabilities)
 }
 
 // Default implementation matching the Python _cost_function
@@ -292,6 +458,40 @@ impl DefaultWorkerSelector {
             kv_router_config: kv_router_config.unwrap_or_default(),
         }
     }
+
+    /// The decision to route `request` to `worker`, for the candidates with these logits.
+    /// `probabilities` is `None` if the worker was picked uniformly at random.
+    fn decision(
+        &self,
+        worker: WorkerWithDpRank,
+        request: &SchedulingRequest,
+        request_blocks: u64,
+        temperature: f64,
+        mut candidates: Vec<WorkerCandidate>,
+        probabilities: Option<HashMap<WorkerWithDpRank, f64>>,
+    ) -> RoutingDecision {
+        let uniform = 1.0 / candidates.len() as f64;
+        for candidate in &mut candidates {
+            candidate.probability = match &probabilities {
+                Some(probabilities) => probabilities[&candidate.worker],
+                None => uniform,
+            };
+        }
+        candidates.sort_by(|a, b| a.logit.total_cmp(&b.logit).then(a.worker.cmp(&b.worker)));
+        RoutingDecision {
+            request_id: None,
+            worker,
+            request_blocks,
+            lora_id: request.lora_id,
+            overlap_score_weight: self.kv_router_config.overlap_score_weight,
+            gpu_cache_usage_weight: self.kv_router_config.gpu_cache_usage_weight,
+            waiting_requests_weight: self.kv_router_config.waiting_requests_weight,
+            lora_load_weight: self.kv_router_config.lora_load_weight,
+            temperature,
+            random_fallback: probabilities.is_none(),
+            candidates,
+        }
+    }
 }
 
 impl WorkerSelector for DefaultWorkerSelector {
@@ -309,14 +509,14 @@ impl WorkerSelector for DefaultWorkerSelector {
 
         let request_blocks = request.isl_tokens.div_ceil(block_size);
         let mut worker_logits = HashMap::new();
+        let mut candidates = Vec::new();
 
         // Calculate logits for each worker
-        for (worker_id, ep) in workers.endpoints.iter() {
-            let worker_id = *worker_id;
+        for (worker, ep) in workers.endpoints.iter() {
+            let worker = *worker;
 
             // Get overlap blocks for this worker
-            let overlap_blocks =
-                request.overlap.scores.get(&worker_id).copied().unwrap_or(0) as f64;
+            let overlap_blocks = request.overlap.scores.get(&worker).copied().unwrap_or(0) as f64;
             let new_blocks = request_blocks as f64 - overlap_blocks;
 
             let kv_total_blocks = ep.data.kv_total_blocks as f64;
@@ -325,60 +525,101 @@ impl WorkerSelector for DefaultWorkerSelector {
             let normalized_new_blocks = new_blocks / kv_total_blocks;
             let gpu_cache_usage = (ep.data.kv_active_blocks as f64) / kv_total_blocks;
             let num_requests_waiting = ep.data.num_requests_waiting as f64;
+            // The worker would have to load the adapter of the request first
+            let lora_not_loaded =
+                if request.lora_id != 0 && !ep.data.loaded_lora_ids.contains(&request.lora_id) {
+                    1.0
+                } else {
+                    0.0
+                };
 
             // Calculate logit (lower is better)
             let logit = self.kv_router_config.overlap_score_weight * normalized_new_blocks
                 + self.kv_router_config.gpu_cache_usage_weight * gpu_cache_usage
-                + self.kv_router_config.waiting_requests_weight * num_requests_waiting;
-
-            worker_logits.insert(worker_id, logit);
+                + self.kv_router_config.waiting_requests_weight * num_requests_waiting
+                + self.kv_router_config.lora_load_weight * lora_not_loaded;
+
+            worker_logits.insert(worker, logit);
+            if request.explain {
+                candidates.push(WorkerCandidate {
+                    worker,
+                    overlap_blocks: overlap_blocks as u32,
+                    normalized_new_blocks,
+                    gpu_cache_usage,
+                    num_requests_waiting: ep.data.num_requests_waiting,
+                    lora_not_loaded: lora_not_loaded > 0.0,
+                    logit,
+                    probability: 0.0,
+                });
+            }
 
             tracing::info!(
-                "Formula for {worker_id}: {logit:.3} = {:.1} * {normalized_new_blocks:.3} + {:.1} * {gpu_cache_usage:.3} + {:.1} * {num_requests_waiting:.3}",
+                "Formula for {worker}: {logit:.3} = {:.1} * {normalized_new_blocks:.3} + {:.1} * {gpu_cache_usage:.3} + {:.1} * {num_requests_waiting:.3} + {:.1} * {lora_not_loaded:.1}",
                 self.kv_router_config.overlap_score_weight,
                 self.kv_router_config.gpu_cache_usage_weight,
                 self.kv_router_config.waiting_requests_weight,
+                self.kv_router_config.lora_load_weight,
             );
         }
 
+        let temperature = 1.0; // You can make this configurable if needed
+
         // Return early if no valid workers found
         if worker_logits.is_empty() || worker_logits.values().all(|&v| v == 0.0) {
             tracing::warn!("All worker logits are zero. Fallback to random routing.");
             // Pick random worker
             let mut rng = rand::rng();
             let worker_ids: Vec<_> = workers.endpoints.keys().copied().collect();
-            let worker_id = worker_ids[rng.random_range(0..worker_ids.len())];
-            let overlap_blocks =
-                request.overlap.scores.get(&worker_id).copied().unwrap_or(0) as usize;
+            let worker = worker_ids[rng.random_range(0..worker_ids.len())];
+            let overlap_blocks = request.overlap.scores.get(&worker).copied().unwrap_or(0) as usize;
+            let decision = request.explain.then(|| {
+                self.decision(
+                    worker,
+                    request,
+                    request_blocks as u64,
+                    temperature,
+                    candidates,
+                    None,
+                )
+            });
             return Ok(WorkerSelectionResult {
-                worker_id,
+                worker,
                 required_blocks: request_blocks as u64,
                 overlap_blocks,
+                decision,
             });
         }
 
         // Use softmax sampling to select worker
-        let temperature = 1.0; // You can make this configurable if needed
-        let best_worker_id = softmax_sample(&worker_logits, temperature);
+        let best_worker = softmax_sample(&worker_logits, temperature);
 
         let overlap_blocks = request
             .overlap
             .scores
-            .get(&best_worker_id)
+            .get(&best_worker)
             .copied()
             .unwrap_or(0) as usize;
-        let best_logit = worker_logits[&best_worker_id];
-
-        tracing::info!(
-            "Selected worker: {}, logit: {:.3}",
-            best_worker_id,
-            best_logit
-        );
+        let best_logit = worker_logits[&best_worker];
+
+        tracing::info!("Selected worker: {}, logit: {:.3}", best_worker, best_logit);
+
+        let decision = request.explain.then(|| {
+            let (keys, probabilities) = softmax_probabilities(&worker_logits, temperature);
+            self.decision(
+                best_worker,
+                request,
+                request_blocks as u64,
+                temperature,
+                candidates,
+                Some(keys.into_iter().zip(probabilities).collect()),
+            )
+        });
 
         Ok(WorkerSelectionResult {
-            worker_id: best_worker_id,
+            worker: best_worker,
             required_blocks: request_blocks as u64,
             overlap_blocks,
+            decision,
         })
     }
 }
@@ -391,7 +632,7 @@ mod tests {
     fn test_softmax_sample_single_key() {
         // Test that with a single key, softmax_sample always returns that key
         let mut logits = HashMap::new();
-        let worker_id = 42;
+        let worker_id = WorkerWithDpRank::from(42);
         logits.insert(worker_id, 0.5); // The value doesn't matter
 
         // Test with different temperatures
@@ -442,7 +683,7 @@ mod tests {
         let mut endpoints = HashMap::new();
         for worker in workers {
             endpoints.insert(
-                worker.id,
+                WorkerWithDpRank::from(worker.id),
                 create_endpoint(worker.id, worker.usage, worker.waiting),
             );
         }
@@ -461,13 +702,17 @@ mod tests {
     fn create_request(overlaps: Vec<WorkerOverlap>, isl_tokens: usize) -> SchedulingRequest {
         SchedulingRequest {
             isl_tokens,
+            lora_id: 0,
+            block_hashes: vec![],
             overlap: OverlapScores {
                 scores: overlaps
                     .into_iter()
-                    .map(|wo| (wo.worker_id, wo.overlap_blocks))
+                    .map(|wo| (WorkerWithDpRank::from(wo.worker_id), wo.overlap_blocks))
                     .collect(),
                 frequencies: vec![],
             },
+            explain: false,
+            unavailable: HashSet::new(),
             resp_tx: tokio::sync::oneshot::channel().0,
         }
     }
@@ -485,6 +730,255 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_prefer_workers_with_lora_loaded() {
+        let mut workers = create_workers(vec![
+            WorkerInfo {
+                id: 1,
+                usage: 0.0,
+                waiting: 0,
+            },
+            WorkerInfo {
+                id: 2,
+                usage: 0.0,
+                waiting: 0,
+            },
+        ]);
+        for ep in workers.endpoints.values_mut() {
+            ep.data.kv_total_blocks = 100;
+        }
+        workers
+            .endpoints
+            .get_mut(&WorkerWithDpRank::from(2))
+            .unwrap()
+            .data
+            .loaded_lora_ids = vec![7];
+        let mut request = create_request(vec![], 100);
+        request.lora_id = 7;
+        let selector = DefaultWorkerSelector::new(None);
+
+        // Selection is sampled, worker 2 should win about three times out of four
+        let selected_with_lora = (0..1000)
+            .filter(|_| {
+                let result = selector
+                    .select_worker(&workers, &request, 20)
+                    .expect("Should select a worker");
+                result.worker.worker_id == 2
+            })
+            .count();
+        assert!(selected_with_lora > 600, "{selected_with_lora}");
+
+        // Without an adapter both workers are equal
+        request.lora_id = 0;
+        let selected = (0..1000)
+            .filter(|_| {
+                let result = selector
+                    .select_worker(&workers, &request, 20)
+                    .expect("Should select a worker");
+                result.worker.worker_id == 2
+            })
+            .count();
+        assert!((300..700).contains(&selected), "{selected}");
+    }
+
+    #[test]
+    fn test_explain_selection() {
+        let mut workers = create_workers(vec![
+            WorkerInfo {
+                id: 1,
+                usage: 0.0,
+                waiting: 2,
+            },
+            WorkerInfo {
+                id: 2,
+                usage: 0.0,
+                waiting: 0,
+            },
+        ]);
+        for ep in workers.endpoints.values_mut() {
+            ep.data.kv_total_blocks = 100;
+        }
+        let mut request = create_request(
+            vec![WorkerOverlap {
+                worker_id: 2,
+                overlap_blocks: 3,
+            }],
+            100,
+        );
+        let selector = DefaultWorkerSelector::new(None);
+
+        let result = selector.select_worker(&workers, &request, 20).unwrap();
+        assert!(result.decision.is_none());
+
+        request.explain = true;
+        let result = selector.select_worker(&workers, &request, 20).unwrap();
+        let decision = result.decision.expect("Should explain the selection");
+        assert_eq!(decision.worker, result.worker);
+        assert_eq!(decision.request_blocks, 5);
+        assert!(!decision.random_fallback);
+
+        // Best worker first: 2 has 3 of the 5 blocks cached and no queue
+        let candidates = &decision.candidates;
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].worker, WorkerWithDpRank::from(2));
+        assert_eq!(candidates[0].overlap_blocks, 3);
+        assert!((candidates[0].logit - 0.02).abs() < 1e-9);
+        assert_eq!(candidates[1].num_requests_waiting, 2);
+        assert!((candidates[1].logit - 2.05).abs() < 1e-9);
+        assert!(candidates[0].probability > candidates[1].probability);
+        let total: f64 = candidates.iter().map(|c| c.probability).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pending_prefills() {
+        let workers = create_workers(vec![
+            WorkerInfo {
+                id: 1,
+                usage: 0.0,
+                waiting: 0,
+            },
+            WorkerInfo {
+                id: 2,
+                usage: 0.0,
+                waiting: 0,
+            },
+        ]);
+        let hashes: Vec<LocalBlockHash> = (0..4).map(LocalBlockHash).collect();
+        let mut pending = PendingPrefills::default();
+        let start = Instant::now();
+        let (worker_1, worker_2) = (WorkerWithDpRank::from(1), WorkerWithDpRank::from(2));
+        pending.add(worker_1, hashes.clone(), 4, start);
+
+        // The request sharing a prefix with the pending one overlaps it
+        let mut request = create_request(vec![], 80);
+        request.block_hashes = vec![hashes[0], hashes[1], LocalBlockHash(9)];
+        let predicted = pending.apply(&workers, &mut request);
+        assert_eq!(predicted.endpoints[&worker_1].data.num_requests_waiting, 1);
+        assert_eq!(predicted.endpoints[&worker_1].data.kv_active_blocks, 4);
+        assert_eq!(predicted.endpoints[&worker_2].data.num_requests_waiting, 0);
+        assert_eq!(request.overlap.scores.get(&worker_1), Some(&2));
+
+        // The stored blocks overlap more than the prediction
+        request.overlap.scores.insert(worker_1, 3);
+        pending.apply(&workers, &mut request);
+        assert_eq!(request.overlap.scores.get(&worker_1), Some(&3));
+
+        // The request is reflected after a whole metrics interval
+        pending.metrics_updated(start + Duration::from_millis(50));
+        assert_eq!(pending.prefills.len(), 1);
+        pending.metrics_updated(start + Duration::from_millis(150));
+        assert!(pending.prefills.is_empty());
+
+        // Or after a while without metrics
+        pending.add(worker_2, hashes, 4, start);
+        pending.expire(start + PENDING_PREFILL_TTL / 2);
+        assert_eq!(pending.prefills.len(), 1);
+        pending.expire(start + PENDING_PREFILL_TTL);
+        assert!(pending.prefills.is_empty());
+    }
+
+    #[test]
+    fn test_unavailable_workers() {
+        let mut workers = create_workers(
+            (1..=3)
+                .map(|id| WorkerInfo {
+                    id,
+                    usage: 0.0,
+                    waiting: 0,
+                })
+                .collect(),
+        );
+        retain_available(&mut workers, &HashSet::from([1, 3]));
+        let worker_ids: Vec<_> = workers.endpoints.keys().map(|w| w.worker_id).collect();
+        assert_eq!(worker_ids, vec![2]);
+
+        // with no available worker left, the candidates stay as they were
+        retain_available(&mut workers, &HashSet::from([2]));
+        assert_eq!(workers.endpoints.len(), 1);
+    }
+
+    #[test]
+    fn test_burst_on_hot_prefix_spreads() {
+        let mut workers = create_workers(vec![
+            WorkerInfo {
+                id: 1,
+                usage: 0.0,
+                waiting: 0,
+            },
+            WorkerInfo {
+                id: 2,
+                usage: 0.0,
+                waiting: 0,
+            },
+        ]);
+        for ep in workers.endpoints.values_mut() {
+            ep.data.kv_total_blocks = 100;
+        }
+        let selector = DefaultWorkerSelector::new(None);
+        let (event_tx, _event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let hashes: Vec<LocalBlockHash> = (0..5).map(LocalBlockHash).collect();
+
+        // Worker 1 has the prefix cached, and no metrics update during the burst
+        let mut pending = PendingPrefills::default();
+        let mut selected = HashMap::<i64, usize>::new();
+        for _ in 0..200 {
+            let mut request = create_request(
+                vec![WorkerOverlap {
+                    worker_id: 1,
+                    overlap_blocks: 5,
+                }],
+                100,
+            );
+            request.block_hashes = hashes.clone();
+            let predicted = pending.apply(&workers, &mut request);
+            let selection = selector
+                .select_worker(&predicted, &request, 20)
+                .expect("Should select a worker");
+            let worker =
+                process_worker_selection(&mut pending, selection, hashes.clone(), &event_tx);
+            *selected.entry(worker.worker_id).or_default() += 1;
+        }
+
+        // Without the pending requests worker 1 would always look better, and get ~73% of them
+        let on_worker_2 = selected.get(&2).copied().unwrap_or(0);
+        assert!(on_worker_2 > 80, "{selected:?}");
+    }
+
+    #[test]
+    fn test_data_parallel_ranks() {
+        // Both ranks of worker 1 publish their own metrics
+        let endpoints: Vec<Endpoint> = (0..2)
+            .map(|dp_rank| {
+                let mut ep = create_endpoint(1, 0.0, dp_rank as u64);
+                ep.data.data_parallel_rank = Some(dp_rank);
+                ep.data.kv_total_blocks = 100;
+                ep
+            })
+            .collect();
+        let workers = ProcessedEndpoints::new(endpoints);
+        let (rank_0, rank_1) = (WorkerWithDpRank::new(1, 0), WorkerWithDpRank::new(1, 1));
+        assert_eq!(workers.endpoints.len(), 2);
+        assert_eq!(workers.endpoints[&rank_1].data.num_requests_waiting, 1);
+
+        // A request in flight on one rank is not on the other
+        let hashes: Vec<LocalBlockHash> = (0..4).map(LocalBlockHash).collect();
+        let mut pending = PendingPrefills::default();
+        pending.add(rank_1, hashes.clone(), 4, Instant::now());
+        let mut request = create_request(vec![], 80);
+        request.block_hashes = hashes;
+        let predicted = pending.apply(&workers, &mut request);
+        assert_eq!(predicted.endpoints[&rank_0].data.num_requests_waiting, 0);
+        assert_eq!(predicted.endpoints[&rank_1].data.num_requests_waiting, 2);
+        assert_eq!(request.overlap.scores.get(&rank_0), None);
+        assert_eq!(request.overlap.scores.get(&rank_1), Some(&4));
+
+        let selection = DefaultWorkerSelector::new(None)
+            .select_worker(&predicted, &request, 20)
+            .expect("Should select a rank");
+        assert_eq!(selection.worker.worker_id, 1);
+    }
+
     // #[test]
     // fn test_select_worker_basic() {
     //     // Setup workers
@@ -18,11 +18,14 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::kv_router::protocols::WorkerWithDpRank;
 use crate::kv_router::scheduler::Endpoint;
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct ProcessedEndpoints {
-    pub endpoints: HashMap<i64, Endpoint>,
+    /// The load of each worker, or of each data parallel rank of the workers that publish
+    /// metrics per rank
+    pub endpoints: HashMap<WorkerWithDpRank, Endpoint>,
     pub load_avg: f64,
     pub load_std: f64,
 }
@@ -42,7 +45,7 @@ impl ProcessedEndpoints {
             / load_values.len() as f64;
         let load_std = variance.sqrt();
 
-        let endpoints = endpoints.into_iter().map(|e| (e.worker_id(), e)).collect();
+        let endpoints = endpoints.into_iter().map(|e| (e.worker(), e)).collect();
 
         ProcessedEndpoints {
             endpoints,
@@ -0,0 +1,744 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Offline simulation of KV aware routing, replaying a trace of requests.
+//!
+//! The workers are mocker engines ([`SteppedScheduler`]) stepped on a virtual clock, so a trace
+//! of hours replays in seconds and the results don't depend on the load of the host. Requests
+//! are routed through the real [`KvIndexer`] and [`KvScheduler`], fed with the KV events and
+//! the metrics of the simulated workers, which makes it possible to compare [`KvRouterConfig`]
+//! weights and [`WorkerSelector`] implementations before changing a deployment.
+//!
+//! The virtual clock only moves when the simulator advances it, so drive the simulation on a
+//! current thread runtime: the scheduler then sees every metrics update before the next request.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch, Notify};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::kv_router::indexer::{
+    compute_block_hash_for_seq, KvIndexer, KvIndexerInterface, RouterEvent, WorkerId,
+};
+use crate::kv_router::protocols::{KvCacheEvent, WorkerSelectionResult, WorkerWithDpRank};
+use crate::kv_router::scheduler::{
+    DefaultWorkerSelector, Endpoint, KVHitRateEvent, KvScheduler, KvSchedulerError,
+    SchedulingRequest,
+};
+use crate::kv_router::scoring::ProcessedEndpoints;
+use crate::kv_router::{KvRouterConfig, WorkerSelector};
+use crate::mocker::protocols::{DirectRequest, MockEngineArgs, Token};
+use crate::mocker::scheduler::{ForwardPassOutput, SteppedScheduler};
+
+/// How often the simulated workers publish their metrics by default
+pub const DEFAULT_METRICS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Shortest simulated forward pass, matching the tick of the mocker's simulation loop
+const MIN_FORWARD_PASS: Duration = Duration::from_millis(1);
+
+/// A request of a trace, one JSON object per line.
+///
+/// The prompt is either given as `token_ids`, or as `hash_ids` where each id stands for a block
+/// of `trace_block_size` tokens and requests sharing a prefix of ids share that prefix of
+/// tokens. `input_length` then truncates the prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceRequest {
+    /// Arrival of the request, in milliseconds since the start of the trace
+    pub timestamp: u64,
+
+    /// The tokens of the prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_ids: Option<Vec<Token>>,
+
+    /// The ids of the blocks of the prompt
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hash_ids: Vec<u32>,
+
+    /// The number of tokens of the prompt, when given as `hash_ids`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_length: Option<usize>,
+
+    /// The number of tokens to generate
+    pub output_length: usize,
+}
+
+impl TraceRequest {
+    /// The tokens of the prompt, made up from the `hash_ids` if the trace has no `token_ids`
+    pub fn tokens(&self, trace_block_size: usize) -> anyhow::Result<Vec<Token>> {
+        let tokens = match &self.token_ids {
+            Some(tokens) => tokens.clone(),
+            None => {
+                let mut tokens: Vec<Token> = self
+                    .hash_ids
+                    .iter()
+                    .flat_map(|&id| std::iter::repeat_n(id, trace_block_size))
+                    .collect();
+                if let Some(input_length) = self.input_length {
+                    anyhow::ensure!(
+                        input_length <= tokens.len(),
+                        "input_length {input_length} is longer than the {} tokens of the hash_ids",
+                        tokens.len()
+                    );
+                    tokens.truncate(input_length);
+                }
+                tokens
+            }
+        };
+        anyhow::ensure!(!tokens.is_empty(), "request has an empty prompt");
+        anyhow::ensure!(self.output_length > 0, "request generates no tokens");
+        Ok(tokens)
+    }
+}
+
+/// Read a JSONL trace, ordered by arrival
+pub fn load_trace(path: &Path) -> anyhow::Result<Vec<TraceRequest>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read trace {}", path.display()))?;
+    let mut trace = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str::<TraceRequest>(line)
+                .with_context(|| format!("Invalid request on line {} of the trace", i + 1))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    trace.sort_by_key(|request| request.timestamp);
+    Ok(trace)
+}
+
+/// Configuration of a simulation
+#[derive(Debug, Clone, Builder)]
+pub struct SimulatorConfig {
+    /// Number of simulated workers
+    #[builder(default = "4")]
+    pub num_workers: usize,
+
+    /// Configuration of the simulated engines, its block size is also the router's
+    #[builder(default)]
+    pub engine_args: MockEngineArgs,
+
+    /// Weights of the default worker selector
+    #[builder(default)]
+    pub kv_router_config: KvRouterConfig,
+
+    /// How often the workers publish their metrics, in virtual time
+    #[builder(default = "DEFAULT_METRICS_INTERVAL")]
+    pub metrics_interval: Duration,
+
+    /// Number of tokens each of the `hash_ids` of the trace stands for. Default: the block size
+    #[builder(default)]
+    pub trace_block_size: Option<usize>,
+}
+
+impl SimulatorConfig {
+    pub fn builder() -> SimulatorConfigBuilder {
+        SimulatorConfigBuilder::default()
+    }
+}
+
+/// Latency distribution, in milliseconds
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl LatencyStats {
+    fn new(mut samples: Vec<Duration>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort();
+        let ms = |d: Duration| d.as_micros() as f64 / 1000.0;
+        let percentile = |p: f64| ms(samples[((samples.len() - 1) as f64 * p).round() as usize]);
+        Self {
+            count: samples.len(),
+            mean: samples.iter().copied().map(ms).sum::<f64>() / samples.len() as f64,
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max: ms(samples[samples.len() - 1]),
+        }
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mean {:.2}  p50 {:.2}  p90 {:.2}  p99 {:.2}  max {:.2}",
+            self.mean, self.p50, self.p90, self.p99, self.max
+        )
+    }
+}
+
+/// What a simulated worker was routed, and how much of it was cached
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkerReport {
+    pub worker_id: WorkerId,
+    pub requests: usize,
+    pub isl_blocks: usize,
+    pub overlap_blocks: usize,
+}
+
+impl WorkerReport {
+    /// Fraction of the prompt blocks routed to the worker that it had cached
+    pub fn hit_rate(&self) -> f64 {
+        ratio(self.overlap_blocks, self.isl_blocks)
+    }
+}
+
+/// Outcome of a simulation
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimulationReport {
+    pub requests: usize,
+    pub completed: usize,
+    /// Virtual time until the last request completed, in seconds
+    pub duration_secs: f64,
+    pub ttft_ms: LatencyStats,
+    pub itl_ms: LatencyStats,
+    pub workers: Vec<WorkerReport>,
+}
+
+impl SimulationReport {
+    /// Fraction of the prompt blocks that were cached on the worker they were routed to
+    pub fn hit_rate(&self) -> f64 {
+        ratio(
+            self.workers.iter().map(|w| w.overlap_blocks).sum(),
+            self.workers.iter().map(|w| w.isl_blocks).sum(),
+        )
+    }
+
+    /// Requests of the busiest worker over the mean requests per worker, 1.0 is a perfect balance
+    pub fn load_imbalance(&self) -> f64 {
+        let max = self.workers.iter().map(|w| w.requests).max().unwrap_or(0);
+        let mean = ratio(self.requests, self.workers.len());
+        if mean == 0.0 {
+            return 0.0;
+        }
+        max as f64 / mean
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "requests: {} ({} completed) in {:.3}s",
+            self.requests, self.completed, self.duration_secs
+        )?;
+        writeln!(f, "TTFT (ms): {}", self.ttft_ms)?;
+        writeln!(f, "ITL (ms):  {}", self.itl_ms)?;
+        writeln!(f, "KV hit rate: {:.1}%", self.hit_rate() * 100.0)?;
+        writeln!(
+            f,
+            "load imbalance (max / mean requests): {:.2}",
+            self.load_imbalance()
+        )?;
+        writeln!(f, "worker  requests  isl blocks  hit rate")?;
+        for worker in &self.workers {
+            writeln!(
+                f,
+                "{:>6}  {:>8}  {:>10}  {:>7.1}%",
+                worker.worker_id,
+                worker.requests,
+                worker.isl_blocks,
+                worker.hit_rate() * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        return 0.0;
+    }
+    numerator as f64 / denominator as f64
+}
+
+/// Wraps the selector under test to notice when it waits for capacity, which only frees up as
+/// the simulator advances the virtual clock
+struct SimulatedSelector {
+    inner: Box<dyn WorkerSelector + Send + Sync>,
+    busy: Arc<Notify>,
+}
+
+impl WorkerSelector for SimulatedSelector {
+    fn select_worker(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &SchedulingRequest,
+        block_size: usize,
+    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+        let selection = self.inner.select_worker(workers, request, block_size);
+        if matches!(selection, Err(KvSchedulerError::AllWorkersBusy)) {
+            self.busy.notify_one();
+        }
+        selection
+    }
+}
+
+struct SimulatedWorker {
+    scheduler: SteppedScheduler,
+    kv_event_rx: mpsc::UnboundedReceiver<KvCacheEvent>,
+    /// The forward pass in progress, and the KV events it produced
+    in_flight: Option<(ForwardPassOutput, Vec<KvCacheEvent>)>,
+    /// When the worker finishes its forward pass in progress, or starts a new one. None if idle.
+    next_step: Option<Duration>,
+}
+
+struct RequestTimes {
+    arrival: Duration,
+    last_token: Option<Duration>,
+}
+
+/// The simulated workers, and the virtual clock they run on
+struct Cluster {
+    workers: Vec<SimulatedWorker>,
+    requests: HashMap<Uuid, RequestTimes>,
+    completed: usize,
+    ttft: Vec<Duration>,
+    itl: Vec<Duration>,
+    now: Duration,
+    next_metrics: Duration,
+    metrics_interval: Duration,
+    endpoints_tx: watch::Sender<ProcessedEndpoints>,
+    event_tx: mpsc::Sender<RouterEvent>,
+}
+
+impl Cluster {
+    fn endpoints(&self) -> ProcessedEndpoints {
+        ProcessedEndpoints::new(
+            self.workers
+                .iter()
+                .enumerate()
+                .map(|(worker_id, worker)| Endpoint {
+                    name: format!("worker-{worker_id}"),
+                    subject: format!("simulated-worker-{worker_id:x}"),
+                    data: worker.scheduler.forward_pass_metrics(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Advance the virtual clock to `until`, or until all the workers are idle, stepping the
+    /// workers and publishing their metrics on the way
+    async fn advance(&mut self, until: Option<Duration>) -> anyhow::Result<()> {
+        let until = until.map(|until| until.max(self.now));
+        loop {
+            let next_step = self
+                .workers
+                .iter()
+                .enumerate()
+                .filter_map(|(i, worker)| worker.next_step.map(|t| (t, i)))
+                .min();
+            let next = match (next_step, until) {
+                (Some((t, _)), Some(until)) => t.min(until),
+                (Some((t, _)), None) => t,
+                (None, Some(until)) => until,
+                (None, None) => return Ok(()),
+            };
+
+            if self.next_metrics <= next {
+                self.now = self.next_metrics;
+                self.next_metrics += self.metrics_interval;
+                self.endpoints_tx.send_replace(self.endpoints());
+                // Let the scheduler pick up the metrics before the next request
+                tokio::task::yield_now().await;
+                continue;
+            }
+
+            match next_step {
+                Some((t, i)) if until.is_none_or(|until| t <= until) => {
+                    self.now = t;
+                    self.step_worker(i).await?;
+                }
+                _ => {
+                    self.now = next;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Finish the forward pass in progress on a worker, and start the next one
+    async fn step_worker(&mut self, worker_id: usize) -> anyhow::Result<()> {
+        if let Some((pass, events)) = self.workers[worker_id].in_flight.take() {
+            self.record_tokens(&pass);
+            for event in events {
+                self.event_tx
+                    .send(RouterEvent::new(worker_id as WorkerId, event))
+                    .await
+                    .map_err(|_| anyhow::anyhow!("KV indexer shut down"))?;
+            }
+        }
+
+        let now = self.now;
+        let worker = &mut self.workers[worker_id];
+        if worker.scheduler.is_idle() {
+            worker.next_step = None;
+            return Ok(());
+        }
+
+        let pass = worker.scheduler.step();
+        if pass.generated.is_empty() && worker.scheduler.running_count() == 0 {
+            anyhow::bail!(
+                "worker {worker_id} cannot schedule its waiting requests, they need more KV blocks or batched tokens than it has"
+            );
+        }
+        let mut events = Vec::new();
+        while let Ok(event) = worker.kv_event_rx.try_recv() {
+            events.push(event);
+        }
+        worker.next_step = Some(now + pass.duration.max(MIN_FORWARD_PASS));
+        worker.in_flight = Some((pass, events));
+        Ok(())
+    }
+
+    fn record_tokens(&mut self, pass: &ForwardPassOutput) {
+        for uuid in &pass.generated {
+            let Some(times) = self.requests.get_mut(uuid) else {
+                continue;
+            };
+            match times.last_token {
+                None => self.ttft.push(self.now - times.arrival),
+                Some(last_token) => self.itl.push(self.now - last_token),
+            }
+            times.last_token = Some(self.now);
+        }
+        for uuid in &pass.completed {
+            if self.requests.remove(uuid).is_some() {
+                self.completed += 1;
+            }
+        }
+    }
+
+    fn dispatch(
+        &mut self,
+        worker_id: usize,
+        tokens: Vec<Token>,
+        max_output_tokens: usize,
+        arrival: Duration,
+    ) -> anyhow::Result<()> {
+        let now = self.now;
+        let worker = self
+            .workers
+            .get_mut(worker_id)
+            .with_context(|| format!("Routed to unknown worker {worker_id}"))?;
+        let uuid = worker.scheduler.receive(DirectRequest {
+            tokens,
+            max_output_tokens,
+            uuid: None,
+        });
+        if worker.next_step.is_none() {
+            worker.next_step = Some(now);
+        }
+        self.requests.insert(
+            uuid,
+            RequestTimes {
+                arrival,
+                last_token: None,
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Replays traces against simulated workers, routing through the KV router
+pub struct KvRoutingSimulator {
+    config: SimulatorConfig,
+    selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+}
+
+impl KvRoutingSimulator {
+    /// Create a simulator routing with `selector`, or with the [`DefaultWorkerSelector`] and the
+    /// weights of the config if None
+    pub fn new(
+        config: SimulatorConfig,
+        selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+    ) -> Self {
+        Self { config, selector }
+    }
+
+    /// Replay a trace ordered by arrival until every request completed
+    pub async fn run(self, trace: Vec<TraceRequest>) -> anyhow::Result<SimulationReport> {
+        let SimulatorConfig {
+            num_workers,
+            engine_args,
+            kv_router_config,
+            metrics_interval,
+            trace_block_size,
+        } = self.config;
+        anyhow::ensure!(num_workers > 0, "the simulation needs at least one worker");
+        anyhow::ensure!(
+            !metrics_interval.is_zero(),
+            "the metrics interval must be positive"
+        );
+        let block_size = engine_args.block_size;
+        let trace_block_size = trace_block_size.unwrap_or(block_size);
+
+        let workers = (0..num_workers)
+            .map(|_| {
+                let (kv_event_tx, kv_event_rx) = mpsc::unbounded_channel();
+                SimulatedWorker {
+                    scheduler: SteppedScheduler::new(engine_args.clone(), Some(kv_event_tx)),
+                    kv_event_rx,
+                    in_flight: None,
+                    next_step: None,
+                }
+            })
+            .collect();
+
+        let cancel = CancellationToken::new();
+        let indexer = KvIndexer::new(cancel.clone(), block_size);
+
+        let (endpoints_tx, endpoints_rx) = watch::channel(ProcessedEndpoints::default());
+        let mut cluster = Cluster {
+            workers,
+            requests: HashMap::new(),
+            completed: 0,
+            ttft: Vec::new(),
+            itl: Vec::new(),
+            now: Duration::ZERO,
+            next_metrics: Duration::ZERO,
+            metrics_interval,
+            endpoints_tx,
+            event_tx: indexer.event_sender(),
+        };
+        cluster.endpoints_tx.send_replace(cluster.endpoints());
+
+        let inner: Box<dyn WorkerSelector + Send + Sync> = match self.selector {
+            Some(selector) => selector,
+            None => Box::new(DefaultWorkerSelector::new(Some(kv_router_config))),
+        };
+        let busy = Arc::new(Notify::new());
+        let selector = SimulatedSelector {
+            inner,
+            busy: busy.clone(),
+        };
+        let (hit_rate_tx, mut hit_rate_rx) = mpsc::unbounded_channel::<KVHitRateEvent>();
+        let scheduler = KvScheduler::start_with_event_sink(
+            block_size,
+            endpoints_rx,
+            Some(Box::new(selector)),
+            hit_rate_tx,
+        )
+        .await?;
+
+        let mut reports: Vec<WorkerReport> = (0..num_workers)
+            .map(|worker_id| WorkerReport {
+                worker_id: worker_id as WorkerId,
+                ..Default::default()
+            })
+            .collect();
+
+        let num_requests = trace.len();
+        for request in trace {
+            let arrival = Duration::from_millis(request.timestamp);
+            cluster.advance(Some(arrival)).await?;
+
+            let tokens = request.tokens(trace_block_size)?;
+            let block_hashes = compute_block_hash_for_seq(&tokens, block_size);
+            // Match against the KV events the workers published so far
+            indexer.flush().await?;
+            let overlap = indexer.find_matches(block_hashes.clone()).await?;
+
+            let schedule = scheduler.schedule(
+                overlap,
+                block_hashes,
+                tokens.len(),
+                0,
+                false,
+                HashSet::new(),
+            );
+            tokio::pin!(schedule);
+            let worker: WorkerWithDpRank = loop {
+                tokio::select! {
+                    biased;
+
+                    scheduled = &mut schedule => break scheduled?.0,
+
+                    _ = busy.notified() => {
+                        let next_metrics = cluster.next_metrics;
+                        cluster.advance(Some(next_metrics)).await?;
+                    }
+                }
+            };
+
+            while let Ok(event) = hit_rate_rx.try_recv() {
+                let Some(report) = reports.get_mut(event.worker_id as usize) else {
+                    continue;
+                };
+                report.requests += 1;
+                report.isl_blocks += event.isl_blocks;
+                report.overlap_blocks += event.overlap_blocks;
+            }
+
+            cluster.dispatch(
+                worker.worker_id as usize,
+                tokens,
+                request.output_length,
+                arrival,
+            )?;
+        }
+        cluster.advance(None).await?;
+        cancel.cancel();
+
+        Ok(SimulationReport {
+            requests: num_requests,
+            completed: cluster.completed,
+            duration_secs: cluster.now.as_secs_f64(),
+            ttft_ms: LatencyStats::new(cluster.ttft),
+            itl_ms: LatencyStats::new(cluster.itl),
+            workers: reports,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine_args() -> MockEngineArgs {
+        MockEngineArgs::builder()
+            .num_gpu_blocks(1024)
+            .block_size(16)
+            .build()
+            .unwrap()
+    }
+
+    /// Requests in groups of 4 sharing a prefix of 8 blocks, arriving 50ms apart
+    fn shared_prefix_trace(num_requests: u64) -> Vec<TraceRequest> {
+        (0..num_requests)
+            .map(|i| {
+                let prefix = (i % 4) as u32 * 100;
+                let mut hash_ids: Vec<u32> = (prefix..prefix + 8).collect();
+                hash_ids.push(1000 + i as u32);
+                TraceRequest {
+                    timestamp: i * 50,
+                    token_ids: None,
+                    hash_ids,
+                    input_length: None,
+                    output_length: 8,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_trace_request_tokens() {
+        let request: TraceRequest = serde_json::from_str(
+            r#"{"timestamp": 0, "hash_ids": [1, 2], "input_length": 6, "output_length": 4}"#,
+        )
+        .unwrap();
+        assert_eq!(request.tokens(4).unwrap(), vec![1, 1, 1, 1, 2, 2]);
+
+        let request: TraceRequest =
+            serde_json::from_str(r#"{"timestamp": 0, "token_ids": [7, 8, 9], "output_length": 4}"#)
+                .unwrap();
+        assert_eq!(request.tokens(4).unwrap(), vec![7, 8, 9]);
+
+        let request: TraceRequest =
+            serde_json::from_str(r#"{"timestamp": 0, "hash_ids": [1], "output_length": 0}"#)
+                .unwrap();
+        assert!(request.tokens(4).is_err());
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        let stats = LatencyStats::new((1..=100).map(Duration::from_millis).collect());
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.p50, 51.0);
+        assert_eq!(stats.p99, 99.0);
+        assert_eq!(stats.max, 100.0);
+        assert!((stats.mean - 50.5).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_simulation_reuses_shared_prefixes() {
+        let config = SimulatorConfig::builder()
+            .num_workers(2)
+            .engine_args(engine_args())
+            .build()
+            .unwrap();
+        let report = KvRoutingSimulator::new(config, None)
+            .run(shared_prefix_trace(40))
+            .await
+            .unwrap();
+
+        assert_eq!(report.requests, 40);
+        assert_eq!(report.completed, 40);
+        assert_eq!(report.ttft_ms.count, 40);
+        assert_eq!(report.itl_ms.count, 40 * 7);
+        assert_eq!(report.workers.iter().map(|w| w.requests).sum::<usize>(), 40);
+        // Once the first request of each group is cached, the others reuse its prefix
+        assert!(
+            report.hit_rate() > 0.5,
+            "hit rate {} with shared prefixes",
+            report.hit_rate()
+        );
+    }
+
+    /// Always picks the first worker, ignoring the cache
+    struct FirstWorkerSelector;
+
+    impl WorkerSelector for FirstWorkerSelector {
+        fn select_worker(
+            &self,
+            _workers: &ProcessedEndpoints,
+            request: &SchedulingRequest,
+            block_size: usize,
+        ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+            let worker = WorkerWithDpRank::from(0);
+            Ok(WorkerSelectionResult {
+                worker,
+                required_blocks: request.isl_tokens.div_ceil(block_size) as u64,
+                overlap_blocks: request.overlap.scores.get(&worker).copied().unwrap_or(0) as usize,
+                decision: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_simulation_with_custom_selector() {
+        let config = SimulatorConfig::builder()
+            .num_workers(3)
+            .engine_args(engine_args())
+            .build()
+            .unwrap();
+        let report = KvRoutingSimulator::new(config, Some(Box::new(FirstWorkerSelector)))
+            .run(shared_prefix_trace(12))
+            .await
+            .unwrap();
+
+        assert_eq!(report.completed, 12);
+        assert_eq!(report.workers[0].requests, 12);
+        assert_eq!(report.load_imbalance(), 3.0);
+    }
+}
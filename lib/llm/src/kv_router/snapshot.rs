@@ -0,0 +1,249 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Persistence of the KV router's view of the worker caches across restarts.
+//!
+//! A snapshot directory holds the last snapshot of the [`RadixTree`](super::indexer::RadixTree)
+//! and a [`KvRecorder`] log of the events received since. The snapshot stores how many events
+//! of the log it already includes, so a restarted router loads the snapshot and replays only
+//! the rest of the log.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::kv_router::indexer::{KvIndexer, RouterEvent};
+use crate::kv_router::recorder::KvRecorder;
+
+/// File name of the snapshot in the snapshot directory
+pub const SNAPSHOT_FILE: &str = "radix_tree.json";
+
+/// File name of the event log in the snapshot directory
+pub const EVENTS_FILE: &str = "kv_events.jsonl";
+
+/// A point in time copy of the indexer, as the events that rebuild it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RadixTreeSnapshot {
+    /// The KV block size of the indexer. A snapshot is not valid for another block size.
+    pub kv_block_size: usize,
+
+    /// Number of events at the start of the event log that this snapshot includes
+    pub recorded_events: usize,
+
+    /// Events rebuilding the tree, see [`RadixTree::dump_events`](super::indexer::RadixTree::dump_events)
+    pub events: Vec<RouterEvent>,
+}
+
+impl RadixTreeSnapshot {
+    /// Load a snapshot, `None` if there isn't one yet
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read(path)
+            .with_context(|| format!("Failed reading KV router snapshot {}", path.display()))?;
+        let snapshot = serde_json::from_slice(&contents)
+            .with_context(|| format!("Invalid KV router snapshot {}", path.display()))?;
+        Ok(Some(snapshot))
+    }
+
+    /// Save the snapshot. The previous snapshot is replaced atomically, so a crash never
+    /// leaves a partial one behind.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(self)?)
+            .with_context(|| format!("Failed writing KV router snapshot {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed replacing KV router snapshot {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Records the events applied to a [`KvIndexer`] and periodically snapshots it, so that a
+/// restarted router can restore it with [`KvSnapshotter::restore`].
+pub struct KvSnapshotter {
+    dir: PathBuf,
+    kv_block_size: usize,
+    recorder: KvRecorder,
+    recorded_events: usize,
+}
+
+impl KvSnapshotter {
+    /// Restore the snapshot in `dir` and the events recorded after it into `indexer`, then
+    /// start a new snapshot and event log. Creates `dir` if needed.
+    pub async fn restore(
+        dir: PathBuf,
+        indexer: &KvIndexer,
+        cancel: CancellationToken,
+    ) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed creating KV router snapshot dir {}", dir.display()))?;
+        let kv_block_size = indexer.block_size();
+        let snapshot_path = dir.join(SNAPSHOT_FILE);
+        let events_path = dir.join(EVENTS_FILE);
+        let event_tx = indexer.event_sender();
+
+        match RadixTreeSnapshot::load(&snapshot_path)? {
+            Some(snapshot) if snapshot.kv_block_size != kv_block_size => {
+                tracing::warn!(
+                    snapshot_block_size = snapshot.kv_block_size,
+                    kv_block_size,
+                    "KV router snapshot has a different block size, starting cold"
+                );
+            }
+            Some(snapshot) => {
+                let restored = snapshot.events.len();
+                for event in snapshot.events {
+                    event_tx.send(event).await?;
+                }
+                let replayed = if events_path.exists() {
+                    KvRecorder::send_events_after(&events_path, &event_tx, snapshot.recorded_events)
+                        .await?
+                } else {
+                    0
+                };
+                tracing::info!(
+                    restored,
+                    replayed,
+                    "Restored KV router state from {}",
+                    dir.display()
+                );
+            }
+            None => {}
+        }
+
+        // Save the restored state before the new event log replaces the old one
+        RadixTreeSnapshot {
+            kv_block_size,
+            recorded_events: 0,
+            events: indexer.dump_events().await?,
+        }
+        .save(&snapshot_path)?;
+        let recorder = KvRecorder::new(cancel.child_token(), &events_path, None, None, None)
+            .await
+            .with_context(|| format!("Failed creating {}", events_path.display()))?;
+
+        Ok(KvSnapshotter {
+            dir,
+            kv_block_size,
+            recorder,
+            recorded_events: 0,
+        })
+    }
+
+    /// Record an event that is also sent to the indexer
+    pub async fn record(&mut self, event: RouterEvent) {
+        if let Err(err) = self.recorder.event_sender().send(event).await {
+            tracing::warn!(%err, "KV event recorder is closed");
+            return;
+        }
+        self.recorded_events += 1;
+    }
+
+    /// Snapshot the indexer. Every event recorded so far must already be sent to the indexer.
+    pub async fn snapshot(&mut self, indexer: &KvIndexer) -> anyhow::Result<()> {
+        let snapshot = RadixTreeSnapshot {
+            kv_block_size: self.kv_block_size,
+            recorded_events: self.recorded_events,
+            events: indexer.dump_events().await?,
+        };
+        let path = self.dir.join(SNAPSHOT_FILE);
+        tokio::task::spawn_blocking(move || snapshot.save(&path)).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv_router::indexer::{compute_block_hash_for_seq, KvIndexerInterface};
+    use crate::kv_router::protocols::*;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn store_event(worker_id: i64, tokens: &[u32], kv_block_size: usize) -> RouterEvent {
+        let blocks = compute_block_hash_for_seq(tokens, kv_block_size)
+            .into_iter()
+            .enumerate()
+            .map(|(i, tokens_hash)| KvCacheStoredBlockData {
+                block_hash: ExternalSequenceBlockHash(tokens[0] as u64 * 100 + i as u64),
+                tokens_hash,
+            })
+            .collect();
+        RouterEvent::new(
+            worker_id,
+            KvCacheEvent {
+                event_id: 0,
+                data: KvCacheEventData::Stored(KvCacheStoreData {
+                    parent_hash: None,
+                    blocks,
+                    lora_id: 0,
+                }),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_restore_snapshot_and_replay() {
+        let dir = tempdir().unwrap();
+        let kv_block_size = 4;
+        let first: Vec<u32> = (1..=8).collect();
+        let second: Vec<u32> = (20..=27).collect();
+
+        // A router sees one event before and one after its last snapshot, then stops
+        {
+            let cancel = CancellationToken::new();
+            let indexer = KvIndexer::new(cancel.clone(), kv_block_size);
+            let mut snapshotter =
+                KvSnapshotter::restore(dir.path().to_path_buf(), &indexer, cancel.clone())
+                    .await
+                    .unwrap();
+            for (event, take_snapshot) in [
+                (store_event(1, &first, kv_block_size), true),
+                (store_event(2, &second, kv_block_size), false),
+            ] {
+                snapshotter.record(event.clone()).await;
+                indexer.event_sender().send(event).await.unwrap();
+                if take_snapshot {
+                    snapshotter.snapshot(&indexer).await.unwrap();
+                }
+            }
+            cancel.cancel();
+            // Let the recorder flush
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        // A new router is warm for both
+        let cancel = CancellationToken::new();
+        let indexer = KvIndexer::new(cancel.clone(), kv_block_size);
+        let _snapshotter =
+            KvSnapshotter::restore(dir.path().to_path_buf(), &indexer, cancel.clone())
+                .await
+                .unwrap();
+        let scores = indexer.find_matches_for_request(&first).await.unwrap();
+        assert_eq!(scores.scores.get(&WorkerWithDpRank::from(1)), Some(&2));
+        let scores = indexer.find_matches_for_request(&second).await.unwrap();
+        assert_eq!(scores.scores.get(&WorkerWithDpRank::from(2)), Some(&2));
+
+        // Its own snapshot includes everything, the old event log was replaced
+        let snapshot = RadixTreeSnapshot::load(&dir.path().join(SNAPSHOT_FILE))
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.recorded_events, 0);
+        assert_eq!(snapshot.events.len(), 4);
+        cancel.cancel();
+    }
+}
@@ -16,11 +16,13 @@ pub mod disagg_router;
 pub mod discovery;
 pub mod engines;
 pub mod gguf;
+pub mod guided_decoding;
 pub mod http;
 pub mod hub;
 // pub mod key_value_store;
 pub mod kv_router;
 pub mod local_model;
+pub mod migration;
 pub mod mocker;
 pub mod model_card;
 pub mod model_type;
@@ -79,6 +79,18 @@ impl LocalModel {
         self.card.kv_cache_block_size = block_size;
     }
 
+    /// Select the tool call parser by name. The preprocessor uses it to turn the model's
+    /// tool call output into OpenAI `tool_calls`.
+    pub fn set_tool_call_parser(&mut self, parser: String) {
+        self.card.tool_call_parser = Some(parser);
+    }
+
+    /// Declare that the engine constrains sampling to `guided_grammar` requests, which the
+    /// preprocessor rejects otherwise.
+    pub fn set_enforces_guided_grammar(&mut self, enforces_guided_grammar: bool) {
+        self.card.enforces_guided_grammar = enforces_guided_grammar;
+    }
+
     /// Make an LLM ready for use:
     /// - Download it from Hugging Face (and NGC in future) if necessary
     /// - Resolve the path
@@ -149,9 +161,11 @@ impl LocalModel {
         self.ensure_unique(endpoint.component(), self.display_name())
             .await?;
 
-        // Store model config files in NATS object store
-        let nats_client = endpoint.drt().nats_client();
-        self.card.move_to_nats(nats_client.clone()).await?;
+        // Store model config files in NATS object store. Local runtimes have no NATS, their
+        // processes share the disk of the host.
+        if let Some(nats_client) = endpoint.drt().try_nats_client() {
+            self.card.move_to_nats(nats_client).await?;
+        }
 
         // Publish the Model Deployment Card to etcd
         let kvstore: Box<dyn KeyValueStore> = Box::new(EtcdStorage::new(etcd_client.clone()));
@@ -0,0 +1,305 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Migration of requests to another worker in the middle of their response stream
+//!
+//! Engines finish every response stream with a [`FinishReason`]. A stream which ends without one,
+//! and without an error, was cut off, most likely because its worker went away. [`Migration`]
+//! then sends the request again, through the router, with the tokens generated so far appended to
+//! the prompt and the token limits reduced by their count. The responses of the new worker
+//! continue the original stream, the client does not see the break.
+//!
+//! [`FinishReason`]: crate::protocols::common::FinishReason
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_stream::stream;
+use futures::StreamExt;
+
+use dynamo_runtime::{
+    pipeline::{
+        async_trait, AsyncEngineContextProvider, ManyOut, Operator, ResponseStream,
+        ServerStreamingEngine, SingleIn,
+    },
+    protocols::annotated::Annotated,
+};
+
+use crate::{
+    kv_router::ANNOTATION_ROUTING_DECISION,
+    protocols::{
+        common::llm_backend::{LLMEngineOutput, PreprocessedRequest},
+        TokenIdType,
+    },
+};
+
+/// Moves the requests whose worker went away to another worker
+pub struct Migration {
+    /// How many times a request may be migrated
+    migration_limit: u32,
+}
+
+impl Migration {
+    pub fn new(migration_limit: u32) -> Arc<Self> {
+        Arc::new(Self { migration_limit })
+    }
+}
+
+/// The request continuing `request` after its worker generated `generated`, or `None` if
+/// `generated` already used up its token budget.
+fn continuation(
+    request: &PreprocessedRequest,
+    generated: &[TokenIdType],
+) -> Option<PreprocessedRequest> {
+    let count = generated.len() as u32;
+    let mut request = request.clone();
+    request.token_ids.extend_from_slice(generated);
+    if let Some(max_tokens) = request.stop_conditions.max_tokens {
+        if max_tokens <= count {
+            return None;
+        }
+        request.stop_conditions.max_tokens = Some(max_tokens - count);
+    }
+    if let Some(min_tokens) = request.stop_conditions.min_tokens {
+        request.stop_conditions.min_tokens = Some(min_tokens.saturating_sub(count));
+    }
+    // the router places the continuation afresh
+    request.estimated_prefix_hit_num_blocks = None;
+    request.dp_rank = None;
+    // the client already got the routing decision of the first worker
+    request
+        .annotations
+        .retain(|annotation| annotation != ANNOTATION_ROUTING_DECISION);
+    Some(request)
+}
+
+#[async_trait]
+impl
+    Operator<
+        SingleIn<PreprocessedRequest>,
+        ManyOut<Annotated<LLMEngineOutput>>,
+        SingleIn<PreprocessedRequest>,
+        ManyOut<Annotated<LLMEngineOutput>>,
+    > for Migration
+{
+    async fn generate(
+        &self,
+        request: SingleIn<PreprocessedRequest>,
+        next: ServerStreamingEngine<PreprocessedRequest, Annotated<LLMEngineOutput>>,
+    ) -> Result<ManyOut<Annotated<LLMEngineOutput>>> {
+        if self.migration_limit == 0 {
+            return next.generate(request).await;
+        }
+
+        let (request, context) = request.into_parts();
+        let mut responses = next.generate(context.fork(request.clone())).await?;
+        let engine_ctx = responses.context();
+        let migration_limit = self.migration_limit;
+
+        let stream_ctx = engine_ctx.clone();
+        let output = stream! {
+            let mut generated: Vec<TokenIdType> = Vec::new();
+            let mut migrations = 0;
+            'responses: loop {
+                let mut finished = false;
+                while let Some(response) = responses.next().await {
+                    if let Some(data) = response.data.as_ref() {
+                        generated.extend_from_slice(&data.token_ids);
+                        finished |= data.finish_reason.is_some();
+                    }
+                    finished |= response.is_error();
+                    yield response;
+                }
+                if finished || stream_ctx.is_stopped() {
+                    break;
+                }
+
+                // the stream was cut off, continue it on another worker
+                loop {
+                    let Some(next_request) = continuation(&request, &generated) else {
+                        break 'responses;
+                    };
+                    if migrations >= migration_limit {
+                        tracing::warn!(
+                            request_id = stream_ctx.id(),
+                            migrations,
+                            "response stream ended before the request finished, migration limit reached"
+                        );
+                        break 'responses;
+                    }
+                    migrations += 1;
+                    tracing::info!(
+                        request_id = stream_ctx.id(),
+                        migrations,
+                        generated = generated.len(),
+                        "response stream ended before the request finished, migrating the request"
+                    );
+                    match next.generate(context.fork(next_request)).await {
+                        Ok(stream) => {
+                            responses = stream;
+                            continue 'responses;
+                        }
+                        Err(err) => {
+                            tracing::warn!(
+                                request_id = stream_ctx.id(),
+                                error = format!("{err:#}"),
+                                "failed to migrate request"
+                            );
+                        }
+                    }
+                }
+            }
+        };
+
+        Ok(ResponseStream::new(Box::pin(output), engine_ctx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use dynamo_runtime::pipeline::{
+        AsyncEngine, Context, Error, ServiceBackend, ServiceFrontend, Source,
+    };
+
+    use crate::protocols::common::{FinishReason, StopConditions};
+
+    fn delta(token_id: TokenIdType) -> Annotated<LLMEngineOutput> {
+        Annotated::from_data(LLMEngineOutput {
+            token_ids: vec![token_id],
+            tokens: None,
+            text: None,
+            cum_log_probs: None,
+            log_probs: None,
+            top_logprobs: None,
+            finish_reason: None,
+            index: None,
+        })
+    }
+
+    /// Counts the tokens after the prompt, and dies after `dies_after` of them on its first
+    /// request. Records the requests it gets.
+    struct Worker {
+        dies_after: usize,
+        requests: Mutex<Vec<PreprocessedRequest>>,
+    }
+
+    #[async_trait]
+    impl AsyncEngine<SingleIn<PreprocessedRequest>, ManyOut<Annotated<LLMEngineOutput>>, Error>
+        for Worker
+    {
+        async fn generate(
+            &self,
+            request: SingleIn<PreprocessedRequest>,
+        ) -> Result<ManyOut<Annotated<LLMEngineOutput>>> {
+            let (request, context) = request.into_parts();
+            let first = {
+                let mut requests = self.requests.lock().unwrap();
+                requests.push(request.clone());
+                requests.len() == 1
+            };
+            let max_tokens = request.stop_conditions.max_tokens.unwrap() as usize;
+            let start = *request.token_ids.last().unwrap();
+            let mut outputs: Vec<_> = (1..=max_tokens as u32).map(|i| delta(start + i)).collect();
+            match first {
+                true => outputs.truncate(self.dies_after),
+                false => outputs.push(Annotated::from_data(LLMEngineOutput::length())),
+            }
+            Ok(ResponseStream::new(
+                Box::pin(futures::stream::iter(outputs)),
+                context.context(),
+            ))
+        }
+    }
+
+    async fn run(
+        migration_limit: u32,
+        dies_after: usize,
+    ) -> (Vec<Annotated<LLMEngineOutput>>, Vec<PreprocessedRequest>) {
+        let worker = Arc::new(Worker {
+            dies_after,
+            requests: Mutex::new(vec![]),
+        });
+        let operator = Migration::new(migration_limit).into_operator();
+        let frontend = ServiceFrontend::<
+            SingleIn<PreprocessedRequest>,
+            ManyOut<Annotated<LLMEngineOutput>>,
+        >::new();
+        let engine = frontend
+            .link(operator.forward_edge())
+            .unwrap()
+            .link(ServiceBackend::from_engine(worker.clone()))
+            .unwrap()
+            .link(operator.backward_edge())
+            .unwrap()
+            .link(frontend)
+            .unwrap();
+
+        let request = PreprocessedRequest::builder()
+            .token_ids(vec![10])
+            .stop_conditions(StopConditions {
+                max_tokens: Some(4),
+                min_tokens: Some(3),
+                ..Default::default()
+            })
+            .sampling_options(Default::default())
+            .annotations(vec![ANNOTATION_ROUTING_DECISION.to_string()])
+            .build()
+            .unwrap();
+        let outputs = engine
+            .generate(Context::new(request))
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        let requests = worker.requests.lock().unwrap().clone();
+        (outputs, requests)
+    }
+
+    fn tokens(outputs: &[Annotated<LLMEngineOutput>]) -> Vec<TokenIdType> {
+        outputs
+            .iter()
+            .filter_map(|output| output.data.as_ref())
+            .flat_map(|data| data.token_ids.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_migration_continues_the_stream() {
+        let (outputs, requests) = run(1, 2).await;
+        assert_eq!(tokens(&outputs), vec![11, 12, 13, 14]);
+        assert_eq!(
+            outputs.last().unwrap().data.as_ref().unwrap().finish_reason,
+            Some(FinishReason::Length)
+        );
+
+        // the continuation carries the generated tokens and the remaining budget
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].token_ids, vec![10, 11, 12]);
+        assert_eq!(requests[1].stop_conditions.max_tokens, Some(2));
+        assert_eq!(requests[1].stop_conditions.min_tokens, Some(1));
+        assert!(requests[0].has_annotation(ANNOTATION_ROUTING_DECISION));
+        assert!(!requests[1].has_annotation(ANNOTATION_ROUTING_DECISION));
+    }
+
+    #[tokio::test]
+    async fn test_migration_limit() {
+        let (outputs, requests) = run(0, 2).await;
+        assert_eq!(tokens(&outputs), vec![11, 12]);
+        assert_eq!(requests.len(), 1);
+    }
+}
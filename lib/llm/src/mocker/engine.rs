@@ -0,0 +1,333 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Mock Engine
+//! An [`AsyncEngine`] for preprocessed requests backed by the simulated [`Scheduler`].
+//!
+//! Requests are scheduled against the simulated KV cache and stream one token each time the
+//! scheduler generates one, so latency follows the simulated prefill and decode costs. The
+//! generated tokens cycle through the prompt, which keeps them inside the vocabulary of the
+//! model card the engine is served with.
+//!
+//! When served on a component, the engine publishes the KV events of its cache through a
+//! [`KvEventPublisher`], serves its stored blocks on the `kv_dump` endpoint, and its
+//! [`ForwardPassMetrics`] on the `load_metrics` endpoint, so a KV router in front of several
+//! mockers behaves as it would in front of real workers.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_stream::stream;
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+use tokio::time::{interval, Duration};
+use uuid::Uuid;
+
+use dynamo_runtime::component::Component;
+use dynamo_runtime::engine::{AsyncEngine, AsyncEngineContextProvider, ResponseStream};
+use dynamo_runtime::pipeline::{Error, ManyOut, SingleIn};
+use dynamo_runtime::protocols::annotated::Annotated;
+use dynamo_runtime::traits::DistributedRuntimeProvider;
+use dynamo_runtime::CancellationToken;
+
+use crate::backend::ExecutionContext;
+use crate::kv_router::protocols::{ForwardPassMetrics, KvCacheEvent};
+use crate::kv_router::publisher::{KvEventPublisher, WorkerMetricsPublisher};
+use crate::mocker::protocols::{DirectRequest, MockEngineArgs};
+use crate::mocker::scheduler::Scheduler;
+use crate::preprocessor::PreprocessedRequest;
+use crate::protocols::common::llm_backend::LLMEngineOutput;
+
+/// Number of tokens generated when the request does not set `max_tokens`
+const DEFAULT_MAX_TOKENS: usize = 128;
+
+/// How often the forward pass metrics are refreshed on the `load_metrics` endpoint
+const METRICS_PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A request the scheduler is generating tokens for
+struct ActiveRequest {
+    prompt: Vec<u32>,
+    max_tokens: usize,
+    generated: usize,
+    tx: mpsc::UnboundedSender<Annotated<LLMEngineOutput>>,
+}
+
+type ActiveRequests = Arc<Mutex<HashMap<Uuid, ActiveRequest>>>;
+
+pub struct MockEngine {
+    scheduler: Scheduler,
+    active: ActiveRequests,
+}
+
+impl MockEngine {
+    /// Start the simulated scheduler. It runs until `cancel_token` is cancelled.
+    ///
+    /// KV cache events are sent on `kv_event_tx` if provided.
+    pub fn new(
+        args: MockEngineArgs,
+        kv_event_tx: Option<mpsc::UnboundedSender<KvCacheEvent>>,
+        cancel_token: CancellationToken,
+    ) -> Self {
+        let (output_tx, mut output_rx) = mpsc::unbounded_channel::<Uuid>();
+        let scheduler = Scheduler::new(
+            args,
+            Some(output_tx),
+            kv_event_tx,
+            Some(cancel_token.clone()),
+        );
+
+        let active: ActiveRequests = Arc::new(Mutex::new(HashMap::new()));
+        let active_clone = active.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = cancel_token.cancelled() => {
+                        break;
+                    }
+                    uuid = output_rx.recv() => {
+                        let Some(uuid) = uuid else {
+                            break;
+                        };
+                        emit_token(&active_clone, uuid);
+                    }
+                }
+            }
+        });
+
+        MockEngine { scheduler, active }
+    }
+
+    /// Current load of the simulated engine
+    pub async fn forward_pass_metrics(&self) -> ForwardPassMetrics {
+        self.scheduler.get_forward_pass_metrics().await
+    }
+}
+
+/// Stream the next token of a request, and finish it once it reached `max_tokens`.
+fn emit_token(active: &ActiveRequests, uuid: Uuid) {
+    let mut active = active.lock().unwrap();
+    // Tokens of finished or cancelled requests, and tokens re-generated after a preemption
+    // beyond `max_tokens`, are not for anyone.
+    let Some(request) = active.get_mut(&uuid) else {
+        return;
+    };
+
+    let token = if request.prompt.is_empty() {
+        0
+    } else {
+        request.prompt[request.generated % request.prompt.len()]
+    };
+    request.generated += 1;
+
+    let sent = request.tx.send(Annotated::from_data(LLMEngineOutput {
+        token_ids: vec![token],
+        tokens: None,
+        text: None,
+        cum_log_probs: None,
+        log_probs: None,
+        top_logprobs: None,
+        finish_reason: None,
+        index: None,
+    }));
+    if request.generated >= request.max_tokens {
+        let _ = request
+            .tx
+            .send(Annotated::from_data(LLMEngineOutput::length()));
+        active.remove(&uuid);
+    } else if sent.is_err() {
+        // The response stream was dropped
+        active.remove(&uuid);
+    }
+}
+
+#[async_trait]
+impl AsyncEngine<SingleIn<PreprocessedRequest>, ManyOut<Annotated<LLMEngineOutput>>, Error>
+    for MockEngine
+{
+    async fn generate(
+        &self,
+        incoming_request: SingleIn<PreprocessedRequest>,
+    ) -> Result<ManyOut<Annotated<LLMEngineOutput>>, Error> {
+        let (request, context) = incoming_request.into_parts();
+        let ctx = context.context();
+
+        let max_tokens = request
+            .stop_conditions
+            .max_tokens
+            .map(|max_tokens| max_tokens as usize)
+            .unwrap_or(DEFAULT_MAX_TOKENS)
+            .max(1);
+
+        let uuid = Uuid::new_v4();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        self.active.lock().unwrap().insert(
+            uuid,
+            ActiveRequest {
+                prompt: request.token_ids.clone(),
+                max_tokens,
+                generated: 0,
+                tx,
+            },
+        );
+        self.scheduler
+            .receive(DirectRequest {
+                tokens: request.token_ids,
+                max_output_tokens: max_tokens,
+                uuid: Some(uuid),
+            })
+            .await;
+
+        let active = self.active.clone();
+        let stream_ctx = ctx.clone();
+        let output = stream! {
+            loop {
+                tokio::select! {
+                    _ = stream_ctx.stopped() => {
+                        active.lock().unwrap().remove(&uuid);
+                        yield Annotated::from_data(LLMEngineOutput::cancelled());
+                        break;
+                    }
+                    delta = rx.recv() => {
+                        let Some(delta) = delta else {
+                            break;
+                        };
+                        yield delta;
+                    }
+                }
+            }
+        };
+        Ok(ResponseStream::new(Box::pin(output), ctx))
+    }
+}
+
+/// Create the mocker engine.
+///
+/// With a component, the engine publishes its KV events to the KV router, and serves its
+/// stored blocks and forward pass metrics on the component's `kv_dump` and `load_metrics`
+/// endpoints. The component's service must already exist.
+pub async fn make_mocker_engine(
+    args: MockEngineArgs,
+    component: Option<Component>,
+    cancel_token: CancellationToken,
+) -> anyhow::Result<ExecutionContext> {
+    let Some(component) = component else {
+        return Ok(Arc::new(MockEngine::new(args, None, cancel_token)));
+    };
+
+    let worker_id = component
+        .drt()
+        .primary_lease()
+        .map(|lease| lease.id())
+        .ok_or_else(|| anyhow::anyhow!("The mocker needs etcd to publish KV events"))?;
+
+    let kv_publisher = KvEventPublisher::new(component.clone(), worker_id, args.block_size, None)?;
+    let (kv_event_tx, mut kv_event_rx) = mpsc::unbounded_channel::<KvCacheEvent>();
+    let engine = Arc::new(MockEngine::new(
+        args,
+        Some(kv_event_tx),
+        cancel_token.clone(),
+    ));
+
+    let dump_endpoint = kv_publisher.create_dump_endpoint(component.clone());
+    tokio::spawn(async move {
+        if let Err(err) = dump_endpoint.await {
+            tracing::error!(%err, "Failed serving mocker KV blocks");
+        }
+    });
+
+    // Forward the KV events of the simulated cache to the router
+    tokio::spawn(async move {
+        while let Some(event) = kv_event_rx.recv().await {
+            if let Err(err) = kv_publisher.publish(event) {
+                tracing::error!(%err, "Failed to publish mocker KV event");
+                break;
+            }
+        }
+    });
+
+    let metrics_publisher = Arc::new(WorkerMetricsPublisher::new()?);
+    let endpoint_publisher = metrics_publisher.clone();
+    tokio::spawn(async move {
+        if let Err(err) = endpoint_publisher.create_endpoint(component).await {
+            tracing::error!(%err, "Failed serving mocker load metrics");
+        }
+    });
+
+    let metrics_engine = engine.clone();
+    tokio::spawn(async move {
+        let mut ticker = interval(METRICS_PUBLISH_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = cancel_token.cancelled() => {
+                    break;
+                }
+                _ = ticker.tick() => {
+                    let metrics = metrics_engine.forward_pass_metrics().await;
+                    if let Err(err) = metrics_publisher.publish(Arc::new(metrics)) {
+                        tracing::error!(%err, "Failed to publish mocker forward pass metrics");
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(engine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dynamo_runtime::pipeline::Context;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_mock_engine_streams_max_tokens() {
+        let cancel_token = CancellationToken::new();
+        let args = MockEngineArgs::builder()
+            .num_gpu_blocks(100)
+            .block_size(4)
+            .build()
+            .unwrap();
+        let (kv_event_tx, mut kv_event_rx) = mpsc::unbounded_channel();
+        let engine = MockEngine::new(args, Some(kv_event_tx), cancel_token.clone());
+
+        let prompt: Vec<u32> = (1..=10).collect();
+        let request = PreprocessedRequest::builder()
+            .token_ids(prompt.clone())
+            .stop_conditions(crate::protocols::common::StopConditions {
+                max_tokens: Some(5),
+                ..Default::default()
+            })
+            .sampling_options(Default::default())
+            .build()
+            .unwrap();
+
+        let stream = engine.generate(Context::new(request)).await.unwrap();
+        let outputs: Vec<_> = stream.filter_map(|r| async move { r.data }).collect().await;
+
+        let tokens: Vec<u32> = outputs.iter().flat_map(|o| o.token_ids.clone()).collect();
+        assert_eq!(tokens, prompt[..5]);
+        assert!(outputs.last().unwrap().finish_reason.is_some());
+
+        // The two full blocks of the prompt were stored in the simulated cache
+        let event = kv_event_rx.try_recv().unwrap();
+        assert!(matches!(
+            event.data,
+            crate::kv_router::protocols::KvCacheEventData::Stored(_)
+        ));
+
+        cancel_token.cancel();
+    }
+}
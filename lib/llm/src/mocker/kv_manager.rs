@@ -40,16 +40,35 @@
 //! is returned to the scheduler for preemption. Initial KV block allocations for new requests
 //! should not fail due to the watermark checking.
 //!
+//! ## KV Events
+//! When created with an event sink, the manager reports full blocks entering the cache as
+//! `Stored` events and full blocks leaving it as `Removed` events, in the format the KV router
+//! expects from a real engine. The token content of a block must be registered with
+//! [`KvManager::register_blocks`] before the signal that stores it is processed.
+//!
 //! ## NOTE
 //! For simplicity (or non-simplicity), reference counting is tracked manually instead of using
 //! the more idiomatic built-in Arc reference counter. This can be considered a shadow / mirror
 //! implementation of the main block manager.
 
+use crate::kv_router::indexer::compute_block_hash_for_seq;
+use crate::kv_router::protocols::{
+    ExternalSequenceBlockHash, KvCacheEvent, KvCacheEventData, KvCacheRemoveData, KvCacheStoreData,
+    KvCacheStoredBlockData, LocalBlockHash,
+};
 use crate::mocker::evictor::LRUEvictor;
-use crate::mocker::protocols::{MoveBlock, PrefillCost, UniqueBlock};
+use crate::mocker::protocols::{GlobalHash, MoveBlock, PrefillCost, UniqueBlock};
 use crate::mocker::sequence::ActiveSequence;
 use derive_getters::Getters;
 use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc;
+
+/// What the KV router needs to know about a full block to index it
+#[derive(Debug, Clone, Copy)]
+struct BlockMeta {
+    parent_hash: Option<GlobalHash>,
+    tokens_hash: LocalBlockHash,
+}
 
 #[derive(Getters)]
 pub struct KvManager {
@@ -64,10 +83,28 @@ pub struct KvManager {
     inactive_blocks: LRUEvictor<UniqueBlock>,
 
     all_blocks: HashSet<UniqueBlock>,
+
+    #[getter(skip)]
+    kv_event_tx: Option<mpsc::UnboundedSender<KvCacheEvent>>,
+
+    #[getter(skip)]
+    block_meta: HashMap<GlobalHash, BlockMeta>,
+
+    #[getter(skip)]
+    next_event_id: u64,
 }
 
 impl KvManager {
     pub fn new(max_capacity: usize, block_size: usize) -> Self {
+        Self::new_with_event_sink(max_capacity, block_size, None)
+    }
+
+    /// Create a KvManager that reports stored and removed full blocks on `kv_event_tx`
+    pub fn new_with_event_sink(
+        max_capacity: usize,
+        block_size: usize,
+        kv_event_tx: Option<mpsc::UnboundedSender<KvCacheEvent>>,
+    ) -> Self {
         let active_blocks = HashMap::new();
         let inactive_blocks = LRUEvictor::default();
         let all_blocks = HashSet::new();
@@ -78,9 +115,73 @@ impl KvManager {
             active_blocks,
             inactive_blocks,
             all_blocks,
+            kv_event_tx,
+            block_meta: HashMap::new(),
+            next_event_id: 0,
         }
     }
 
+    /// Record the parent and token hash of the full blocks of a sequence, so that storing
+    /// them can be published as KV events. Does nothing without an event sink.
+    pub fn register_blocks(&mut self, sequence: &ActiveSequence) {
+        if self.kv_event_tx.is_none() {
+            return;
+        }
+        for block in sequence.tokens().blocks() {
+            self.block_meta
+                .entry(block.sequence_hash())
+                .or_insert_with(|| BlockMeta {
+                    parent_hash: block.parent_sequence_hash(),
+                    tokens_hash: compute_block_hash_for_seq(block.tokens(), self.block_size)[0],
+                });
+        }
+    }
+
+    fn publish(&mut self, data: KvCacheEventData) {
+        let Some(tx) = &self.kv_event_tx else {
+            return;
+        };
+        let event = KvCacheEvent {
+            event_id: self.next_event_id,
+            data,
+        };
+        self.next_event_id += 1;
+        if tx.send(event).is_err() {
+            tracing::trace!("KV event receiver dropped");
+        }
+    }
+
+    /// Publish a full block entering the cache
+    fn publish_stored(&mut self, block: &UniqueBlock) {
+        let UniqueBlock::FullBlock(hash) = block else {
+            return;
+        };
+        let Some(meta) = self.block_meta.get(hash).copied() else {
+            return;
+        };
+        self.publish(KvCacheEventData::Stored(KvCacheStoreData {
+            parent_hash: meta.parent_hash.map(ExternalSequenceBlockHash),
+            blocks: vec![KvCacheStoredBlockData {
+                block_hash: ExternalSequenceBlockHash(*hash),
+                tokens_hash: meta.tokens_hash,
+            }],
+            lora_id: 0,
+        }));
+    }
+
+    /// Publish a full block leaving the cache
+    fn publish_removed(&mut self, block: &UniqueBlock) {
+        let UniqueBlock::FullBlock(hash) = block else {
+            return;
+        };
+        if self.block_meta.remove(hash).is_none() {
+            return;
+        }
+        self.publish(KvCacheEventData::Removed(KvCacheRemoveData {
+            block_hashes: vec![ExternalSequenceBlockHash(*hash)],
+        }));
+    }
+
     /// Process a MoveBlock instruction synchronously
     pub fn process(&mut self, event: &MoveBlock) -> bool {
         match event {
@@ -109,6 +210,7 @@ impl KvManager {
                         if let Some(evicted) = self.inactive_blocks.evict() {
                             // Remove evicted block from all_blocks
                             self.all_blocks.remove(&evicted);
+                            self.publish_removed(&evicted);
                         } else {
                             // Cannot evict block, meaning no free blocks left in inactive pool
                             // Send a signal, scheduler would expect to handle preemption upon receiving this
@@ -120,6 +222,7 @@ impl KvManager {
                     self.active_blocks.insert(hash.clone(), 1);
                     // Add to all_blocks as it's a new block
                     self.all_blocks.insert(hash.clone());
+                    self.publish_stored(hash);
                 }
             }
             MoveBlock::Destroy(hashes) => {
@@ -128,6 +231,7 @@ impl KvManager {
                     self.active_blocks.remove(hash).unwrap();
                     // Remove from all_blocks when destroyed
                     assert!(self.all_blocks.remove(hash));
+                    self.publish_removed(hash);
                 }
             }
             MoveBlock::Deref(hashes) => {
@@ -166,7 +270,8 @@ impl KvManager {
 
                 // Update all_blocks
                 assert!(self.all_blocks.remove(&uuid_block));
-                self.all_blocks.insert(hash_block);
+                self.all_blocks.insert(hash_block.clone());
+                self.publish_stored(&hash_block);
             }
         }
 
@@ -424,4 +529,49 @@ mod tests {
         // Check that the inactive_blocks is size 1 and contains only 5
         assert_inactive_blocks(&manager, 1, &[5]);
     }
+
+    #[test]
+    fn test_kv_events() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut manager = KvManager::new_with_event_sink(2, 4, Some(tx));
+
+        // Two full blocks and no partial block
+        let tokens: Vec<u32> = (0..8).collect();
+        let sequence = ActiveSequence::new(tokens.clone(), 1, Some(4), None);
+        manager.register_blocks(&sequence);
+        assert!(manager.process(sequence.creation_signal().as_ref().unwrap()));
+
+        let expected_tokens_hashes = compute_block_hash_for_seq(&tokens, 4);
+        let mut stored = Vec::new();
+        for expected_tokens_hash in expected_tokens_hashes {
+            let event = rx.try_recv().unwrap();
+            let KvCacheEventData::Stored(data) = event.data else {
+                panic!("Expected a stored event, got {:?}", event.data);
+            };
+            assert_eq!(data.parent_hash, stored.last().copied());
+            assert_eq!(data.blocks.len(), 1);
+            assert_eq!(data.blocks[0].tokens_hash, expected_tokens_hash);
+            stored.push(data.blocks[0].block_hash);
+        }
+        assert!(rx.try_recv().is_err());
+
+        // Freeing the sequence keeps its blocks cached, using new blocks evicts them
+        for signal in sequence.free_signal() {
+            manager.process(&signal);
+        }
+        assert!(rx.try_recv().is_err());
+        let blocks = vec![UniqueBlock::FullBlock(100), UniqueBlock::FullBlock(101)];
+        assert!(manager.process(&MoveBlock::Use(blocks, None)));
+
+        let mut removed = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            let KvCacheEventData::Removed(data) = event.data else {
+                panic!("Expected a removed event, got {:?}", event.data);
+            };
+            removed.extend(data.block_hashes);
+        }
+        removed.sort();
+        stored.sort();
+        assert_eq!(removed, stored);
+    }
 }
@@ -13,6 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -61,6 +62,53 @@ pub struct PrefillCost {
     pub prefill_compute: f64,
 }
 
+/// Configuration of the simulated engine
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
+#[serde(default)]
+pub struct MockEngineArgs {
+    /// Number of KV cache blocks on the simulated GPU
+    #[builder(default = "16384")]
+    pub num_gpu_blocks: usize,
+
+    /// Number of tokens in a KV cache block
+    #[builder(default = "64")]
+    pub block_size: usize,
+
+    /// Maximum number of new tokens prefilled in a single forward pass
+    #[builder(default = "8192")]
+    pub max_num_batched_tokens: usize,
+
+    /// Fraction of the KV cache kept free when admitting new requests
+    #[builder(default = "0.01")]
+    pub watermark: f64,
+
+    /// Prefill chunk size in tokens
+    #[builder(default = "256")]
+    pub chunk_size: usize,
+
+    /// Divides the simulated prefill time, 2.0 means prefill runs twice as fast
+    #[builder(default = "1.0")]
+    pub prefill_speedup_ratio: f64,
+
+    /// Divides the simulated decode time, 2.0 means decode runs twice as fast
+    #[builder(default = "1.0")]
+    pub decode_speedup_ratio: f64,
+}
+
+impl MockEngineArgs {
+    pub fn builder() -> MockEngineArgsBuilder {
+        MockEngineArgsBuilder::default()
+    }
+}
+
+impl Default for MockEngineArgs {
+    fn default() -> Self {
+        MockEngineArgs::builder()
+            .build()
+            .expect("All MockEngineArgs fields have defaults")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -20,6 +20,10 @@
 //! 2. Scheduling waiting requests against available KV cache resources
 //! 3. Simulating the execution of running requests with realistic timing
 //!
+//! The scheduling and simulation logic lives in the synchronous [`SteppedScheduler`], which the
+//! asynchronous [`Scheduler`] drives on wall-clock intervals. Offline tools can step it directly
+//! on a virtual clock instead.
+//!
 //! ## Scheduling Process
 //! The scheduler uses a watermark-based approach to determine if there's sufficient
 //! KV cache space for new requests. It also enforces a batched tokens budget to prevent
@@ -40,10 +44,10 @@
 //! ## NOTE
 //! The current prefill and decoding time simulations are not scientific at all and are WIP
 
-use crate::kv_router::protocols::ForwardPassMetrics;
+use crate::kv_router::protocols::{ForwardPassMetrics, KvCacheEvent};
 use crate::mocker::evictor::LRUEvictor;
 use crate::mocker::kv_manager::KvManager;
-use crate::mocker::protocols::DirectRequest;
+use crate::mocker::protocols::{DirectRequest, MockEngineArgs};
 use crate::mocker::protocols::{MoveBlock, PrefillCost, UniqueBlock};
 use crate::mocker::sequence::ActiveSequence;
 use std::collections::HashMap;
@@ -180,32 +184,255 @@ impl SchedulerState {
     }
 }
 
+/// Outcome of simulating a single forward pass of the engine
+#[derive(Debug, Default)]
+pub struct ForwardPassOutput {
+    /// The UUID of the owning request for every token generated in the pass
+    pub generated: Vec<Uuid>,
+    /// Requests that generated their last token in the pass
+    pub completed: Vec<Uuid>,
+    /// Simulated duration of the pass
+    pub duration: Duration,
+}
+
+/// Synchronous core of the [`Scheduler`], advancing the simulated engine one step at a time.
+///
+/// The [`Scheduler`] drives it on wall-clock intervals. It can also be driven directly on a
+/// virtual clock, which is how the offline KV routing simulator replays traces.
+pub struct SteppedScheduler {
+    state: SchedulerState,
+    kv_manager: KvManager,
+    args: MockEngineArgs,
+}
+
+impl SteppedScheduler {
+    /// Create a new SteppedScheduler, sending the KV cache events of the engine on `kv_event_tx`.
+    pub fn new(
+        args: MockEngineArgs,
+        kv_event_tx: Option<mpsc::UnboundedSender<KvCacheEvent>>,
+    ) -> Self {
+        let kv_manager =
+            KvManager::new_with_event_sink(args.num_gpu_blocks, args.block_size, kv_event_tx);
+
+        Self {
+            state: SchedulerState::default(),
+            kv_manager,
+            args,
+        }
+    }
+
+    /// Add a new request to the waiting queue, returning its UUID
+    pub fn receive(&mut self, request: DirectRequest) -> Uuid {
+        self.state.receive(request)
+    }
+
+    /// Move waiting requests to running until the KV cache or the token budget is exhausted
+    pub fn schedule(&mut self) {
+        let MockEngineArgs {
+            block_size,
+            chunk_size,
+            max_num_batched_tokens: token_capacity,
+            watermark,
+            ..
+        } = self.args;
+        let state = &mut self.state;
+        let kv_manager = &mut self.kv_manager;
+
+        // Process DirectRequests, converting them to ActiveSequence and scheduling them until we can't
+        // schedule anymore.
+        while let Some((uuid, request)) = state.next() {
+            let active_sequence = get_active_sequence(request, block_size, chunk_size);
+
+            // Calculate token budget using new_tokens from PrefillCost
+            let total_prefill_tokens = state.num_batched_tokens();
+            let tokens_budget = token_capacity.saturating_sub(total_prefill_tokens);
+
+            // Check if it can be scheduled
+            let Some(prefill_cost) =
+                kv_manager.try_schedule(&active_sequence, watermark, tokens_budget)
+            else {
+                state.make_ready(uuid, active_sequence);
+                break;
+            };
+
+            // Get creation signal and schedule the request
+            kv_manager.register_blocks(&active_sequence);
+            let signal = state.run(uuid, active_sequence);
+            kv_manager.process(&signal);
+            state.set_prefill_cost(uuid, Some(prefill_cost));
+        }
+    }
+
+    /// Simulate one forward pass (prefill + decode) of the running requests
+    pub fn simulate(&mut self) -> ForwardPassOutput {
+        let MockEngineArgs {
+            block_size,
+            prefill_speedup_ratio,
+            decode_speedup_ratio,
+            ..
+        } = self.args;
+        let state = &mut self.state;
+        let kv_manager = &mut self.kv_manager;
+        let mut output = ForwardPassOutput::default();
+
+        // Base time needed for decoding (assumed memory bound on KV cache)
+        let active_tokens = kv_manager.num_active_blocks() * block_size;
+        // TODO: 2 is a dummy / magic scaling factor
+        let decode_us = (active_tokens / 2) as f64 / decode_speedup_ratio;
+        let mut generation_time = Duration::from_micros(decode_us as u64);
+
+        // Process each running request
+        let uuids: Vec<Uuid> = state.running.keys().cloned().collect();
+        for uuid in uuids {
+            // Check if UUID is still in running_requests, if not skip this iteration
+            if !state.running.contains(&uuid) {
+                continue;
+            }
+
+            // Get prefill compute value first
+            let prefill_compute = state.get_prefill_compute(&uuid);
+
+            // Get the active sequence for this UUID
+            let sequence = state
+                .requests
+                .get_mut(&uuid)
+                .and_then(|req| {
+                    if let Request::Active(seq) = req {
+                        Some(seq)
+                    } else {
+                        None
+                    }
+                })
+                .expect("UUID in running_requests must have a corresponding active sequence");
+
+            // Generate token and get signals
+            let signals = sequence.generate();
+
+            // A promoted block is stored in the cache, it needs its metadata for the KV event
+            if signals
+                .iter()
+                .any(|signal| matches!(signal, MoveBlock::Promote(..)))
+            {
+                kv_manager.register_blocks(sequence);
+            }
+
+            // Accumulate sleep duration based on prefill_compute if available
+            // prefill compute = (cached_tokens + new_tokens) * new_tokens
+            let sleep_us = if let Some(compute) = prefill_compute {
+                // TODO: 1024 is a dummy / magic scaling factor
+                (compute / 1024.0 / prefill_speedup_ratio) as u64
+            } else {
+                0
+            };
+            generation_time += Duration::from_micros(sleep_us);
+
+            // Process all signals with the KvManager
+            // Handling of preemption on failure
+            if !process_signals(kv_manager, &signals) {
+                sequence.pop(); // revert the failed generation op
+
+                // free_signal derefs the preempted blocks
+                let Some(free_signal) = state.preempt() else {
+                    panic!("Failed to acquire signal to free KV blocks from preemption");
+                };
+
+                for signal in free_signal {
+                    kv_manager.process(&signal);
+                }
+                continue;
+            }
+
+            output.generated.push(uuid);
+
+            // Check if we're done after generating
+            if sequence.generated_tokens() >= sequence.max_output_tokens() {
+                state.complete(&uuid);
+                output.completed.push(uuid);
+                continue;
+            }
+
+            // Transition to decode (no prefill cost)
+            if sequence.generated_tokens() == 1 {
+                state.set_prefill_cost(uuid, None);
+            }
+        }
+
+        output.duration = generation_time;
+        output
+    }
+
+    /// Schedule waiting requests, then simulate one forward pass
+    pub fn step(&mut self) -> ForwardPassOutput {
+        self.schedule();
+        self.simulate()
+    }
+
+    /// Whether there are no waiting or running requests
+    pub fn is_idle(&self) -> bool {
+        self.state.requests.is_empty()
+    }
+
+    /// Get the count of waiting requests
+    pub fn waiting_count(&self) -> usize {
+        self.state.waiting.len()
+    }
+
+    /// Get the count of running requests
+    pub fn running_count(&self) -> usize {
+        self.state.running.len()
+    }
+
+    /// Get the current capacity of the KvManager
+    pub fn kv_usage_perc(&self) -> f64 {
+        self.kv_manager.current_capacity_perc()
+    }
+
+    /// Returns forward pass metrics for monitoring purposes
+    pub fn forward_pass_metrics(&self) -> ForwardPassMetrics {
+        // Get the active blocks and total capacity from KvManager
+        let active_blocks_count = self.kv_manager.active_blocks().len() as u64;
+        let total_capacity = self.kv_manager.max_capacity() as u64;
+
+        // Calculate GPU cache usage percentage
+        let gpu_cache_usage_perc = if total_capacity > 0 {
+            active_blocks_count as f32 / total_capacity as f32
+        } else {
+            0.0
+        };
+
+        ForwardPassMetrics {
+            data_parallel_rank: None, // Default for backwards compatibility
+            request_active_slots: self.state.running.len() as u64,
+            request_total_slots: 420, // Dummy value as specified
+            kv_active_blocks: active_blocks_count,
+            kv_total_blocks: total_capacity,
+            num_requests_waiting: self.state.waiting.len() as u64,
+            gpu_cache_usage_perc,
+            gpu_prefix_cache_hit_rate: 0.0, // Placeholder value as specified
+            loaded_lora_ids: vec![],        // The mocker does not simulate LoRA adapters
+        }
+    }
+}
+
 /// Manages scheduling of requests using KvManager resources
 #[derive(Clone)]
 pub struct Scheduler {
-    state: Arc<Mutex<SchedulerState>>,
-    kv_manager: Arc<Mutex<KvManager>>,
+    core: Arc<Mutex<SteppedScheduler>>,
     request_tx: mpsc::Sender<DirectRequest>,
 }
 
 impl Scheduler {
-    /// Create a new Scheduler with the given parameters
+    /// Create a new Scheduler with the given parameters.
+    ///
+    /// The UUID of a request is sent on `output_tx` for every token it generates, and the KV
+    /// cache events of the simulated engine on `kv_event_tx`.
     pub fn new(
-        kv_capacity: usize,
-        watermark: f64,
-        block_size: usize,
-        chunk_size: Option<usize>,
-        output_tx: Option<mpsc::Sender<Uuid>>,
+        args: MockEngineArgs,
+        output_tx: Option<mpsc::UnboundedSender<Uuid>>,
+        kv_event_tx: Option<mpsc::UnboundedSender<KvCacheEvent>>,
         cancellation_token: Option<CancellationToken>,
     ) -> Self {
-        // Create KvManager internally
-        let kv_manager = KvManager::new(kv_capacity, block_size);
-
-        let token_capacity: usize = 8192;
-        let state = Arc::new(Mutex::new(SchedulerState::default()));
-
-        let kv_manager = Arc::new(Mutex::new(kv_manager));
-        let chunk_size = chunk_size.unwrap_or(256);
+        let core = Arc::new(Mutex::new(SteppedScheduler::new(args, kv_event_tx)));
 
         // Create channel for request handling
         let (request_tx, mut request_rx) = mpsc::channel::<DirectRequest>(1024);
@@ -215,9 +442,7 @@ impl Scheduler {
         let token_clone = cancellation_token.clone();
 
         // Create a clone for the background task
-        let state_clone = state.clone();
-        let kv_manager_clone = kv_manager.clone();
-        let output_tx_clone = output_tx.clone();
+        let core_clone = core.clone();
 
         // Spawn main background task with cancellation token
         tokio::spawn(async move {
@@ -230,35 +455,12 @@ impl Scheduler {
 
                     // Enqueue new request
                     Some(request) = request_rx.recv() => {
-                        let mut state = state_clone.lock().await;
-                        state.receive(request);
+                        core_clone.lock().await.receive(request);
                     }
 
                     // Try Scheduling Requests
                     _ = schedule_interval.tick() => {
-                        let mut state_guard = state_clone.lock().await;
-                        let mut kv_manager_guard = kv_manager_clone.lock().await;
-
-                        // Process DirectRequests, converting them to ActiveSequence and scheduling them until we can't
-                        // schedule anymore.
-                        while let Some((uuid, request)) = state_guard.next() {
-                            let active_sequence = get_active_sequence(request, block_size, chunk_size);
-
-                            // Calculate token budget using new_tokens from PrefillCost
-                            let total_prefill_tokens = state_guard.num_batched_tokens();
-                            let tokens_budget = token_capacity.saturating_sub(total_prefill_tokens);
-
-                            // Check if it can be scheduled
-                            let Some(prefill_cost) = kv_manager_guard.try_schedule(&active_sequence, watermark, tokens_budget) else {
-                                state_guard.make_ready(uuid, active_sequence);
-                                break;
-                            };
-
-                            // Get creation signal and schedule the request
-                            let signal = state_guard.run(uuid, active_sequence);
-                            kv_manager_guard.process(&signal);
-                            state_guard.set_prefill_cost(uuid, Some(prefill_cost));
-                        }
+                        core_clone.lock().await.schedule();
                     }
 
                     // Check for cancellation
@@ -268,89 +470,25 @@ impl Scheduler {
 
                     // Simulate running requests (prefill + decode)
                     _ = simulate_interval.tick() => {
-                        let mut state_guard = state_clone.lock().await;
-                        let mut kv_manager_guard = kv_manager_clone.lock().await;
-
-                        // Base time needed for decoding (assumed memory bound on KV cache)
-                        let active_tokens = kv_manager_guard.num_active_blocks() * block_size;
-                        // TODO: 2 is a dummy / magic scaling factor
-                        let mut generation_time = Duration::from_micros((active_tokens / 2) as u64);
-
-                        // Process each running request
-                        let uuids: Vec<Uuid> = state_guard.running.keys().cloned().collect();
-                        for uuid in uuids {
-                            // Check if UUID is still in running_requests, if not skip this iteration
-                            if !state_guard.running.contains(&uuid) {
-                                continue;
-                            }
-
-                            // Get prefill compute value first
-                            let prefill_compute = state_guard.get_prefill_compute(&uuid);
-
-                            // Get the active sequence for this UUID
-                            let sequence = state_guard.requests.get_mut(&uuid)
-                                .and_then(|req| if let Request::Active(seq) = req { Some(seq) } else { None })
-                                .expect("UUID in running_requests must have a corresponding active sequence");
-
-                            // Generate token and get signals
-                            let signals = sequence.generate();
-
-                            // Accumulate sleep duration based on prefill_compute if available
-                            // prefill compute = (cached_tokens + new_tokens) * new_tokens
-                            let sleep_ms = if let Some(compute) = prefill_compute {
-                                // TODO: 1024 is a dummy / magic scaling factor
-                                (compute / 1024.0) as u64
-                            } else { 0 };
-                            generation_time += Duration::from_micros(sleep_ms);
-
-                            // Process all signals with the KvManager
-                            // Handling of preemption on failure
-                            if !process_signals(&mut kv_manager_guard, &signals) {
-                                sequence.pop();  // revert the failed generation op
-
-                                // free_signal derefs the preempted blocks
-                                let Some(free_signal) = state_guard.preempt() else {
-                                    panic!("Failed to acquire signal to free KV blocks from preemption");
-                                };
-
-                                for signal in free_signal {
-                                    kv_manager_guard.process(&signal);
-                                }
-                                continue;
-                            }
-
-                            // Send UUID notification for each generated token
-                            // TODO: hook this up to an AsyncEngine
-                            if let Some(tx) = &output_tx_clone {
-                                let _ = tx.try_send(uuid);
-                            }
-
-                            // Check if we're done after generating
-                            if sequence.generated_tokens() >= sequence.max_output_tokens() {
-                                state_guard.complete(&uuid);
-                                continue;
-                            }
+                        let output = core_clone.lock().await.simulate();
 
-                            // Transition to decode (no prefill cost)
-                            if sequence.generated_tokens() == 1 {
-                                state_guard.set_prefill_cost(uuid, None);
+                        // Send UUID notification for each generated token
+                        if let Some(tx) = &output_tx {
+                            for uuid in output.generated {
+                                let _ = tx.send(uuid);
                             }
                         }
 
                         // Sleep once for the accumulated duration
-                        if generation_time.as_millis() > 0 {
-                            tokio::time::sleep(generation_time).await;
+                        if output.duration.as_millis() > 0 {
+                            tokio::time::sleep(output.duration).await;
                         }
                     }
                 }
             }
         });
 
-        Self {
-            state,
-            kv_manager,
-            request_tx,
-        }
+        Self { core, request_tx }
     }
 
     /// Add a new request to the waiting queue
@@ -360,48 +498,22 @@ impl Scheduler {
 
     /// Get the count of waiting requests
     pub async fn waiting_count(&self) -> usize {
-        let state = self.state.lock().await;
-        state.waiting.len()
+        self.core.lock().await.waiting_count()
     }
 
     /// Get the count of running requests
     pub async fn running_count(&self) -> usize {
-        let state = self.state.lock().await;
-        state.running.len()
+        self.core.lock().await.running_count()
     }
 
     /// Get the current capacity of the KvManager
     pub async fn kv_usage_perc(&self) -> f64 {
-        let kv_manager = self.kv_manager.lock().await;
-        kv_manager.current_capacity_perc()
+        self.core.lock().await.kv_usage_perc()
     }
 
     /// Returns forward pass metrics for monitoring purposes
     pub async fn get_forward_pass_metrics(&self) -> ForwardPassMetrics {
-        let state = self.state.lock().await;
-        let kv_manager = self.kv_manager.lock().await;
-
-        // Get the active blocks and total capacity from KvManager
-        let active_blocks_count = kv_manager.active_blocks().len() as u64;
-        let total_capacity = kv_manager.max_capacity() as u64;
-
-        // Calculate GPU cache usage percentage
-        let gpu_cache_usage_perc = if total_capacity > 0 {
-            active_blocks_count as f32 / total_capacity as f32
-        } else {
-            0.0
-        };
-
-        ForwardPassMetrics {
-            data_parallel_rank: None, // Default for backwards compatibility
-            request_active_slots: state.running.len() as u64,
-            request_total_slots: 420, // Dummy value as specified
-            kv_active_blocks: active_blocks_count,
-            kv_total_blocks: total_capacity,
-            num_requests_waiting: state.waiting.len() as u64,
-            gpu_cache_usage_perc,
-            gpu_prefix_cache_hit_rate: 0.0, // Placeholder value as specified
-        }
+        self.core.lock().await.forward_pass_metrics()
     }
 }
 
@@ -430,12 +542,9 @@ fn get_active_sequence(request: Request, block_size: usize, chunk_size: usize) -
 /// This validation is important because in normal operation, the only legitimate failure
 /// case should be when trying to acquire a new generation block - any other failures would
 /// indicate an unexpected state in the system.
-fn process_signals(
-    kv_manager_guard: &mut tokio::sync::MutexGuard<'_, KvManager>,
-    signals: &[MoveBlock],
-) -> bool {
+fn process_signals(kv_manager: &mut KvManager, signals: &[MoveBlock]) -> bool {
     for signal in signals {
-        if kv_manager_guard.process(signal) {
+        if kv_manager.process(signal) {
             continue;
         }
 
@@ -473,26 +582,22 @@ mod tests {
     async fn test_scheduler_token_generation_patterns(#[case] use_shared_tokens: bool) {
         std::env::set_var("RUST_LOG", "debug");
 
-        let kv_capacity: usize = 500;
-        let watermark: f64 = 0.01; // 1% watermark
-        let block_size: usize = 64;
-        let chunk_size: usize = 256;
+        let args = MockEngineArgs::builder()
+            .num_gpu_blocks(500)
+            .watermark(0.01) // 1% watermark
+            .block_size(64)
+            .chunk_size(256)
+            .build()
+            .unwrap();
         let num_requests: usize = 100;
         let input_len: usize = 1000;
         let max_output_tokens: usize = 100;
 
         // Create channel for token output
-        let (output_tx, mut output_rx) = mpsc::channel::<Uuid>(1024);
+        let (output_tx, mut output_rx) = mpsc::unbounded_channel::<Uuid>();
 
         // Create scheduler with internal KvManager
-        let scheduler = Scheduler::new(
-            kv_capacity,
-            watermark,
-            block_size,
-            Some(chunk_size),
-            Some(output_tx),
-            None,
-        );
+        let scheduler = Scheduler::new(args, Some(output_tx), None, None);
 
         // Create shared tokens for caching case
         let shared_tokens = if use_shared_tokens {
@@ -583,4 +688,43 @@ mod tests {
             expected_tokens
         );
     }
+
+    #[test]
+    fn test_stepped_scheduler_completes_requests() {
+        let args = MockEngineArgs::builder()
+            .num_gpu_blocks(100)
+            .block_size(16)
+            .build()
+            .unwrap();
+        let mut scheduler = SteppedScheduler::new(args, None);
+        assert!(scheduler.is_idle());
+
+        let max_output_tokens = 10;
+        let uuids: Vec<Uuid> = (0..4)
+            .map(|i| {
+                scheduler.receive(DirectRequest {
+                    tokens: (0..64).map(|t| t + i * 100).collect(),
+                    max_output_tokens,
+                    uuid: None,
+                })
+            })
+            .collect();
+        assert_eq!(scheduler.waiting_count(), 4);
+
+        let mut generated = 0;
+        let mut completed = Vec::new();
+        let mut elapsed = Duration::ZERO;
+        while !scheduler.is_idle() {
+            let output = scheduler.step();
+            generated += output.generated.len();
+            completed.extend(output.completed);
+            elapsed += output.duration;
+        }
+
+        assert_eq!(generated, uuids.len() * max_output_tokens);
+        assert_eq!(completed.len(), uuids.len());
+        assert!(uuids.iter().all(|uuid| completed.contains(uuid)));
+        assert!(elapsed > Duration::ZERO);
+        assert_eq!(scheduler.forward_pass_metrics().request_active_slots, 0);
+    }
 }
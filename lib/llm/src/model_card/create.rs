@@ -91,6 +91,8 @@ impl ModelDeploymentCard {
             last_published: None,
             context_length,
             kv_cache_block_size: 0,
+            tool_call_parser: None,
+            enforces_guided_grammar: false,
         })
     }
 
@@ -129,6 +131,8 @@ impl ModelDeploymentCard {
             last_published: None,
             context_length,
             kv_cache_block_size: 0, // set later
+            tool_call_parser: None, // set later
+            enforces_guided_grammar: false, // set later
         })
     }
 }
@@ -122,6 +122,17 @@ pub struct ModelDeploymentCard {
     /// Size of a KV cache block - vllm only currently
     /// Passed to the engine and the KV router.
     pub kv_cache_block_size: usize,
+
+    /// Name of the parser which recognizes this model's tool call format, see
+    /// [`crate::preprocessor::tools::ToolCallParserRegistry`]. Bare JSON if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_parser: Option<String>,
+
+    /// Whether the engine constrains sampling to the grammar of a `guided_grammar` request.
+    /// The preprocessor cannot check output against a grammar, so it rejects such requests for
+    /// engines which don't.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub enforces_guided_grammar: bool,
 }
 
 impl ModelDeploymentCard {
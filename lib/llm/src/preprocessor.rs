@@ -33,6 +33,9 @@ use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use std::{collections::HashMap, sync::Arc};
 use tracing;
 
+use crate::guided_decoding::{GuidedDecoding, GuidedDecodingValidator};
+use crate::http::service::error::HttpError;
+use crate::http::service::rate_limit::{TenantUsage, TENANT_USAGE_KEY};
 use crate::model_card::model::{ModelDeploymentCard, ModelInfo, TokenizerKind};
 use crate::preprocessor::prompt::OAIChatLikeRequest;
 use crate::tokenizers::Encoding;
@@ -44,7 +47,10 @@ use dynamo_runtime::pipeline::{
 use dynamo_runtime::protocols::annotated::{Annotated, AnnotationsProvider};
 
 use crate::protocols::{
-    common::{SamplingOptionsProvider, StopConditionsProvider},
+    common::{
+        FinishReason, GuidedDecodingProvider, OutputOptionsProvider, SamplingOptionsProvider,
+        StopConditionsProvider,
+    },
     openai::{
         chat_completions::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse},
         completions::{NvCreateCompletionRequest, NvCreateCompletionResponse},
@@ -55,6 +61,9 @@ use crate::protocols::{
 use crate::tokenizers::{traits::Tokenizer, HuggingFaceTokenizer};
 
 use crate::preprocessor::prompt::{PromptFormatter, PromptInput, TextInput, TokenInput};
+use crate::preprocessor::tools::{
+    ToolCallJail, ToolCallParser, ToolCallParserRegistry, ToolCallingMatcher, JSON_PARSER,
+};
 
 pub use crate::protocols::common::llm_backend::{BackendOutput, PreprocessedRequest};
 
@@ -101,11 +110,25 @@ pub struct OpenAIPreprocessor {
     formatter: Arc<dyn OAIPromptFormatter>,
     tokenizer: Arc<dyn Tokenizer>,
     model_info: Arc<dyn ModelInfo>,
+    tool_call_parser: Arc<dyn ToolCallParser>,
+    /// Whether the engine enforces `guided_grammar` requests, see
+    /// [`ModelDeploymentCard::enforces_guided_grammar`]
+    enforces_guided_grammar: bool,
 }
 
 impl OpenAIPreprocessor {
     pub async fn new(mdc: ModelDeploymentCard) -> Result<Arc<Self>> {
+        Self::new_with_tool_call_parsers(mdc, &ToolCallParserRegistry::default()).await
+    }
+
+    /// Like [`OpenAIPreprocessor::new`], but resolves the model's tool call parser from the given
+    /// registry, which may include custom parsers.
+    pub async fn new_with_tool_call_parsers(
+        mdc: ModelDeploymentCard,
+        tool_call_parsers: &ToolCallParserRegistry,
+    ) -> Result<Arc<Self>> {
         let mdcsum = mdc.mdcsum();
+        let enforces_guided_grammar = mdc.enforces_guided_grammar;
         let formatter = PromptFormatter::from_mdc(mdc.clone()).await?;
         let PromptFormatter::OAI(formatter) = formatter;
 
@@ -129,11 +152,21 @@ impl OpenAIPreprocessor {
         };
         let model_info = model_info.get_model_info().await?;
 
+        let parser_name = mdc.tool_call_parser.as_deref().unwrap_or(JSON_PARSER);
+        let Some(tool_call_parser) = tool_call_parsers.get(parser_name) else {
+            anyhow::bail!(
+                "Unknown tool call parser '{parser_name}'. Available parsers: {}",
+                tool_call_parsers.names().join(", ")
+            );
+        };
+
         Ok(Arc::new(Self {
             formatter,
             tokenizer,
             model_info,
             mdcsum,
+            tool_call_parser,
+            enforces_guided_grammar,
         }))
     }
 
@@ -153,6 +186,8 @@ impl OpenAIPreprocessor {
             + AnnotationsProvider
             + SamplingOptionsProvider
             + StopConditionsProvider
+            + OutputOptionsProvider
+            + GuidedDecodingProvider
             + NvExtProvider,
     >(
         &self,
@@ -258,9 +293,12 @@ impl OpenAIPreprocessor {
 
         builder.stop_conditions(stop_conditions);
         builder.sampling_options(request.extract_sampling_options()?);
+        builder.output_options(request.extract_output_options()?);
+        builder.guided_decoding(request.extract_guided_decoding()?);
         builder.annotations(request.annotations().unwrap_or_default());
         builder.mdc_sum(Some(self.mdcsum.clone()));
         builder.estimated_prefix_hit_num_blocks(None);
+        builder.lora_id(request.nvext().and_then(|ext| ext.lora_id));
 
         Ok((builder.build()?, annotations))
     }
@@ -346,7 +384,15 @@ impl OpenAIPreprocessor {
                         if response.event.is_none() {
                             response.event = metrics_annotated.event;
                         }
-                        response.comment = metrics_annotated.comment;
+                        // Keep the comments of existing events (like the routing decision) ahead of the metrics
+                        response.comment =
+                            match (response.comment.take(), metrics_annotated.comment) {
+                                (Some(mut comments), Some(metrics)) => {
+                                    comments.extend(metrics);
+                                    Some(comments)
+                                }
+                                (comments, metrics) => metrics.or(comments),
+                            };
                     }
 
                     tracing::trace!(
@@ -369,6 +415,228 @@ impl OpenAIPreprocessor {
     }
 }
 
+impl OpenAIPreprocessor {
+    /// Creates the validator for the structured output constraint of the request, if any.
+    /// Rejects the request with a 400 if the constraint is malformed or can be neither enforced
+    /// by the engine nor validated, such as a grammar for an engine without grammar sampling.
+    fn guided_decoding_validator(
+        &self,
+        request: &PreprocessedRequest,
+    ) -> Result<Option<GuidedDecodingValidator>> {
+        let validator = match &request.guided_decoding {
+            None => return Ok(None),
+            // the engine samples with the grammar, which the validator cannot check
+            Some(GuidedDecoding::Grammar(grammar)) if self.enforces_guided_grammar => {
+                if !grammar.trim().is_empty() {
+                    return Ok(None);
+                }
+                Err(anyhow::anyhow!("guided_grammar must not be empty"))
+            }
+            Some(guided_decoding) => GuidedDecodingValidator::new(guided_decoding),
+        };
+        validator.map(Some).map_err(|err| {
+            HttpError {
+                code: 400,
+                message: format!("{err:#}"),
+            }
+            .into()
+        })
+    }
+
+    /// Validates the text of each choice against the requested structured output once the
+    /// choice finishes, replacing the final response with an error if the text does not conform.
+    /// This catches output of engines which cannot enforce the constraint while sampling.
+    ///
+    /// A choice cut off by the token limit is not validated: its text is incomplete, which the
+    /// client learns from the `length` finish reason.
+    pub fn transform_guided_decoding_stream(
+        stream: ManyOut<Annotated<BackendOutput>>,
+        validator: GuidedDecodingValidator,
+    ) -> ManyOut<Annotated<BackendOutput>> {
+        let context = stream.context();
+        let request_id = context.id().to_string();
+
+        // generated text of each choice, keyed by choice index
+        let mut outputs: HashMap<u32, String> = HashMap::new();
+
+        let stream = stream.map(move |response| {
+            let Some(data) = &response.data else {
+                return response;
+            };
+
+            let index = data.index.unwrap_or(0);
+            let output = outputs.entry(index).or_default();
+            if let Some(text) = &data.text {
+                output.push_str(text);
+            }
+
+            match data.finish_reason {
+                Some(FinishReason::EoS | FinishReason::Stop) => {}
+                Some(FinishReason::Length) => {
+                    outputs.remove(&index);
+                    return response;
+                }
+                _ => return response,
+            }
+
+            let output = outputs.remove(&index).unwrap_or_default();
+            match validator.validate(&output) {
+                Ok(()) => response,
+                Err(err) => {
+                    tracing::debug!(
+                        request_id,
+                        "Output violates the structured output constraint: {err:#}"
+                    );
+                    Annotated::from_error(format!("{err:#}"))
+                }
+            }
+        });
+
+        ResponseStream::new(Box::pin(stream), context)
+    }
+
+    /// Jails assistant text that may be a tool call and, once the choice finishes, replaces it
+    /// with OpenAI `tool_calls` deltas and a `tool_calls` finish reason. Text that turns out not
+    /// to be a tool call is released unchanged on the final delta.
+    pub fn transform_tool_calls_stream(
+        stream: ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>,
+        matcher: ToolCallingMatcher,
+    ) -> ManyOut<Annotated<NvCreateChatCompletionStreamResponse>> {
+        let context = stream.context();
+
+        struct State {
+            response_stream: ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>,
+            jail: ToolCallJail,
+            last_response: Option<NvCreateChatCompletionStreamResponse>,
+            finished: bool,
+        }
+
+        let state = State {
+            response_stream: stream,
+            jail: ToolCallJail::new(matcher),
+            last_response: None,
+            finished: false,
+        };
+
+        let stream = stream::unfold(state, |mut inner| async move {
+            if inner.finished {
+                return None;
+            }
+
+            match inner.response_stream.next().await {
+                Some(mut response) => {
+                    // the delta generator only issues a single choice; the jail follows it
+                    if let Some(data) = response.data.as_mut() {
+                        for choice in data.inner.choices.iter_mut() {
+                            if let Some(content) = choice.delta.content.take() {
+                                // the log probabilities of held back tokens are held back too
+                                let logprobs = choice
+                                    .logprobs
+                                    .as_mut()
+                                    .and_then(|logprobs| logprobs.content.take());
+                                match logprobs {
+                                    Some(logprobs) => {
+                                        let (content, logprobs) =
+                                            inner.jail.push_with_logprobs(&content, logprobs);
+                                        choice.delta.content = content;
+                                        if let Some(choice_logprobs) = choice.logprobs.as_mut() {
+                                            choice_logprobs.content = Some(logprobs);
+                                        }
+                                    }
+                                    None => choice.delta.content = inner.jail.push(&content),
+                                }
+                            }
+                            if choice.finish_reason.is_some() {
+                                if let Err(err) = release_tool_calls(&mut inner.jail, choice) {
+                                    inner.finished = true;
+                                    return Some((Annotated::from_error(err.to_string()), inner));
+                                }
+                            }
+                        }
+                        inner.last_response = Some(data.clone());
+                    }
+                    Some((response, inner))
+                }
+                None => {
+                    // stream closed without a finish reason; flush anything still jailed
+                    inner.finished = true;
+                    if !inner.jail.is_jailed() {
+                        return None;
+                    }
+                    let mut data = inner.last_response.take()?;
+
+                    #[allow(deprecated)]
+                    let mut choice = async_openai::types::ChatChoiceStream {
+                        index: 0,
+                        delta: async_openai::types::ChatCompletionStreamResponseDelta {
+                            role: None,
+                            content: None,
+                            tool_calls: None,
+                            function_call: None,
+                            refusal: None,
+                        },
+                        finish_reason: None,
+                        logprobs: None,
+                    };
+
+                    let response = match release_tool_calls(&mut inner.jail, &mut choice) {
+                        Ok(()) => {
+                            data.inner.choices = vec![choice];
+                            Annotated::from_data(data)
+                        }
+                        Err(err) => Annotated::from_error(err.to_string()),
+                    };
+                    Some((response, inner))
+                }
+            }
+        });
+
+        ResponseStream::new(Box::pin(stream), context)
+    }
+}
+
+/// Ends the jailed choice, moving any parsed tool calls and released text onto the delta.
+fn release_tool_calls(
+    jail: &mut ToolCallJail,
+    choice: &mut async_openai::types::ChatChoiceStream,
+) -> Result<()> {
+    let (text, calls) = jail.finish()?;
+
+    let logprobs = jail.finish_logprobs(text.as_ref().map_or(0, String::len));
+    if !logprobs.is_empty() {
+        choice
+            .logprobs
+            .get_or_insert_with(|| async_openai::types::ChatChoiceLogprobs {
+                content: None,
+                refusal: None,
+            })
+            .content
+            .get_or_insert_with(Vec::new)
+            .extend(logprobs);
+    }
+
+    if let Some(text) = text {
+        choice
+            .delta
+            .content
+            .get_or_insert_with(String::new)
+            .push_str(&text);
+    }
+
+    if !calls.is_empty() {
+        choice.delta.tool_calls = Some(
+            calls
+                .iter()
+                .enumerate()
+                .map(|(index, call)| call.to_chunk(index as u32))
+                .collect(),
+        );
+        choice.finish_reason = Some(async_openai::types::FinishReason::ToolCalls);
+    }
+
+    Ok(())
+}
+
 // for pals, we do not want to add the generation prompt to the formatted prompt
 // we also need to know if the template support this add_generation_prompt bool
 // any prompt template that does not support this should return an error
@@ -401,12 +669,22 @@ impl
         let response_generator = request.response_generator();
         let mut response_generator = Box::new(response_generator);
 
+        // match tool calls in the response if the request offers tools
+        let tool_matcher =
+            ToolCallingMatcher::from_request(&request.inner, self.tool_call_parser.clone())?;
+
         // convert the chat completion request to a common completion request
         let (common_request, annotations) = self.preprocess_request(&request)?;
+        let guided_decoding_validator = self.guided_decoding_validator(&common_request)?;
 
         // update isl
         response_generator.update_isl(common_request.token_ids.len() as u32);
 
+        // admit the prompt against the prompt tokens limit of the tenant
+        if let Ok(usage) = context.get::<TenantUsage>(TENANT_USAGE_KEY) {
+            usage.admit_prompt(common_request.token_ids.len())?;
+        }
+
         // repack the common completion request
         let common_request = context.map(|_| common_request);
 
@@ -420,8 +698,20 @@ impl
         // forward the common completion request to the next operator
         let response_stream = next.generate(common_request).await?;
 
+        // reject output which violates the requested structured output
+        let response_stream = match guided_decoding_validator {
+            Some(validator) => Self::transform_guided_decoding_stream(response_stream, validator),
+            None => response_stream,
+        };
+
         // transform the postprocessor stream
         let stream = Self::transform_postprocessor_stream(response_stream, response_generator);
+
+        // convert jailed tool call payloads into openai tool_calls deltas
+        let stream = match tool_matcher {
+            Some(matcher) => Self::transform_tool_calls_stream(stream, matcher),
+            None => stream,
+        };
         let context = stream.context();
 
         // prepend the annotations to the response stream
@@ -460,10 +750,16 @@ impl
         let mut response_generator = Box::new(response_generator);
         // convert the chat completion request to a common completion request
         let (common_request, annotations) = self.preprocess_request(&request)?;
+        let guided_decoding_validator = self.guided_decoding_validator(&common_request)?;
 
         // update isl
         response_generator.update_isl(common_request.token_ids.len() as u32);
 
+        // admit the prompt against the prompt tokens limit of the tenant
+        if let Ok(usage) = context.get::<TenantUsage>(TENANT_USAGE_KEY) {
+            usage.admit_prompt(common_request.token_ids.len())?;
+        }
+
         // repack the common completion request
         let common_request = context.map(|_| common_request);
 
@@ -477,6 +773,12 @@ impl
         // forward the common completion request to the next operator
         let response_stream = next.generate(common_request).await?;
 
+        // reject output which violates the requested structured output
+        let response_stream = match guided_decoding_validator {
+            Some(validator) => Self::transform_guided_decoding_stream(response_stream, validator),
+            None => response_stream,
+        };
+
         // transform the postprocessor stream
         let stream = Self::transform_postprocessor_stream(response_stream, response_generator);
         let context = stream.context();
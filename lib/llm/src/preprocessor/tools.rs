@@ -13,22 +13,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod jail;
+mod parsers;
 mod request;
 mod response;
 
+pub use jail::ToolCallJail;
+pub use parsers::*;
 pub use request::*;
 pub use response::*;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
 /// Matches and processes tool calling patterns in LLM responses
 ///
 /// Supports multiple formats for tool calls:
 /// - Single/multiple function calls with parameters/arguments
+/// - Model family specific formats, see [`ToolCallParser`]
 /// - Auto or user selected tool usage
 pub struct ToolCallingMatcher {
     tool_choice: ToolChoice,
+    parser: Arc<dyn ToolCallParser>,
 }
 
 // Same as CalledFunction with named parameters
@@ -46,70 +53,71 @@ pub struct CalledFunctionArguments {
 }
 
 impl ToolCallingMatcher {
+    /// Creates a matcher which recognizes bare JSON tool calls.
     pub fn new(tool_choice: ToolChoice) -> anyhow::Result<Self> {
-        Ok(Self { tool_choice })
+        Self::with_parser(tool_choice, Arc::new(JsonParser))
+    }
+
+    /// Creates a matcher which recognizes the tool call format of the given parser.
+    pub fn with_parser(
+        tool_choice: ToolChoice,
+        parser: Arc<dyn ToolCallParser>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            tool_choice,
+            parser,
+        })
+    }
+
+    /// Creates a matcher for a chat completion request, or `None` if the request does not
+    /// enable tool calling.
+    pub fn from_request(
+        request: &async_openai::types::CreateChatCompletionRequest,
+        parser: Arc<dyn ToolCallParser>,
+    ) -> anyhow::Result<Option<Self>> {
+        ToolChoice::from_openai(request.tool_choice.as_ref(), request.tools.as_deref())?
+            .map(|tool_choice| Self::with_parser(tool_choice, parser))
+            .transpose()
+    }
+
+    pub fn tool_choice(&self) -> &ToolChoice {
+        &self.tool_choice
+    }
+
+    pub fn parser(&self) -> &dyn ToolCallParser {
+        self.parser.as_ref()
+    }
+
+    /// Returns true if the model must call a tool for the response to be valid.
+    pub fn is_tool_required(&self) -> bool {
+        matches!(self.tool_choice, ToolChoice::Tool(_) | ToolChoice::Required)
     }
 
     pub fn get_call(&self, message: &str) -> anyhow::Result<Vec<ToolCallResponse>> {
+        Ok(self.parse(message)?.1)
+    }
+
+    /// Splits a message into the regular text and the tool calls it contains.
+    pub fn parse(&self, message: &str) -> anyhow::Result<(String, Vec<ToolCallResponse>)> {
         if matches!(self.tool_choice, ToolChoice::None) {
-            return Ok(Vec::new());
+            return Ok((message.to_string(), Vec::new()));
         }
 
-        if let Ok(deser) = serde_json::from_str::<CalledFunctionParameters>(message) {
-            let id = format!("call-{}", Uuid::new_v4());
-            Ok(vec![ToolCallResponse {
-                id,
-                tp: ToolCallType::Function,
-                function: CalledFunction {
-                    name: deser.name,
-                    arguments: serde_json::to_string(&deser.parameters)?,
-                },
-            }])
-        } else if let Ok(deser) = serde_json::from_str::<Vec<CalledFunctionParameters>>(message) {
-            Ok(deser
-                .into_iter()
-                .map(|deser| {
-                    let id = format!("call-{}", Uuid::new_v4());
-                    Ok(ToolCallResponse {
-                        id,
-                        tp: ToolCallType::Function,
-                        function: CalledFunction {
-                            name: deser.name,
-                            arguments: serde_json::to_string(&deser.parameters)?,
-                        },
-                    })
-                })
-                .collect::<anyhow::Result<Vec<_>>>()?)
-        } else if let Ok(deser) = serde_json::from_str::<CalledFunctionArguments>(message) {
-            let id = format!("call-{}", Uuid::new_v4());
-            Ok(vec![ToolCallResponse {
-                id,
-                tp: ToolCallType::Function,
-                function: CalledFunction {
-                    name: deser.name,
-                    arguments: serde_json::to_string(&deser.arguments)?,
-                },
-            }])
-        } else if let Ok(deser) = serde_json::from_str::<Vec<CalledFunctionArguments>>(message) {
-            Ok(deser
-                .into_iter()
-                .map(|deser| {
-                    let id = format!("call-{}", Uuid::new_v4());
-                    Ok(ToolCallResponse {
-                        id,
-                        tp: ToolCallType::Function,
-                        function: CalledFunction {
-                            name: deser.name,
-                            arguments: serde_json::to_string(&deser.arguments)?,
-                        },
-                    })
-                })
-                .collect::<anyhow::Result<Vec<_>>>()?)
-        } else {
-            if matches!(self.tool_choice, ToolChoice::Tool(_)) {
-                anyhow::bail!("Tool choice was required but no tools were called.")
-            }
-            Ok(Vec::new())
+        let parsed = self.parser.parse(message)?;
+        if parsed.calls.is_empty() && self.is_tool_required() {
+            anyhow::bail!("Tool choice was required but no tools were called.")
         }
+
+        let calls = parsed
+            .calls
+            .into_iter()
+            .map(|function| ToolCallResponse {
+                id: format!("call-{}", Uuid::new_v4()),
+                tp: ToolCallType::Function,
+                function,
+            })
+            .collect();
+
+        Ok((parsed.normal_text, calls))
     }
 }
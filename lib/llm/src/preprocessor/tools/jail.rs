@@ -0,0 +1,372 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+
+use async_openai::types::ChatCompletionTokenLogprob;
+
+use super::{ToolCallResponse, ToolCallingMatcher};
+
+/// Holds back streamed assistant text that may turn out to be a tool call.
+///
+/// Tool calls are only recognizable once the full payload has been generated, so any text that
+/// could be the start of a call is jailed until the choice finishes. At that point the jailed
+/// text is either converted into tool calls or released as regular content.
+///
+/// Similar to the hidden stop sequence jail in [`crate::backend::Decoder`], text that ends with
+/// a partial match of one of the parser's start markers is held back until the marker is either
+/// completed or ruled out.
+///
+/// The log probabilities of the held back tokens are held back with their text, so that the
+/// client does not see a tool call through the tokens of the `logprobs`.
+pub struct ToolCallJail {
+    matcher: ToolCallingMatcher,
+    state: JailState,
+    jail: String,
+    /// Log probabilities of the tokens whose text has not been released in full
+    logprobs: VecDeque<ChatCompletionTokenLogprob>,
+    /// Bytes of released text not yet matched with the tokens of `logprobs`
+    released: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JailState {
+    /// No non-whitespace text has been observed yet; the output may still be a bare JSON call.
+    Undecided,
+    /// The output is plain text; it is released unless it contains a start marker.
+    Scanning,
+    /// A tool call has started; everything is held back until the choice finishes.
+    Jailed,
+}
+
+impl ToolCallJail {
+    pub fn new(matcher: ToolCallingMatcher) -> Self {
+        let state = if matcher.is_tool_required() {
+            // if a tool call is required, every byte of output belongs to the call
+            JailState::Jailed
+        } else if matcher.parser().bare_json() {
+            JailState::Undecided
+        } else {
+            JailState::Scanning
+        };
+
+        Self {
+            matcher,
+            state,
+            jail: String::new(),
+            logprobs: VecDeque::new(),
+            released: 0,
+        }
+    }
+
+    /// Returns true if text is currently being held back.
+    pub fn is_jailed(&self) -> bool {
+        self.state == JailState::Jailed || !self.jail.is_empty()
+    }
+
+    /// Feeds the next piece of generated text into the jail and returns the text which may be
+    /// forwarded to the client, if any.
+    pub fn push(&mut self, text: &str) -> Option<String> {
+        self.jail.push_str(text);
+
+        match self.state {
+            JailState::Jailed => None,
+            JailState::Scanning => self.scan(),
+            JailState::Undecided => match self.jail.trim_start().chars().next() {
+                None => None,
+                Some('{') | Some('[') => {
+                    self.state = JailState::Jailed;
+                    None
+                }
+                Some(_) => {
+                    self.state = JailState::Scanning;
+                    self.scan()
+                }
+            },
+        }
+    }
+
+    /// Like [`ToolCallJail::push`], for text made of the tokens with the given log
+    /// probabilities. Also returns the log probabilities of the tokens whose text is released.
+    pub fn push_with_logprobs(
+        &mut self,
+        text: &str,
+        logprobs: Vec<ChatCompletionTokenLogprob>,
+    ) -> (Option<String>, Vec<ChatCompletionTokenLogprob>) {
+        self.logprobs.extend(logprobs);
+        let released = self.push(text);
+        let logprobs = self.release_logprobs(released.as_ref().map_or(0, String::len));
+        (released, logprobs)
+    }
+
+    /// Ends the log probabilities of the choice, after [`ToolCallJail::finish`] released
+    /// `released` bytes of text. The log probabilities of the text consumed by tool calls are
+    /// dropped.
+    pub fn finish_logprobs(&mut self, released: usize) -> Vec<ChatCompletionTokenLogprob> {
+        let logprobs = self.release_logprobs(released);
+        self.logprobs.clear();
+        self.released = 0;
+        logprobs
+    }
+
+    /// The log probabilities of the tokens whose text is fully released with `released` more
+    /// bytes of text.
+    fn release_logprobs(&mut self, released: usize) -> Vec<ChatCompletionTokenLogprob> {
+        self.released += released;
+        let mut logprobs = Vec::new();
+        while let Some(token) = self.logprobs.front() {
+            if token.token.len() > self.released {
+                break;
+            }
+            self.released -= token.token.len();
+            logprobs.extend(self.logprobs.pop_front());
+        }
+        if self.logprobs.is_empty() {
+            // text without log probabilities
+            self.released = 0;
+        }
+        logprobs
+    }
+
+    /// Releases the scanned text up to the first start marker, or up to a trailing partial
+    /// match of a start marker.
+    fn scan(&mut self) -> Option<String> {
+        let markers = self.matcher.parser().start_markers();
+
+        let released = match markers
+            .iter()
+            .filter_map(|marker| self.jail.find(marker))
+            .min()
+        {
+            Some(offset) => {
+                self.state = JailState::Jailed;
+                let jailed = self.jail.split_off(offset);
+                std::mem::replace(&mut self.jail, jailed)
+            }
+            None => {
+                let held = markers
+                    .iter()
+                    .map(|marker| partial_match_len(&self.jail, marker))
+                    .max()
+                    .unwrap_or(0);
+                let held = self.jail.split_off(self.jail.len() - held);
+                std::mem::replace(&mut self.jail, held)
+            }
+        };
+
+        (!released.is_empty()).then_some(released)
+    }
+
+    /// Ends the choice. Returns any text that is still held back along with the tool calls
+    /// parsed from it. If tool calls were found, the text making up the calls is consumed.
+    pub fn finish(&mut self) -> anyhow::Result<(Option<String>, Vec<ToolCallResponse>)> {
+        let state = std::mem::replace(&mut self.state, JailState::Scanning);
+        let jail = std::mem::take(&mut self.jail);
+
+        let (text, calls) = match state {
+            JailState::Undecided | JailState::Scanning => (jail, Vec::new()),
+            JailState::Jailed => self.matcher.parse(&jail)?,
+        };
+
+        Ok(((!text.is_empty()).then_some(text), calls))
+    }
+}
+
+/// Length of the longest suffix of `text` which is a proper prefix of `marker`.
+fn partial_match_len(text: &str, marker: &str) -> usize {
+    (1..marker.len().min(text.len() + 1))
+        .rev()
+        .find(|&len| {
+            let start = text.len() - len;
+            text.is_char_boundary(start) && marker.starts_with(&text[start..])
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::super::{Function, HermesParser, MistralParser, Tool, ToolChoice, ToolType};
+    use super::*;
+
+    fn jail(tool_choice: ToolChoice) -> ToolCallJail {
+        ToolCallJail::new(ToolCallingMatcher::new(tool_choice).unwrap())
+    }
+
+    #[test]
+    fn test_plain_text_is_released() {
+        let mut jail = jail(ToolChoice::Auto);
+        assert_eq!(jail.push("  "), None);
+        assert_eq!(jail.push("Hello"), Some("  Hello".to_string()));
+        assert_eq!(jail.push(" world"), Some(" world".to_string()));
+
+        let (text, calls) = jail.finish().unwrap();
+        assert!(text.is_none());
+        assert!(calls.is_empty());
+    }
+
+    #[test]
+    fn test_tool_call_is_jailed() {
+        let mut jail = jail(ToolChoice::Auto);
+        assert_eq!(jail.push("{\"name\": \"get_weather\", "), None);
+        assert_eq!(jail.push("\"arguments\": {\"city\": \"Paris\"}}"), None);
+        assert!(jail.is_jailed());
+
+        let (text, calls) = jail.finish().unwrap();
+        assert!(text.is_none());
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].function.name, "get_weather");
+        assert_eq!(calls[0].function.arguments, "{\"city\":\"Paris\"}");
+    }
+
+    #[test]
+    fn test_json_without_tool_call_is_released_on_finish() {
+        let mut jail = jail(ToolChoice::Auto);
+        assert_eq!(jail.push("{\"answer\": 42}"), None);
+
+        let (text, calls) = jail.finish().unwrap();
+        assert_eq!(text, Some("{\"answer\": 42}".to_string()));
+        assert!(calls.is_empty());
+    }
+
+    #[test]
+    fn test_required_tool_call_missing() {
+        let tool = Tool {
+            tp: ToolType::Function,
+            function: Function {
+                description: None,
+                name: "get_weather".to_string(),
+                parameters: None,
+            },
+        };
+        let mut jail = jail(ToolChoice::Tool(tool));
+        assert_eq!(jail.push("It is sunny"), None);
+        assert!(jail.finish().is_err());
+    }
+
+    #[test]
+    fn test_partial_marker_is_held() {
+        let matcher =
+            ToolCallingMatcher::with_parser(ToolChoice::Auto, Arc::new(HermesParser)).unwrap();
+        let mut jail = ToolCallJail::new(matcher);
+
+        assert_eq!(jail.push("Let me "), Some("Let me ".to_string()));
+        assert_eq!(jail.push("check.<tool"), Some("check.".to_string()));
+        assert!(jail.is_jailed());
+        assert_eq!(
+            jail.push("_call>{\"name\": \"get_weather\", \"arguments\": {}}"),
+            None
+        );
+        assert_eq!(jail.push("</tool_call>"), None);
+
+        let (text, calls) = jail.finish().unwrap();
+        assert!(text.is_none());
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].function.name, "get_weather");
+    }
+
+    #[test]
+    fn test_partial_marker_is_released() {
+        let matcher =
+            ToolCallingMatcher::with_parser(ToolChoice::Auto, Arc::new(HermesParser)).unwrap();
+        let mut jail = ToolCallJail::new(matcher);
+
+        assert_eq!(jail.push("a <to"), Some("a ".to_string()));
+        assert_eq!(jail.push("p> b"), Some("<top> b".to_string()));
+        assert!(!jail.is_jailed());
+    }
+
+    fn logprob(token: &str) -> ChatCompletionTokenLogprob {
+        ChatCompletionTokenLogprob {
+            token: token.to_string(),
+            logprob: -0.5,
+            bytes: Some(token.as_bytes().to_vec()),
+            top_logprobs: vec![],
+        }
+    }
+
+    fn tokens(logprobs: &[ChatCompletionTokenLogprob]) -> Vec<&str> {
+        logprobs
+            .iter()
+            .map(|logprob| logprob.token.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_logprobs_are_held_with_their_text() {
+        let matcher =
+            ToolCallingMatcher::with_parser(ToolChoice::Auto, Arc::new(HermesParser)).unwrap();
+        let mut jail = ToolCallJail::new(matcher);
+
+        let (text, logprobs) =
+            jail.push_with_logprobs("Let me", vec![logprob("Let"), logprob(" me")]);
+        assert_eq!(text, Some("Let me".to_string()));
+        assert_eq!(tokens(&logprobs), ["Let", " me"]);
+
+        // the partial marker is held, and the token it belongs to
+        let (text, logprobs) =
+            jail.push_with_logprobs(" check.<tool", vec![logprob(" check."), logprob("<tool")]);
+        assert_eq!(text, Some(" check.".to_string()));
+        assert_eq!(tokens(&logprobs), [" check."]);
+
+        let call = "_call>{\"name\": \"get_weather\", \"arguments\": {}}</tool_call>";
+        let (text, logprobs) = jail.push_with_logprobs(call, vec![logprob(call)]);
+        assert_eq!(text, None);
+        assert!(logprobs.is_empty());
+
+        // the tokens of the tool call are never released
+        let (text, calls) = jail.finish().unwrap();
+        assert!(text.is_none());
+        assert_eq!(calls.len(), 1);
+        assert!(jail.finish_logprobs(0).is_empty());
+    }
+
+    #[test]
+    fn test_logprobs_are_released_without_tool_call() {
+        let mut jail = jail(ToolChoice::Auto);
+        let (text, logprobs) = jail.push_with_logprobs("{\"answer\"", vec![logprob("{\"answer\"")]);
+        assert_eq!(text, None);
+        assert!(logprobs.is_empty());
+        let (text, logprobs) = jail.push_with_logprobs(": 42}", vec![logprob(": 42}")]);
+        assert_eq!(text, None);
+        assert!(logprobs.is_empty());
+
+        let (text, calls) = jail.finish().unwrap();
+        assert!(calls.is_empty());
+        let text = text.unwrap();
+        assert_eq!(
+            tokens(&jail.finish_logprobs(text.len())),
+            ["{\"answer\"", ": 42}"]
+        );
+    }
+
+    #[test]
+    fn test_marker_after_text() {
+        let matcher =
+            ToolCallingMatcher::with_parser(ToolChoice::Auto, Arc::new(MistralParser)).unwrap();
+        let mut jail = ToolCallJail::new(matcher);
+
+        assert_eq!(
+            jail.push("Sure. [TOOL_CALLS][{\"name\": \"f\", \"arguments\": {}}]"),
+            Some("Sure. ".to_string())
+        );
+
+        let (text, calls) = jail.finish().unwrap();
+        assert!(text.is_none());
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].function.name, "f");
+    }
+}
@@ -0,0 +1,496 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Model family specific tool call parsers.
+//!
+//! Most open models do not emit bare JSON for tool calls, they wrap the call in model specific
+//! tags or control tokens. Each [`ToolCallParser`] knows one of these formats, and the
+//! [`ToolCallParserRegistry`] maps the parser name found in the
+//! [`crate::model_card::model::ModelDeploymentCard`] to an implementation.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::CalledFunction;
+
+/// Bare JSON objects or arrays, e.g. `{"name": "f", "arguments": {..}}`
+pub const JSON_PARSER: &str = "json";
+
+/// Hermes / Qwen 2.5 style, e.g. `<tool_call>{"name": "f", "arguments": {..}}</tool_call>`
+pub const HERMES_PARSER: &str = "hermes";
+
+/// Llama 3.x JSON style, e.g. `<|python_tag|>{"name": "f", "parameters": {..}}`
+pub const LLAMA3_JSON_PARSER: &str = "llama3_json";
+
+/// Mistral style, e.g. `[TOOL_CALLS][{"name": "f", "arguments": {..}}]` or `[TOOL_CALLS]f[ARGS]{..}`
+pub const MISTRAL_PARSER: &str = "mistral";
+
+/// Qwen 3 Coder XML style, e.g.
+/// `<tool_call><function=f><parameter=p>value</parameter></function></tool_call>`
+pub const QWEN3_CODER_PARSER: &str = "qwen3_coder";
+
+const TOOL_CALL_START: &str = "<tool_call>";
+const TOOL_CALL_END: &str = "</tool_call>";
+const PYTHON_TAG: &str = "<|python_tag|>";
+const MISTRAL_TOOL_CALLS: &str = "[TOOL_CALLS]";
+const MISTRAL_ARGS: &str = "[ARGS]";
+
+static QWEN3_FUNCTION_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<function=([^>]+)>(.*?)</function>").unwrap());
+
+static QWEN3_PARAMETER_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<parameter=([^>]+)>(.*?)</parameter>").unwrap());
+
+/// Model output split into regular assistant text and the tool calls it contained.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedToolCalls {
+    pub normal_text: String,
+    pub calls: Vec<CalledFunction>,
+}
+
+impl ParsedToolCalls {
+    fn text_only(text: &str) -> Self {
+        Self {
+            normal_text: text.to_string(),
+            calls: Vec::new(),
+        }
+    }
+}
+
+/// Recognizes the tool call format of a model family.
+pub trait ToolCallParser: Send + Sync {
+    /// Markers which open a tool call section. Streamed text is jailed from the first
+    /// marker onwards.
+    fn start_markers(&self) -> &[&'static str];
+
+    /// If true, output whose first non-whitespace character opens a JSON object or array is
+    /// treated as a potential tool call even without a start marker.
+    fn bare_json(&self) -> bool {
+        false
+    }
+
+    /// Splits the complete output of a choice into regular text and tool calls.
+    fn parse(&self, text: &str) -> anyhow::Result<ParsedToolCalls>;
+}
+
+/// Maps parser names to [`ToolCallParser`] implementations.
+///
+/// [`ToolCallParserRegistry::default`] contains all the built-in parsers; custom parsers can
+/// be added with [`ToolCallParserRegistry::register`].
+#[derive(Clone)]
+pub struct ToolCallParserRegistry {
+    parsers: HashMap<String, Arc<dyn ToolCallParser>>,
+}
+
+impl Default for ToolCallParserRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            parsers: HashMap::new(),
+        };
+        registry.register(JSON_PARSER, Arc::new(JsonParser));
+        registry.register(HERMES_PARSER, Arc::new(HermesParser));
+        registry.register(LLAMA3_JSON_PARSER, Arc::new(Llama3JsonParser));
+        registry.register(MISTRAL_PARSER, Arc::new(MistralParser));
+        registry.register(QWEN3_CODER_PARSER, Arc::new(Qwen3CoderParser));
+        registry
+    }
+}
+
+impl ToolCallParserRegistry {
+    /// Adds a parser, replacing any parser previously registered under the same name.
+    pub fn register(&mut self, name: impl Into<String>, parser: Arc<dyn ToolCallParser>) {
+        self.parsers.insert(name.into(), parser);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn ToolCallParser>> {
+        self.parsers.get(name).cloned()
+    }
+
+    /// Names of all registered parsers, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.parsers.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// Bare JSON tool calls; this is the fallback when a model does not specify a parser.
+pub struct JsonParser;
+
+impl ToolCallParser for JsonParser {
+    fn start_markers(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn bare_json(&self) -> bool {
+        true
+    }
+
+    fn parse(&self, text: &str) -> anyhow::Result<ParsedToolCalls> {
+        Ok(match parse_json_calls(text) {
+            Some(calls) => ParsedToolCalls {
+                normal_text: String::new(),
+                calls,
+            },
+            None => ParsedToolCalls::text_only(text),
+        })
+    }
+}
+
+pub struct HermesParser;
+
+impl ToolCallParser for HermesParser {
+    fn start_markers(&self) -> &[&'static str] {
+        &[TOOL_CALL_START]
+    }
+
+    fn parse(&self, text: &str) -> anyhow::Result<ParsedToolCalls> {
+        Ok(parse_tagged(
+            text,
+            TOOL_CALL_START,
+            TOOL_CALL_END,
+            parse_json_calls,
+        ))
+    }
+}
+
+pub struct Llama3JsonParser;
+
+impl ToolCallParser for Llama3JsonParser {
+    fn start_markers(&self) -> &[&'static str] {
+        &[PYTHON_TAG]
+    }
+
+    fn bare_json(&self) -> bool {
+        true
+    }
+
+    fn parse(&self, text: &str) -> anyhow::Result<ParsedToolCalls> {
+        let (normal_text, calls_text) = text.split_once(PYTHON_TAG).unwrap_or(("", text));
+        Ok(match parse_json_calls(calls_text) {
+            Some(calls) => ParsedToolCalls {
+                normal_text: normal_text.trim().to_string(),
+                calls,
+            },
+            None => ParsedToolCalls::text_only(text),
+        })
+    }
+}
+
+pub struct MistralParser;
+
+impl ToolCallParser for MistralParser {
+    fn start_markers(&self) -> &[&'static str] {
+        &[MISTRAL_TOOL_CALLS]
+    }
+
+    fn bare_json(&self) -> bool {
+        true
+    }
+
+    fn parse(&self, text: &str) -> anyhow::Result<ParsedToolCalls> {
+        let Some((normal_text, calls_text)) = text.split_once(MISTRAL_TOOL_CALLS) else {
+            return JsonParser.parse(text);
+        };
+
+        let mut calls = Vec::new();
+        for segment in calls_text.split(MISTRAL_TOOL_CALLS) {
+            if let Some(found) = parse_json_calls(segment) {
+                calls.extend(found);
+                continue;
+            }
+            // newer tokenizers emit `name[ARGS]{...}` instead of a JSON array
+            let call = segment
+                .split_once(MISTRAL_ARGS)
+                .and_then(|(name, arguments)| {
+                    Some(CalledFunction {
+                        name: name.trim().to_string(),
+                        arguments: normalize_arguments(arguments)?,
+                    })
+                });
+            match call {
+                Some(call) => calls.push(call),
+                None => return Ok(ParsedToolCalls::text_only(text)),
+            }
+        }
+
+        Ok(ParsedToolCalls {
+            normal_text: normal_text.trim().to_string(),
+            calls,
+        })
+    }
+}
+
+pub struct Qwen3CoderParser;
+
+impl ToolCallParser for Qwen3CoderParser {
+    fn start_markers(&self) -> &[&'static str] {
+        &[TOOL_CALL_START]
+    }
+
+    fn parse(&self, text: &str) -> anyhow::Result<ParsedToolCalls> {
+        Ok(parse_tagged(text, TOOL_CALL_START, TOOL_CALL_END, |body| {
+            let calls: Vec<CalledFunction> = QWEN3_FUNCTION_REGEX
+                .captures_iter(body)
+                .map(|function| {
+                    let arguments: serde_json::Map<String, Value> = QWEN3_PARAMETER_REGEX
+                        .captures_iter(&function[2])
+                        .map(|parameter| {
+                            let value = parameter[2].trim_matches('\n');
+                            let value = serde_json::from_str(value)
+                                .unwrap_or_else(|_| Value::String(value.to_string()));
+                            (parameter[1].trim().to_string(), value)
+                        })
+                        .collect();
+                    CalledFunction {
+                        name: function[1].trim().to_string(),
+                        arguments: Value::Object(arguments).to_string(),
+                    }
+                })
+                .collect();
+            (!calls.is_empty()).then_some(calls)
+        }))
+    }
+}
+
+/// A tool call as emitted by most JSON based formats. Llama names the arguments `parameters`.
+#[derive(serde::Deserialize)]
+struct JsonToolCall {
+    name: String,
+    #[serde(default, alias = "parameters")]
+    arguments: Option<Value>,
+}
+
+impl From<JsonToolCall> for CalledFunction {
+    fn from(call: JsonToolCall) -> Self {
+        let arguments = match call.arguments {
+            // some models emit the arguments as an already serialized JSON string
+            Some(Value::String(arguments)) => arguments,
+            Some(Value::Null) | None => "{}".to_string(),
+            Some(arguments) => arguments.to_string(),
+        };
+        CalledFunction {
+            name: call.name,
+            arguments,
+        }
+    }
+}
+
+/// Parses one or more JSON tool calls. Accepts objects, arrays of objects, and several values
+/// separated by whitespace, `;` or `,`. Returns `None` if the text contains anything else.
+fn parse_json_calls(text: &str) -> Option<Vec<CalledFunction>> {
+    let is_separator = |c: char| c.is_whitespace() || c == ';' || c == ',';
+
+    let mut calls = Vec::new();
+    let mut rest = text.trim_start_matches(is_separator);
+    while !rest.is_empty() {
+        let mut values = serde_json::Deserializer::from_str(rest).into_iter::<Value>();
+        let value = values.next()?.ok()?;
+        let offset = values.byte_offset();
+
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    calls.push(serde_json::from_value::<JsonToolCall>(item).ok()?.into());
+                }
+            }
+            value @ Value::Object(_) => {
+                calls.push(serde_json::from_value::<JsonToolCall>(value).ok()?.into());
+            }
+            _ => return None,
+        }
+
+        rest = rest[offset..].trim_start_matches(is_separator);
+    }
+
+    (!calls.is_empty()).then_some(calls)
+}
+
+/// Re-serializes JSON arguments in compact form; `None` if they are not valid JSON.
+fn normalize_arguments(arguments: &str) -> Option<String> {
+    serde_json::from_str::<Value>(arguments.trim())
+        .ok()
+        .map(|arguments| arguments.to_string())
+}
+
+/// Walks every `start ... end` section of the text. Sections whose body parses are turned into
+/// tool calls, everything else is kept as regular text. A section left open by the end of the
+/// output runs to the end of the text.
+fn parse_tagged(
+    text: &str,
+    start: &str,
+    end: &str,
+    parse_body: impl Fn(&str) -> Option<Vec<CalledFunction>>,
+) -> ParsedToolCalls {
+    let mut normal_text = String::new();
+    let mut calls = Vec::new();
+    let mut rest = text;
+
+    while let Some(offset) = rest.find(start) {
+        normal_text.push_str(&rest[..offset]);
+        let body_start = offset + start.len();
+        let (body, section_end) = match rest[body_start..].find(end) {
+            Some(body_len) => (
+                &rest[body_start..body_start + body_len],
+                body_start + body_len + end.len(),
+            ),
+            None => (&rest[body_start..], rest.len()),
+        };
+
+        match parse_body(body) {
+            Some(found) => calls.extend(found),
+            None => normal_text.push_str(&rest[offset..section_end]),
+        }
+        rest = &rest[section_end..];
+    }
+    normal_text.push_str(rest);
+
+    if calls.is_empty() {
+        ParsedToolCalls::text_only(text)
+    } else {
+        ParsedToolCalls {
+            normal_text: normal_text.trim().to_string(),
+            calls,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str, text: &str) -> ParsedToolCalls {
+        ToolCallParserRegistry::default()
+            .get(name)
+            .unwrap()
+            .parse(text)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_json() {
+        let parsed = parse(
+            JSON_PARSER,
+            r#"[{"name": "a", "arguments": {"x": 1}}, {"name": "b", "parameters": {"y": 2}}]"#,
+        );
+        assert!(parsed.normal_text.is_empty());
+        assert_eq!(parsed.calls.len(), 2);
+        assert_eq!(parsed.calls[0].name, "a");
+        assert_eq!(parsed.calls[0].arguments, r#"{"x":1}"#);
+        assert_eq!(parsed.calls[1].name, "b");
+        assert_eq!(parsed.calls[1].arguments, r#"{"y":2}"#);
+
+        let parsed = parse(JSON_PARSER, "The answer is 42");
+        assert_eq!(parsed.normal_text, "The answer is 42");
+        assert!(parsed.calls.is_empty());
+    }
+
+    #[test]
+    fn test_hermes() {
+        let parsed = parse(
+            HERMES_PARSER,
+            "Let me check.\n<tool_call>\n{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\"}}\n</tool_call>\n<tool_call>\n{\"name\": \"get_time\", \"arguments\": {}}\n</tool_call>",
+        );
+        assert_eq!(parsed.normal_text, "Let me check.");
+        assert_eq!(parsed.calls.len(), 2);
+        assert_eq!(parsed.calls[0].name, "get_weather");
+        assert_eq!(parsed.calls[0].arguments, r#"{"city":"Paris"}"#);
+        assert_eq!(parsed.calls[1].name, "get_time");
+    }
+
+    #[test]
+    fn test_hermes_unclosed_tag() {
+        let parsed = parse(
+            HERMES_PARSER,
+            "<tool_call>{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\"}}",
+        );
+        assert_eq!(parsed.calls.len(), 1);
+        assert_eq!(parsed.calls[0].name, "get_weather");
+    }
+
+    #[test]
+    fn test_hermes_invalid_body_is_text() {
+        let text = "<tool_call>not json</tool_call>";
+        let parsed = parse(HERMES_PARSER, text);
+        assert_eq!(parsed.normal_text, text);
+        assert!(parsed.calls.is_empty());
+    }
+
+    #[test]
+    fn test_llama3_json() {
+        let parsed = parse(
+            LLAMA3_JSON_PARSER,
+            "<|python_tag|>{\"name\": \"get_weather\", \"parameters\": {\"city\": \"Paris\"}}; {\"name\": \"get_time\", \"parameters\": {}}",
+        );
+        assert!(parsed.normal_text.is_empty());
+        assert_eq!(parsed.calls.len(), 2);
+        assert_eq!(parsed.calls[0].arguments, r#"{"city":"Paris"}"#);
+        assert_eq!(parsed.calls[1].name, "get_time");
+
+        let parsed = parse(
+            LLAMA3_JSON_PARSER,
+            "{\"name\": \"get_weather\", \"parameters\": {\"city\": \"Paris\"}}",
+        );
+        assert_eq!(parsed.calls.len(), 1);
+    }
+
+    #[test]
+    fn test_mistral() {
+        let parsed = parse(
+            MISTRAL_PARSER,
+            "[TOOL_CALLS][{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\"}}]",
+        );
+        assert_eq!(parsed.calls.len(), 1);
+        assert_eq!(parsed.calls[0].name, "get_weather");
+
+        let parsed = parse(
+            MISTRAL_PARSER,
+            "[TOOL_CALLS]get_weather[ARGS]{\"city\": \"Paris\"}[TOOL_CALLS]get_time[ARGS]{}",
+        );
+        assert_eq!(parsed.calls.len(), 2);
+        assert_eq!(parsed.calls[0].arguments, r#"{"city":"Paris"}"#);
+        assert_eq!(parsed.calls[1].name, "get_time");
+    }
+
+    #[test]
+    fn test_qwen3_coder() {
+        let parsed = parse(
+            QWEN3_CODER_PARSER,
+            "Sure.\n<tool_call>\n<function=get_weather>\n<parameter=city>\nParis\n</parameter>\n<parameter=days>\n3\n</parameter>\n</function>\n</tool_call>",
+        );
+        assert_eq!(parsed.normal_text, "Sure.");
+        assert_eq!(parsed.calls.len(), 1);
+        assert_eq!(parsed.calls[0].name, "get_weather");
+        assert_eq!(parsed.calls[0].arguments, r#"{"city":"Paris","days":3}"#);
+    }
+
+    #[test]
+    fn test_registry_names() {
+        let registry = ToolCallParserRegistry::default();
+        assert_eq!(
+            registry.names(),
+            vec![
+                HERMES_PARSER,
+                JSON_PARSER,
+                LLAMA3_JSON_PARSER,
+                MISTRAL_PARSER,
+                QWEN3_CODER_PARSER
+            ]
+        );
+    }
+}
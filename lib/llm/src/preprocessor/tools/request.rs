@@ -15,6 +15,7 @@
 
 use std::collections::HashMap;
 
+use async_openai::types::{ChatCompletionTool, ChatCompletionToolChoiceOption};
 use serde_json::Value;
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
@@ -31,6 +32,9 @@ pub enum ToolChoice {
     #[serde(rename = "auto")]
     /// Allow automatic selection of any given tool, or none.
     Auto,
+    #[serde(rename = "required")]
+    /// Require selection of at least one of the given tools.
+    Required,
     #[serde(untagged)]
     /// Force selection of a given tool.
     Tool(Tool),
@@ -49,3 +53,54 @@ pub struct Tool {
     pub tp: ToolType,
     pub function: Function,
 }
+
+impl ToolChoice {
+    /// Resolves an OpenAI `tool_choice` against the `tools` offered in the same request.
+    ///
+    /// Returns `None` when the request does not enable tool calling, either because no tools
+    /// were provided or because the client explicitly asked for `"none"`.
+    pub fn from_openai(
+        tool_choice: Option<&ChatCompletionToolChoiceOption>,
+        tools: Option<&[ChatCompletionTool]>,
+    ) -> anyhow::Result<Option<Self>> {
+        let tools = match tools {
+            Some(tools) if !tools.is_empty() => tools,
+            _ => return Ok(None),
+        };
+
+        match tool_choice {
+            None | Some(ChatCompletionToolChoiceOption::Auto) => Ok(Some(ToolChoice::Auto)),
+            Some(ChatCompletionToolChoiceOption::None) => Ok(None),
+            Some(ChatCompletionToolChoiceOption::Required) => Ok(Some(ToolChoice::Required)),
+            Some(ChatCompletionToolChoiceOption::Named(named)) => {
+                let Some(tool) = tools
+                    .iter()
+                    .find(|tool| tool.function.name == named.function.name)
+                else {
+                    anyhow::bail!(
+                        "tool_choice references unknown function: {}",
+                        named.function.name
+                    );
+                };
+                Ok(Some(ToolChoice::Tool(Tool::from(tool))))
+            }
+        }
+    }
+}
+
+impl From<&ChatCompletionTool> for Tool {
+    fn from(tool: &ChatCompletionTool) -> Self {
+        Tool {
+            tp: ToolType::Function,
+            function: Function {
+                description: tool.function.description.clone(),
+                name: tool.function.name.clone(),
+                parameters: tool
+                    .function
+                    .parameters
+                    .clone()
+                    .and_then(|parameters| serde_json::from_value(parameters).ok()),
+            },
+        }
+    }
+}
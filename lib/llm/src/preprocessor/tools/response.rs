@@ -38,3 +38,32 @@ pub struct ToolCallResponse {
     pub tp: ToolCallType,
     pub function: CalledFunction,
 }
+
+impl ToolCallResponse {
+    /// Converts this tool call into the streaming delta form used by
+    /// [`async_openai::types::ChatCompletionStreamResponseDelta::tool_calls`].
+    pub fn to_chunk(&self, index: u32) -> async_openai::types::ChatCompletionMessageToolCallChunk {
+        async_openai::types::ChatCompletionMessageToolCallChunk {
+            index,
+            id: Some(self.id.clone()),
+            r#type: Some(async_openai::types::ChatCompletionToolType::Function),
+            function: Some(async_openai::types::FunctionCallStream {
+                name: Some(self.function.name.clone()),
+                arguments: Some(self.function.arguments.clone()),
+            }),
+        }
+    }
+}
+
+impl From<ToolCallResponse> for async_openai::types::ChatCompletionMessageToolCall {
+    fn from(call: ToolCallResponse) -> Self {
+        async_openai::types::ChatCompletionMessageToolCall {
+            id: call.id,
+            r#type: async_openai::types::ChatCompletionToolType::Function,
+            function: async_openai::types::FunctionCall {
+                name: call.function.name,
+                arguments: call.function.arguments,
+            },
+        }
+    }
+}
@@ -45,6 +45,18 @@ pub trait StopConditionsProvider {
     fn extract_stop_conditions(&self) -> Result<StopConditions>;
 }
 
+/// OutputOptionsProvider is a trait that allows the caller to extract the options controlling
+/// what information the inference engine returns in the response.
+pub trait OutputOptionsProvider {
+    fn extract_output_options(&self) -> Result<OutputOptions>;
+}
+
+/// GuidedDecodingProvider is a trait that allows the caller to extract the structured output
+/// constraint, if any, from the object that implements it.
+pub trait GuidedDecodingProvider {
+    fn extract_guided_decoding(&self) -> Result<Option<GuidedDecoding>>;
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum FinishReason {
     #[serde(rename = "eos")]
@@ -343,6 +355,30 @@ impl SamplingOptions {
     }
 }
 
+/// Constrains the text generated by the inference engine to a structured format.
+///
+/// Engines which support constrained sampling are expected to enforce the constraint at the token
+/// level; see [`crate::guided_decoding`] for the helpers used to translate it and to validate the
+/// output of engines which cannot.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuidedDecoding {
+    /// The output must be a JSON object.
+    JsonObject,
+
+    /// The output must be JSON matching the given JSON schema.
+    JsonSchema(serde_json::Value),
+
+    /// The output must fully match the given regular expression.
+    Regex(String),
+
+    /// The output must match the given grammar, in GBNF format.
+    Grammar(String),
+
+    /// The output must be exactly one of the given strings.
+    Choice(Vec<String>),
+}
+
 /// Collection of options that control what information the inference engine returns in the response.
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct OutputOptions {
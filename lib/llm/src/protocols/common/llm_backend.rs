@@ -21,6 +21,20 @@ use crate::protocols::TokenIdType;
 
 pub type TokenType = Option<String>;
 pub type LogProbs = Vec<f64>;
+pub type TopLogProbs = Vec<Vec<TopLogProb>>;
+
+/// One of the most likely candidate tokens at a given output position.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopLogProb {
+    /// Candidate token id
+    pub token_id: TokenIdType,
+
+    /// Detokenized candidate token; filled in by the Backend if the engine did not provide it
+    pub token: TokenType,
+
+    /// Log probability of the candidate token
+    pub logprob: f64,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BackendOutput {
@@ -40,6 +54,10 @@ pub struct BackendOutput {
     /// Optional log probabilities
     pub log_probs: Option<LogProbs>,
 
+    /// Optional most likely candidate tokens for each position of `token_ids`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<TopLogProbs>,
+
     // TODO: Enrich this with more information as can apply our first-level postprocessing
     // logic and return more detailed information
     pub finish_reason: Option<FinishReason>,
@@ -76,6 +94,11 @@ pub struct LLMEngineOutput {
     /// Optional log probabilities
     pub log_probs: Option<LogProbs>,
 
+    /// Optional most likely candidate tokens for each position of `token_ids`. The number of
+    /// candidates is requested via [`super::OutputOptions::logprobs`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<TopLogProbs>,
+
     // TODO: Enrich this with more information as can apply our first-level postprocessing
     // logic and return more detailed information
     pub finish_reason: Option<FinishReason>,
@@ -92,6 +115,7 @@ impl LLMEngineOutput {
             text: None,
             cum_log_probs: None,
             log_probs: None,
+            top_logprobs: None,
             finish_reason: Some(FinishReason::Cancelled),
             index: None,
         }
@@ -104,6 +128,7 @@ impl LLMEngineOutput {
             text: None,
             cum_log_probs: None,
             log_probs: None,
+            top_logprobs: None,
             finish_reason: Some(FinishReason::Stop),
             index: None,
         }
@@ -116,6 +141,7 @@ impl LLMEngineOutput {
             text: None,
             cum_log_probs: None,
             log_probs: None,
+            top_logprobs: None,
             finish_reason: Some(FinishReason::Length),
             index: None,
         }
@@ -128,6 +154,7 @@ impl LLMEngineOutput {
             text: None,
             cum_log_probs: None,
             log_probs: None,
+            top_logprobs: None,
             finish_reason: Some(FinishReason::Error(err_msg)),
             index: None,
         }
@@ -16,7 +16,7 @@
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
-use super::{SamplingOptions, StopConditions};
+use super::{GuidedDecoding, OutputOptions, SamplingOptions, StopConditions};
 use crate::protocols::TokenIdType;
 
 /// [`PreprocessedRequest`] is the internal representation of an LLM request. The [`dynamo.llm-preprocessor`]
@@ -38,6 +38,17 @@ pub struct PreprocessedRequest {
     /// are needed.
     pub sampling_options: SamplingOptions,
 
+    /// OutputOptions control what information the inference engine returns in the response,
+    /// such as the number of log probabilities per output token.
+    #[builder(default)]
+    #[serde(default)]
+    pub output_options: OutputOptions,
+
+    /// Optional structured output constraint the engine should enforce while sampling.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guided_decoding: Option<GuidedDecoding>,
+
     /// The EOS token ID(s) for the Model
     /// Not every backend needs this, but those that do can find it here.
     /// TODO - refactor this to a better location
@@ -55,6 +66,18 @@ pub struct PreprocessedRequest {
     /// Estimated number of prefix hit tokens (only used in kv aware routing)
     #[builder(default)]
     pub estimated_prefix_hit_num_blocks: Option<u32>,
+
+    /// The LoRA adapter to generate with, `None` for the base model.
+    /// KV aware routing only matches the cached blocks of the same adapter.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lora_id: Option<u64>,
+
+    /// The data parallel rank of the worker the KV router chose for this request, `None` when
+    /// the worker is free to pick one.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dp_rank: Option<u32>,
 }
 
 impl PreprocessedRequest {
@@ -28,6 +28,7 @@ pub mod completions;
 pub mod embeddings;
 pub mod models;
 pub mod nvext;
+pub mod responses;
 
 /// Minimum allowed value for OpenAI's `temperature` sampling option
 pub const MIN_TEMPERATURE: f32 = 0.0;
@@ -65,6 +66,12 @@ pub const MAX_PRESENCE_PENALTY: f32 = 2.0;
 /// Allowed range of values for OpenAI's `presence_penalty` sampling option
 pub const PRESENCE_PENALTY_RANGE: (f32, f32) = (MIN_PRESENCE_PENALTY, MAX_PRESENCE_PENALTY);
 
+/// Allowed range of values for OpenAI's chat completion `top_logprobs` output option
+pub const TOP_LOGPROBS_RANGE: (u8, u8) = (0, 20);
+
+/// Allowed range of values for OpenAI's legacy completion `logprobs` output option
+pub const COMPLETION_LOGPROBS_RANGE: (u8, u8) = (0, 5);
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AnnotatedDelta<R> {
     pub delta: R,
@@ -180,6 +187,63 @@ where
     Ok(Some(value))
 }
 
+/// Log probability of a single generated token along with its most likely alternatives.
+struct TokenLogprob {
+    token: String,
+    logprob: f32,
+    top_logprobs: Vec<(String, f32)>,
+}
+
+/// Extracts the log probabilities of the tokens in a backend response, keeping at most
+/// `num_top_logprobs` of the most likely alternatives for each token. Returns `None` if the
+/// engine did not report log probabilities.
+fn token_logprobs(
+    delta: &common::llm_backend::BackendOutput,
+    num_top_logprobs: usize,
+) -> Option<Vec<TokenLogprob>> {
+    let log_probs = delta.log_probs.as_ref()?;
+
+    let token_logprobs = log_probs
+        .iter()
+        .enumerate()
+        .map(|(position, logprob)| {
+            let token = delta
+                .tokens
+                .get(position)
+                .cloned()
+                .flatten()
+                .unwrap_or_default();
+
+            let mut candidates = delta
+                .top_logprobs
+                .as_ref()
+                .and_then(|top_logprobs| top_logprobs.get(position))
+                .cloned()
+                .unwrap_or_default();
+            candidates.sort_by(|a, b| b.logprob.total_cmp(&a.logprob));
+
+            let top_logprobs = candidates
+                .into_iter()
+                .take(num_top_logprobs)
+                .map(|candidate| {
+                    (
+                        candidate.token.unwrap_or_default(),
+                        candidate.logprob as f32,
+                    )
+                })
+                .collect();
+
+            TokenLogprob {
+                token,
+                logprob: *logprob as f32,
+                top_logprobs,
+            }
+        })
+        .collect();
+
+    Some(token_logprobs)
+}
+
 pub trait DeltaGeneratorExt<ResponseType: Send + Sync + 'static + std::fmt::Debug>:
     Send + Sync + 'static
 {
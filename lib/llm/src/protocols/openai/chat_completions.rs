@@ -21,6 +21,8 @@ use super::nvext::NvExt;
 use super::nvext::NvExtProvider;
 use super::OpenAISamplingOptionsProvider;
 use super::OpenAIStopConditionsProvider;
+use super::{validate_range, TOP_LOGPROBS_RANGE};
+use crate::protocols::common::{self, GuidedDecodingProvider, OutputOptionsProvider};
 
 mod aggregator;
 mod delta;
@@ -137,6 +139,57 @@ impl OpenAISamplingOptionsProvider for NvCreateChatCompletionRequest {
     }
 }
 
+/// Implements `OutputOptionsProvider` for `NvCreateChatCompletionRequest`,
+/// mapping OpenAI's `logprobs` and `top_logprobs` onto the engine output options.
+impl OutputOptionsProvider for NvCreateChatCompletionRequest {
+    fn extract_output_options(&self) -> anyhow::Result<common::OutputOptions> {
+        let top_logprobs = validate_range(self.inner.top_logprobs, &TOP_LOGPROBS_RANGE)
+            .map_err(|e| anyhow::anyhow!("Error validating top_logprobs: {}", e))?;
+
+        let logprobs = match (self.inner.logprobs.unwrap_or(false), top_logprobs) {
+            (true, top_logprobs) => Some(top_logprobs.unwrap_or(0) as u32),
+            (false, Some(_)) => anyhow::bail!("top_logprobs requires logprobs to be true"),
+            (false, None) => None,
+        };
+
+        Ok(common::OutputOptions {
+            logprobs,
+            ..Default::default()
+        })
+    }
+}
+
+/// Implements `GuidedDecodingProvider` for `NvCreateChatCompletionRequest`,
+/// combining OpenAI's `response_format` with the `guided_*` [`NvExt`] fields.
+impl GuidedDecodingProvider for NvCreateChatCompletionRequest {
+    fn extract_guided_decoding(&self) -> anyhow::Result<Option<common::GuidedDecoding>> {
+        let response_format = match &self.inner.response_format {
+            None | Some(async_openai::types::ResponseFormat::Text) => None,
+            Some(async_openai::types::ResponseFormat::JsonObject) => {
+                Some(common::GuidedDecoding::JsonObject)
+            }
+            Some(async_openai::types::ResponseFormat::JsonSchema { json_schema }) => {
+                match &json_schema.schema {
+                    Some(schema) => Some(common::GuidedDecoding::JsonSchema(schema.clone())),
+                    None => Some(common::GuidedDecoding::JsonObject),
+                }
+            }
+        };
+
+        let nvext = self
+            .nvext
+            .as_ref()
+            .and_then(|nvext| nvext.guided_decoding());
+
+        match (response_format, nvext) {
+            (Some(_), Some(_)) => {
+                anyhow::bail!("response_format cannot be combined with nvext guided decoding")
+            }
+            (response_format, nvext) => Ok(response_format.or(nvext)),
+        }
+    }
+}
+
 /// Implements `OpenAIStopConditionsProvider` for `NvCreateChatCompletionRequest`,
 /// providing access to stop conditions that control chat completion behavior.
 impl OpenAIStopConditionsProvider for NvCreateChatCompletionRequest {
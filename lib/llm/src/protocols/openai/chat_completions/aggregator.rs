@@ -60,6 +60,8 @@ struct DeltaChoice {
     finish_reason: Option<async_openai::types::FinishReason>,
     /// Optional log probabilities for the chat choice.
     logprobs: Option<async_openai::types::ChatChoiceLogprobs>,
+    /// Tool calls accumulated from streamed tool call chunks, keyed by tool call index.
+    tool_calls: Vec<async_openai::types::ChatCompletionMessageToolCall>,
 }
 
 impl Default for DeltaAggregator {
@@ -134,14 +136,25 @@ impl DeltaAggregator {
                                     text: "".to_string(),
                                     role: choice.delta.role,
                                     finish_reason: None,
-                                    logprobs: choice.logprobs,
+                                    logprobs: None,
+                                    tool_calls: Vec::new(),
                                 });
 
+                        // Append log probabilities if available.
+                        if let Some(logprobs) = choice.logprobs {
+                            state_choice.merge_logprobs(logprobs);
+                        }
+
                         // Append content if available.
                         if let Some(content) = &choice.delta.content {
                             state_choice.text.push_str(content);
                         }
 
+                        // Merge tool call chunks if available.
+                        for chunk in choice.delta.tool_calls.unwrap_or_default() {
+                            state_choice.merge_tool_call_chunk(chunk);
+                        }
+
                         // Update finish reason if provided.
                         if let Some(finish_reason) = choice.finish_reason {
                             state_choice.finish_reason = Some(finish_reason);
@@ -186,6 +199,56 @@ impl DeltaAggregator {
     }
 }
 
+impl DeltaChoice {
+    /// Appends the per-token log probabilities of a streamed chunk to the accumulated ones.
+    fn merge_logprobs(&mut self, logprobs: async_openai::types::ChatChoiceLogprobs) {
+        let Some(state) = &mut self.logprobs else {
+            self.logprobs = Some(logprobs);
+            return;
+        };
+
+        if let Some(content) = logprobs.content {
+            state.content.get_or_insert_with(Vec::new).extend(content);
+        }
+        if let Some(refusal) = logprobs.refusal {
+            state.refusal.get_or_insert_with(Vec::new).extend(refusal);
+        }
+    }
+
+    /// Merges a streamed tool call chunk into the accumulated tool calls. The first chunk for a
+    /// given index carries the id and function name; subsequent chunks append to the arguments.
+    fn merge_tool_call_chunk(
+        &mut self,
+        chunk: async_openai::types::ChatCompletionMessageToolCallChunk,
+    ) {
+        let index = chunk.index as usize;
+        while self.tool_calls.len() <= index {
+            self.tool_calls
+                .push(async_openai::types::ChatCompletionMessageToolCall {
+                    id: String::new(),
+                    r#type: async_openai::types::ChatCompletionToolType::Function,
+                    function: async_openai::types::FunctionCall {
+                        name: String::new(),
+                        arguments: String::new(),
+                    },
+                });
+        }
+
+        let tool_call = &mut self.tool_calls[index];
+        if let Some(id) = chunk.id {
+            tool_call.id = id;
+        }
+        if let Some(function) = chunk.function {
+            if let Some(name) = function.name {
+                tool_call.function.name.push_str(&name);
+            }
+            if let Some(arguments) = function.arguments {
+                tool_call.function.arguments.push_str(&arguments);
+            }
+        }
+    }
+}
+
 #[allow(deprecated)]
 impl From<DeltaChoice> for async_openai::types::ChatChoice {
     /// Converts a [`DeltaChoice`] into an [`async_openai::types::ChatChoice`].
@@ -193,11 +256,20 @@ impl From<DeltaChoice> for async_openai::types::ChatChoice {
     /// # Note
     /// The `function_call` field is deprecated.
     fn from(delta: DeltaChoice) -> Self {
+        // a message that only carries tool calls has no content
+        let (content, tool_calls) = if delta.tool_calls.is_empty() {
+            (Some(delta.text), None)
+        } else if delta.text.is_empty() {
+            (None, Some(delta.tool_calls))
+        } else {
+            (Some(delta.text), Some(delta.tool_calls))
+        };
+
         async_openai::types::ChatChoice {
             message: async_openai::types::ChatCompletionResponseMessage {
                 role: delta.role.expect("delta should have a Role"),
-                content: Some(delta.text),
-                tool_calls: None,
+                content,
+                tool_calls,
                 refusal: None,
                 function_call: None,
                 audio: None,
@@ -244,6 +316,11 @@ impl NvCreateChatCompletionResponse {
 mod tests {
 
     use super::*;
+    use crate::protocols::common::llm_backend::{BackendOutput, TopLogProb};
+    use crate::protocols::openai::chat_completions::delta::{
+        DeltaGenerator, DeltaGeneratorOptions,
+    };
+    use crate::protocols::openai::DeltaGeneratorExt;
     use futures::stream;
 
     #[allow(deprecated)]
@@ -460,4 +537,117 @@ mod tests {
         );
         assert_eq!(choice1.message.role, async_openai::types::Role::Assistant);
     }
+
+    #[allow(deprecated)]
+    #[tokio::test]
+    async fn test_tool_call_chunks() {
+        let mut annotated_delta =
+            create_test_delta(0, "", Some(async_openai::types::Role::Assistant), None);
+        let data = annotated_delta.data.as_mut().unwrap();
+        data.inner.choices[0].delta.content = None;
+        data.inner.choices[0].delta.tool_calls = Some(vec![
+            async_openai::types::ChatCompletionMessageToolCallChunk {
+                index: 0,
+                id: Some("call-1".to_string()),
+                r#type: Some(async_openai::types::ChatCompletionToolType::Function),
+                function: Some(async_openai::types::FunctionCallStream {
+                    name: Some("get_weather".to_string()),
+                    arguments: Some("{\"city\":".to_string()),
+                }),
+            },
+        ]);
+
+        let mut annotated_finish = create_test_delta(
+            0,
+            "",
+            None,
+            Some(async_openai::types::FinishReason::ToolCalls),
+        );
+        let data = annotated_finish.data.as_mut().unwrap();
+        data.inner.choices[0].delta.content = None;
+        data.inner.choices[0].delta.tool_calls = Some(vec![
+            async_openai::types::ChatCompletionMessageToolCallChunk {
+                index: 0,
+                id: None,
+                r#type: None,
+                function: Some(async_openai::types::FunctionCallStream {
+                    name: None,
+                    arguments: Some("\"Paris\"}".to_string()),
+                }),
+            },
+        ]);
+
+        let stream = Box::pin(stream::iter(vec![annotated_delta, annotated_finish]));
+        let response = DeltaAggregator::apply(stream).await.unwrap();
+
+        let choice = &response.inner.choices[0];
+        assert!(choice.message.content.is_none());
+        assert_eq!(
+            choice.finish_reason,
+            Some(async_openai::types::FinishReason::ToolCalls)
+        );
+        let tool_calls = choice.message.tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls.len(), 1);
+        assert_eq!(tool_calls[0].id, "call-1");
+        assert_eq!(tool_calls[0].function.name, "get_weather");
+        assert_eq!(tool_calls[0].function.arguments, "{\"city\":\"Paris\"}");
+    }
+
+    #[tokio::test]
+    async fn test_logprobs() {
+        let mut generator = DeltaGenerator::new(
+            "test".to_string(),
+            DeltaGeneratorOptions {
+                enable_usage: false,
+                enable_logprobs: true,
+                top_logprobs: 1,
+            },
+        );
+
+        let deltas = [("Hi", -0.5), ("!", -1.0)]
+            .into_iter()
+            .map(|(token, logprob)| BackendOutput {
+                token_ids: vec![1],
+                tokens: vec![Some(token.to_string())],
+                text: Some(token.to_string()),
+                cum_log_probs: None,
+                log_probs: Some(vec![logprob]),
+                top_logprobs: Some(vec![vec![
+                    TopLogProb {
+                        token_id: 2,
+                        token: Some("b".to_string()),
+                        logprob: -2.0,
+                    },
+                    TopLogProb {
+                        token_id: 1,
+                        token: Some(token.to_string()),
+                        logprob,
+                    },
+                ]]),
+                finish_reason: None,
+                index: None,
+            })
+            .map(|output| {
+                Annotated::from_data(generator.choice_from_postprocessor(output).unwrap())
+            })
+            .collect::<Vec<_>>();
+
+        let response = DeltaAggregator::apply(Box::pin(stream::iter(deltas)))
+            .await
+            .unwrap();
+
+        let content = response.inner.choices[0]
+            .logprobs
+            .as_ref()
+            .and_then(|logprobs| logprobs.content.as_ref())
+            .unwrap();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0].token, "Hi");
+        assert_eq!(content[0].logprob, -0.5);
+        assert_eq!(content[0].bytes, Some(b"Hi".to_vec()));
+        assert_eq!(content[0].top_logprobs.len(), 1);
+        assert_eq!(content[0].top_logprobs[0].token, "Hi");
+        assert_eq!(content[1].token, "!");
+        assert_eq!(content[1].top_logprobs[0].logprob, -1.0);
+    }
 }
@@ -14,7 +14,7 @@
 // limitations under the License.
 
 use super::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse};
-use crate::protocols::common;
+use crate::protocols::{common, openai::token_logprobs};
 
 /// Provides a method for generating a [`DeltaGenerator`] from a chat completion request.
 impl NvCreateChatCompletionRequest {
@@ -26,6 +26,7 @@ impl NvCreateChatCompletionRequest {
         let options = DeltaGeneratorOptions {
             enable_usage: true,
             enable_logprobs: self.inner.logprobs.unwrap_or(false),
+            top_logprobs: self.inner.top_logprobs.unwrap_or(0),
         };
 
         DeltaGenerator::new(self.inner.model.clone(), options)
@@ -39,6 +40,8 @@ pub struct DeltaGeneratorOptions {
     pub enable_usage: bool,
     /// Determines whether log probabilities should be included in the response.
     pub enable_logprobs: bool,
+    /// Number of most likely alternative tokens to include for each generated token.
+    pub top_logprobs: u8,
 }
 
 /// Generates incremental chat completion responses in a streaming fashion.
@@ -112,6 +115,45 @@ impl DeltaGenerator {
         self.usage.prompt_tokens = isl;
     }
 
+    /// Converts the log probabilities reported by the backend into their OpenAI representation.
+    ///
+    /// # Arguments
+    /// * `delta` - The backend response containing the generated tokens and their log probabilities.
+    ///
+    /// # Returns
+    /// * `Some(ChatChoiceLogprobs)` if log probabilities were requested and reported by the engine.
+    pub fn create_logprobs(
+        &self,
+        delta: &crate::protocols::common::llm_backend::BackendOutput,
+    ) -> Option<async_openai::types::ChatChoiceLogprobs> {
+        if !self.options.enable_logprobs {
+            return None;
+        }
+
+        let content = token_logprobs(delta, self.options.top_logprobs as usize)?
+            .into_iter()
+            .map(|token| async_openai::types::ChatCompletionTokenLogprob {
+                bytes: Some(token.token.as_bytes().to_vec()),
+                token: token.token,
+                logprob: token.logprob,
+                top_logprobs: token
+                    .top_logprobs
+                    .into_iter()
+                    .map(|(token, logprob)| async_openai::types::TopLogprobs {
+                        bytes: Some(token.as_bytes().to_vec()),
+                        token,
+                        logprob,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Some(async_openai::types::ChatChoiceLogprobs {
+            content: Some(content),
+            refusal: None,
+        })
+    }
+
     /// Creates a choice within a chat completion response.
     ///
     /// # Arguments
@@ -204,8 +246,7 @@ impl crate::protocols::openai::DeltaGeneratorExt<NvCreateChatCompletionStreamRes
             self.usage.completion_tokens += token_length;
         }
 
-        // TODO: Implement log probabilities aggregation.
-        let logprobs = None;
+        let logprobs = self.create_logprobs(&delta);
 
         // Map backend finish reasons to OpenAI's finish reasons.
         let finish_reason = match delta.finish_reason {
@@ -19,9 +19,13 @@ use serde::{Deserialize, Serialize};
 use validator::Validate;
 
 use super::{
-    common::{self, SamplingOptionsProvider, StopConditionsProvider},
+    common::{
+        self, GuidedDecodingProvider, OutputOptionsProvider, SamplingOptionsProvider,
+        StopConditionsProvider,
+    },
     nvext::{NvExt, NvExtProvider},
-    ContentProvider, OpenAISamplingOptionsProvider, OpenAIStopConditionsProvider,
+    validate_range, ContentProvider, OpenAISamplingOptionsProvider, OpenAIStopConditionsProvider,
+    COMPLETION_LOGPROBS_RANGE,
 };
 
 mod aggregator;
@@ -129,6 +133,27 @@ impl OpenAISamplingOptionsProvider for NvCreateCompletionRequest {
     }
 }
 
+impl OutputOptionsProvider for NvCreateCompletionRequest {
+    fn extract_output_options(&self) -> anyhow::Result<common::OutputOptions> {
+        let logprobs = validate_range(self.inner.logprobs, &COMPLETION_LOGPROBS_RANGE)
+            .map_err(|e| anyhow::anyhow!("Error validating logprobs: {}", e))?;
+
+        Ok(common::OutputOptions {
+            logprobs: logprobs.map(u32::from),
+            ..Default::default()
+        })
+    }
+}
+
+impl GuidedDecodingProvider for NvCreateCompletionRequest {
+    fn extract_guided_decoding(&self) -> anyhow::Result<Option<common::GuidedDecoding>> {
+        Ok(self
+            .nvext
+            .as_ref()
+            .and_then(|nvext| nvext.guided_decoding()))
+    }
+}
+
 impl OpenAIStopConditionsProvider for NvCreateCompletionRequest {
     fn get_max_tokens(&self) -> Option<u32> {
         self.inner.max_tokens
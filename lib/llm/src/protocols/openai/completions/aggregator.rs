@@ -103,12 +103,14 @@ impl DeltaAggregator {
                                     index: choice.index,
                                     text: "".to_string(),
                                     finish_reason: None,
-                                    logprobs: choice.logprobs,
+                                    logprobs: None,
                                 });
 
                         state_choice.text.push_str(&choice.text);
 
-                        // TODO - handle logprobs
+                        if let Some(logprobs) = choice.logprobs {
+                            state_choice.merge_logprobs(logprobs);
+                        }
 
                         // Handle CompletionFinishReason -> FinishReason conversation
                         state_choice.finish_reason = match choice.finish_reason {
@@ -161,6 +163,20 @@ impl DeltaAggregator {
     }
 }
 
+impl DeltaChoice {
+    fn merge_logprobs(&mut self, logprobs: async_openai::types::Logprobs) {
+        match &mut self.logprobs {
+            Some(state) => {
+                state.tokens.extend(logprobs.tokens);
+                state.token_logprobs.extend(logprobs.token_logprobs);
+                state.top_logprobs.extend(logprobs.top_logprobs);
+                state.text_offset.extend(logprobs.text_offset);
+            }
+            None => self.logprobs = Some(logprobs),
+        }
+    }
+}
+
 impl From<DeltaChoice> for async_openai::types::Choice {
     fn from(delta: DeltaChoice) -> Self {
         let finish_reason = delta.finish_reason.map(Into::into);
@@ -196,7 +212,10 @@ mod tests {
     use futures::stream;
 
     use super::*;
+    use crate::protocols::common::llm_backend::{BackendOutput, TopLogProb};
+    use crate::protocols::openai::completions::delta::{DeltaGenerator, DeltaGeneratorOptions};
     use crate::protocols::openai::completions::NvCreateCompletionResponse;
+    use crate::protocols::openai::DeltaGeneratorExt;
 
     fn create_test_delta(
         index: u32,
@@ -398,4 +417,69 @@ mod tests {
             Some(async_openai::types::CompletionFinishReason::Stop)
         );
     }
+
+    fn backend_output(token: &str, logprob: f64) -> BackendOutput {
+        BackendOutput {
+            token_ids: vec![1],
+            tokens: vec![Some(token.to_string())],
+            text: Some(token.to_string()),
+            cum_log_probs: None,
+            log_probs: Some(vec![logprob]),
+            top_logprobs: Some(vec![vec![
+                TopLogProb {
+                    token_id: 2,
+                    token: Some("b".to_string()),
+                    logprob: -2.0,
+                },
+                TopLogProb {
+                    token_id: 1,
+                    token: Some(token.to_string()),
+                    logprob,
+                },
+                TopLogProb {
+                    token_id: 3,
+                    token: Some("c".to_string()),
+                    logprob: -3.0,
+                },
+            ]]),
+            finish_reason: None,
+            index: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_logprobs() {
+        let mut generator = DeltaGenerator::new(
+            "test".to_string(),
+            DeltaGeneratorOptions {
+                enable_usage: false,
+                enable_logprobs: true,
+                top_logprobs: 2,
+            },
+        );
+
+        let deltas = [backend_output("Hi", -0.5), backend_output("!", -1.0)]
+            .into_iter()
+            .map(|output| {
+                Annotated::from_data(generator.choice_from_postprocessor(output).unwrap())
+            })
+            .collect::<Vec<_>>();
+
+        let response = DeltaAggregator::apply(Box::pin(stream::iter(deltas)))
+            .await
+            .unwrap();
+
+        let logprobs = response.inner.choices[0].logprobs.as_ref().unwrap();
+        assert_eq!(logprobs.tokens, vec!["Hi", "!"]);
+        assert_eq!(logprobs.token_logprobs, vec![Some(-0.5), Some(-1.0)]);
+        assert_eq!(logprobs.text_offset, vec![0, 2]);
+        assert_eq!(
+            logprobs.top_logprobs[0],
+            serde_json::json!({"Hi": -0.5, "b": -2.0})
+        );
+        assert_eq!(
+            logprobs.top_logprobs[1],
+            serde_json::json!({"!": -1.0, "b": -2.0})
+        );
+    }
 }
@@ -13,8 +13,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
 use super::{NvCreateCompletionRequest, NvCreateCompletionResponse};
-use crate::protocols::common;
+use crate::protocols::{common, openai::token_logprobs};
 
 impl NvCreateCompletionRequest {
     // put this method on the request
@@ -22,7 +24,8 @@ impl NvCreateCompletionRequest {
     pub fn response_generator(&self) -> DeltaGenerator {
         let options = DeltaGeneratorOptions {
             enable_usage: true,
-            enable_logprobs: false,
+            enable_logprobs: self.inner.logprobs.is_some(),
+            top_logprobs: self.inner.logprobs.unwrap_or(0),
         };
 
         DeltaGenerator::new(self.inner.model.clone(), options)
@@ -33,6 +36,7 @@ impl NvCreateCompletionRequest {
 pub struct DeltaGeneratorOptions {
     pub enable_usage: bool,
     pub enable_logprobs: bool,
+    pub top_logprobs: u8,
 }
 
 #[derive(Debug, Clone)]
@@ -44,6 +48,8 @@ pub struct DeltaGenerator {
     system_fingerprint: Option<String>,
     usage: async_openai::types::CompletionUsage,
     options: DeltaGeneratorOptions,
+    // character offset of the next token in the text of each choice
+    text_offsets: HashMap<u32, u32>,
 }
 
 impl DeltaGenerator {
@@ -75,6 +81,7 @@ impl DeltaGenerator {
             system_fingerprint: None,
             usage,
             options,
+            text_offsets: HashMap::new(),
         }
     }
 
@@ -82,11 +89,51 @@ impl DeltaGenerator {
         self.usage.prompt_tokens = isl;
     }
 
+    /// Converts the log probabilities reported by the backend for the choice at `index` into
+    /// their OpenAI representation. Returns `None` unless `logprobs` was requested and reported.
+    pub fn create_logprobs(
+        &mut self,
+        index: u32,
+        delta: &common::llm_backend::BackendOutput,
+    ) -> Option<async_openai::types::Logprobs> {
+        if !self.options.enable_logprobs {
+            return None;
+        }
+
+        let token_logprobs = token_logprobs(delta, self.options.top_logprobs as usize)?;
+        let text_offset = self.text_offsets.entry(index).or_default();
+
+        let mut logprobs = async_openai::types::Logprobs {
+            tokens: Vec::with_capacity(token_logprobs.len()),
+            token_logprobs: Vec::with_capacity(token_logprobs.len()),
+            top_logprobs: Vec::with_capacity(token_logprobs.len()),
+            text_offset: Vec::with_capacity(token_logprobs.len()),
+        };
+
+        for token in token_logprobs {
+            let top_logprobs = token
+                .top_logprobs
+                .into_iter()
+                .map(|(token, logprob)| (token, serde_json::Value::from(logprob)))
+                .collect::<serde_json::Map<_, _>>();
+
+            logprobs.text_offset.push(*text_offset);
+            *text_offset += token.token.chars().count() as u32;
+
+            logprobs.tokens.push(token.token);
+            logprobs.token_logprobs.push(Some(token.logprob));
+            logprobs.top_logprobs.push(top_logprobs.into());
+        }
+
+        Some(logprobs)
+    }
+
     pub fn create_choice(
         &self,
         index: u32,
         text: Option<String>,
         finish_reason: Option<async_openai::types::CompletionFinishReason>,
+        logprobs: Option<async_openai::types::Logprobs>,
     ) -> NvCreateCompletionResponse {
         // todo - update for tool calling
 
@@ -105,7 +152,7 @@ impl DeltaGenerator {
                 text: text.unwrap_or_default(),
                 index,
                 finish_reason,
-                logprobs: None,
+                logprobs,
             }],
             usage: if self.options.enable_usage {
                 Some(usage)
@@ -136,13 +183,12 @@ impl crate::protocols::openai::DeltaGeneratorExt<NvCreateCompletionResponse> for
             self.usage.completion_tokens += token_length;
         }
 
-        // TODO logprobs
-
         let finish_reason = delta.finish_reason.map(Into::into);
 
         // create choice
         let index = delta.index.unwrap_or(0);
-        let response = self.create_choice(index, delta.text.clone(), finish_reason);
+        let logprobs = self.create_logprobs(index, &delta);
+        let response = self.create_choice(index, delta.text.clone(), finish_reason, logprobs);
         Ok(response)
     }
 
@@ -17,6 +17,9 @@ use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use validator::{Validate, ValidationError};
 
+use crate::protocols::common::GuidedDecoding;
+use dynamo_runtime::pipeline::network::egress::queue::Priority;
+
 pub trait NvExtProvider {
     fn nvext(&self) -> Option<&NvExt>;
     fn raw_prompt(&self) -> Option<String>;
@@ -61,6 +64,37 @@ pub struct NvExt {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[builder(default, setter(strip_option))]
     pub annotations: Option<Vec<String>>,
+
+    /// Guided decoding: constrains the output to JSON matching the given JSON schema.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub guided_json: Option<serde_json::Value>,
+
+    /// Guided decoding: constrains the output to fully match the given regular expression.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub guided_regex: Option<String>,
+
+    /// Guided decoding: constrains the output to match the given grammar, in GBNF format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub guided_grammar: Option<String>,
+
+    /// Guided decoding: constrains the output to be exactly one of the given choices.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub guided_choice: Option<Vec<String>>,
+
+    /// Admission priority of the request when the frontend queues requests: high, normal or low.
+    /// Takes precedence over the `x-request-priority` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub priority: Option<Priority>,
+
+    /// ID of the LoRA adapter to generate with, as loaded on the workers. The base model if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub lora_id: Option<u64>,
 }
 
 impl Default for NvExt {
@@ -73,9 +107,37 @@ impl NvExt {
     pub fn builder() -> NvExtBuilder {
         NvExtBuilder::default()
     }
+
+    /// Returns the guided decoding constraint requested via the `guided_*` fields, if any.
+    pub fn guided_decoding(&self) -> Option<GuidedDecoding> {
+        if let Some(schema) = &self.guided_json {
+            return Some(GuidedDecoding::JsonSchema(schema.clone()));
+        }
+        if let Some(regex) = &self.guided_regex {
+            return Some(GuidedDecoding::Regex(regex.clone()));
+        }
+        if let Some(grammar) = &self.guided_grammar {
+            return Some(GuidedDecoding::Grammar(grammar.clone()));
+        }
+        self.guided_choice.clone().map(GuidedDecoding::Choice)
+    }
 }
 
-fn validate_nv_ext(_nv_ext: &NvExt) -> Result<(), ValidationError> {
+fn validate_nv_ext(nv_ext: &NvExt) -> Result<(), ValidationError> {
+    let guided = [
+        nv_ext.guided_json.is_some(),
+        nv_ext.guided_regex.is_some(),
+        nv_ext.guided_grammar.is_some(),
+        nv_ext.guided_choice.is_some(),
+    ];
+    if guided.into_iter().filter(|set| *set).count() > 1 {
+        let mut error = ValidationError::new("guided_decoding");
+        error.message = Some(
+            "only one of guided_json, guided_regex, guided_grammar or guided_choice may be set"
+                .into(),
+        );
+        return Err(error);
+    }
     Ok(())
 }
 
@@ -136,6 +198,27 @@ mod tests {
         assert!(nv_ext.validate().is_ok());
     }
 
+    // Test that only one guided decoding constraint may be requested
+    #[test]
+    fn test_nv_ext_guided_decoding() {
+        let nv_ext = NvExt::builder()
+            .guided_regex("[0-9]+".to_string())
+            .build()
+            .unwrap();
+        assert!(nv_ext.validate().is_ok());
+        assert_eq!(
+            nv_ext.guided_decoding(),
+            Some(GuidedDecoding::Regex("[0-9]+".to_string()))
+        );
+
+        let nv_ext = NvExt::builder()
+            .guided_regex("[0-9]+".to_string())
+            .guided_choice(vec!["yes".to_string(), "no".to_string()])
+            .build()
+            .unwrap();
+        assert!(nv_ext.validate().is_err());
+    }
+
     // Test invalid `top_k` validation using proptest
     proptest! {
         #[test]
@@ -0,0 +1,780 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OpenAI Responses API (`/v1/responses`)
+//!
+//! `async-openai` does not model the Responses API, so the wire types are defined here. Requests
+//! are translated onto [`NvCreateChatCompletionRequest`] and served by the chat completions
+//! engine; the resulting chat completion stream is turned back into Responses output items and
+//! semantic streaming events by the [`ResponseEventGenerator`].
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_openai::types::{
+    ChatCompletionMessageToolCall, ChatCompletionNamedToolChoice,
+    ChatCompletionRequestAssistantMessage, ChatCompletionRequestAssistantMessageContent,
+    ChatCompletionRequestMessage, ChatCompletionRequestMessageContentPartImage,
+    ChatCompletionRequestMessageContentPartText, ChatCompletionRequestSystemMessage,
+    ChatCompletionRequestSystemMessageContent, ChatCompletionRequestToolMessage,
+    ChatCompletionRequestToolMessageContent, ChatCompletionRequestUserMessage,
+    ChatCompletionRequestUserMessageContent, ChatCompletionRequestUserMessageContentPart,
+    ChatCompletionTool, ChatCompletionToolChoiceOption, ChatCompletionToolType, FunctionCall,
+    FunctionName, FunctionObject, ImageDetail, ImageUrl, ResponseFormat, ResponseFormatJsonSchema,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use super::chat_completions::NvCreateChatCompletionRequest;
+use super::nvext::NvExt;
+
+mod events;
+
+pub use events::{
+    response_event_stream, ResponseEvent, ResponseEventGenerator, ResponseStreamEvent,
+};
+
+/// A request structure for creating a model response, mirroring OpenAI's `CreateResponse`
+/// with [`NvExt`] extensions.
+///
+/// # Fields
+/// - `inner`: The OpenAI Responses request, embedded using `serde(flatten)`.
+/// - `nvext`: The optional NVIDIA extension field, forwarded to the chat completions engine.
+#[derive(Serialize, Deserialize, Validate, Debug, Clone)]
+pub struct NvCreateResponse {
+    #[serde(flatten)]
+    pub inner: CreateResponse,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nvext: Option<NvExt>,
+}
+
+/// The subset of OpenAI's `CreateResponse` request that can be served by a chat completions
+/// engine. Server side state (`store`, `previous_response_id`) is accepted but not supported.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CreateResponse {
+    /// Model used to generate the response
+    pub model: String,
+
+    /// Text, or a list of input items, used to generate the response
+    pub input: ResponseInput,
+
+    /// System (or developer) message inserted ahead of the input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+
+    /// Function tools the model may call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ResponseTool>>,
+
+    /// How the model should select which tool to call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ResponseToolChoice>,
+
+    /// Whether the model may call multiple tools in one turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    /// Upper bound on the number of generated tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+
+    /// Stream the response as server-sent semantic events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+
+    /// Text output configuration, i.e. structured outputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<ResponseTextConfig>,
+
+    /// Key-value pairs echoed back on the response object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, String>>,
+
+    /// Not supported; responses are not stored by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_response_id: Option<String>,
+
+    /// Accepted for compatibility; responses are never stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+/// Input to a response: either a single user text or a list of conversation items
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ResponseInput {
+    Text(String),
+    Items(Vec<InputItem>),
+}
+
+impl Default for ResponseInput {
+    fn default() -> Self {
+        ResponseInput::Text(String::new())
+    }
+}
+
+/// A conversation item provided as input.
+///
+/// Messages may omit their `type`, so items are distinguished by their required fields.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum InputItem {
+    Message(InputMessage),
+    FunctionCall(FunctionCallItem),
+    FunctionCallOutput(FunctionCallOutputItem),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum InputRole {
+    User,
+    Assistant,
+    System,
+    Developer,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputMessage {
+    pub role: InputRole,
+    pub content: InputContent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum InputContent {
+    Text(String),
+    Parts(Vec<InputContentPart>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputContentPart {
+    InputText {
+        text: String,
+    },
+    /// Text produced by the model in an earlier turn
+    OutputText {
+        text: String,
+    },
+    InputImage {
+        image_url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<ImageDetail>,
+    },
+}
+
+/// A function call previously emitted by the model
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FunctionCallItem {
+    pub call_id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
+/// The result of a function call, provided by the client
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FunctionCallOutputItem {
+    pub call_id: String,
+    pub output: String,
+}
+
+/// A tool the model may call. Only function tools are served; hosted tools (web search, file
+/// search, computer use) are rejected when the request is parsed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseTool {
+    Function {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parameters: Option<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strict: Option<bool>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ResponseToolChoice {
+    Mode(ToolChoiceMode),
+    Function(NamedFunctionChoice),
+}
+
+impl Default for ResponseToolChoice {
+    fn default() -> Self {
+        ResponseToolChoice::Mode(ToolChoiceMode::Auto)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoiceMode {
+    None,
+    Auto,
+    Required,
+}
+
+/// Forces a call to the named function; `type` is always `function`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NamedFunctionChoice {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResponseTextConfig {
+    pub format: ResponseTextFormat,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseTextFormat {
+    Text,
+    JsonObject,
+    JsonSchema {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        schema: Option<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strict: Option<bool>,
+    },
+}
+
+/// The response object returned by `/v1/responses`, and carried by the lifecycle streaming
+/// events (`response.created`, `response.completed`, ...).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NvResponse {
+    pub id: String,
+    /// Always `response`
+    pub object: String,
+    /// Seconds since epoch
+    pub created_at: u64,
+    pub model: String,
+    pub status: ResponseStatus,
+    pub output: Vec<OutputItem>,
+    pub usage: Option<ResponseUsage>,
+    pub error: Option<ResponseError>,
+    pub incomplete_details: Option<IncompleteDetails>,
+    pub instructions: Option<String>,
+    pub max_output_tokens: Option<u32>,
+    pub metadata: HashMap<String, String>,
+    pub parallel_tool_calls: bool,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub tool_choice: ResponseToolChoice,
+    pub tools: Vec<ResponseTool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<ResponseTextConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStatus {
+    InProgress,
+    Completed,
+    Incomplete,
+    Failed,
+}
+
+/// Status of an individual output item
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    InProgress,
+    Completed,
+    Incomplete,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputItem {
+    Message {
+        id: String,
+        /// Always `assistant`
+        role: String,
+        status: ItemStatus,
+        content: Vec<OutputContent>,
+    },
+    FunctionCall {
+        id: String,
+        call_id: String,
+        name: String,
+        arguments: String,
+        status: ItemStatus,
+    },
+}
+
+impl OutputItem {
+    pub fn id(&self) -> &str {
+        match self {
+            OutputItem::Message { id, .. } => id,
+            OutputItem::FunctionCall { id, .. } => id,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputContent {
+    OutputText {
+        text: String,
+        annotations: Vec<serde_json::Value>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponseUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResponseError {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IncompleteDetails {
+    /// `max_output_tokens` or `content_filter`
+    pub reason: String,
+}
+
+impl NvResponse {
+    /// Creates the `in_progress` response object for a request; `request_id` is used to derive
+    /// the `resp_` prefixed response id.
+    pub fn new(request: &CreateResponse, request_id: &str) -> Self {
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        NvResponse {
+            id: format!("resp_{}", request_id.replace('-', "")),
+            object: "response".to_string(),
+            created_at,
+            model: request.model.clone(),
+            status: ResponseStatus::InProgress,
+            output: Vec::new(),
+            usage: None,
+            error: None,
+            incomplete_details: None,
+            instructions: request.instructions.clone(),
+            max_output_tokens: request.max_output_tokens,
+            metadata: request.metadata.clone().unwrap_or_default(),
+            parallel_tool_calls: request.parallel_tool_calls.unwrap_or(true),
+            temperature: request.temperature,
+            top_p: request.top_p,
+            tool_choice: request.tool_choice.clone().unwrap_or_default(),
+            tools: request.tools.clone().unwrap_or_default(),
+            text: request.text.clone(),
+        }
+    }
+
+    /// Concatenated text of all `output_text` parts, i.e. the SDKs' `output_text` helper
+    pub fn output_text(&self) -> String {
+        self.output
+            .iter()
+            .filter_map(|item| match item {
+                OutputItem::Message { content, .. } => Some(content),
+                _ => None,
+            })
+            .flatten()
+            .map(|part| match part {
+                OutputContent::OutputText { text, .. } => text.as_str(),
+            })
+            .collect()
+    }
+}
+
+/// Translates a Responses request onto a chat completions request.
+///
+/// `instructions` become a leading system message, input items become chat messages
+/// (consecutive function calls are grouped into a single assistant message) and
+/// `text.format` becomes the chat `response_format`.
+impl TryFrom<NvCreateResponse> for NvCreateChatCompletionRequest {
+    type Error = anyhow::Error;
+
+    fn try_from(request: NvCreateResponse) -> Result<Self> {
+        let NvCreateResponse { inner, nvext } = request;
+
+        if inner.previous_response_id.is_some() {
+            anyhow::bail!(
+                "previous_response_id is not supported; responses are not stored by the server"
+            );
+        }
+
+        let mut messages = Vec::new();
+        if let Some(instructions) = inner.instructions {
+            messages.push(system_message(instructions));
+        }
+
+        match inner.input {
+            ResponseInput::Text(text) => messages.push(user_message(InputContent::Text(text))?),
+            ResponseInput::Items(items) => {
+                for item in items {
+                    push_input_item(&mut messages, item)?;
+                }
+            }
+        }
+
+        if messages.is_empty() {
+            anyhow::bail!("input must contain at least one item");
+        }
+
+        let tools = inner.tools.map(|tools| {
+            tools
+                .into_iter()
+                .map(|tool| match tool {
+                    ResponseTool::Function {
+                        name,
+                        description,
+                        parameters,
+                        strict,
+                    } => ChatCompletionTool {
+                        r#type: ChatCompletionToolType::Function,
+                        function: FunctionObject {
+                            name,
+                            description,
+                            parameters,
+                            strict,
+                        },
+                    },
+                })
+                .collect()
+        });
+
+        let tool_choice = inner.tool_choice.map(|choice| match choice {
+            ResponseToolChoice::Mode(ToolChoiceMode::None) => ChatCompletionToolChoiceOption::None,
+            ResponseToolChoice::Mode(ToolChoiceMode::Auto) => ChatCompletionToolChoiceOption::Auto,
+            ResponseToolChoice::Mode(ToolChoiceMode::Required) => {
+                ChatCompletionToolChoiceOption::Required
+            }
+            ResponseToolChoice::Function(named) => {
+                ChatCompletionToolChoiceOption::Named(ChatCompletionNamedToolChoice {
+                    r#type: ChatCompletionToolType::Function,
+                    function: FunctionName { name: named.name },
+                })
+            }
+        });
+
+        let response_format = inner.text.map(|text| match text.format {
+            ResponseTextFormat::Text => ResponseFormat::Text,
+            ResponseTextFormat::JsonObject => ResponseFormat::JsonObject,
+            ResponseTextFormat::JsonSchema {
+                name,
+                description,
+                schema,
+                strict,
+            } => ResponseFormat::JsonSchema {
+                json_schema: ResponseFormatJsonSchema {
+                    description,
+                    name,
+                    schema,
+                    strict,
+                },
+            },
+        });
+
+        let inner = async_openai::types::CreateChatCompletionRequest {
+            model: inner.model,
+            messages,
+            temperature: inner.temperature,
+            top_p: inner.top_p,
+            max_completion_tokens: inner.max_output_tokens,
+            stream: inner.stream,
+            tools,
+            tool_choice,
+            parallel_tool_calls: inner.parallel_tool_calls,
+            response_format,
+            user: inner.user,
+            ..Default::default()
+        };
+
+        Ok(NvCreateChatCompletionRequest { inner, nvext })
+    }
+}
+
+fn push_input_item(
+    messages: &mut Vec<ChatCompletionRequestMessage>,
+    item: InputItem,
+) -> Result<()> {
+    match item {
+        InputItem::Message(message) => {
+            let message = match message.role {
+                InputRole::User => user_message(message.content)?,
+                // developer messages carry the same weight as system prompts for chat templates
+                InputRole::System | InputRole::Developer => {
+                    system_message(text_content(message.content, "system")?)
+                }
+                InputRole::Assistant => {
+                    ChatCompletionRequestMessage::Assistant(ChatCompletionRequestAssistantMessage {
+                        content: Some(ChatCompletionRequestAssistantMessageContent::Text(
+                            text_content(message.content, "assistant")?,
+                        )),
+                        ..Default::default()
+                    })
+                }
+            };
+            messages.push(message);
+        }
+        InputItem::FunctionCall(call) => {
+            let tool_call = ChatCompletionMessageToolCall {
+                id: call.call_id,
+                r#type: ChatCompletionToolType::Function,
+                function: FunctionCall {
+                    name: call.name,
+                    arguments: call.arguments,
+                },
+            };
+
+            // parallel calls and the text preceding them belong to the same assistant turn
+            if let Some(ChatCompletionRequestMessage::Assistant(assistant)) = messages.last_mut() {
+                assistant
+                    .tool_calls
+                    .get_or_insert_with(Vec::new)
+                    .push(tool_call);
+            } else {
+                messages.push(ChatCompletionRequestMessage::Assistant(
+                    ChatCompletionRequestAssistantMessage {
+                        tool_calls: Some(vec![tool_call]),
+                        ..Default::default()
+                    },
+                ));
+            }
+        }
+        InputItem::FunctionCallOutput(output) => {
+            messages.push(ChatCompletionRequestMessage::Tool(
+                ChatCompletionRequestToolMessage {
+                    content: ChatCompletionRequestToolMessageContent::Text(output.output),
+                    tool_call_id: output.call_id,
+                },
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn system_message(text: String) -> ChatCompletionRequestMessage {
+    ChatCompletionRequestMessage::System(ChatCompletionRequestSystemMessage {
+        content: ChatCompletionRequestSystemMessageContent::Text(text),
+        name: None,
+    })
+}
+
+/// Text-only content is flattened into a plain string, which every chat template understands;
+/// content with images is forwarded as content parts.
+fn user_message(content: InputContent) -> Result<ChatCompletionRequestMessage> {
+    let content = match content {
+        InputContent::Parts(parts)
+            if parts
+                .iter()
+                .any(|part| matches!(part, InputContentPart::InputImage { .. })) =>
+        {
+            let parts = parts
+                .into_iter()
+                .map(|part| match part {
+                    InputContentPart::InputText { text }
+                    | InputContentPart::OutputText { text } => {
+                        ChatCompletionRequestUserMessageContentPart::Text(
+                            ChatCompletionRequestMessageContentPartText { text },
+                        )
+                    }
+                    InputContentPart::InputImage { image_url, detail } => {
+                        ChatCompletionRequestUserMessageContentPart::ImageUrl(
+                            ChatCompletionRequestMessageContentPartImage {
+                                image_url: ImageUrl {
+                                    url: image_url,
+                                    detail,
+                                },
+                            },
+                        )
+                    }
+                })
+                .collect();
+            ChatCompletionRequestUserMessageContent::Array(parts)
+        }
+        content => ChatCompletionRequestUserMessageContent::Text(text_content(content, "user")?),
+    };
+
+    Ok(ChatCompletionRequestMessage::User(
+        ChatCompletionRequestUserMessage {
+            content,
+            name: None,
+        },
+    ))
+}
+
+fn text_content(content: InputContent, role: &str) -> Result<String> {
+    match content {
+        InputContent::Text(text) => Ok(text),
+        InputContent::Parts(parts) => parts
+            .into_iter()
+            .map(|part| match part {
+                InputContentPart::InputText { text } | InputContentPart::OutputText { text } => {
+                    Ok(text)
+                }
+                InputContentPart::InputImage { .. } => {
+                    anyhow::bail!(
+                        "input_image content is only supported in user messages, not {role}"
+                    )
+                }
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(|texts| texts.join("\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: serde_json::Value) -> NvCreateChatCompletionRequest {
+        let request: NvCreateResponse = serde_json::from_value(value).unwrap();
+        request.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_text_input_with_instructions() {
+        let chat = parse(serde_json::json!({
+            "model": "test-model",
+            "input": "Tell me a joke",
+            "instructions": "Be brief",
+            "max_output_tokens": 64,
+            "temperature": 0.5,
+            "stream": true,
+        }));
+
+        assert_eq!(chat.inner.model, "test-model");
+        assert_eq!(chat.inner.max_completion_tokens, Some(64));
+        assert_eq!(chat.inner.temperature, Some(0.5));
+        assert_eq!(chat.inner.stream, Some(true));
+
+        let messages = serde_json::to_value(&chat.inner.messages).unwrap();
+        assert_eq!(
+            messages,
+            serde_json::json!([
+                {"role": "system", "content": "Be brief"},
+                {"role": "user", "content": "Tell me a joke"},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_function_call_items() {
+        let chat = parse(serde_json::json!({
+            "model": "test-model",
+            "input": [
+                {"role": "user", "content": [{"type": "input_text", "text": "Weather in Paris?"}]},
+                {"type": "function_call", "call_id": "call_1", "name": "get_weather", "arguments": "{\"city\":\"Paris\"}"},
+                {"type": "function_call", "call_id": "call_2", "name": "get_time", "arguments": "{}"},
+                {"type": "function_call_output", "call_id": "call_1", "output": "sunny"},
+                {"type": "function_call_output", "call_id": "call_2", "output": "noon"},
+            ],
+            "tools": [{
+                "type": "function",
+                "name": "get_weather",
+                "parameters": {"type": "object", "properties": {"city": {"type": "string"}}},
+            }],
+            "tool_choice": {"type": "function", "name": "get_weather"},
+        }));
+
+        assert_eq!(chat.inner.messages.len(), 4);
+        match &chat.inner.messages[1] {
+            ChatCompletionRequestMessage::Assistant(assistant) => {
+                let calls = assistant.tool_calls.as_ref().unwrap();
+                assert_eq!(calls.len(), 2);
+                assert_eq!(calls[0].id, "call_1");
+                assert_eq!(calls[0].function.name, "get_weather");
+                assert_eq!(calls[1].function.name, "get_time");
+            }
+            other => panic!("expected assistant message, got {other:?}"),
+        }
+        assert!(matches!(
+            &chat.inner.messages[2],
+            ChatCompletionRequestMessage::Tool(tool) if tool.tool_call_id == "call_1"
+        ));
+
+        let tools = chat.inner.tools.unwrap();
+        assert_eq!(tools[0].function.name, "get_weather");
+        assert!(matches!(
+            chat.inner.tool_choice,
+            Some(ChatCompletionToolChoiceOption::Named(ref named)) if named.function.name == "get_weather"
+        ));
+    }
+
+    #[test]
+    fn test_text_format() {
+        let chat = parse(serde_json::json!({
+            "model": "test-model",
+            "input": "list three colors",
+            "text": {"format": {"type": "json_schema", "name": "colors", "schema": {"type": "array"}}},
+        }));
+
+        match chat.inner.response_format {
+            Some(ResponseFormat::JsonSchema { json_schema }) => {
+                assert_eq!(json_schema.name, "colors");
+                assert_eq!(
+                    json_schema.schema,
+                    Some(serde_json::json!({"type": "array"}))
+                );
+            }
+            other => panic!("unexpected response_format: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_requests() {
+        let request: NvCreateResponse = serde_json::from_value(serde_json::json!({
+            "model": "test-model",
+            "input": "hello",
+            "previous_response_id": "resp_123",
+        }))
+        .unwrap();
+        assert!(NvCreateChatCompletionRequest::try_from(request).is_err());
+
+        let request: NvCreateResponse = serde_json::from_value(serde_json::json!({
+            "model": "test-model",
+            "input": [{"role": "assistant", "content": [{"type": "input_image", "image_url": "http://x/y.png"}]}],
+        }))
+        .unwrap();
+        assert!(NvCreateChatCompletionRequest::try_from(request).is_err());
+
+        // hosted tools are rejected when parsing
+        assert!(
+            serde_json::from_value::<NvCreateResponse>(serde_json::json!({
+                "model": "test-model",
+                "input": "hello",
+                "tools": [{"type": "web_search_preview"}],
+            }))
+            .is_err()
+        );
+    }
+}
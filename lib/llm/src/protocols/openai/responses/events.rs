@@ -0,0 +1,721 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use async_openai::types::{CreateChatCompletionStreamResponse, FinishReason};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    IncompleteDetails, ItemStatus, NvResponse, OutputContent, OutputItem, ResponseError,
+    ResponseStatus, ResponseUsage,
+};
+use crate::protocols::openai::chat_completions::NvCreateChatCompletionStreamResponse;
+use crate::types::Annotated;
+
+/// A semantic streaming event of the Responses API, as sent on the `/v1/responses` SSE stream.
+/// The SSE `event` name is the `type` of the event, see [`ResponseStreamEvent::event_type`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResponseStreamEvent {
+    #[serde(flatten)]
+    pub event: ResponseEvent,
+
+    /// Position of the event in the stream, starting at zero
+    pub sequence_number: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum ResponseEvent {
+    #[serde(rename = "response.created")]
+    Created { response: NvResponse },
+
+    #[serde(rename = "response.in_progress")]
+    InProgress { response: NvResponse },
+
+    #[serde(rename = "response.output_item.added")]
+    OutputItemAdded { output_index: u32, item: OutputItem },
+
+    #[serde(rename = "response.content_part.added")]
+    ContentPartAdded {
+        item_id: String,
+        output_index: u32,
+        content_index: u32,
+        part: OutputContent,
+    },
+
+    #[serde(rename = "response.output_text.delta")]
+    OutputTextDelta {
+        item_id: String,
+        output_index: u32,
+        content_index: u32,
+        delta: String,
+    },
+
+    #[serde(rename = "response.output_text.done")]
+    OutputTextDone {
+        item_id: String,
+        output_index: u32,
+        content_index: u32,
+        text: String,
+    },
+
+    #[serde(rename = "response.content_part.done")]
+    ContentPartDone {
+        item_id: String,
+        output_index: u32,
+        content_index: u32,
+        part: OutputContent,
+    },
+
+    #[serde(rename = "response.function_call_arguments.delta")]
+    FunctionCallArgumentsDelta {
+        item_id: String,
+        output_index: u32,
+        delta: String,
+    },
+
+    #[serde(rename = "response.function_call_arguments.done")]
+    FunctionCallArgumentsDone {
+        item_id: String,
+        output_index: u32,
+        arguments: String,
+    },
+
+    #[serde(rename = "response.output_item.done")]
+    OutputItemDone { output_index: u32, item: OutputItem },
+
+    #[serde(rename = "response.completed")]
+    Completed { response: NvResponse },
+
+    #[serde(rename = "response.incomplete")]
+    Incomplete { response: NvResponse },
+
+    #[serde(rename = "response.failed")]
+    Failed { response: NvResponse },
+}
+
+impl ResponseStreamEvent {
+    /// The `type` of the event, used as the SSE event name
+    pub fn event_type(&self) -> &'static str {
+        match self.event {
+            ResponseEvent::Created { .. } => "response.created",
+            ResponseEvent::InProgress { .. } => "response.in_progress",
+            ResponseEvent::OutputItemAdded { .. } => "response.output_item.added",
+            ResponseEvent::ContentPartAdded { .. } => "response.content_part.added",
+            ResponseEvent::OutputTextDelta { .. } => "response.output_text.delta",
+            ResponseEvent::OutputTextDone { .. } => "response.output_text.done",
+            ResponseEvent::ContentPartDone { .. } => "response.content_part.done",
+            ResponseEvent::FunctionCallArgumentsDelta { .. } => {
+                "response.function_call_arguments.delta"
+            }
+            ResponseEvent::FunctionCallArgumentsDone { .. } => {
+                "response.function_call_arguments.done"
+            }
+            ResponseEvent::OutputItemDone { .. } => "response.output_item.done",
+            ResponseEvent::Completed { .. } => "response.completed",
+            ResponseEvent::Incomplete { .. } => "response.incomplete",
+            ResponseEvent::Failed { .. } => "response.failed",
+        }
+    }
+}
+
+/// Builds a Responses API response, and its streaming events, from the chunks of a chat
+/// completion stream.
+///
+/// Only the first choice is considered; the Responses API always generates a single output.
+/// Output items are appended to the in-progress [`NvResponse`] as they are opened, so the
+/// snapshot carried by the lifecycle events is always consistent with the item events.
+pub struct ResponseEventGenerator {
+    response: NvResponse,
+    sequence_number: u64,
+
+    /// Output index of the message item receiving text, if one is open
+    message: Option<usize>,
+
+    /// Open function calls as (chat tool call index, output index)
+    function_calls: Vec<(u32, usize)>,
+
+    finish_reason: Option<FinishReason>,
+    finished: bool,
+}
+
+impl ResponseEventGenerator {
+    pub fn new(response: NvResponse) -> Self {
+        Self {
+            response,
+            sequence_number: 0,
+            message: None,
+            function_calls: Vec::new(),
+            finish_reason: None,
+            finished: false,
+        }
+    }
+
+    /// Events announcing the response: `response.created` and `response.in_progress`
+    pub fn start(&mut self) -> Vec<ResponseStreamEvent> {
+        vec![
+            self.event(ResponseEvent::Created {
+                response: self.response.clone(),
+            }),
+            self.event(ResponseEvent::InProgress {
+                response: self.response.clone(),
+            }),
+        ]
+    }
+
+    /// Returns true once a terminal event has been generated
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Consumes the generator, returning the response object
+    pub fn into_response(self) -> NvResponse {
+        self.response
+    }
+
+    /// Processes one annotated chat completion chunk; an `error` annotation fails the response.
+    pub fn process_annotated(
+        &mut self,
+        annotated: Annotated<NvCreateChatCompletionStreamResponse>,
+    ) -> Vec<ResponseStreamEvent> {
+        if annotated.event.as_deref() == Some("error") {
+            let message = annotated
+                .comment
+                .map(|comments| comments.join(" -- "))
+                .unwrap_or_else(|| "unspecified error".to_string());
+            return self.fail(message);
+        }
+
+        match annotated.data {
+            Some(chunk) => self.process_chunk(&chunk.inner),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn process_chunk(
+        &mut self,
+        chunk: &CreateChatCompletionStreamResponse,
+    ) -> Vec<ResponseStreamEvent> {
+        let mut events = Vec::new();
+        if self.finished {
+            return events;
+        }
+
+        if let Some(usage) = &chunk.usage {
+            self.response.usage = Some(ResponseUsage {
+                input_tokens: usage.prompt_tokens,
+                output_tokens: usage.completion_tokens,
+                total_tokens: usage.total_tokens,
+            });
+        }
+
+        let Some(choice) = chunk.choices.iter().find(|choice| choice.index == 0) else {
+            return events;
+        };
+
+        if let Some(content) = choice.delta.content.as_deref().filter(|c| !c.is_empty()) {
+            let output_index = self.open_message(&mut events);
+            if let OutputItem::Message {
+                id, content: parts, ..
+            } = &mut self.response.output[output_index]
+            {
+                let OutputContent::OutputText { text, .. } = &mut parts[0];
+                text.push_str(content);
+                let item_id = id.clone();
+                events.push(self.event(ResponseEvent::OutputTextDelta {
+                    item_id,
+                    output_index: output_index as u32,
+                    content_index: 0,
+                    delta: content.to_string(),
+                }));
+            }
+        }
+
+        for tool_call in choice.delta.tool_calls.iter().flatten() {
+            let output_index = match self
+                .function_calls
+                .iter()
+                .find(|(index, _)| *index == tool_call.index)
+            {
+                Some((_, output_index)) => *output_index,
+                None => {
+                    // text generated ahead of a call is complete once the call starts
+                    self.close_message(&mut events);
+
+                    let name = tool_call
+                        .function
+                        .as_ref()
+                        .and_then(|function| function.name.clone())
+                        .unwrap_or_default();
+                    let call_id = tool_call
+                        .id
+                        .clone()
+                        .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple()));
+                    let item = OutputItem::FunctionCall {
+                        id: format!("fc_{}", uuid::Uuid::new_v4().simple()),
+                        call_id,
+                        name,
+                        arguments: String::new(),
+                        status: ItemStatus::InProgress,
+                    };
+
+                    let output_index = self.response.output.len();
+                    self.response.output.push(item.clone());
+                    self.function_calls.push((tool_call.index, output_index));
+                    events.push(self.event(ResponseEvent::OutputItemAdded {
+                        output_index: output_index as u32,
+                        item,
+                    }));
+                    output_index
+                }
+            };
+
+            let delta = tool_call
+                .function
+                .as_ref()
+                .and_then(|function| function.arguments.as_deref())
+                .unwrap_or_default();
+            if delta.is_empty() {
+                continue;
+            }
+
+            if let OutputItem::FunctionCall { id, arguments, .. } =
+                &mut self.response.output[output_index]
+            {
+                arguments.push_str(delta);
+                let item_id = id.clone();
+                events.push(self.event(ResponseEvent::FunctionCallArgumentsDelta {
+                    item_id,
+                    output_index: output_index as u32,
+                    delta: delta.to_string(),
+                }));
+            }
+        }
+
+        if let Some(finish_reason) = choice.finish_reason {
+            self.finish_reason = Some(finish_reason);
+        }
+
+        events
+    }
+
+    /// Closes all open output items and generates the terminal event: `response.incomplete` if
+    /// generation stopped on the token limit or a content filter, `response.completed` otherwise.
+    pub fn finish(&mut self) -> Vec<ResponseStreamEvent> {
+        let mut events = Vec::new();
+        if self.finished {
+            return events;
+        }
+
+        let incomplete_reason = match self.finish_reason {
+            Some(FinishReason::Length) => Some("max_output_tokens"),
+            Some(FinishReason::ContentFilter) => Some("content_filter"),
+            _ => None,
+        };
+
+        self.close_message(&mut events);
+        for (_, output_index) in std::mem::take(&mut self.function_calls) {
+            if let OutputItem::FunctionCall {
+                id,
+                arguments,
+                status,
+                ..
+            } = &mut self.response.output[output_index]
+            {
+                *status = ItemStatus::Completed;
+                let event = ResponseEvent::FunctionCallArgumentsDone {
+                    item_id: id.clone(),
+                    output_index: output_index as u32,
+                    arguments: arguments.clone(),
+                };
+                events.push(self.event(event));
+            }
+            events.push(self.event(ResponseEvent::OutputItemDone {
+                output_index: output_index as u32,
+                item: self.response.output[output_index].clone(),
+            }));
+        }
+
+        self.finished = true;
+        match incomplete_reason {
+            Some(reason) => {
+                self.response.status = ResponseStatus::Incomplete;
+                self.response.incomplete_details = Some(IncompleteDetails {
+                    reason: reason.to_string(),
+                });
+                events.push(self.event(ResponseEvent::Incomplete {
+                    response: self.response.clone(),
+                }));
+            }
+            None => {
+                self.response.status = ResponseStatus::Completed;
+                events.push(self.event(ResponseEvent::Completed {
+                    response: self.response.clone(),
+                }));
+            }
+        }
+
+        events
+    }
+
+    /// Fails the response, generating `response.failed`. No further events are generated.
+    pub fn fail(&mut self, message: String) -> Vec<ResponseStreamEvent> {
+        if self.finished {
+            return Vec::new();
+        }
+        self.finished = true;
+        self.response.status = ResponseStatus::Failed;
+        self.response.error = Some(ResponseError {
+            code: "server_error".to_string(),
+            message,
+        });
+        vec![self.event(ResponseEvent::Failed {
+            response: self.response.clone(),
+        })]
+    }
+
+    /// Returns the output index of the open message item, opening one if needed
+    fn open_message(&mut self, events: &mut Vec<ResponseStreamEvent>) -> usize {
+        if let Some(output_index) = self.message {
+            return output_index;
+        }
+
+        let id = format!("msg_{}", uuid::Uuid::new_v4().simple());
+        let part = OutputContent::OutputText {
+            text: String::new(),
+            annotations: Vec::new(),
+        };
+        let output_index = self.response.output.len();
+        self.response.output.push(OutputItem::Message {
+            id: id.clone(),
+            role: "assistant".to_string(),
+            status: ItemStatus::InProgress,
+            content: vec![part.clone()],
+        });
+        self.message = Some(output_index);
+
+        events.push(self.event(ResponseEvent::OutputItemAdded {
+            output_index: output_index as u32,
+            item: OutputItem::Message {
+                id: id.clone(),
+                role: "assistant".to_string(),
+                status: ItemStatus::InProgress,
+                content: Vec::new(),
+            },
+        }));
+        events.push(self.event(ResponseEvent::ContentPartAdded {
+            item_id: id,
+            output_index: output_index as u32,
+            content_index: 0,
+            part,
+        }));
+        output_index
+    }
+
+    fn close_message(&mut self, events: &mut Vec<ResponseStreamEvent>) {
+        let Some(output_index) = self.message.take() else {
+            return;
+        };
+
+        let item_status = match self.finish_reason {
+            Some(FinishReason::Length) | Some(FinishReason::ContentFilter) => {
+                ItemStatus::Incomplete
+            }
+            _ => ItemStatus::Completed,
+        };
+
+        let OutputItem::Message {
+            id,
+            status,
+            content,
+            ..
+        } = &mut self.response.output[output_index]
+        else {
+            return;
+        };
+        *status = item_status;
+        let item_id = id.clone();
+        let part = content[0].clone();
+        let OutputContent::OutputText { text, .. } = &part;
+
+        let text_done = ResponseEvent::OutputTextDone {
+            item_id: item_id.clone(),
+            output_index: output_index as u32,
+            content_index: 0,
+            text: text.clone(),
+        };
+        events.push(self.event(text_done));
+        events.push(self.event(ResponseEvent::ContentPartDone {
+            item_id,
+            output_index: output_index as u32,
+            content_index: 0,
+            part,
+        }));
+        events.push(self.event(ResponseEvent::OutputItemDone {
+            output_index: output_index as u32,
+            item: self.response.output[output_index].clone(),
+        }));
+    }
+
+    fn event(&mut self, event: ResponseEvent) -> ResponseStreamEvent {
+        let sequence_number = self.sequence_number;
+        self.sequence_number += 1;
+        ResponseStreamEvent {
+            event,
+            sequence_number,
+        }
+    }
+}
+
+/// Converts a chat completion stream into the semantic event stream of a response. The stream
+/// always starts with `response.created` and ends with a terminal event.
+pub fn response_event_stream<S>(
+    stream: S,
+    generator: ResponseEventGenerator,
+) -> impl Stream<Item = ResponseStreamEvent> + Send
+where
+    S: Stream<Item = Annotated<NvCreateChatCompletionStreamResponse>> + Send + Unpin + 'static,
+{
+    let mut generator = generator;
+    let pending: VecDeque<ResponseStreamEvent> = generator.start().into();
+
+    futures::stream::unfold(
+        (stream, generator, pending),
+        |(mut stream, mut generator, mut pending)| async move {
+            loop {
+                if let Some(event) = pending.pop_front() {
+                    return Some((event, (stream, generator, pending)));
+                }
+                if generator.is_finished() {
+                    return None;
+                }
+                match stream.next().await {
+                    Some(annotated) => pending.extend(generator.process_annotated(annotated)),
+                    None => pending.extend(generator.finish()),
+                }
+            }
+        },
+    )
+}
+
+impl NvResponse {
+    /// Folds a chat completion stream into a response object; a failed response is returned
+    /// as an error.
+    pub async fn from_annotated_stream<S>(
+        stream: S,
+        mut generator: ResponseEventGenerator,
+    ) -> Result<NvResponse>
+    where
+        S: Stream<Item = Annotated<NvCreateChatCompletionStreamResponse>> + Unpin,
+    {
+        let mut stream = stream;
+        while let Some(annotated) = stream.next().await {
+            generator.process_annotated(annotated);
+            if generator.is_finished() {
+                break;
+            }
+        }
+        generator.finish();
+
+        let response = generator.into_response();
+        if let Some(error) = &response.error {
+            anyhow::bail!("{}", error.message);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::CreateResponse;
+    use super::*;
+    use async_openai::types::{
+        ChatChoiceStream, ChatCompletionMessageToolCallChunk, ChatCompletionStreamResponseDelta,
+        ChatCompletionToolType, CompletionUsage, FunctionCallStream,
+    };
+
+    #[allow(deprecated)]
+    fn chunk(
+        content: Option<&str>,
+        tool_calls: Option<Vec<ChatCompletionMessageToolCallChunk>>,
+        finish_reason: Option<FinishReason>,
+    ) -> Annotated<NvCreateChatCompletionStreamResponse> {
+        // ALLOW: function_call is deprecated
+        let delta = ChatCompletionStreamResponseDelta {
+            content: content.map(str::to_string),
+            function_call: None,
+            tool_calls,
+            role: None,
+            refusal: None,
+        };
+        let inner = CreateChatCompletionStreamResponse {
+            id: "test_id".to_string(),
+            model: "test-model".to_string(),
+            created: 1234567890,
+            service_tier: None,
+            usage: Some(CompletionUsage {
+                prompt_tokens: 5,
+                completion_tokens: 3,
+                total_tokens: 8,
+                prompt_tokens_details: None,
+                completion_tokens_details: None,
+            }),
+            system_fingerprint: None,
+            choices: vec![ChatChoiceStream {
+                index: 0,
+                delta,
+                finish_reason,
+                logprobs: None,
+            }],
+            object: "chat.completion.chunk".to_string(),
+        };
+
+        Annotated {
+            data: Some(NvCreateChatCompletionStreamResponse { inner }),
+            id: None,
+            event: None,
+            comment: None,
+        }
+    }
+
+    fn generator() -> ResponseEventGenerator {
+        let request = CreateResponse {
+            model: "test-model".to_string(),
+            ..Default::default()
+        };
+        ResponseEventGenerator::new(NvResponse::new(&request, "1234"))
+    }
+
+    fn event_types(events: &[ResponseStreamEvent]) -> Vec<&'static str> {
+        events.iter().map(|event| event.event_type()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_text_stream() {
+        let stream = futures::stream::iter(vec![
+            chunk(Some("Hello"), None, None),
+            chunk(Some(" world"), None, Some(FinishReason::Stop)),
+        ]);
+        let events: Vec<_> = response_event_stream(stream, generator()).collect().await;
+
+        assert_eq!(
+            event_types(&events),
+            vec![
+                "response.created",
+                "response.in_progress",
+                "response.output_item.added",
+                "response.content_part.added",
+                "response.output_text.delta",
+                "response.output_text.delta",
+                "response.output_text.done",
+                "response.content_part.done",
+                "response.output_item.done",
+                "response.completed",
+            ]
+        );
+        for (expected, event) in events.iter().enumerate() {
+            assert_eq!(event.sequence_number, expected as u64);
+        }
+
+        let ResponseEvent::Completed { response } = &events.last().unwrap().event else {
+            panic!("expected response.completed");
+        };
+        assert_eq!(response.id, "resp_1234");
+        assert_eq!(response.status, ResponseStatus::Completed);
+        assert_eq!(response.output_text(), "Hello world");
+        assert_eq!(response.usage.as_ref().unwrap().total_tokens, 8);
+
+        let value = serde_json::to_value(&events[4]).unwrap();
+        assert_eq!(value["type"], "response.output_text.delta");
+        assert_eq!(value["delta"], "Hello");
+        assert_eq!(value["sequence_number"], 4);
+    }
+
+    #[tokio::test]
+    async fn test_function_call_stream() {
+        let call = ChatCompletionMessageToolCallChunk {
+            index: 0,
+            id: Some("call_abc".to_string()),
+            r#type: Some(ChatCompletionToolType::Function),
+            function: Some(FunctionCallStream {
+                name: Some("get_weather".to_string()),
+                arguments: Some("{\"city\":\"Paris\"}".to_string()),
+            }),
+        };
+        let stream = futures::stream::iter(vec![
+            chunk(Some("Checking."), None, None),
+            chunk(None, Some(vec![call]), Some(FinishReason::ToolCalls)),
+        ]);
+
+        let response = NvResponse::from_annotated_stream(stream, generator())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, ResponseStatus::Completed);
+        assert_eq!(response.output.len(), 2);
+        assert!(matches!(
+            &response.output[0],
+            OutputItem::Message {
+                status: ItemStatus::Completed,
+                ..
+            }
+        ));
+        match &response.output[1] {
+            OutputItem::FunctionCall {
+                call_id,
+                name,
+                arguments,
+                status,
+                ..
+            } => {
+                assert_eq!(call_id, "call_abc");
+                assert_eq!(name, "get_weather");
+                assert_eq!(arguments, "{\"city\":\"Paris\"}");
+                assert_eq!(*status, ItemStatus::Completed);
+            }
+            other => panic!("expected function call, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_incomplete_and_failed() {
+        let stream = futures::stream::iter(vec![chunk(
+            Some("truncated"),
+            None,
+            Some(FinishReason::Length),
+        )]);
+        let response = NvResponse::from_annotated_stream(stream, generator())
+            .await
+            .unwrap();
+        assert_eq!(response.status, ResponseStatus::Incomplete);
+        assert_eq!(
+            response.incomplete_details.unwrap().reason,
+            "max_output_tokens"
+        );
+
+        let stream = futures::stream::iter(vec![
+            chunk(Some("partial"), None, None),
+            Annotated::from_error("engine crashed".to_string()),
+        ]);
+        let events: Vec<_> = response_event_stream(stream, generator()).collect().await;
+        let ResponseEvent::Failed { response } = &events.last().unwrap().event else {
+            panic!("expected response.failed");
+        };
+        assert_eq!(response.error.as_ref().unwrap().message, "engine crashed");
+    }
+}
@@ -290,6 +290,33 @@ where
         timed: bool,
         max_count: Option<usize>,
         max_time: Option<f64>,
+    ) -> io::Result<usize> {
+        Self::send_events_inner(filename, event_tx, timed, 0, max_count, max_time).await
+    }
+
+    /// Send the events of a JSONL file to the provided event sender, as fast as possible,
+    /// skipping the first `skip` recorded events.
+    ///
+    /// Used to replay the events recorded after a point of which the state is already known.
+    ///
+    /// ### Returns
+    ///
+    /// A Result indicating success or failure with the number of events sent
+    pub async fn send_events_after<P: AsRef<Path>>(
+        filename: P,
+        event_tx: &mpsc::Sender<T>,
+        skip: usize,
+    ) -> io::Result<usize> {
+        Self::send_events_inner(filename, event_tx, false, skip, None, None).await
+    }
+
+    async fn send_events_inner<P: AsRef<Path>>(
+        filename: P,
+        event_tx: &mpsc::Sender<T>,
+        timed: bool,
+        skip: usize,
+        max_count: Option<usize>,
+        max_time: Option<f64>,
     ) -> io::Result<usize> {
         // Store the display name before using filename
         let display_name = filename.as_ref().display().to_string();
@@ -313,6 +340,7 @@ where
 
         let mut count = 0;
         let mut line_number = 0;
+        let mut skipped = 0;
         let mut prev_timestamp: Option<u64> = None;
 
         // Read and send events line by line
@@ -340,6 +368,12 @@ where
                 continue;
             }
 
+            // Skip the events the caller already has, one per line
+            if skipped < skip {
+                skipped += 1;
+                continue;
+            }
+
             // Try to parse the JSON
             let record: RecordEntry<T> = match serde_json::from_str(&line) {
                 Ok(record) => record,
@@ -202,8 +202,11 @@ pub mod llm_kvbm {
             bytes: Vec<u8>,
         ) -> Result<()> {
             let subject = format!("{}.{}", self.subject(), event_name.as_ref());
-            self.drt()
-                .nats_client()
+            let nats_client = self
+                .drt()
+                .try_nats_client()
+                .ok_or_else(|| anyhow::anyhow!("Publishing requires NATS"))?;
+            nats_client
                 .client()
                 .publish(subject, bytes.into())
                 .await
@@ -301,6 +304,7 @@ pub mod llm_kvbm {
                             })
                             .collect(),
                         parent_hash: parent_hash.map(ExternalSequenceBlockHash),
+                        lora_id: 0,
                     };
                     let data = KvCacheEventData::Stored(store_data);
                     let event = KvCacheEvent {
@@ -584,6 +588,7 @@ mod tests {
                         tokens_hash: LocalBlockHash(1),
                     }],
                     parent_hash: None,
+                    lora_id: 0,
                 }),
             },
         );
@@ -598,6 +603,7 @@ mod tests {
                         tokens_hash: LocalBlockHash(2),
                     }],
                     parent_hash: None,
+                    lora_id: 0,
                 }),
             },
         );
@@ -641,6 +647,7 @@ mod tests {
                         tokens_hash: LocalBlockHash(3),
                     }],
                     parent_hash: None,
+                    lora_id: 0,
                 }),
             },
         );
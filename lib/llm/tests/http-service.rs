@@ -16,8 +16,10 @@
 use anyhow::Error;
 use async_stream::stream;
 use dynamo_llm::http::service::{
+    auth::{ApiKey, AuthConfig, StaticKeyAuthenticator, AUTH_FAILURE_FORBIDDEN},
     error::HttpError,
     metrics::{Endpoint, RequestType, Status},
+    rate_limit::{RateLimitConf, RateLimiter, LIMIT_REQUESTS},
     service_v2::HttpService,
     Metrics,
 };
@@ -143,6 +145,7 @@ fn compute_index(endpoint: &Endpoint, request_type: &RequestType, status: &Statu
         Endpoint::Completions => 0,
         Endpoint::ChatCompletions => 1,
         Endpoint::Embeddings => todo!(),
+        Endpoint::Responses => 2,
     };
 
     let request_type = match request_type {
@@ -158,8 +161,12 @@ fn compute_index(endpoint: &Endpoint, request_type: &RequestType, status: &Statu
     endpoint * 4 + request_type * 2 + status
 }
 
-fn compare_counters(metrics: &Metrics, model: &str, expected: &[u64; 8]) {
-    for endpoint in &[Endpoint::Completions, Endpoint::ChatCompletions] {
+fn compare_counters(metrics: &Metrics, model: &str, expected: &[u64; 12]) {
+    for endpoint in &[
+        Endpoint::Completions,
+        Endpoint::ChatCompletions,
+        Endpoint::Responses,
+    ] {
         for request_type in &[RequestType::Unary, RequestType::Stream] {
             for status in &[Status::Success, Status::Error] {
                 let index = compute_index(endpoint, request_type, status);
@@ -180,7 +187,7 @@ fn inc_counter(
     endpoint: Endpoint,
     request_type: RequestType,
     status: Status,
-    expected: &mut [u64; 8],
+    expected: &mut [u64; 12],
 ) {
     let index = compute_index(&endpoint, &request_type, &status);
     expected[index] += 1;
@@ -213,8 +220,8 @@ async fn test_http_service() {
     let metrics = state.metrics_clone();
     metrics.register(&registry).unwrap();
 
-    let mut foo_counters = [0u64; 8];
-    let mut bar_counters = [0u64; 8];
+    let mut foo_counters = [0u64; 12];
+    let mut bar_counters = [0u64; 12];
 
     compare_counters(&metrics, "foo", &foo_counters);
     compare_counters(&metrics, "bar", &bar_counters);
@@ -392,6 +399,98 @@ async fn test_http_service() {
     compare_counters(&metrics, "bar", &bar_counters);
     // ==== ChatCompletions / Unary / Error ====
 
+    // ==== Responses / Stream / Success ====
+    let request = serde_json::json!({"model": "foo", "input": "hi", "stream": true});
+
+    let response = client
+        .post("http://localhost:8989/v1/responses")
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status().is_success(), "{:?}", response);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("response.created"), "{}", body);
+    assert!(body.contains("response.output_text.delta"), "{}", body);
+    assert!(body.contains("response.completed"), "{}", body);
+    inc_counter(
+        Endpoint::Responses,
+        RequestType::Stream,
+        Status::Success,
+        &mut foo_counters,
+    );
+    compare_counters(&metrics, "foo", &foo_counters);
+    compare_counters(&metrics, "bar", &bar_counters);
+    // ==== Responses / Stream / Success ====
+
+    // ==== Responses / Unary / Success ====
+    let request = serde_json::json!({"model": "foo", "input": "hi"});
+
+    let response = client
+        .post("http://localhost:8989/v1/responses")
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status().is_success(), "{:?}", response);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["object"], "response");
+    assert_eq!(body["status"], "completed");
+    assert_eq!(body["output"][0]["content"][0]["text"], "choice 0");
+    inc_counter(
+        Endpoint::Responses,
+        RequestType::Unary,
+        Status::Success,
+        &mut foo_counters,
+    );
+    compare_counters(&metrics, "foo", &foo_counters);
+    compare_counters(&metrics, "bar", &bar_counters);
+    // ==== Responses / Unary / Success ====
+
+    // ==== Responses / Stream / Error ====
+    let request = serde_json::json!({"model": "bar", "input": "hi", "stream": true});
+
+    let response = client
+        .post("http://localhost:8989/v1/responses")
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    inc_counter(
+        Endpoint::Responses,
+        RequestType::Stream,
+        Status::Error,
+        &mut bar_counters,
+    );
+    compare_counters(&metrics, "foo", &foo_counters);
+    compare_counters(&metrics, "bar", &bar_counters);
+    // ==== Responses / Stream / Error ====
+
+    // ==== Responses / Unary / Error ====
+    let request = serde_json::json!({"model": "bar", "input": "hi"});
+
+    let response = client
+        .post("http://localhost:8989/v1/responses")
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    inc_counter(
+        Endpoint::Responses,
+        RequestType::Unary,
+        Status::Error,
+        &mut bar_counters,
+    );
+    compare_counters(&metrics, "foo", &foo_counters);
+    compare_counters(&metrics, "bar", &bar_counters);
+    // ==== Responses / Unary / Error ====
+
     // ==== Completions / Unary / Error ====
     let mut request = async_openai::types::CreateCompletionRequestArgs::default()
         .model("bar")
@@ -469,3 +568,280 @@ async fn test_http_service() {
     cancel_token.cancel();
     task.await.unwrap().unwrap();
 }
+
+#[tokio::test]
+async fn test_http_service_auth() {
+    let keys = StaticKeyAuthenticator::new(vec![ApiKey {
+        id: "team-a".to_string(),
+        key: "secret-a".to_string(),
+        models: Some(["foo".to_string()].into_iter().collect()),
+    }])
+    .unwrap();
+
+    let service = HttpService::builder()
+        .port(8990)
+        .auth(Some(AuthConfig::new().with_authenticator(keys)))
+        .build()
+        .unwrap();
+    let state = service.state_clone();
+    let manager = state.manager();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    let counter = Arc::new(CounterEngine {});
+    manager
+        .add_chat_completions_model("foo", counter.clone())
+        .unwrap();
+    manager.add_chat_completions_model("bar", counter).unwrap();
+
+    let metrics = state.metrics_clone();
+    let client = reqwest::Client::new();
+
+    let request = |model: &str| {
+        serde_json::json!({
+            "model": model,
+            "messages": [{"role": "user", "content": "hi"}],
+        })
+    };
+
+    // no credentials
+    let response = client
+        .post("http://localhost:8990/v1/chat/completions")
+        .json(&request("foo"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // unknown key
+    let response = client
+        .post("http://localhost:8990/v1/chat/completions")
+        .bearer_auth("secret-b")
+        .json(&request("foo"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // model not in the allow-list of the key
+    let response = client
+        .post("http://localhost:8990/v1/chat/completions")
+        .bearer_auth("secret-a")
+        .json(&request("bar"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        metrics.get_auth_failure_counter("team-a", AUTH_FAILURE_FORBIDDEN),
+        1
+    );
+
+    // allowed
+    let response = client
+        .post("http://localhost:8990/v1/chat/completions")
+        .bearer_auth("secret-a")
+        .json(&request("foo"))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    assert_eq!(
+        metrics.get_key_request_counter(
+            "team-a",
+            "foo",
+            &Endpoint::ChatCompletions,
+            &Status::Success
+        ),
+        1
+    );
+
+    // only the allowed models are listed
+    let response = client
+        .get("http://localhost:8990/v1/models")
+        .bearer_auth("secret-a")
+        .send()
+        .await
+        .unwrap();
+    let models: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(models["data"].as_array().unwrap().len(), 1);
+    assert_eq!(models["data"][0]["id"], "foo");
+
+    // health and metrics do not require credentials
+    let response = client
+        .get("http://localhost:8990/metrics")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_service_rate_limit() {
+    let conf: RateLimitConf = serde_json::from_value(serde_json::json!({
+        "tenant_header": "x-tenant-id",
+        "default": {"requests_per_minute": 1},
+    }))
+    .unwrap();
+
+    let service = HttpService::builder()
+        .port(8991)
+        .rate_limiter(Some(Arc::new(RateLimiter::new(conf))))
+        .build()
+        .unwrap();
+    let state = service.state_clone();
+    let manager = state.manager();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    manager
+        .add_chat_completions_model("foo", Arc::new(CounterEngine {}))
+        .unwrap();
+
+    let metrics = state.metrics_clone();
+    let client = reqwest::Client::new();
+
+    let request = serde_json::json!({
+        "model": "foo",
+        "messages": [{"role": "user", "content": "hi"}],
+    });
+
+    let response = client
+        .post("http://localhost:8991/v1/chat/completions")
+        .header("x-tenant-id", "team-a")
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+
+    // the second request within the minute is rejected
+    let response = client
+        .post("http://localhost:8991/v1/chat/completions")
+        .header("x-tenant-id", "team-a")
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+    assert_eq!(
+        metrics.get_rate_limited_counter("team-a", LIMIT_REQUESTS),
+        1
+    );
+
+    // other tenants have their own limits
+    let response = client
+        .post("http://localhost:8991/v1/chat/completions")
+        .header("x-tenant-id", "team-b")
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+
+    // listing models is not rate limited
+    let response = client
+        .get("http://localhost:8991/v1/models")
+        .header("x-tenant-id", "team-a")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_service_sse_keep_alive_and_disconnect() {
+    let service = HttpService::builder()
+        .port(8992)
+        .sse_keep_alive(Some(std::time::Duration::from_millis(50)))
+        .build()
+        .unwrap();
+    let state = service.state_clone();
+    let manager = state.manager();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    manager
+        .add_chat_completions_model("foo", Arc::new(CounterEngine {}))
+        .unwrap();
+
+    let metrics = state.metrics_clone();
+    let client = reqwest::Client::new();
+
+    // the counter engine waits max_tokens milliseconds before the first response
+    let request = |max_tokens: u32| {
+        serde_json::json!({
+            "model": "foo",
+            "messages": [{"role": "user", "content": "hi"}],
+            "max_tokens": max_tokens,
+            "stream": true,
+        })
+    };
+
+    // heartbeats are sent while the engine is idle
+    let mut response = client
+        .post("http://localhost:8992/v1/chat/completions")
+        .json(&request(300))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let chunk = response.chunk().await.unwrap().unwrap();
+    assert!(chunk.starts_with(b":"), "{:?}", chunk);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("[DONE]"), "{}", body);
+    assert_eq!(
+        metrics.get_cancelled_counter("foo", &Endpoint::ChatCompletions),
+        0
+    );
+
+    // the client goes away before the first token
+    let response = client
+        .post("http://localhost:8992/v1/chat/completions")
+        .json(&request(2000))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    drop(response);
+
+    let cancelled = async {
+        while metrics.get_cancelled_counter("foo", &Endpoint::ChatCompletions) == 0
+            || metrics.get_inflight_count("foo") > 0
+        {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(std::time::Duration::from_secs(1), cancelled)
+        .await
+        .expect("disconnect was not detected before the first token");
+    compare_counter(
+        &metrics,
+        "foo",
+        &Endpoint::ChatCompletions,
+        &RequestType::Stream,
+        &Status::Error,
+        1,
+    );
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
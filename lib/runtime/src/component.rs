@@ -42,6 +42,7 @@ use super::{
 
 use crate::pipeline::network::{ingress::push_endpoint::PushEndpoint, PushWorkHandler};
 use crate::protocols::Endpoint as EndpointId;
+use crate::transports::local::LocalService;
 use async_nats::{
     rustls::quic,
     service::{Service, ServiceExt},
@@ -58,6 +59,7 @@ mod client;
 #[allow(clippy::module_inception)]
 mod component;
 mod endpoint;
+pub mod health;
 mod namespace;
 mod registry;
 pub mod service;
@@ -80,6 +82,7 @@ pub enum TransportType {
 #[derive(Default)]
 pub struct RegistryInner {
     services: HashMap<String, Service>,
+    local_services: HashMap<String, LocalService>,
     stats_handlers: HashMap<String, Arc<std::sync::Mutex<HashMap<String, EndpointStatsHandler>>>>,
 }
 
@@ -14,22 +14,25 @@
 // limitations under the License.
 
 use crate::pipeline::{
-    AddressedPushRouter, AddressedRequest, AsyncEngine, Data, ManyOut, PushRouter, RouterMode,
-    SingleIn,
+    network::egress::push_router::InflightTracker, AddressedPushRouter, AddressedRequest,
+    AsyncEngine, Data, ManyOut, PushRouter, RouterMode, SingleIn,
 };
 use rand::Rng;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc,
 };
 use tokio::{net::unix::pipe::Receiver, sync::Mutex};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     pipeline::async_trait,
     transports::etcd::{Client as EtcdClient, WatchEvent},
 };
 
+use super::health::HealthTracker;
+
 use super::*;
 
 /// Each state will be have a nonce associated with it
@@ -59,6 +62,10 @@ pub struct Client {
     pub instance_source: Arc<InstanceSource>,
     // These are the instances that are reported as down from sending rpc
     instance_inhibited: Arc<Mutex<HashMap<i64, std::time::Instant>>>,
+    // Circuit breakers of the instances, if health tracking is enabled
+    health: Option<Arc<HealthTracker>>,
+    // Requests in flight per instance, counted across the routers of this client
+    inflight: Arc<InflightTracker>,
 }
 
 #[derive(Clone, Debug)]
@@ -76,6 +83,8 @@ impl Client {
             endpoint,
             instance_source: Arc::new(InstanceSource::Static),
             instance_inhibited: Arc::new(Mutex::new(HashMap::new())),
+            health: None,
+            inflight: Arc::default(),
         })
     }
 
@@ -93,9 +102,37 @@ impl Client {
             endpoint,
             instance_source,
             instance_inhibited: Arc::new(Mutex::new(HashMap::new())),
+            health: None,
+            inflight: Arc::default(),
         })
     }
 
+    /// Track the health of the instances: the instances whose circuit is open are left out of
+    /// [`Client::instances_avail`]. Starts the active checks of the tracker, if it has them,
+    /// until the tracker is dropped.
+    pub fn with_health(mut self, tracker: Arc<HealthTracker>) -> Self {
+        if let Some(interval) = tracker.config().check_interval {
+            let client = self.clone();
+            let tracker = Arc::downgrade(&tracker);
+            let cancel = self.endpoint.drt().child_token();
+            self.endpoint.drt().runtime().secondary().spawn(async move {
+                health_checks(client, tracker, interval, cancel).await;
+            });
+        }
+        self.health = Some(tracker);
+        self
+    }
+
+    /// The health tracker of the instances, if enabled
+    pub fn health(&self) -> Option<&Arc<HealthTracker>> {
+        self.health.as_ref()
+    }
+
+    /// The requests in flight per instance, shared by the routers of this client
+    pub(crate) fn inflight(&self) -> &Arc<InflightTracker> {
+        &self.inflight
+    }
+
     pub fn path(&self) -> String {
         self.endpoint.path()
     }
@@ -134,7 +171,8 @@ impl Client {
         Ok(instances)
     }
 
-    /// Instances available from watching etcd minus those reported as down
+    /// Instances available from watching etcd minus those reported as down, and those whose
+    /// circuit is open
     pub async fn instances_avail(&self) -> Vec<Instance> {
         // TODO: Can we get the remaining TTL from the lease for the instance?
         const ETCD_LEASE_TTL: u64 = 10; // seconds
@@ -168,7 +206,13 @@ impl Client {
             .collect();
 
         *inhibited = new_inhibited;
-        filtered
+        match &self.health {
+            Some(health) => filtered
+                .into_iter()
+                .filter(|instance| health.is_available(instance.id()))
+                .collect(),
+            None => filtered,
+        }
     }
 
     /// Mark an instance as down/unavailable
@@ -276,3 +320,51 @@ impl Client {
         Ok(instance_source)
     }
 }
+
+/// Periodically check that the instances of the client answer the service stats scrape, until the
+/// tracker is dropped or the runtime shuts down
+async fn health_checks(
+    client: Client,
+    tracker: std::sync::Weak<HealthTracker>,
+    interval: std::time::Duration,
+    cancel: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = ticker.tick() => {}
+        }
+        let Some(tracker) = tracker.upgrade() else {
+            break;
+        };
+
+        let instance_ids = client.instance_ids();
+        tracker.retain(&instance_ids);
+        if instance_ids.is_empty() {
+            continue;
+        }
+        let services = match client
+            .endpoint
+            .component
+            .scrape_stats(tracker.config().check_timeout)
+            .await
+        {
+            Ok(services) => services,
+            Err(err) => {
+                // not the fault of the instances
+                tracing::debug!(%err, endpoint = tracker.name(), "health check scrape failed");
+                continue;
+            }
+        };
+        let answered: HashSet<String> = services
+            .into_endpoints()
+            .map(|endpoint| endpoint.subject)
+            .collect();
+        for instance_id in instance_ids {
+            let subject = client.endpoint.subject_to(instance_id);
+            tracker.record_check(instance_id, answered.contains(&subject));
+        }
+    }
+    tracing::debug!(endpoint = client.path(), "health checks stopped");
+}
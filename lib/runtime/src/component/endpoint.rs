@@ -14,8 +14,10 @@
 // limitations under the License.
 
 use derive_getters::Dissolve;
+use either::Either;
 
 use super::*;
+use crate::pipeline::network::ingress::push_endpoint::EndpointSource;
 
 pub use async_nats::service::endpoint::Stats as EndpointStats;
 
@@ -69,12 +71,17 @@ impl EndpointConfigBuilder {
         // acquire the registry lock
         let registry = endpoint.drt().component_registry.inner.lock().await;
 
-        // get the group
-        let group = registry
-            .services
-            .get(&service_name)
-            .map(|service| service.group(endpoint.component.service_name()))
-            .ok_or(error!("Service not found"))?;
+        // get the group, or the service on the bus of a local runtime
+        let service = match registry.services.get(&service_name) {
+            Some(service) => Either::Left(service.group(endpoint.component.service_name())),
+            None => Either::Right(
+                registry
+                    .local_services
+                    .get(&service_name)
+                    .cloned()
+                    .ok_or(error!("Service not found"))?,
+            ),
+        };
 
         // get the stats handler map
         let handler_map = registry
@@ -94,10 +101,19 @@ impl EndpointConfigBuilder {
         }
 
         // creates an endpoint for the service
-        let service_endpoint = group
-            .endpoint(&endpoint.name_with_id(lease_id))
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to start endpoint: {e}"))?;
+        let service_endpoint: EndpointSource = match service {
+            Either::Left(group) => group
+                .endpoint(&endpoint.name_with_id(lease_id))
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to start endpoint: {e}"))?
+                .into(),
+            Either::Right(service) => service
+                .endpoint(
+                    endpoint.name_with_id(lease_id),
+                    endpoint.subject_to(lease_id),
+                )
+                .into(),
+        };
 
         let cancel_token = lease
             .map(|l| l.child_token())
@@ -0,0 +1,751 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Health of the instances of an endpoint, as seen by a [`super::Client`]
+//!
+//! Each instance has a circuit breaker deciding whether it gets traffic:
+//! - closed: the instance gets traffic. The outcomes of its requests are counted over a window,
+//!   the circuit opens when too many of them failed, when too many failed in a row, or when the
+//!   average time to the first response is too high.
+//! - open: the instance gets no traffic. After `open_duration` the circuit half-opens.
+//! - half-open: a single probe request is let through. Its success closes the circuit, its
+//!   failure opens it again.
+//!
+//! The outcomes come from the real traffic of the routers, and from active checks which
+//! periodically scrape the service stats of the instances. The checks are counted apart from the
+//! requests: an instance which does not answer too many checks in a row has its circuit opened,
+//! an instance which answers while half-open closes its circuit.
+
+use prometheus::{GaugeVec, IntCounterVec, IntGaugeVec, Opts, Registry};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Weight of the latest latency in the average latency of an instance
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// Value for the `outcome` label of the requests and checks counters
+pub const OUTCOME_SUCCESS: &str = "success";
+
+/// Value for the `outcome` label of the requests and checks counters
+pub const OUTCOME_FAILURE: &str = "failure";
+
+/// Configuration of a [`HealthTracker`]
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// Share of failed requests in the window above which the circuit opens
+    pub max_error_rate: f64,
+
+    /// Requests needed in the window before the error rate and latency are judged
+    pub min_requests: u32,
+
+    /// Failed requests in a row after which the circuit opens, whatever the window
+    pub max_consecutive_failures: u32,
+
+    /// Average time to the first response above which the circuit opens, unbounded if `None`
+    pub max_latency: Option<Duration>,
+
+    /// Length of the window over which requests and failures are counted
+    pub window: Duration,
+
+    /// How long an open circuit stays open before a probe is let through
+    pub open_duration: Duration,
+
+    /// Interval of the active checks, disabled if `None`
+    pub check_interval: Option<Duration>,
+
+    /// How long the active checks wait for the instances to answer
+    pub check_timeout: Duration,
+
+    /// Active checks in a row the instance did not answer after which the circuit opens
+    pub max_failed_checks: u32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            max_error_rate: 0.5,
+            min_requests: 10,
+            max_consecutive_failures: 5,
+            max_latency: None,
+            window: Duration::from_secs(30),
+            open_duration: Duration::from_secs(10),
+            check_interval: Some(Duration::from_secs(5)),
+            check_timeout: Duration::from_secs(1),
+            max_failed_checks: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// Value of the circuit state gauge: 0 closed, 1 half-open, 2 open
+    fn gauge_value(&self) -> i64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::HalfOpen => 1,
+            CircuitState::Open => 2,
+        }
+    }
+}
+
+/// The health of an instance, as reported by [`HealthTracker::instances`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceHealth {
+    pub instance_id: i64,
+    pub state: CircuitState,
+    /// Requests since the instance is tracked
+    pub requests: u64,
+    /// Failed requests since the instance is tracked
+    pub failures: u64,
+    /// Share of failed requests in the current window
+    pub error_rate: f64,
+    pub consecutive_failures: u32,
+    /// Average time to the first response, in milliseconds
+    pub latency_ms: Option<f64>,
+    /// Active checks in a row the instance did not answer
+    pub failed_checks: u32,
+}
+
+/// Circuit breaker and request statistics of a single instance
+struct Breaker {
+    state: CircuitState,
+    /// When the circuit last opened
+    opened_at: Instant,
+    /// When the probe of a half-open circuit was sent, if it was
+    probe_sent_at: Option<Instant>,
+    window_start: Instant,
+    window_requests: u32,
+    window_failures: u32,
+    consecutive_failures: u32,
+    /// Smoothed time to the first response, in seconds
+    latency: Option<f64>,
+    requests: u64,
+    failures: u64,
+    /// Active checks in a row the instance did not answer
+    failed_checks: u32,
+}
+
+impl Breaker {
+    fn new(now: Instant) -> Self {
+        Self {
+            state: CircuitState::Closed,
+            opened_at: now,
+            probe_sent_at: None,
+            window_start: now,
+            window_requests: 0,
+            window_failures: 0,
+            consecutive_failures: 0,
+            latency: None,
+            requests: 0,
+            failures: 0,
+            failed_checks: 0,
+        }
+    }
+
+    fn error_rate(&self) -> f64 {
+        match self.window_requests {
+            0 => 0.0,
+            requests => self.window_failures as f64 / requests as f64,
+        }
+    }
+
+    /// Start a new window once the current one is over
+    fn roll_window(&mut self, now: Instant, config: &HealthConfig) {
+        if now.duration_since(self.window_start) >= config.window {
+            self.window_start = now;
+            self.window_requests = 0;
+            self.window_failures = 0;
+        }
+    }
+
+    /// Half-open the circuit once it was open long enough
+    fn refresh(&mut self, now: Instant, config: &HealthConfig) {
+        if self.state == CircuitState::Open
+            && now.duration_since(self.opened_at) >= config.open_duration
+        {
+            self.state = CircuitState::HalfOpen;
+            self.probe_sent_at = None;
+        }
+    }
+
+    /// Whether the instance may get a request. A half-open circuit lets a single probe through,
+    /// or another one if the previous probe got no outcome within `open_duration`.
+    fn is_available(&mut self, now: Instant, config: &HealthConfig) -> bool {
+        self.refresh(now, config);
+        match self.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => match self.probe_sent_at {
+                Some(sent_at) => now.duration_since(sent_at) >= config.open_duration,
+                None => true,
+            },
+        }
+    }
+
+    fn on_dispatch(&mut self, now: Instant, config: &HealthConfig) {
+        self.refresh(now, config);
+        if self.state == CircuitState::HalfOpen {
+            self.probe_sent_at = Some(now);
+        }
+    }
+
+    fn open(&mut self, now: Instant) {
+        self.state = CircuitState::Open;
+        self.opened_at = now;
+        self.probe_sent_at = None;
+    }
+
+    fn close(&mut self, now: Instant) {
+        self.state = CircuitState::Closed;
+        self.probe_sent_at = None;
+        self.consecutive_failures = 0;
+        self.failed_checks = 0;
+        self.window_start = now;
+        self.window_requests = 0;
+        self.window_failures = 0;
+    }
+
+    fn success(&mut self, now: Instant, latency: Duration, config: &HealthConfig) {
+        self.refresh(now, config);
+        self.roll_window(now, config);
+        self.requests += 1;
+        self.window_requests += 1;
+        self.consecutive_failures = 0;
+        let latency = latency.as_secs_f64();
+        self.latency = Some(match self.latency {
+            Some(average) => average + LATENCY_SMOOTHING * (latency - average),
+            None => latency,
+        });
+
+        match self.state {
+            CircuitState::HalfOpen => self.close(now),
+            // a request sent before the circuit opened, the circuit stays open
+            CircuitState::Open => {}
+            CircuitState::Closed => {
+                let too_slow = match (config.max_latency, self.latency) {
+                    (Some(max_latency), Some(latency)) => latency > max_latency.as_secs_f64(),
+                    _ => false,
+                };
+                if too_slow && self.window_requests >= config.min_requests {
+                    self.open(now);
+                }
+            }
+        }
+    }
+
+    fn failure(&mut self, now: Instant, config: &HealthConfig) {
+        self.refresh(now, config);
+        self.roll_window(now, config);
+        self.requests += 1;
+        self.failures += 1;
+        self.window_requests += 1;
+        self.window_failures += 1;
+        self.consecutive_failures += 1;
+
+        match self.state {
+            CircuitState::HalfOpen => self.open(now),
+            CircuitState::Open => {}
+            CircuitState::Closed => {
+                let too_many_errors = self.window_requests >= config.min_requests
+                    && self.error_rate() > config.max_error_rate;
+                if too_many_errors || self.consecutive_failures >= config.max_consecutive_failures {
+                    self.open(now);
+                }
+            }
+        }
+    }
+
+    /// An active check found the instance answering
+    fn alive(&mut self, now: Instant, config: &HealthConfig) {
+        self.refresh(now, config);
+        self.failed_checks = 0;
+        if self.state == CircuitState::HalfOpen {
+            self.close(now);
+        }
+    }
+
+    /// An active check got no answer from the instance
+    fn unresponsive(&mut self, now: Instant, config: &HealthConfig) {
+        self.refresh(now, config);
+        self.failed_checks += 1;
+        match self.state {
+            CircuitState::HalfOpen => self.open(now),
+            CircuitState::Open => {}
+            CircuitState::Closed => {
+                if self.failed_checks >= config.max_failed_checks {
+                    self.open(now);
+                }
+            }
+        }
+    }
+
+    fn health(&self, instance_id: i64) -> InstanceHealth {
+        InstanceHealth {
+            instance_id,
+            state: self.state,
+            requests: self.requests,
+            failures: self.failures,
+            error_rate: self.error_rate(),
+            consecutive_failures: self.consecutive_failures,
+            latency_ms: self.latency.map(|latency| latency * 1000.0),
+            failed_checks: self.failed_checks,
+        }
+    }
+}
+
+/// Prometheus metrics of the health trackers, labeled by endpoint and instance
+pub struct HealthMetrics {
+    circuit_state: IntGaugeVec,
+    requests: IntCounterVec,
+    checks: IntCounterVec,
+    latency: GaugeVec,
+}
+
+impl Default for HealthMetrics {
+    fn default() -> Self {
+        Self::new("dynamo")
+    }
+}
+
+impl HealthMetrics {
+    /// Create HealthMetrics with the given prefix
+    /// The following metrics will be created:
+    /// - `{prefix}_instance_circuit_state` - IntGaugeVec for the circuit state, 0 closed, 1 half-open, 2 open
+    /// - `{prefix}_instance_requests_total` - IntCounterVec for the requests by outcome
+    /// - `{prefix}_instance_health_checks_total` - IntCounterVec for the active checks by outcome
+    /// - `{prefix}_instance_latency_seconds` - GaugeVec for the average time to the first response
+    pub fn new(prefix: &str) -> Self {
+        let circuit_state = IntGaugeVec::new(
+            Opts::new(
+                format!("{}_instance_circuit_state", prefix),
+                "Circuit state of the instance: 0 closed, 1 half-open, 2 open",
+            ),
+            &["endpoint", "instance"],
+        )
+        .unwrap();
+
+        let requests = IntCounterVec::new(
+            Opts::new(
+                format!("{}_instance_requests_total", prefix),
+                "Total number of requests to the instance by outcome",
+            ),
+            &["endpoint", "instance", "outcome"],
+        )
+        .unwrap();
+
+        let checks = IntCounterVec::new(
+            Opts::new(
+                format!("{}_instance_health_checks_total", prefix),
+                "Total number of active health checks of the instance by outcome",
+            ),
+            &["endpoint", "instance", "outcome"],
+        )
+        .unwrap();
+
+        let latency = GaugeVec::new(
+            Opts::new(
+                format!("{}_instance_latency_seconds", prefix),
+                "Average time to the first response of the instance",
+            ),
+            &["endpoint", "instance"],
+        )
+        .unwrap();
+
+        HealthMetrics {
+            circuit_state,
+            requests,
+            checks,
+            latency,
+        }
+    }
+
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.circuit_state.clone()))?;
+        registry.register(Box::new(self.requests.clone()))?;
+        registry.register(Box::new(self.checks.clone()))?;
+        registry.register(Box::new(self.latency.clone()))?;
+        Ok(())
+    }
+
+    /// Get the circuit state gauge of the given instance
+    pub fn get_circuit_state(&self, endpoint: &str, instance_id: i64) -> i64 {
+        self.circuit_state
+            .with_label_values(&[endpoint, &instance_label(instance_id)])
+            .get()
+    }
+
+    /// Get the number of requests to the given instance with the given outcome
+    /// (see [`OUTCOME_SUCCESS`] and [`OUTCOME_FAILURE`])
+    pub fn get_requests(&self, endpoint: &str, instance_id: i64, outcome: &str) -> u64 {
+        self.requests
+            .with_label_values(&[endpoint, &instance_label(instance_id), outcome])
+            .get()
+    }
+
+    /// Get the number of active checks of the given instance with the given outcome
+    pub fn get_checks(&self, endpoint: &str, instance_id: i64, outcome: &str) -> u64 {
+        self.checks
+            .with_label_values(&[endpoint, &instance_label(instance_id), outcome])
+            .get()
+    }
+}
+
+/// Instances are labeled like in their subjects
+fn instance_label(instance_id: i64) -> String {
+    format!("{instance_id:x}")
+}
+
+/// Tracks the health of the instances of an endpoint and decides which ones get traffic
+pub struct HealthTracker {
+    name: String,
+    config: HealthConfig,
+    breakers: Mutex<HashMap<i64, Breaker>>,
+    metrics: Arc<HealthMetrics>,
+}
+
+impl std::fmt::Debug for HealthTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HealthTracker")
+            .field("name", &self.name)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl HealthTracker {
+    /// Create a tracker; `name` is the value of the `endpoint` label of its metrics
+    pub fn new(name: impl Into<String>, config: HealthConfig, metrics: Arc<HealthMetrics>) -> Self {
+        Self {
+            name: name.into(),
+            config,
+            breakers: Mutex::new(HashMap::new()),
+            metrics,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn config(&self) -> &HealthConfig {
+        &self.config
+    }
+
+    /// Whether the instance may get a request
+    pub fn is_available(&self, instance_id: i64) -> bool {
+        self.is_available_at(instance_id, Instant::now())
+    }
+
+    /// A request is being sent to the instance. If its circuit is half-open, it is the probe.
+    pub fn on_dispatch(&self, instance_id: i64) {
+        self.on_dispatch_at(instance_id, Instant::now())
+    }
+
+    /// The instance produced its first response after `latency`
+    pub fn record_success(&self, instance_id: i64, latency: Duration) {
+        self.record_success_at(instance_id, latency, Instant::now())
+    }
+
+    /// The request to the instance failed before its first response
+    pub fn record_failure(&self, instance_id: i64) {
+        self.record_failure_at(instance_id, Instant::now())
+    }
+
+    /// The outcome of an active check of the instance
+    pub fn record_check(&self, instance_id: i64, alive: bool) {
+        let outcome = if alive {
+            OUTCOME_SUCCESS
+        } else {
+            OUTCOME_FAILURE
+        };
+        self.metrics
+            .checks
+            .with_label_values(&[&self.name, &instance_label(instance_id), outcome])
+            .inc();
+        self.update(instance_id, |breaker, config| {
+            let now = Instant::now();
+            match alive {
+                true => breaker.alive(now, config),
+                false => breaker.unresponsive(now, config),
+            }
+        });
+    }
+
+    /// Forget the instances which are gone
+    pub fn retain(&self, instance_ids: &[i64]) {
+        let mut breakers = self.breakers.lock().unwrap();
+        breakers.retain(|instance_id, _| {
+            let keep = instance_ids.contains(instance_id);
+            if !keep {
+                let instance = instance_label(*instance_id);
+                let _ = self
+                    .metrics
+                    .circuit_state
+                    .remove_label_values(&[&self.name, &instance]);
+                let _ = self
+                    .metrics
+                    .latency
+                    .remove_label_values(&[&self.name, &instance]);
+            }
+            keep
+        });
+    }
+
+    /// The health of the tracked instances, by instance id
+    pub fn instances(&self) -> Vec<InstanceHealth> {
+        let now = Instant::now();
+        let mut breakers = self.breakers.lock().unwrap();
+        let mut instances: Vec<InstanceHealth> = breakers
+            .iter_mut()
+            .map(|(instance_id, breaker)| {
+                breaker.refresh(now, &self.config);
+                breaker.health(*instance_id)
+            })
+            .collect();
+        instances.sort_by_key(|instance| instance.instance_id);
+        instances
+    }
+
+    fn is_available_at(&self, instance_id: i64, now: Instant) -> bool {
+        let mut breakers = self.breakers.lock().unwrap();
+        let Some(breaker) = breakers.get_mut(&instance_id) else {
+            // nothing known against it
+            return true;
+        };
+        let before = breaker.state;
+        let available = breaker.is_available(now, &self.config);
+        self.publish(instance_id, breaker, before);
+        available
+    }
+
+    fn on_dispatch_at(&self, instance_id: i64, now: Instant) {
+        self.update(instance_id, |breaker, config| {
+            breaker.on_dispatch(now, config)
+        });
+    }
+
+    fn record_success_at(&self, instance_id: i64, latency: Duration, now: Instant) {
+        self.metrics
+            .requests
+            .with_label_values(&[&self.name, &instance_label(instance_id), OUTCOME_SUCCESS])
+            .inc();
+        self.update(instance_id, |breaker, config| {
+            breaker.success(now, latency, config)
+        });
+    }
+
+    fn record_failure_at(&self, instance_id: i64, now: Instant) {
+        self.metrics
+            .requests
+            .with_label_values(&[&self.name, &instance_label(instance_id), OUTCOME_FAILURE])
+            .inc();
+        self.update(instance_id, |breaker, config| breaker.failure(now, config));
+    }
+
+    /// Apply `f` to the breaker of the instance, then publish its state
+    fn update(&self, instance_id: i64, f: impl FnOnce(&mut Breaker, &HealthConfig)) {
+        let mut breakers = self.breakers.lock().unwrap();
+        let breaker = breakers
+            .entry(instance_id)
+            .or_insert_with(|| Breaker::new(Instant::now()));
+        let before = breaker.state;
+        f(breaker, &self.config);
+        self.publish(instance_id, breaker, before);
+    }
+
+    /// Log a change of the circuit state and update the metrics of the instance
+    fn publish(&self, instance_id: i64, breaker: &Breaker, before: CircuitState) {
+        match breaker.state {
+            state if state == before => {}
+            CircuitState::Open => tracing::warn!(
+                endpoint = self.name,
+                instance_id,
+                error_rate = breaker.error_rate(),
+                consecutive_failures = breaker.consecutive_failures,
+                failed_checks = breaker.failed_checks,
+                "instance circuit opened"
+            ),
+            state => tracing::info!(
+                endpoint = self.name,
+                instance_id,
+                ?state,
+                "instance circuit changed"
+            ),
+        }
+
+        let instance = instance_label(instance_id);
+        self.metrics
+            .circuit_state
+            .with_label_values(&[&self.name, &instance])
+            .set(breaker.state.gauge_value());
+        if let Some(latency) = breaker.latency {
+            self.metrics
+                .latency
+                .with_label_values(&[&self.name, &instance])
+                .set(latency);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> HealthTracker {
+        HealthTracker::new(
+            "test",
+            HealthConfig {
+                max_error_rate: 0.5,
+                min_requests: 4,
+                max_consecutive_failures: 3,
+                max_latency: Some(Duration::from_millis(100)),
+                window: Duration::from_secs(30),
+                open_duration: Duration::from_secs(10),
+                check_interval: None,
+                check_timeout: Duration::from_secs(1),
+                max_failed_checks: 2,
+            },
+            Arc::new(HealthMetrics::default()),
+        )
+    }
+
+    fn state(tracker: &HealthTracker, instance_id: i64) -> CircuitState {
+        tracker
+            .breakers
+            .lock()
+            .unwrap()
+            .get(&instance_id)
+            .map(|breaker| breaker.state)
+            .unwrap_or(CircuitState::Closed)
+    }
+
+    #[test]
+    fn test_circuit_opens_on_consecutive_failures() {
+        let tracker = tracker();
+        let now = Instant::now();
+        let fast = Duration::from_millis(10);
+
+        tracker.record_success_at(1, fast, now);
+        tracker.record_failure_at(1, now);
+        tracker.record_failure_at(1, now);
+        assert!(tracker.is_available_at(1, now));
+        tracker.record_failure_at(1, now);
+        assert_eq!(state(&tracker, 1), CircuitState::Open);
+        assert!(!tracker.is_available_at(1, now));
+        assert!(tracker.is_available_at(2, now));
+        assert_eq!(tracker.metrics.get_circuit_state("test", 1), 2);
+        assert_eq!(tracker.metrics.get_requests("test", 1, OUTCOME_FAILURE), 3);
+    }
+
+    #[test]
+    fn test_circuit_opens_on_error_rate_and_latency() {
+        let tracker = tracker();
+        let now = Instant::now();
+        let fast = Duration::from_millis(10);
+
+        // alternating failures never reach 3 in a row, but exceed half of the window
+        for _ in 0..2 {
+            tracker.record_failure_at(1, now);
+            tracker.record_success_at(1, fast, now);
+        }
+        assert_eq!(state(&tracker, 1), CircuitState::Closed);
+        tracker.record_failure_at(1, now);
+        assert_eq!(state(&tracker, 1), CircuitState::Open);
+
+        // a slow instance opens once the window holds enough requests
+        for _ in 0..3 {
+            tracker.record_success_at(2, Duration::from_millis(500), now);
+        }
+        assert_eq!(state(&tracker, 2), CircuitState::Closed);
+        tracker.record_success_at(2, Duration::from_millis(500), now);
+        assert_eq!(state(&tracker, 2), CircuitState::Open);
+    }
+
+    #[test]
+    fn test_half_open_probe() {
+        let tracker = tracker();
+        let now = Instant::now();
+        for _ in 0..3 {
+            tracker.record_failure_at(1, now);
+        }
+        assert!(!tracker.is_available_at(1, now + Duration::from_secs(5)));
+
+        // after open_duration a single probe goes through
+        let later = now + Duration::from_secs(10);
+        assert!(tracker.is_available_at(1, later));
+        assert_eq!(state(&tracker, 1), CircuitState::HalfOpen);
+        tracker.on_dispatch_at(1, later);
+        assert!(!tracker.is_available_at(1, later));
+
+        // its failure opens the circuit again
+        tracker.record_failure_at(1, later);
+        assert_eq!(state(&tracker, 1), CircuitState::Open);
+        assert!(!tracker.is_available_at(1, later + Duration::from_secs(5)));
+
+        // the success of the next probe closes it
+        let later = later + Duration::from_secs(10);
+        assert!(tracker.is_available_at(1, later));
+        tracker.on_dispatch_at(1, later);
+        tracker.record_success_at(1, Duration::from_millis(10), later);
+        assert_eq!(state(&tracker, 1), CircuitState::Closed);
+        assert!(tracker.is_available_at(1, later));
+
+        let health = tracker.instances();
+        assert_eq!(health.len(), 1);
+        assert_eq!(health[0].requests, 5);
+        assert_eq!(health[0].failures, 4);
+        assert_eq!(health[0].consecutive_failures, 0);
+    }
+
+    #[test]
+    fn test_checks() {
+        let tracker = tracker();
+        tracker.record_check(1, false);
+        tracker.record_check(1, true);
+        tracker.record_check(1, false);
+        assert_eq!(state(&tracker, 1), CircuitState::Closed);
+        tracker.record_check(1, false);
+        assert_eq!(state(&tracker, 1), CircuitState::Open);
+        assert_eq!(tracker.metrics.get_checks("test", 1, OUTCOME_FAILURE), 3);
+
+        // the checks are not requests
+        let health = tracker.instances();
+        assert_eq!(health[0].requests, 0);
+        assert_eq!(health[0].failures, 0);
+        assert_eq!(health[0].failed_checks, 2);
+        assert_eq!(tracker.metrics.get_requests("test", 1, OUTCOME_FAILURE), 0);
+
+        // an answering instance only closes a half-open circuit
+        tracker.record_check(1, true);
+        assert_eq!(state(&tracker, 1), CircuitState::Open);
+        tracker.breakers.lock().unwrap().get_mut(&1).unwrap().state = CircuitState::HalfOpen;
+        tracker.record_check(1, true);
+        assert_eq!(state(&tracker, 1), CircuitState::Closed);
+
+        tracker.retain(&[2]);
+        assert!(tracker.instances().is_empty());
+    }
+}
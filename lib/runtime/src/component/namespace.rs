@@ -20,7 +20,7 @@ use futures::{Stream, TryStreamExt};
 
 use super::*;
 
-use crate::traits::events::{EventPublisher, EventSubscriber};
+use crate::traits::events::{EventPublisher, EventStream, EventSubscriber};
 
 #[async_trait]
 impl EventPublisher for Namespace {
@@ -43,23 +43,15 @@ impl EventPublisher for Namespace {
         bytes: Vec<u8>,
     ) -> Result<()> {
         let subject = format!("{}.{}", self.subject(), event_name.as_ref());
-        Ok(self
-            .drt()
-            .nats_client()
-            .client()
-            .publish(subject, bytes.into())
-            .await?)
+        self.drt().publish_event(subject, bytes).await
     }
 }
 
 #[async_trait]
 impl EventSubscriber for Namespace {
-    async fn subscribe(
-        &self,
-        event_name: impl AsRef<str> + Send + Sync,
-    ) -> Result<async_nats::Subscriber> {
+    async fn subscribe(&self, event_name: impl AsRef<str> + Send + Sync) -> Result<EventStream> {
         let subject = format!("{}.{}", self.subject(), event_name.as_ref());
-        Ok(self.drt().nats_client().client().subscribe(subject).await?)
+        self.drt().subscribe_events(subject).await
     }
 
     async fn subscribe_with_type<T: for<'de> Deserialize<'de> + Send + 'static>(
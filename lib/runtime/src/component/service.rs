@@ -61,35 +61,42 @@ impl ServiceConfigBuilder {
 
         let mut guard = component.drt.component_registry.inner.lock().await;
 
-        if guard.services.contains_key(&service_name) {
+        if guard.services.contains_key(&service_name)
+            || guard.local_services.contains_key(&service_name)
+        {
             return Err(anyhow::anyhow!("Service already exists"));
         }
 
-        // create service on the secondary runtime
-        let builder = component.drt.nats_client.client().service_builder();
-
-        tracing::debug!("Starting service: {}", service_name);
-        let service_builder = builder
-            .description(description)
-            .stats_handler(move |name, stats| {
-                log::trace!("stats_handler: {name}, {stats:?}");
-                let mut guard = stats_handler_registry.lock().unwrap();
-                match guard.get_mut(&name) {
-                    Some(handler) => handler(stats),
-                    None => serde_json::Value::Null,
-                }
-            });
-        tracing::debug!("Got builder");
-        let service = service_builder
-            .start(service_name.clone(), version)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to start service: {e}"))?;
-
-        // new copy of service_name as the previous one is moved into the task above
-        let service_name = component.service_name();
-
-        // insert the service into the registry
-        guard.services.insert(service_name.clone(), service);
+        let stats_handler = move |name: String, stats: EndpointStats| {
+            log::trace!("stats_handler: {name}, {stats:?}");
+            let mut guard = stats_handler_registry.lock().unwrap();
+            match guard.get_mut(&name) {
+                Some(handler) => handler(stats),
+                None => serde_json::Value::Null,
+            }
+        };
+
+        if let Some(bus) = &component.drt.local_bus {
+            tracing::debug!("Starting local service: {}", service_name);
+            let service = bus.add_service(service_name.clone(), version, Box::new(stats_handler));
+            guard.local_services.insert(service_name.clone(), service);
+        } else {
+            // create service on the secondary runtime
+            let builder = component.drt.nats().client().service_builder();
+
+            tracing::debug!("Starting service: {}", service_name);
+            let service_builder = builder
+                .description(description)
+                .stats_handler(stats_handler);
+            tracing::debug!("Got builder");
+            let service = service_builder
+                .start(service_name.clone(), version)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to start service: {e}"))?;
+
+            // insert the service into the registry
+            guard.services.insert(service_name.clone(), service);
+        }
 
         // insert the stats handler into the registry
         guard
@@ -17,8 +17,10 @@ pub use crate::component::Component;
 use crate::{
     component::{self, ComponentBuilder, Endpoint, InstanceSource, Namespace},
     discovery::DiscoveryClient,
+    pipeline::network::egress::addressed_router::RequestPlane,
     service::ServiceClient,
-    transports::{etcd, nats, tcp},
+    traits::events::EventStream,
+    transports::{etcd, local, nats, tcp},
     ErrorContext,
 };
 
@@ -68,7 +70,8 @@ impl DistributedRuntime {
         Ok(Self {
             runtime,
             etcd_client,
-            nats_client,
+            nats_client: Some(nats_client),
+            local_bus: None,
             tcp_server: Arc::new(OnceCell::new()),
             component_registry: component::Registry::new(),
             is_static,
@@ -76,6 +79,29 @@ impl DistributedRuntime {
         })
     }
 
+    /// Create a runtime which uses the `backend` in place of etcd and NATS, for tests and
+    /// single host deployments. The runtimes sharing a backend, e.g.
+    /// [`local::LocalBackend::global`] or one served by another process of the host with
+    /// [`local::LocalBackend::serve`] and joined with [`local::LocalBackend::connect`], discover
+    /// each other's instances, receive each other's events and route requests to each other's
+    /// endpoints as if they were connected to the same etcd and NATS servers.
+    ///
+    /// NATS specific features, such as JetStream or the object store, are not available:
+    /// [`DistributedRuntime::try_nats_client`] returns `None`.
+    pub async fn local(runtime: Runtime, backend: local::LocalBackend) -> Result<Self> {
+        let etcd_client = etcd::Client::local(backend.kv().clone(), true, runtime.clone()).await?;
+        Ok(Self {
+            runtime,
+            etcd_client: Some(etcd_client),
+            nats_client: None,
+            local_bus: Some(backend.bus().clone()),
+            tcp_server: Arc::new(OnceCell::new()),
+            component_registry: component::Registry::new(),
+            is_static: false,
+            instance_sources: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
     pub async fn from_settings(runtime: Runtime) -> Result<Self> {
         let config = DistributedConfig::from_settings(false);
         Self::new(runtime, config).await
@@ -132,7 +158,48 @@ impl DistributedRuntime {
     }
 
     pub(crate) fn service_client(&self) -> ServiceClient {
-        ServiceClient::new(self.nats_client.clone())
+        match &self.local_bus {
+            Some(bus) => ServiceClient::new_local(bus.clone()),
+            None => ServiceClient::new(self.nats()),
+        }
+    }
+
+    /// The transport of the requests sent to the endpoints
+    pub(crate) fn request_plane(&self) -> RequestPlane {
+        match &self.local_bus {
+            Some(bus) => RequestPlane::Local(bus.clone()),
+            None => RequestPlane::Nats(self.nats().client().clone()),
+        }
+    }
+
+    /// Publish an event on `subject`
+    pub(crate) async fn publish_event(&self, subject: String, payload: Vec<u8>) -> Result<()> {
+        match &self.local_bus {
+            Some(bus) => bus.publish(subject, payload.into()),
+            None => {
+                self.nats()
+                    .client()
+                    .publish(subject, payload.into())
+                    .await?
+            }
+        }
+        Ok(())
+    }
+
+    /// The events published on `subject`
+    pub(crate) async fn subscribe_events(&self, subject: String) -> Result<EventStream> {
+        let events: EventStream = match &self.local_bus {
+            Some(bus) => Box::pin(bus.subscribe(subject)),
+            None => Box::pin(self.nats().client().subscribe(subject).await?),
+        };
+        Ok(events)
+    }
+
+    /// The NATS client of a runtime without a local bus
+    pub(crate) fn nats(&self) -> &nats::Client {
+        self.nats_client
+            .as_ref()
+            .expect("a runtime without a local bus has a NATS client")
     }
 
     pub async fn tcp_server(&self) -> Result<Arc<tcp::server::TcpStreamServer>> {
@@ -147,10 +214,24 @@ impl DistributedRuntime {
             .clone())
     }
 
+    /// # Panics
+    ///
+    /// Panics on a local runtime, which has no NATS client; use
+    /// [`DistributedRuntime::try_nats_client`] where the runtime may be local.
     pub fn nats_client(&self) -> nats::Client {
+        self.nats().clone()
+    }
+
+    /// The NATS client, or `None` for a local runtime
+    pub fn try_nats_client(&self) -> Option<nats::Client> {
         self.nats_client.clone()
     }
 
+    /// The bus replacing NATS in a local runtime
+    pub fn local_bus(&self) -> Option<local::LocalBus> {
+        self.local_bus.clone()
+    }
+
     // todo(ryan): deprecate this as we move to Discovery traits and Component Identifiers
     pub fn etcd_client(&self) -> Option<etcd::Client> {
         self.etcd_client.clone()
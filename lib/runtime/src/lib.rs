@@ -45,6 +45,7 @@ pub mod runtime;
 pub mod service;
 pub mod slug;
 pub mod storage;
+pub mod trace;
 pub mod traits;
 pub mod transports;
 pub mod utils;
@@ -82,7 +83,9 @@ pub struct DistributedRuntime {
 
     // we might consider a unifed transport manager here
     etcd_client: Option<transports::etcd::Client>,
-    nats_client: transports::nats::Client,
+    // exactly one of these two carries the event and request planes
+    nats_client: Option<transports::nats::Client>,
+    local_bus: Option<transports::local::LocalBus>,
     tcp_server: Arc<OnceCell<Arc<transports::tcp::server::TcpStreamServer>>>,
 
     // local registry for components
@@ -37,6 +37,8 @@
 //! "test_logging" = "info"
 //! "test_logging::api" = "trace"
 //! ```
+//!
+//! The spans of distributed requests can also be exported as traces, see [`crate::trace`].
 
 use std::collections::{BTreeMap, HashMap};
 use std::sync::Once;
@@ -127,14 +129,20 @@ pub fn init() {
                 .event_format(CustomJsonFormatter::new())
                 .with_writer(std::io::stderr)
                 .with_filter(filter_layer);
-            tracing_subscriber::registry().with(l).init();
+            tracing_subscriber::registry()
+                .with(l)
+                .with(crate::trace::layer_from_env())
+                .init();
         } else {
             let l = fmt::layer()
                 .with_ansi(!crate::config::disable_ansi_logging())
                 .event_format(fmt::format().compact().with_timer(TimeFormatter::new()))
                 .with_writer(std::io::stderr)
                 .with_filter(filter_layer);
-            tracing_subscriber::registry().with(l).init();
+            tracing_subscriber::registry()
+                .with(l)
+                .with(crate::trace::layer_from_env())
+                .init();
         };
     });
 }
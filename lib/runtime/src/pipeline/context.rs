@@ -29,12 +29,14 @@ use crate::engine::AsyncEngineController;
 use async_trait::async_trait;
 
 use super::registry::Registry;
+use crate::trace::TraceParent;
 
 pub struct Context<T: Data> {
     current: T,
     controller: Arc<Controller>, //todo: hold this as an arc
     registry: Registry,
     stages: Vec<String>,
+    traceparent: Option<TraceParent>,
 }
 
 impl<T: Send + Sync + 'static> Context<T> {
@@ -45,6 +47,7 @@ impl<T: Send + Sync + 'static> Context<T> {
             controller: Arc::new(Controller::default()),
             registry: Registry::new(),
             stages: Vec::new(),
+            traceparent: None,
         }
     }
 
@@ -54,6 +57,7 @@ impl<T: Send + Sync + 'static> Context<T> {
             controller: Arc::new(controller),
             registry: Registry::new(),
             stages: Vec::new(),
+            traceparent: None,
         }
     }
 
@@ -63,6 +67,7 @@ impl<T: Send + Sync + 'static> Context<T> {
             controller: Arc::new(Controller::new(id)),
             registry: Registry::new(),
             stages: Vec::new(),
+            traceparent: None,
         }
     }
 
@@ -109,6 +114,7 @@ impl<T: Send + Sync + 'static> Context<T> {
                 controller: self.controller,
                 registry: self.registry,
                 stages: self.stages,
+                traceparent: self.traceparent,
             },
         )
     }
@@ -118,10 +124,31 @@ impl<T: Send + Sync + 'static> Context<T> {
         self.transfer(())
     }
 
+    /// A Context of the same request for a new object. It shares the controller and the shared
+    /// objects of the registry, but not the unique ones. Used to send a request more than once.
+    pub fn fork<U: Send + Sync + 'static>(&self, current: U) -> Context<U> {
+        Context {
+            current,
+            controller: self.controller.clone(),
+            registry: self.registry.clone_shared(),
+            stages: self.stages.clone(),
+            traceparent: self.traceparent,
+        }
+    }
+
     pub fn stages(&self) -> &Vec<String> {
         &self.stages
     }
 
+    /// The trace context of the request, sent along with it to the workers
+    pub fn traceparent(&self) -> Option<TraceParent> {
+        self.traceparent
+    }
+
+    pub fn set_traceparent(&mut self, traceparent: Option<TraceParent>) {
+        self.traceparent = traceparent;
+    }
+
     pub fn add_stage(&mut self, stage: &str) {
         self.stages.push(stage.to_string());
     }
@@ -464,4 +491,24 @@ mod tests {
 
         assert_eq!(ctx.current.message, "Processed length: 5");
     }
+    #[test]
+    fn test_fork() {
+        let mut ctx = Context::new(Input {
+            value: "Hello".to_string(),
+        });
+        ctx.insert("shared", 42);
+        ctx.insert_unique("unique", 7);
+        let traceparent = TraceParent::new_root();
+        ctx.set_traceparent(Some(traceparent));
+
+        let fork = ctx.fork(Processed { length: 5 });
+        assert_eq!(fork.id(), ctx.id());
+        assert_eq!(*fork.get::<i32>("shared").unwrap(), 42);
+        assert!(fork.clone_unique::<i32>("unique").is_err());
+        assert_eq!(fork.traceparent(), Some(traceparent));
+
+        // stopping the fork stops the request
+        fork.context().stop_generating();
+        assert!(ctx.context().is_stopped());
+    }
 }
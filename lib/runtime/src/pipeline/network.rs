@@ -274,6 +274,9 @@ struct RequestControlMessage {
     request_type: RequestType,
     response_type: ResponseType,
     connection_info: ConnectionInfo,
+    /// Trace context of the caller, if it sent one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    traceparent: Option<crate::trace::TraceParent>,
 }
 
 pub struct Ingress<Req: PipelineIO, Resp: PipelineIO> {
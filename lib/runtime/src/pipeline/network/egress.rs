@@ -15,5 +15,7 @@
 
 pub mod addressed_router;
 pub mod push_router;
+pub mod queue;
+pub mod retry;
 
 use super::*;
@@ -17,6 +17,8 @@ use async_nats::client::Client;
 use tracing as log;
 
 use super::*;
+use crate::trace::{self, TraceParent};
+use crate::transports::local::LocalBus;
 use crate::Result;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +41,9 @@ struct RequestControlMessage {
     request_type: RequestType,
     response_type: ResponseType,
     connection_info: ConnectionInfo,
+    /// Trace context of the caller, if it sent one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    traceparent: Option<TraceParent>,
 }
 
 pub struct AddressedRequest<T> {
@@ -56,9 +61,36 @@ impl<T> AddressedRequest<T> {
     }
 }
 
+/// Transport of the requests, their responses are streamed back over TCP
+#[derive(Clone)]
+pub enum RequestPlane {
+    Nats(Client),
+    Local(LocalBus),
+}
+
+impl From<Client> for RequestPlane {
+    fn from(client: Client) -> Self {
+        RequestPlane::Nats(client)
+    }
+}
+
+impl RequestPlane {
+    /// Send `payload` to the endpoint at `address`, returning once it was received
+    async fn request(&self, address: String, payload: bytes::Bytes) -> Result<()> {
+        match self {
+            // we might need to add a timeout on this if there is no subscriber to the subject; however, I think nats
+            // will handle this for us
+            RequestPlane::Nats(client) => {
+                let _response = client.request(address, payload).await?;
+            }
+            RequestPlane::Local(bus) => bus.request(&address, payload).await?,
+        }
+        Ok(())
+    }
+}
+
 pub struct AddressedPushRouter {
-    // todo: generalize with a generic
-    req_transport: Client,
+    req_transport: RequestPlane,
 
     // todo: generalize with a generic
     resp_transport: Arc<tcp::server::TcpStreamServer>,
@@ -66,11 +98,11 @@ pub struct AddressedPushRouter {
 
 impl AddressedPushRouter {
     pub fn new(
-        req_transport: Client,
+        req_transport: impl Into<RequestPlane>,
         resp_transport: Arc<tcp::server::TcpStreamServer>,
     ) -> Result<Arc<Self>> {
         Ok(Arc::new(Self {
-            req_transport,
+            req_transport: req_transport.into(),
             resp_transport,
         }))
     }
@@ -120,6 +152,8 @@ where
             request_type: RequestType::SingleIn,
             response_type: ResponseType::ManyOut,
             connection_info,
+            // the span sending the request if traced, else the trace context of the request
+            traceparent: trace::current().or(context.traceparent()),
         };
 
         // next build the two part message where we package the connection info and the request into
@@ -145,12 +179,12 @@ where
 
         // TRANSPORT ABSTRACT REQUIRED - END HERE
 
-        log::trace!(request_id, "enqueueing two-part message to nats");
+        log::trace!(
+            request_id,
+            "enqueueing two-part message on the request plane"
+        );
 
-        // we might need to add a timeout on this if there is no subscriber to the subject; however, I think nats
-        // will handle this for us
-        let _response = self
-            .req_transport
+        self.req_transport
             .request(address.to_string(), buffer)
             .await?;
 
@@ -17,22 +17,28 @@ use async_nats::client::{
     RequestError as NatsRequestError, RequestErrorKind::NoResponders as NatsNoResponders,
 };
 use async_trait::async_trait;
+use futures::{stream, StreamExt};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     future::Future,
     marker::PhantomData,
     sync::{
         atomic::{AtomicU64, Ordering},
-        Arc,
+        Arc, Mutex,
     },
+    time::Instant,
 };
 
+use super::queue::{AdmissionPermit, AdmissionQueue, Priority, PRIORITY_KEY};
+use super::retry::{RetryPolicy, RETRY_CONNECT, RETRY_NO_INSTANCE, RETRY_STREAM};
 use crate::{
-    component::{Client, Endpoint, InstanceSource},
-    engine::{AsyncEngine, Data},
+    component::{health::HealthTracker, Client, Endpoint, InstanceSource},
+    engine::{AsyncEngine, AsyncEngineContextProvider, Data, ResponseStream},
     pipeline::{
-        error::PipelineErrorExt, AddressedPushRouter, AddressedRequest, Error, ManyOut, SingleIn,
+        error::PipelineErrorExt, AddressedPushRouter, AddressedRequest, Context, Error, ManyOut,
+        SingleIn,
     },
     traits::DistributedRuntimeProvider,
 };
@@ -58,10 +64,22 @@ where
     /// Number of round robin requests handled. Used to decide which server is next.
     round_robin_counter: Arc<AtomicU64>,
 
+    /// Number of response streams in flight per instance, shared by the routers of the client.
+    /// Used by [`RouterMode::LeastLoaded`].
+    inflight: Arc<InflightTracker>,
+
     /// The next step in the chain. PushRouter (this object) picks an instances,
     /// addresses it, then passes it to AddressedPushRouter which does the network traffic.
     addressed: Arc<AddressedPushRouter>,
 
+    /// Optional bound on the requests in flight to the instances. Requests beyond it wait for
+    /// admission by priority, or are rejected.
+    queue: Option<Arc<AdmissionQueue>>,
+
+    /// Optional retry of the requests which fail before their first response, on another
+    /// instance
+    retry: Option<Arc<RetryPolicy>>,
+
     /// An internal Rust type. This says that PushRouter is generic over the T and U types,
     /// which are the input and output types of it's `generate` function. It allows the
     /// compiler to specialize us at compile time.
@@ -74,6 +92,8 @@ pub enum RouterMode {
     RoundRobin,
     Random,
     Direct(i64),
+    /// The instance with the fewest requests in flight from this router
+    LeastLoaded,
     // Marker value, KV routing itself is in dynamo-llm
     KV,
 }
@@ -84,9 +104,112 @@ impl RouterMode {
     }
 }
 
+/// Counts the response streams in flight per instance
+#[derive(Debug, Default)]
+pub(crate) struct InflightTracker {
+    counts: Mutex<HashMap<i64, usize>>,
+}
+
+impl InflightTracker {
+    /// Count a request to `instance_id` until the returned guard is dropped
+    fn track(self: &Arc<Self>, instance_id: i64) -> InflightGuard {
+        *self.counts.lock().unwrap().entry(instance_id).or_default() += 1;
+        InflightGuard {
+            tracker: self.clone(),
+            instance_id,
+        }
+    }
+
+    /// Count a request to the candidate with the fewest requests in flight. Ties go to the first
+    /// such candidate after `offset`, so that idle instances take turns.
+    fn track_least_loaded(
+        self: &Arc<Self>,
+        candidates: &[i64],
+        offset: usize,
+    ) -> Option<InflightGuard> {
+        let mut counts = self.counts.lock().unwrap();
+        let count = candidates.len();
+        let instance_id = (0..count)
+            .map(|i| candidates[(offset + i) % count])
+            .min_by_key(|instance_id| counts.get(instance_id).copied().unwrap_or(0))?;
+        *counts.entry(instance_id).or_default() += 1;
+        Some(InflightGuard {
+            tracker: self.clone(),
+            instance_id,
+        })
+    }
+
+    fn get(&self, instance_id: i64) -> usize {
+        self.counts
+            .lock()
+            .unwrap()
+            .get(&instance_id)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// A request in flight to an instance
+struct InflightGuard {
+    tracker: Arc<InflightTracker>,
+    instance_id: i64,
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        let mut counts = self.tracker.counts.lock().unwrap();
+        if let Some(count) = counts.get_mut(&self.instance_id) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.instance_id);
+            }
+        }
+    }
+}
+
+/// Keep `guard` alive until the response stream is dropped
+fn hold_until_dropped<U: Data, G: Send + Sync + 'static>(
+    stream: ManyOut<U>,
+    guard: G,
+) -> ManyOut<U> {
+    let ctx = stream.context();
+    let stream = stream.map(move |response| {
+        let _guard = &guard;
+        response
+    });
+    ResponseStream::new(Box::pin(stream), ctx)
+}
+
+/// Record the outcome of a request to `instance_id` once its first response arrived, or its
+/// stream ended without one
+fn observe_health<U: Data>(
+    mut stream: ManyOut<U>,
+    health: Arc<HealthTracker>,
+    instance_id: i64,
+    started: Instant,
+) -> ManyOut<U> {
+    let ctx = stream.context();
+    let stream_ctx = ctx.clone();
+    let observed = async_stream::stream! {
+        let mut responded = false;
+        while let Some(response) = stream.next().await {
+            if !responded {
+                responded = true;
+                health.record_success(instance_id, started.elapsed());
+            }
+            yield response;
+        }
+        // a cancelled request says nothing about the instance
+        if !responded && !stream_ctx.is_stopped() {
+            health.record_failure(instance_id);
+        }
+    };
+    ResponseStream::new(Box::pin(observed), ctx)
+}
+
 async fn addressed_router(endpoint: &Endpoint) -> anyhow::Result<Arc<AddressedPushRouter>> {
     AddressedPushRouter::new(
-        endpoint.drt().nats_client.client().clone(),
+        endpoint.drt().request_plane(),
         endpoint.drt().tcp_server().await?,
     )
 }
@@ -99,14 +222,31 @@ where
     pub async fn from_client(client: Client, router_mode: RouterMode) -> anyhow::Result<Self> {
         let addressed = addressed_router(&client.endpoint).await?;
         Ok(PushRouter {
+            inflight: client.inflight().clone(),
             client,
             addressed,
             router_mode,
             round_robin_counter: Arc::new(AtomicU64::new(0)),
+            queue: None,
+            retry: None,
             _phantom: PhantomData,
         })
     }
 
+    /// Admit requests through the given queue before routing them to an instance.
+    /// The queue may be shared by several routers to the same instances.
+    pub fn with_admission_queue(mut self, queue: Option<Arc<AdmissionQueue>>) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    /// Retry the requests which fail before their first response, excluding the instances they
+    /// failed on. Requests to a specific instance are not retried.
+    pub fn with_retry_policy(mut self, retry: Option<Arc<RetryPolicy>>) -> Self {
+        self.retry = retry;
+        self
+    }
+
     /// Issue a request to the next available instance in a round-robin fashion
     pub async fn round_robin(&self, request: SingleIn<T>) -> anyhow::Result<ManyOut<U>> {
         let slf = self;
@@ -127,9 +267,10 @@ where
             };
             tracing::trace!("round robin router selected {instance_id}");
 
-            Ok(instance_id)
+            Ok(slf.inflight.track(instance_id))
         };
-        self.generate_with_fault_tolerance(routing_algorithm, request)
+        let permit = self.admit(&request).await?;
+        self.generate_with_fault_tolerance(routing_algorithm, request, permit, true)
             .await
     }
 
@@ -151,9 +292,10 @@ where
                 instances[offset as usize].id()
             };
             tracing::trace!("random router selected {instance_id}");
-            Ok(instance_id)
+            Ok(slf.inflight.track(instance_id))
         };
-        self.generate_with_fault_tolerance(routing_algorithm, request)
+        let permit = self.admit(&request).await?;
+        self.generate_with_fault_tolerance(routing_algorithm, request, permit, true)
             .await
     }
 
@@ -162,6 +304,33 @@ where
         &self,
         request: SingleIn<T>,
         instance_id: i64,
+    ) -> anyhow::Result<ManyOut<U>> {
+        let permit = self.admit(&request).await?;
+        self.direct_admitted(request, instance_id, permit).await
+    }
+
+    /// Wait for the request to be admitted by the admission queue, if there is one. Routers which
+    /// choose the instance themselves, like the KV router, call this before choosing it, so the
+    /// choice reflects the load when the request is sent, and then hand the permit to
+    /// [`PushRouter::direct_admitted`].
+    pub async fn admit(&self, request: &SingleIn<T>) -> anyhow::Result<Option<AdmissionPermit>> {
+        let Some(queue) = self.queue.as_ref() else {
+            return Ok(None);
+        };
+        let priority = request
+            .get::<Priority>(PRIORITY_KEY)
+            .map(|priority| *priority)
+            .unwrap_or_default();
+        Ok(Some(queue.acquire(priority).await?))
+    }
+
+    /// Issue a request which was already admitted to a specific endpoint. The request holds the
+    /// permit until its response stream is dropped.
+    pub async fn direct_admitted(
+        &self,
+        request: SingleIn<T>,
+        instance_id: i64,
+        permit: Option<AdmissionPermit>,
     ) -> anyhow::Result<ManyOut<U>> {
         let slf = self;
         let routing_algorithm = move || async move {
@@ -176,12 +345,48 @@ where
                     slf.client.endpoint.etcd_root()
                 ));
             }
-            Ok(instance_id)
+            Ok(slf.inflight.track(instance_id))
         };
-        self.generate_with_fault_tolerance(routing_algorithm, request)
+        self.generate_with_fault_tolerance(routing_algorithm, request, permit, false)
             .await
     }
 
+    /// Issue a request to the instance with the fewest requests in flight from this router
+    pub async fn least_loaded(&self, request: SingleIn<T>) -> anyhow::Result<ManyOut<U>> {
+        let slf = self;
+        let routing_algorithm = move || async move {
+            let instance_ids: Vec<i64> = slf
+                .client
+                .instances_avail()
+                .await
+                .iter()
+                .map(|instance| instance.id())
+                .collect();
+            if instance_ids.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "no instances found for endpoint {:?}",
+                    slf.client.endpoint.etcd_root()
+                ));
+            }
+            let counter = slf.round_robin_counter.fetch_add(1, Ordering::Relaxed);
+            let offset = (counter % instance_ids.len() as u64) as usize;
+            let guard = slf
+                .inflight
+                .track_least_loaded(&instance_ids, offset)
+                .ok_or_else(|| anyhow::anyhow!("no instance selected"))?;
+            tracing::trace!("least loaded router selected {}", guard.instance_id);
+            Ok(guard)
+        };
+        let permit = self.admit(&request).await?;
+        self.generate_with_fault_tolerance(routing_algorithm, request, permit, true)
+            .await
+    }
+
+    /// Number of requests in flight to the given instance, from all the routers of the client
+    pub fn inflight_requests(&self, instance_id: i64) -> usize {
+        self.inflight.get(instance_id)
+    }
+
     pub async fn r#static(&self, request: SingleIn<T>) -> anyhow::Result<ManyOut<U>> {
         let subject = self.client.endpoint.subject();
         tracing::debug!("static got subject: {subject}");
@@ -190,29 +395,188 @@ where
         self.addressed.generate(request).await
     }
 
+    #[tracing::instrument(
+        name = "route",
+        skip_all,
+        fields(endpoint = %self.client.endpoint.path(), instance_id = tracing::field::Empty)
+    )]
     async fn generate_with_fault_tolerance<F, R>(
         &self,
         routing_algorithm: F,
         request: SingleIn<T>,
+        permit: Option<AdmissionPermit>,
+        failover: bool,
     ) -> anyhow::Result<ManyOut<U>>
     where
-        F: FnOnce() -> R,
-        R: Future<Output = anyhow::Result<i64>>,
+        F: Fn() -> R,
+        R: Future<Output = anyhow::Result<InflightGuard>>,
     {
-        let instance_id = routing_algorithm().await?;
+        let retry = match self.retry.as_ref() {
+            Some(retry) if failover && retry.config().max_retries > 0 => retry,
+            _ => {
+                let inflight = routing_algorithm().await?;
+                let instance_id = inflight.instance_id;
 
-        let subject = self.client.endpoint.subject_to(instance_id);
-        let request = request.map(|req| AddressedRequest::new(req, subject));
+                let subject = self.client.endpoint.subject_to(instance_id);
+                let request = request.map(|req| AddressedRequest::new(req, subject));
+
+                tracing::Span::current().record("instance_id", instance_id);
+                let health = self.client.health().cloned();
+                if let Some(health) = health.as_ref() {
+                    health.on_dispatch(instance_id);
+                }
+                let started = Instant::now();
+                let stream = self.addressed.generate(request).await;
+                if let Some(err) = stream.as_ref().err() {
+                    if let Some(health) = health.as_ref() {
+                        health.record_failure(instance_id);
+                    }
+                    if let Some(req_err) = err.downcast_ref::<NatsRequestError>() {
+                        if matches!(req_err.kind(), NatsNoResponders) {
+                            self.client.report_instance_down(instance_id).await;
+                        }
+                    }
+                }
+                let stream = match health {
+                    Some(health) => {
+                        stream.map(|stream| observe_health(stream, health, instance_id, started))
+                    }
+                    None => stream,
+                };
+
+                // the request holds its admission slot and counts as in flight until the
+                // response stream is dropped
+                return stream.map(|stream| hold_until_dropped(stream, (permit, inflight)));
+            }
+        };
+
+        let (stream, inflight) = self
+            .generate_with_retries(routing_algorithm, request, retry)
+            .await?;
+        Ok(hold_until_dropped(stream, (permit, inflight)))
+    }
+
+    /// Send the request until an instance produces its first response, or its retries are used
+    /// up. Each failed instance is reported down, so that the next attempt picks another one.
+    async fn generate_with_retries<F, R>(
+        &self,
+        routing_algorithm: F,
+        request: SingleIn<T>,
+        retry: &RetryPolicy,
+    ) -> anyhow::Result<(ManyOut<U>, InflightGuard)>
+    where
+        F: Fn() -> R,
+        R: Future<Output = anyhow::Result<InflightGuard>>,
+    {
+        let (request, context) = request.into_parts();
+        // serialize once, every attempt sends the same bytes
+        let payload = serde_json::value::to_raw_value(&request)?;
+        let engine_ctx = context.context();
 
-        let stream = self.addressed.generate(request).await;
-        if let Some(err) = stream.as_ref().err() {
-            if let Some(req_err) = err.downcast_ref::<NatsRequestError>() {
-                if matches!(req_err.kind(), NatsNoResponders) {
-                    self.client.report_instance_down(instance_id).await;
+        let mut first_instance_id = None;
+        let mut retries = 0;
+        loop {
+            let (reason, err) = match routing_algorithm().await {
+                Err(err) if retries == 0 => return Err(err),
+                Err(err) => (RETRY_NO_INSTANCE, err),
+                Ok(inflight) => {
+                    let instance_id = inflight.instance_id;
+                    let first_instance_id = *first_instance_id.get_or_insert(instance_id);
+                    match self.attempt(&context, &payload, instance_id).await {
+                        Ok(stream) => {
+                            if instance_id != first_instance_id {
+                                retry.record_failover();
+                            }
+                            return Ok((stream, inflight));
+                        }
+                        Err((reason, err)) => {
+                            self.client.report_instance_down(instance_id).await;
+                            (reason, err)
+                        }
+                    }
                 }
+            };
+
+            if engine_ctx.is_stopped() {
+                return Err(err);
+            }
+            if retries >= retry.config().max_retries {
+                retry.record_exhausted();
+                return Err(err.context(format!("request failed after {retries} retries")));
             }
+            retries += 1;
+            retry.record_retry(reason, retries == 1);
+            tracing::warn!(
+                request_id = engine_ctx.id(),
+                retries,
+                reason,
+                error = format!("{err:#}"),
+                "retrying request"
+            );
+
+            tokio::select! {
+                _ = tokio::time::sleep(retry.backoff(retries)) => {}
+                _ = engine_ctx.stopped() => return Err(err),
+            }
+        }
+    }
+
+    /// Send the request to `instance_id` and wait for its first response, recording the outcome
+    /// in the health tracker. On failure returns the reason label of the retry along with the
+    /// error.
+    async fn attempt(
+        &self,
+        context: &Context<()>,
+        payload: &serde_json::value::RawValue,
+        instance_id: i64,
+    ) -> Result<ManyOut<U>, (&'static str, anyhow::Error)> {
+        tracing::Span::current().record("instance_id", instance_id);
+        let health = self.client.health();
+        if let Some(health) = health {
+            health.on_dispatch(instance_id);
+        }
+        let started = Instant::now();
+        let outcome = self.attempt_inner(context, payload, instance_id).await;
+        if let Some(health) = health {
+            match &outcome {
+                // a cancelled request says nothing about the instance
+                _ if context.context().is_stopped() => {}
+                Ok(_) => health.record_success(instance_id, started.elapsed()),
+                Err(_) => health.record_failure(instance_id),
+            }
+        }
+        outcome
+    }
+
+    async fn attempt_inner(
+        &self,
+        context: &Context<()>,
+        payload: &serde_json::value::RawValue,
+        instance_id: i64,
+    ) -> Result<ManyOut<U>, (&'static str, anyhow::Error)> {
+        let subject = self.client.endpoint.subject_to(instance_id);
+        let request = context.fork(AddressedRequest::new(payload.to_owned(), subject));
+        let mut responses: ManyOut<U> = self
+            .addressed
+            .generate(request)
+            .await
+            .map_err(|err| (RETRY_CONNECT, err))?;
+
+        let ctx = responses.context();
+        match responses.next().await {
+            Some(first) => Ok(ResponseStream::new(
+                Box::pin(stream::iter([first]).chain(responses)),
+                ctx,
+            )),
+            // the request was cancelled, there is nothing to retry
+            None if ctx.is_stopped() => Ok(ResponseStream::new(Box::pin(stream::empty()), ctx)),
+            None => Err((
+                RETRY_STREAM,
+                anyhow::anyhow!(
+                    "instance {instance_id} closed the response stream before the first response"
+                ),
+            )),
         }
-        stream
     }
 }
 
@@ -229,6 +593,7 @@ where
                 RouterMode::Random => self.random(request).await,
                 RouterMode::RoundRobin => self.round_robin(request).await,
                 RouterMode::Direct(instance_id) => self.direct(request, instance_id).await,
+                RouterMode::LeastLoaded => self.least_loaded(request).await,
                 RouterMode::KV => {
                     anyhow::bail!("KV routing should not call generate on PushRouter");
                 }
@@ -236,3 +601,33 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_loaded_selection() {
+        let tracker = Arc::new(InflightTracker::default());
+        let instances = [1, 2, 3];
+
+        // idle instances take turns
+        let a = tracker.track_least_loaded(&instances, 0).unwrap();
+        let b = tracker.track_least_loaded(&instances, 0).unwrap();
+        let c = tracker.track_least_loaded(&instances, 0).unwrap();
+        assert_eq!([a.instance_id, b.instance_id, c.instance_id], [1, 2, 3]);
+
+        // a finished request frees its instance
+        let d = tracker.track_least_loaded(&instances, 0).unwrap();
+        assert_eq!(d.instance_id, 1);
+        drop(b);
+        let e = tracker.track_least_loaded(&instances, 0).unwrap();
+        assert_eq!(e.instance_id, 2);
+
+        assert_eq!(tracker.get(1), 2);
+        drop((a, d));
+        assert_eq!(tracker.get(1), 0);
+        assert!(!tracker.counts.lock().unwrap().contains_key(&1));
+        drop((c, e));
+    }
+}
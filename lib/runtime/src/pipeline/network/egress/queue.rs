@@ -12,3 +12,493 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+//! Admission queue in front of the [`super::push_router::PushRouter`]
+//!
+//! The queue bounds the number of requests the router has in flight. Requests beyond that wait in
+//! one FIFO per [`Priority`]; a finishing request hands its slot to the oldest waiter of the
+//! highest priority. Requests are rejected with a [`QueueError`] when the queue is full or when
+//! they waited longer than the configured deadline.
+//!
+//! The priority of a request is read from its [`crate::pipeline::Context`] under
+//! [`PRIORITY_KEY`]; requests without one are [`Priority::Normal`].
+
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+
+/// Key of the [`Priority`] in the registry of the request [`crate::pipeline::Context`]
+pub const PRIORITY_KEY: &str = "admission_priority";
+
+/// Value for the `reason` label of the rejected counter when the queue is full
+pub const REJECTED_QUEUE_FULL: &str = "queue_full";
+
+/// Value for the `reason` label of the rejected counter when a request exceeded its deadline
+pub const REJECTED_DEADLINE: &str = "deadline";
+
+/// Priority class of a request; higher priorities are always admitted first
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// Interactive traffic
+    High,
+
+    #[default]
+    Normal,
+
+    /// Batch traffic, only admitted when no other request is waiting
+    Low,
+}
+
+impl Priority {
+    /// All priorities, highest first
+    pub const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::High => "high",
+            Priority::Normal => "normal",
+            Priority::Low => "low",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl FromStr for Priority {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "high" => Ok(Priority::High),
+            "normal" => Ok(Priority::Normal),
+            "low" => Ok(Priority::Low),
+            _ => anyhow::bail!("Invalid priority '{s}', expected one of: high, normal, low"),
+        }
+    }
+}
+
+/// Configuration of an [`AdmissionQueue`]
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    /// Number of requests admitted to the workers at the same time
+    pub max_inflight: usize,
+
+    /// Number of requests waiting for admission; further requests are rejected
+    pub max_depth: usize,
+
+    /// Time a request may wait for admission before it is rejected
+    pub max_wait: Duration,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            max_inflight: 256,
+            max_depth: 1024,
+            max_wait: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A request rejected by the [`AdmissionQueue`]. These map onto 503 Service Unavailable.
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+pub enum QueueError {
+    #[error("Admission queue is full: {0} requests waiting")]
+    Full(usize),
+
+    #[error("Request was not admitted within {0:?}")]
+    DeadlineExceeded(Duration),
+}
+
+/// Prometheus metrics of the admission queues, labeled by queue name
+pub struct QueueMetrics {
+    depth: IntGaugeVec,
+    inflight: IntGaugeVec,
+    wait_time: HistogramVec,
+    rejected: IntCounterVec,
+}
+
+impl Default for QueueMetrics {
+    fn default() -> Self {
+        Self::new("dynamo")
+    }
+}
+
+impl QueueMetrics {
+    /// Create QueueMetrics with the given prefix
+    /// The following metrics will be created:
+    /// - `{prefix}_admission_queue_depth` - IntGaugeVec for the number of waiting requests by priority
+    /// - `{prefix}_admission_queue_inflight_requests` - IntGaugeVec for the number of admitted requests
+    /// - `{prefix}_admission_queue_wait_seconds` - HistogramVec for the time requests waited for admission
+    /// - `{prefix}_admission_queue_rejected_total` - IntCounterVec for the requests rejected by reason
+    pub fn new(prefix: &str) -> Self {
+        let depth = IntGaugeVec::new(
+            Opts::new(
+                format!("{}_admission_queue_depth", prefix),
+                "Number of requests waiting for admission",
+            ),
+            &["queue", "priority"],
+        )
+        .unwrap();
+
+        let inflight = IntGaugeVec::new(
+            Opts::new(
+                format!("{}_admission_queue_inflight_requests", prefix),
+                "Number of requests admitted to the workers",
+            ),
+            &["queue"],
+        )
+        .unwrap();
+
+        let wait_time = HistogramVec::new(
+            HistogramOpts::new(
+                format!("{}_admission_queue_wait_seconds", prefix),
+                "Time requests waited for admission",
+            )
+            .buckets(vec![
+                0.0, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0,
+            ]),
+            &["queue", "priority"],
+        )
+        .unwrap();
+
+        let rejected = IntCounterVec::new(
+            Opts::new(
+                format!("{}_admission_queue_rejected_total", prefix),
+                "Total number of requests rejected by the admission queue",
+            ),
+            &["queue", "reason"],
+        )
+        .unwrap();
+
+        QueueMetrics {
+            depth,
+            inflight,
+            wait_time,
+            rejected,
+        }
+    }
+
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.depth.clone()))?;
+        registry.register(Box::new(self.inflight.clone()))?;
+        registry.register(Box::new(self.wait_time.clone()))?;
+        registry.register(Box::new(self.rejected.clone()))?;
+        Ok(())
+    }
+
+    /// Get the number of requests waiting in the given queue with the given priority
+    pub fn get_depth(&self, queue: &str, priority: Priority) -> i64 {
+        self.depth
+            .with_label_values(&[queue, priority.as_str()])
+            .get()
+    }
+
+    /// Get the number of requests admitted by the given queue
+    pub fn get_inflight(&self, queue: &str) -> i64 {
+        self.inflight.with_label_values(&[queue]).get()
+    }
+
+    /// Get the number of requests rejected by the given queue for the given reason
+    /// (see [`REJECTED_QUEUE_FULL`] and [`REJECTED_DEADLINE`])
+    pub fn get_rejected(&self, queue: &str, reason: &str) -> u64 {
+        self.rejected.with_label_values(&[queue, reason]).get()
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    inflight: usize,
+    waiting: [VecDeque<oneshot::Sender<()>>; 3],
+}
+
+impl QueueState {
+    fn depth(&self) -> usize {
+        self.waiting.iter().map(VecDeque::len).sum()
+    }
+
+    /// Drop waiters that gave up
+    fn prune(&mut self) {
+        for waiting in self.waiting.iter_mut() {
+            waiting.retain(|tx| !tx.is_closed());
+        }
+    }
+}
+
+/// Bounded, prioritized admission of requests
+pub struct AdmissionQueue {
+    name: String,
+    config: QueueConfig,
+    state: Mutex<QueueState>,
+    metrics: Arc<QueueMetrics>,
+}
+
+impl AdmissionQueue {
+    /// Create a queue; `name` is the value of the `queue` label of its metrics
+    pub fn new(name: impl Into<String>, config: QueueConfig, metrics: Arc<QueueMetrics>) -> Self {
+        Self {
+            name: name.into(),
+            config,
+            state: Mutex::new(QueueState::default()),
+            metrics,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn config(&self) -> &QueueConfig {
+        &self.config
+    }
+
+    /// Wait for admission. The request holds its slot until the returned permit is dropped.
+    ///
+    /// If the returned future is dropped while waiting, e.g. because the client went away, the
+    /// request leaves the queue.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        priority: Priority,
+    ) -> Result<AdmissionPermit, QueueError> {
+        let start = Instant::now();
+
+        let rx = {
+            let mut state = self.state.lock().unwrap();
+            state.prune();
+
+            if state.inflight < self.config.max_inflight && state.depth() == 0 {
+                state.inflight += 1;
+                self.update_gauges(&state);
+                return Ok(self.granted(priority, start));
+            }
+
+            let depth = state.depth();
+            if depth >= self.config.max_depth {
+                self.metrics
+                    .rejected
+                    .with_label_values(&[&self.name, REJECTED_QUEUE_FULL])
+                    .inc();
+                return Err(QueueError::Full(depth));
+            }
+
+            let (tx, rx) = oneshot::channel();
+            state.waiting[priority.index()].push_back(tx);
+            self.update_gauges(&state);
+            rx
+        };
+
+        let mut waiter = Waiter {
+            queue: self.clone(),
+            rx: Some(rx),
+        };
+
+        let admitted = tokio::time::timeout(self.config.max_wait, waiter.admitted()).await;
+
+        if let Ok(true) = admitted {
+            waiter.rx = None;
+            return Ok(self.granted(priority, start));
+        }
+
+        // the slot may have been handed over right as the deadline expired
+        if let Some(mut rx) = waiter.rx.take() {
+            rx.close();
+            if rx.try_recv().is_ok() {
+                return Ok(self.granted(priority, start));
+            }
+        }
+        self.remove_closed_waiters();
+
+        self.metrics
+            .rejected
+            .with_label_values(&[&self.name, REJECTED_DEADLINE])
+            .inc();
+        Err(QueueError::DeadlineExceeded(self.config.max_wait))
+    }
+
+    fn granted(self: &Arc<Self>, priority: Priority, start: Instant) -> AdmissionPermit {
+        self.metrics
+            .wait_time
+            .with_label_values(&[&self.name, priority.as_str()])
+            .observe(start.elapsed().as_secs_f64());
+        AdmissionPermit {
+            queue: self.clone(),
+        }
+    }
+
+    /// Hand the slot of a finished request to the next waiter, or free it
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        for priority in Priority::ALL {
+            while let Some(tx) = state.waiting[priority.index()].pop_front() {
+                if tx.send(()).is_ok() {
+                    self.update_gauges(&state);
+                    return;
+                }
+            }
+        }
+        state.inflight = state.inflight.saturating_sub(1);
+        self.update_gauges(&state);
+    }
+
+    fn remove_closed_waiters(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.prune();
+        self.update_gauges(&state);
+    }
+
+    fn update_gauges(&self, state: &QueueState) {
+        for priority in Priority::ALL {
+            self.metrics
+                .depth
+                .with_label_values(&[&self.name, priority.as_str()])
+                .set(state.waiting[priority.index()].len() as i64);
+        }
+        self.metrics
+            .inflight
+            .with_label_values(&[&self.name])
+            .set(state.inflight as i64);
+    }
+}
+
+/// A request waiting for admission; leaves the queue when dropped
+struct Waiter {
+    queue: Arc<AdmissionQueue>,
+    rx: Option<oneshot::Receiver<()>>,
+}
+
+impl Waiter {
+    /// Resolves once the slot of a finished request was handed to this waiter
+    async fn admitted(&mut self) -> bool {
+        match self.rx.as_mut() {
+            Some(rx) => rx.await.is_ok(),
+            None => false,
+        }
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        if let Some(mut rx) = self.rx.take() {
+            rx.close();
+            if rx.try_recv().is_ok() {
+                // the slot was handed over, but nobody is going to use it
+                self.queue.release();
+            } else {
+                self.queue.remove_closed_waiters();
+            }
+        }
+    }
+}
+
+/// The slot of an admitted request, released when dropped
+pub struct AdmissionPermit {
+    queue: Arc<AdmissionQueue>,
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(max_inflight: usize, max_depth: usize, max_wait: Duration) -> Arc<AdmissionQueue> {
+        Arc::new(AdmissionQueue::new(
+            "test",
+            QueueConfig {
+                max_inflight,
+                max_depth,
+                max_wait,
+            },
+            Arc::new(QueueMetrics::default()),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_priority_order() {
+        let queue = queue(1, 8, Duration::from_secs(5));
+        let permit = queue.acquire(Priority::Normal).await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            let queue = queue.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let permit = queue.acquire(priority).await.unwrap();
+                tx.send(priority).unwrap();
+                drop(permit);
+            });
+            // keep the arrival order deterministic
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(queue.metrics.get_depth("test", Priority::Low), 1);
+
+        drop(permit);
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            order.push(rx.recv().await.unwrap());
+        }
+        assert_eq!(order, vec![Priority::High, Priority::Normal, Priority::Low]);
+    }
+
+    #[tokio::test]
+    async fn test_full_and_deadline() {
+        let queue = queue(1, 1, Duration::from_millis(50));
+        let permit = queue.acquire(Priority::Normal).await.unwrap();
+
+        let waiting = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.acquire(Priority::Normal).await.map(|_| ()) })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert_eq!(
+            queue.acquire(Priority::High).await.err(),
+            Some(QueueError::Full(1))
+        );
+        assert_eq!(
+            waiting.await.unwrap(),
+            Err(QueueError::DeadlineExceeded(Duration::from_millis(50)))
+        );
+        assert_eq!(queue.metrics.get_rejected("test", REJECTED_DEADLINE), 1);
+        assert_eq!(queue.metrics.get_depth("test", Priority::Normal), 0);
+
+        // the slot is free again once the admitted request finishes
+        drop(permit);
+        assert_eq!(queue.metrics.get_inflight("test"), 0);
+        let _permit = queue.acquire(Priority::Low).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter() {
+        let queue = queue(1, 8, Duration::from_secs(5));
+        let permit = queue.acquire(Priority::Normal).await.unwrap();
+
+        // the waiting request goes away
+        let waiting = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.acquire(Priority::Normal).await.map(|_| ()) })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        waiting.abort();
+        let _ = waiting.await;
+        assert_eq!(queue.metrics.get_depth("test", Priority::Normal), 0);
+
+        drop(permit);
+        assert_eq!(queue.metrics.get_inflight("test"), 0);
+    }
+}
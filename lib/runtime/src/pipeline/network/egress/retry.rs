@@ -0,0 +1,256 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Retry and failover of requests in the [`super::push_router::PushRouter`]
+//!
+//! A request is retried when it could not be delivered to the chosen instance, or when the
+//! response stream of the instance ended before its first response. Nothing has reached the
+//! caller at that point, so the request can be sent again. The failed instance is reported down,
+//! which excludes it from routing for a while, and the routing algorithm picks another one.
+//!
+//! Once the first response arrived the stream belongs to the caller, later failures are not
+//! retried. Requests to a specific instance, e.g. chosen by the KV router, are not retried either.
+
+use prometheus::{IntCounterVec, Opts, Registry};
+use std::{sync::Arc, time::Duration};
+
+/// Value for the `reason` label of the retries counter when the request could not be delivered
+pub const RETRY_CONNECT: &str = "connect";
+
+/// Value for the `reason` label of the retries counter when the response stream ended before its
+/// first response
+pub const RETRY_STREAM: &str = "stream";
+
+/// Value for the `reason` label of the retries counter when no instance was available
+pub const RETRY_NO_INSTANCE: &str = "no_instance";
+
+/// Configuration of a [`RetryPolicy`]
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Number of times a request may be sent again after its first attempt failed
+    pub max_retries: u32,
+
+    /// Wait before the first retry, doubled for each further retry
+    pub initial_backoff: Duration,
+
+    /// Upper bound of the wait between retries
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Prometheus metrics of the retry policies, labeled by router name
+pub struct RetryMetrics {
+    retries: IntCounterVec,
+    retried_requests: IntCounterVec,
+    failed_over_requests: IntCounterVec,
+    exhausted_requests: IntCounterVec,
+}
+
+impl Default for RetryMetrics {
+    fn default() -> Self {
+        Self::new("dynamo")
+    }
+}
+
+impl RetryMetrics {
+    /// Create RetryMetrics with the given prefix
+    /// The following metrics will be created:
+    /// - `{prefix}_router_retries_total` - IntCounterVec for the retried attempts by reason
+    /// - `{prefix}_router_retried_requests_total` - IntCounterVec for the requests retried at least once
+    /// - `{prefix}_router_failed_over_requests_total` - IntCounterVec for the retried requests served by another instance
+    /// - `{prefix}_router_retries_exhausted_total` - IntCounterVec for the requests which failed after all their retries
+    pub fn new(prefix: &str) -> Self {
+        let retries = IntCounterVec::new(
+            Opts::new(
+                format!("{}_router_retries_total", prefix),
+                "Total number of retried request attempts",
+            ),
+            &["router", "reason"],
+        )
+        .unwrap();
+
+        let retried_requests = IntCounterVec::new(
+            Opts::new(
+                format!("{}_router_retried_requests_total", prefix),
+                "Total number of requests retried at least once",
+            ),
+            &["router"],
+        )
+        .unwrap();
+
+        let failed_over_requests = IntCounterVec::new(
+            Opts::new(
+                format!("{}_router_failed_over_requests_total", prefix),
+                "Total number of retried requests served by another instance than the first one",
+            ),
+            &["router"],
+        )
+        .unwrap();
+
+        let exhausted_requests = IntCounterVec::new(
+            Opts::new(
+                format!("{}_router_retries_exhausted_total", prefix),
+                "Total number of requests which failed after all their retries",
+            ),
+            &["router"],
+        )
+        .unwrap();
+
+        RetryMetrics {
+            retries,
+            retried_requests,
+            failed_over_requests,
+            exhausted_requests,
+        }
+    }
+
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.retries.clone()))?;
+        registry.register(Box::new(self.retried_requests.clone()))?;
+        registry.register(Box::new(self.failed_over_requests.clone()))?;
+        registry.register(Box::new(self.exhausted_requests.clone()))?;
+        Ok(())
+    }
+
+    /// Get the number of attempts retried by the given router for the given reason
+    /// (see [`RETRY_CONNECT`], [`RETRY_STREAM`] and [`RETRY_NO_INSTANCE`])
+    pub fn get_retries(&self, router: &str, reason: &str) -> u64 {
+        self.retries.with_label_values(&[router, reason]).get()
+    }
+
+    /// Get the number of requests the given router retried at least once
+    pub fn get_retried_requests(&self, router: &str) -> u64 {
+        self.retried_requests.with_label_values(&[router]).get()
+    }
+
+    /// Get the number of requests the given router failed over to another instance
+    pub fn get_failed_over_requests(&self, router: &str) -> u64 {
+        self.failed_over_requests.with_label_values(&[router]).get()
+    }
+
+    /// Get the number of requests of the given router which failed after all their retries
+    pub fn get_exhausted_requests(&self, router: &str) -> u64 {
+        self.exhausted_requests.with_label_values(&[router]).get()
+    }
+}
+
+/// How a router retries the requests which failed before their first response
+pub struct RetryPolicy {
+    name: String,
+    config: RetryConfig,
+    metrics: Arc<RetryMetrics>,
+}
+
+impl RetryPolicy {
+    /// Create a policy; `name` is the value of the `router` label of its metrics
+    pub fn new(name: impl Into<String>, config: RetryConfig, metrics: Arc<RetryMetrics>) -> Self {
+        Self {
+            name: name.into(),
+            config,
+            metrics,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn config(&self) -> &RetryConfig {
+        &self.config
+    }
+
+    /// The wait before the given retry, counting from 1
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.config
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.config.max_backoff)
+    }
+
+    /// Count a retry of a request; `first` is true for its first retry
+    pub(crate) fn record_retry(&self, reason: &str, first: bool) {
+        self.metrics
+            .retries
+            .with_label_values(&[&self.name, reason])
+            .inc();
+        if first {
+            self.metrics
+                .retried_requests
+                .with_label_values(&[&self.name])
+                .inc();
+        }
+    }
+
+    /// Count a retried request served by another instance than the one first chosen
+    pub(crate) fn record_failover(&self) {
+        self.metrics
+            .failed_over_requests
+            .with_label_values(&[&self.name])
+            .inc();
+    }
+
+    /// Count a request which failed after all its retries
+    pub(crate) fn record_exhausted(&self) {
+        self.metrics
+            .exhausted_requests
+            .with_label_values(&[&self.name])
+            .inc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new(
+            "test",
+            RetryConfig {
+                max_retries: 5,
+                initial_backoff: Duration::from_millis(100),
+                max_backoff: Duration::from_millis(500),
+            },
+            Arc::new(RetryMetrics::default()),
+        );
+        let backoffs: Vec<u128> = (1..=5).map(|i| policy.backoff(i).as_millis()).collect();
+        assert_eq!(backoffs, vec![100, 200, 400, 500, 500]);
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Arc::new(RetryMetrics::default());
+        let policy = RetryPolicy::new("test", RetryConfig::default(), metrics.clone());
+        policy.record_retry(RETRY_CONNECT, true);
+        policy.record_retry(RETRY_STREAM, false);
+        policy.record_failover();
+        assert_eq!(metrics.get_retries("test", RETRY_CONNECT), 1);
+        assert_eq!(metrics.get_retries("test", RETRY_STREAM), 1);
+        assert_eq!(metrics.get_retried_requests("test"), 1);
+        assert_eq!(metrics.get_failed_over_requests("test"), 1);
+        assert_eq!(metrics.get_exhausted_requests("test"), 0);
+    }
+}
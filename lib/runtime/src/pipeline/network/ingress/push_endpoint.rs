@@ -16,8 +16,10 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use super::*;
+use crate::transports::local::LocalEndpoint;
 use anyhow::Result;
 use async_nats::service::endpoint::Endpoint;
+use bytes::Bytes;
 use derive_builder::Builder;
 use tokio::sync::Notify;
 use tokio_util::sync::CancellationToken;
@@ -31,13 +33,60 @@ pub struct PushEndpoint {
 /// version of crate
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Where a [PushEndpoint] receives its requests: a NATS service endpoint, or an endpoint of the
+/// bus of a local runtime
+pub enum EndpointSource {
+    Nats(Endpoint),
+    Local(LocalEndpoint),
+}
+
+impl From<Endpoint> for EndpointSource {
+    fn from(endpoint: Endpoint) -> Self {
+        EndpointSource::Nats(endpoint)
+    }
+}
+
+impl From<LocalEndpoint> for EndpointSource {
+    fn from(endpoint: LocalEndpoint) -> Self {
+        EndpointSource::Local(endpoint)
+    }
+}
+
+impl EndpointSource {
+    /// The payload of the next request, after acknowledging it to the sender
+    async fn next(&mut self) -> Option<Bytes> {
+        match self {
+            EndpointSource::Nats(endpoint) => {
+                let req = endpoint.next().await?;
+                let response = "".to_string();
+                if let Err(e) = req.respond(Ok(response.into())).await {
+                    tracing::warn!("Failed to respond to request; this may indicate the request has shutdown: {:?}", e);
+                }
+                Some(req.message.payload)
+            }
+            EndpointSource::Local(endpoint) => endpoint.next().await,
+        }
+    }
+
+    async fn stop(self) {
+        match self {
+            EndpointSource::Nats(endpoint) => {
+                if let Err(e) = endpoint.stop().await {
+                    tracing::warn!("Failed to stop NATS service: {:?}", e);
+                }
+            }
+            EndpointSource::Local(mut endpoint) => endpoint.stop(),
+        }
+    }
+}
+
 impl PushEndpoint {
     pub fn builder() -> PushEndpointBuilder {
         PushEndpointBuilder::default()
     }
 
-    pub async fn start(self, endpoint: Endpoint) -> Result<()> {
-        let mut endpoint = endpoint;
+    pub async fn start(self, endpoint: impl Into<EndpointSource>) -> Result<()> {
+        let mut endpoint = endpoint.into();
 
         let inflight = Arc::new(AtomicU64::new(0));
         let notify = Arc::new(Notify::new());
@@ -54,19 +103,12 @@ impl PushEndpoint {
                 // process shutdown
                 _ = self.cancellation_token.cancelled() => {
                     tracing::info!("Shutting down service");
-                    if let Err(e) = endpoint.stop().await {
-                        tracing::warn!("Failed to stop NATS service: {:?}", e);
-                    }
+                    endpoint.stop().await;
                     break;
                 }
             };
 
-            if let Some(req) = req {
-                let response = "".to_string();
-                if let Err(e) = req.respond(Ok(response.into())).await {
-                    tracing::warn!("Failed to respond to request; this may indicate the request has shutdown: {:?}", e);
-                }
-
+            if let Some(payload) = req {
                 let ingress = self.service_handler.clone();
                 let worker_id = "".to_string();
 
@@ -77,7 +119,7 @@ impl PushEndpoint {
 
                 tokio::spawn(async move {
                     tracing::trace!(worker_id, "handling new request");
-                    let result = ingress.handle_payload(req.message.payload).await;
+                    let result = ingress.handle_payload(payload).await;
                     match result {
                         Ok(_) => {
                             tracing::trace!(worker_id, "request handled successfully");
@@ -15,6 +15,7 @@
 
 use super::*;
 use serde::{Deserialize, Serialize};
+use tracing::Instrument;
 
 #[async_trait]
 impl<T: Data, U: Data> PushWorkHandler for Ingress<SingleIn<T>, ManyOut<U>>
@@ -54,10 +55,37 @@ where
             }
         };
 
+        // handle the request in a span, continuing the trace of the caller
+        let traceparent = control_msg
+            .traceparent
+            .map(|traceparent| traceparent.to_string())
+            .unwrap_or_default();
+        let span = tracing::info_span!(
+            "handle_request",
+            traceparent = traceparent.as_str(),
+            request_id = control_msg.id.as_str(),
+        );
+        self.handle_request(control_msg, request)
+            .instrument(span)
+            .await
+    }
+}
+
+impl<T: Data, U: Data> Ingress<SingleIn<T>, ManyOut<U>>
+where
+    T: Data + for<'de> Deserialize<'de> + std::fmt::Debug,
+    U: Data + Serialize + std::fmt::Debug,
+{
+    async fn handle_request(
+        &self,
+        control_msg: RequestControlMessage,
+        request: T,
+    ) -> Result<(), PipelineError> {
         // extend request with context
         tracing::trace!("received control message: {:?}", control_msg);
         tracing::trace!("received request: {:?}", request);
-        let request: context::Context<T> = Context::with_id(request, control_msg.id);
+        let mut request: context::Context<T> = Context::with_id(request, control_msg.id);
+        request.set_traceparent(crate::trace::current().or(control_msg.traceparent));
 
         // todo - eventually have a handler class which will returned an abstracted object, but for now,
         // we only support tcp here, so we can just unwrap the connection info
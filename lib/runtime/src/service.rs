@@ -15,7 +15,12 @@
 
 // Refactored service module to track live vs ready states and associate cancellation tokens
 
-use crate::{error, transports::nats, utils::stream, Result};
+use crate::{
+    error,
+    transports::{local::LocalBus, nats},
+    utils::stream,
+    Result,
+};
 use async_nats::Message;
 use async_stream::try_stream;
 use bytes::Bytes;
@@ -70,12 +75,30 @@ impl ServiceComponent {
 }
 
 pub struct ServiceClient {
-    nats_client: nats::Client,
+    nats_client: Option<nats::Client>,
+    local_bus: Option<LocalBus>,
 }
 
 impl ServiceClient {
     pub fn new(nats_client: nats::Client) -> Self {
-        ServiceClient { nats_client }
+        ServiceClient {
+            nats_client: Some(nats_client),
+            local_bus: None,
+        }
+    }
+
+    /// A client of the services of a local runtime
+    pub fn new_local(local_bus: LocalBus) -> Self {
+        ServiceClient {
+            nats_client: None,
+            local_bus: Some(local_bus),
+        }
+    }
+
+    fn nats_client(&self) -> Result<&nats::Client> {
+        self.nats_client
+            .as_ref()
+            .ok_or_else(|| error!("NATS is not available in a local runtime"))
     }
 
     pub async fn unary(
@@ -84,7 +107,7 @@ impl ServiceClient {
         payload: impl Into<Bytes>,
     ) -> Result<Message> {
         let response = self
-            .nats_client
+            .nats_client()?
             .client()
             .request(subject.into(), payload.into())
             .await?;
@@ -96,7 +119,15 @@ impl ServiceClient {
         service_name: &str,
         timeout: Duration,
     ) -> Result<ServiceSet> {
-        let sub = self.nats_client.scrape_service(service_name).await?;
+        if let Some(bus) = &self.local_bus {
+            // The local services all answer, the timeout only bounds the wait
+            let services = tokio::time::timeout(timeout, bus.service_infos(service_name))
+                .await
+                .map_err(|_| error!("timed out collecting the stats of {service_name}"))??;
+            return Ok(ServiceSet { services });
+        }
+
+        let sub = self.nats_client()?.scrape_service(service_name).await?;
         if timeout.is_zero() {
             tracing::warn!("collect_services: timeout is zero");
         }
@@ -131,6 +162,14 @@ pub struct ServiceSet {
     services: Vec<ServiceInfo>,
 }
 
+impl ServiceSet {
+    pub fn into_endpoints(self) -> impl Iterator<Item = EndpointInfo> {
+        self.services
+            .into_iter()
+            .flat_map(|service| service.endpoints.into_iter())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceInfo {
     pub name: String,
@@ -17,10 +17,12 @@ use std::collections::HashMap;
 use std::pin::Pin;
 use std::time::Duration;
 
-use crate::{slug::Slug, transports::etcd::Client};
+use crate::{
+    slug::Slug,
+    transports::etcd::{Client, WatchEvent},
+};
 use async_stream::stream;
 use async_trait::async_trait;
-use etcd_client::{EventType, PutOptions, WatchOptions};
 
 use super::{KeyValueBucket, KeyValueStore, StorageError, StorageOutcome};
 
@@ -112,20 +114,19 @@ impl KeyValueBucket for EtcdBucket {
     {
         let k = make_key(&self.bucket_name, "");
         tracing::trace!("etcd watch: {k}");
-        let (_watcher, mut watch_stream) = self
+        let (_prefix, watcher, mut events) = self
             .client
-            .etcd_client()
-            .clone()
-            .watch(k.as_bytes(), Some(WatchOptions::new().with_prefix()))
+            .kv_watch_prefix(k)
             .await
-            .map_err(|e| StorageError::EtcdError(e.to_string()))?;
+            .map_err(|e| StorageError::EtcdError(e.to_string()))?
+            .dissolve();
         let output = stream! {
-            while let Ok(Some(resp)) = watch_stream.message().await {
-                for e in resp.events() {
-                    if matches!(e.event_type(), EventType::Put) && e.kv().is_some() {
-                        let b: bytes::Bytes = e.kv().unwrap().value().to_vec().into();
-                        yield b;
-                    }
+            // Keep the watch open as long as the stream
+            let _watcher = watcher;
+            while let Some(event) = events.recv().await {
+                if let WatchEvent::Put(kv) = event {
+                    let b: bytes::Bytes = kv.value().to_vec().into();
+                    yield b;
                 }
             }
         };
@@ -170,13 +171,13 @@ impl EtcdBucket {
         }
 
         // Write it
-        let mut put_resp = self
+        let prev_kv = self
             .client
-            .kv_put_with_options(k, value, Some(PutOptions::new().with_prev_key()))
+            .kv_put_with_prev_key(k, value)
             .await
             .map_err(|e| StorageError::EtcdError(e.to_string()))?;
         // Check if we overwrite something
-        if put_resp.take_prev_key().is_some() {
+        if prev_kv.is_some() {
             // Key created between our get and put
             return Err(StorageError::Retry);
         }
@@ -215,12 +216,12 @@ impl EtcdBucket {
             // So we do too in etcd.
         }
 
-        let mut put_resp = self
+        let prev_kv = self
             .client
-            .kv_put_with_options(k, value, Some(PutOptions::new().with_prev_key()))
+            .kv_put_with_prev_key(k, value)
             .await
             .map_err(|e| StorageError::EtcdError(e.to_string()))?;
-        Ok(match put_resp.take_prev_key() {
+        Ok(match prev_kv {
             // Should this be an error?
             // The key was deleted between our get and put. We re-created it.
             // Version of new key is always 1.
@@ -0,0 +1,709 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Distributed tracing across the request plane.
+//!
+//! Requests carry a W3C [`TraceParent`]. The HTTP service takes it from the `traceparent` header
+//! of the client or starts a new trace, the [`crate::pipeline::Context`] carries it through the
+//! pipeline, and the request plane sends it to the worker in the control header of the request.
+//! The worker handles the request in a span whose parent is the span of the caller.
+//!
+//! Spans are linked by [`TraceLayer`]: a span with a `traceparent` field is the entry of a trace
+//! in this process, as the child of the remote span the field names, or as the root of a new
+//! trace when the field is empty or invalid. The spans within it are its descendants. Spans
+//! outside of any trace are ignored.
+//!
+//! Tracing is enabled by the environment, the finished spans are exported in the OTLP JSON format:
+//! - `DYN_TRACE_OTLP_ENDPOINT`: base URL of an OpenTelemetry collector, e.g.
+//!   `http://localhost:4318`. The spans are posted to its `/v1/traces` endpoint.
+//! - `DYN_TRACE_FILE`: path of a local file, which gets one export request per line.
+//! - `DYN_TRACE_SERVICE_NAME`: the `service.name` of the spans, `dynamo` by default.
+//!
+//! The spans are batched on an exporter thread. [`shutdown`] exports the spans still queued and
+//! joins the thread; the [`crate::Worker`] calls it when the application exits.
+
+use std::{
+    fmt,
+    io::Write,
+    path::PathBuf,
+    str::FromStr,
+    sync::{mpsc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+use tracing::{field::Field, span, Metadata, Subscriber};
+use tracing_subscriber::{
+    filter::filter_fn, layer::Context as LayerContext, registry::LookupSpan, Layer,
+};
+
+/// HTTP header carrying the trace context
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Span field naming the remote parent of the span
+const TRACEPARENT_FIELD: &str = "traceparent";
+
+const OTLP_ENDPOINT_ENV: &str = "DYN_TRACE_OTLP_ENDPOINT";
+const FILE_ENV: &str = "DYN_TRACE_FILE";
+const SERVICE_NAME_ENV: &str = "DYN_TRACE_SERVICE_NAME";
+const DEFAULT_SERVICE_NAME: &str = "dynamo";
+
+/// Finished spans waiting for export, further spans are dropped
+const EXPORT_QUEUE_SIZE: usize = 8192;
+
+/// Spans are exported in batches of at most this many...
+const EXPORT_BATCH_SIZE: usize = 512;
+
+/// ... at least this often
+const EXPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long an export to the collector may take, so that [`shutdown`] does not hang on it
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The exporter threads started by [`TraceLayer::new`], joined by [`shutdown`]
+static EXPORTERS: Mutex<Vec<(mpsc::SyncSender<Message>, JoinHandle<()>)>> = Mutex::new(Vec::new());
+
+// OTLP span kinds
+const SPAN_KIND_INTERNAL: u8 = 1;
+const SPAN_KIND_SERVER: u8 = 2;
+
+/// A W3C trace context: <https://www.w3.org/TR/trace-context/#traceparent-header>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceParent {
+    pub trace_id: u128,
+    /// The span of the caller
+    pub parent_id: u64,
+    pub flags: u8,
+}
+
+impl TraceParent {
+    const FLAG_SAMPLED: u8 = 0x01;
+
+    /// The root of a new sampled trace
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: random_trace_id(),
+            parent_id: random_span_id(),
+            flags: Self::FLAG_SAMPLED,
+        }
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.flags & Self::FLAG_SAMPLED != 0
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+impl FromStr for TraceParent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let is_hex = |field: &str, len: usize| {
+            field.len() == len
+                && field
+                    .bytes()
+                    .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+
+        let mut fields = s.trim().split('-');
+        let (Some(version), Some(trace_id), Some(parent_id), Some(flags)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            anyhow::bail!("expected version-trace_id-parent_id-flags");
+        };
+        if !is_hex(version, 2) || version == "ff" {
+            anyhow::bail!("invalid version {version:?}");
+        }
+        // later versions may append fields
+        if version == "00" && fields.next().is_some() {
+            anyhow::bail!("unexpected fields after the flags");
+        }
+        if !is_hex(trace_id, 32) || !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+            anyhow::bail!("invalid trace_id, parent_id or flags");
+        }
+
+        let traceparent = TraceParent {
+            trace_id: u128::from_str_radix(trace_id, 16)?,
+            parent_id: u64::from_str_radix(parent_id, 16)?,
+            flags: u8::from_str_radix(flags, 16)?,
+        };
+        if traceparent.trace_id == 0 || traceparent.parent_id == 0 {
+            anyhow::bail!("all zero trace_id or parent_id");
+        }
+        Ok(traceparent)
+    }
+}
+
+impl Serialize for TraceParent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TraceParent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+fn random_trace_id() -> u128 {
+    let mut rng = rand::rng();
+    loop {
+        let id = rng.random::<u128>();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+fn random_span_id() -> u64 {
+    let mut rng = rand::rng();
+    loop {
+        let id = rng.random::<u64>();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// The trace context of the current span, to pass to the callees. `None` if tracing is disabled
+/// or the span is not part of a trace.
+pub fn current() -> Option<TraceParent> {
+    of_span(&tracing::Span::current())
+}
+
+/// The trace context of `span`, see [`current`]
+pub fn of_span(span: &tracing::Span) -> Option<TraceParent> {
+    span.with_subscriber(|(id, dispatch)| {
+        let registry = dispatch.downcast_ref::<tracing_subscriber::Registry>()?;
+        let span = registry.span(id)?;
+        let extensions = span.extensions();
+        extensions.get::<SpanData>().map(SpanData::traceparent)
+    })
+    .flatten()
+}
+
+/// Where the spans are exported
+#[derive(Debug, Clone, Default)]
+pub struct TraceConfig {
+    /// Base URL of an OpenTelemetry collector taking OTLP over HTTP
+    pub otlp_endpoint: Option<String>,
+
+    /// Local file receiving one OTLP JSON export request per line
+    pub file: Option<PathBuf>,
+
+    pub service_name: String,
+}
+
+impl TraceConfig {
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        Self {
+            otlp_endpoint: var(OTLP_ENDPOINT_ENV),
+            file: var(FILE_ENV).map(PathBuf::from),
+            service_name: var(SERVICE_NAME_ENV).unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.otlp_endpoint.is_some() || self.file.is_some()
+    }
+}
+
+/// The tracing layer exporting the spans as configured by the environment, if any. Only the spans
+/// at `INFO` level or above are traced.
+pub fn layer_from_env<S>() -> Option<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let config = TraceConfig::from_env();
+    if !config.is_enabled() {
+        return None;
+    }
+    let spans = filter_fn(|metadata: &Metadata<'_>| {
+        metadata.is_span() && *metadata.level() <= tracing::Level::INFO
+    });
+    match TraceLayer::new(&config) {
+        Ok(layer) => Some(layer.with_filter(spans)),
+        Err(err) => {
+            // logging is not set up yet
+            eprintln!("Failed to set up trace export: {err:#}");
+            None
+        }
+    }
+}
+
+/// Trace context and attributes of a span, kept in its extensions
+struct SpanData {
+    trace_id: u128,
+    span_id: u64,
+    parent_id: Option<u64>,
+    sampled: bool,
+    /// The entry of the trace in this process
+    entry: bool,
+    start: SystemTime,
+    attributes: Vec<(&'static str, Value)>,
+}
+
+impl SpanData {
+    fn new(trace_id: u128, parent_id: Option<u64>, sampled: bool, entry: bool) -> Self {
+        Self {
+            trace_id,
+            span_id: random_span_id(),
+            parent_id,
+            sampled,
+            entry,
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+        }
+    }
+
+    fn traceparent(&self) -> TraceParent {
+        TraceParent {
+            trace_id: self.trace_id,
+            parent_id: self.span_id,
+            flags: if self.sampled {
+                TraceParent::FLAG_SAMPLED
+            } else {
+                0
+            },
+        }
+    }
+
+    fn record(&mut self, attributes: Vec<(&'static str, Value)>) {
+        for (key, value) in attributes {
+            self.attributes.retain(|(k, _)| *k != key);
+            self.attributes.push((key, value));
+        }
+    }
+
+    /// The span in the OTLP JSON format
+    fn to_otlp(&self, name: &str, end: SystemTime) -> Value {
+        let attributes: Vec<Value> = self
+            .attributes
+            .iter()
+            .map(|(key, value)| json!({ "key": key, "value": value }))
+            .collect();
+        let mut span = json!({
+            "traceId": format!("{:032x}", self.trace_id),
+            "spanId": format!("{:016x}", self.span_id),
+            "name": name,
+            "kind": if self.entry { SPAN_KIND_SERVER } else { SPAN_KIND_INTERNAL },
+            "startTimeUnixNano": unix_nanos(self.start).to_string(),
+            "endTimeUnixNano": unix_nanos(end).to_string(),
+            "attributes": attributes,
+        });
+        if let Some(parent_id) = self.parent_id {
+            span["parentSpanId"] = json!(format!("{parent_id:016x}"));
+        }
+        span
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0)
+}
+
+/// Collects the fields of a span as OTLP attributes, and its `traceparent` field
+#[derive(Default)]
+struct FieldVisitor {
+    traceparent: Option<String>,
+    attributes: Vec<(&'static str, Value)>,
+}
+
+impl tracing::field::Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == TRACEPARENT_FIELD {
+            self.traceparent = Some(value.to_string());
+        } else {
+            self.attributes
+                .push((field.name(), json!({ "stringValue": value })));
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.attributes
+            .push((field.name(), json!({ "boolValue": value })));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        // 64 bit integers are strings in OTLP JSON
+        self.attributes
+            .push((field.name(), json!({ "intValue": value.to_string() })));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.attributes
+            .push((field.name(), json!({ "intValue": value.to_string() })));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.attributes
+            .push((field.name(), json!({ "doubleValue": value })));
+    }
+}
+
+/// What the layer sends to the exporter thread
+enum Message {
+    /// A finished span, in the OTLP JSON format
+    Span(Value),
+    /// Export the spans received so far and stop
+    Shutdown,
+}
+
+/// Links the spans of the traces and sends them to the exporter once they are closed
+pub struct TraceLayer {
+    sender: mpsc::SyncSender<Message>,
+}
+
+impl TraceLayer {
+    /// Start the exporter thread of `config`
+    pub fn new(config: &TraceConfig) -> anyhow::Result<Self> {
+        let mut exporters = Vec::new();
+        if let Some(endpoint) = &config.otlp_endpoint {
+            let endpoint = endpoint.trim_end_matches('/');
+            let url = match endpoint.ends_with("/v1/traces") {
+                true => endpoint.to_string(),
+                false => format!("{endpoint}/v1/traces"),
+            };
+            exporters.push(Exporter::Otlp { url, client: None });
+        }
+        if let Some(path) = &config.file {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|err| anyhow::anyhow!("opening {}: {err}", path.display()))?;
+            exporters.push(Exporter::File(std::io::BufWriter::new(file)));
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(EXPORT_QUEUE_SIZE);
+        let service_name = config.service_name.clone();
+        let thread = std::thread::Builder::new()
+            .name("trace-exporter".to_string())
+            .spawn(move || export_loop(receiver, exporters, service_name))?;
+        EXPORTERS.lock().unwrap().push((sender.clone(), thread));
+        Ok(Self { sender })
+    }
+}
+
+impl<S> Layer<S> for TraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+
+        let mut data = match visitor.traceparent.as_deref() {
+            Some(traceparent) => match traceparent.parse::<TraceParent>() {
+                Ok(parent) => SpanData::new(
+                    parent.trace_id,
+                    Some(parent.parent_id),
+                    parent.is_sampled(),
+                    true,
+                ),
+                // no valid trace context from the caller, start a new trace
+                Err(_) => SpanData::new(random_trace_id(), None, true, true),
+            },
+            None => {
+                let Some(parent) = span.parent() else {
+                    return;
+                };
+                let extensions = parent.extensions();
+                let Some(parent) = extensions.get::<SpanData>() else {
+                    return;
+                };
+                SpanData::new(parent.trace_id, Some(parent.span_id), parent.sampled, false)
+            }
+        };
+        data.record(visitor.attributes);
+        span.extensions_mut().insert(data);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<SpanData>() else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        values.record(&mut visitor);
+        data.record(visitor.attributes);
+    }
+
+    fn on_close(&self, id: span::Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(data) = extensions.get::<SpanData>() else {
+            return;
+        };
+        if !data.sampled {
+            return;
+        }
+        // drop the span rather than block the traced code when the export falls behind
+        let _ = self
+            .sender
+            .try_send(Message::Span(data.to_otlp(span.name(), SystemTime::now())));
+    }
+}
+
+enum Exporter {
+    /// The blocking client is created on the exporter thread, it may not be created within an
+    /// async runtime
+    Otlp {
+        url: String,
+        client: Option<reqwest::blocking::Client>,
+    },
+    File(std::io::BufWriter<std::fs::File>),
+}
+
+impl Exporter {
+    fn export(&mut self, request: &Value) -> anyhow::Result<()> {
+        match self {
+            Exporter::Otlp { url, client } => {
+                let client = match client {
+                    Some(client) => client,
+                    None => client.insert(
+                        reqwest::blocking::Client::builder()
+                            .timeout(EXPORT_TIMEOUT)
+                            .build()?,
+                    ),
+                };
+                client
+                    .post(url.as_str())
+                    .json(request)
+                    .send()?
+                    .error_for_status()?;
+            }
+            Exporter::File(file) => {
+                serde_json::to_writer(&mut *file, request)?;
+                file.write_all(b"\n")?;
+                file.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An OTLP JSON export request of `spans`
+fn export_request(service_name: &str, spans: Vec<Value>) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": service_name } }]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "dynamo" },
+                "spans": spans,
+            }]
+        }]
+    })
+}
+
+/// Export the spans still queued and stop the exporter threads. Spans finished afterwards are
+/// dropped.
+pub fn shutdown() {
+    let exporters = std::mem::take(&mut *EXPORTERS.lock().unwrap());
+    for (sender, thread) in exporters {
+        // the spans queued before the message are exported first
+        if sender.send(Message::Shutdown).is_ok() && thread.join().is_err() {
+            eprintln!("Trace exporter thread panicked");
+        }
+    }
+}
+
+fn export_loop(
+    receiver: mpsc::Receiver<Message>,
+    mut exporters: Vec<Exporter>,
+    service_name: String,
+) {
+    let mut batch = Vec::new();
+    let mut deadline = Instant::now() + EXPORT_INTERVAL;
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let stop = match receiver.recv_timeout(timeout) {
+            Ok(Message::Span(span)) => {
+                batch.push(span);
+                false
+            }
+            Ok(Message::Shutdown) => true,
+            Err(mpsc::RecvTimeoutError::Timeout) => false,
+            Err(mpsc::RecvTimeoutError::Disconnected) => true,
+        };
+
+        if batch.len() >= EXPORT_BATCH_SIZE || Instant::now() >= deadline || stop {
+            if !batch.is_empty() {
+                let request = export_request(&service_name, std::mem::take(&mut batch));
+                for exporter in exporters.iter_mut() {
+                    if let Err(err) = exporter.export(&request) {
+                        tracing::warn!(error = format!("{err:#}"), "failed to export spans");
+                    }
+                }
+            }
+            deadline = Instant::now() + EXPORT_INTERVAL;
+        }
+        if stop {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_traceparent() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let traceparent: TraceParent = header.parse().unwrap();
+        assert_eq!(traceparent.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+        assert_eq!(traceparent.parent_id, 0x00f067aa0ba902b7);
+        assert!(traceparent.is_sampled());
+        assert_eq!(traceparent.to_string(), header);
+
+        let json = serde_json::to_string(&traceparent).unwrap();
+        assert_eq!(json, format!("\"{header}\""));
+        assert_eq!(
+            serde_json::from_str::<TraceParent>(&json).unwrap(),
+            traceparent
+        );
+
+        // later versions may add fields
+        assert!(
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra"
+                .parse::<TraceParent>()
+                .is_ok()
+        );
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+        ] {
+            assert!(invalid.parse::<TraceParent>().is_err(), "{invalid}");
+        }
+
+        let root = TraceParent::new_root();
+        assert!(root.is_sampled());
+        assert_eq!(root.to_string().parse::<TraceParent>().unwrap(), root);
+    }
+
+    #[test]
+    fn test_span_links() {
+        let (sender, receiver) = mpsc::sync_channel(16);
+        let subscriber = tracing_subscriber::registry().with(TraceLayer { sender });
+
+        let remote = TraceParent::new_root();
+        let (entry, child) = tracing::subscriber::with_default(subscriber, || {
+            // spans outside of a trace are ignored
+            let outside = tracing::info_span!("outside");
+            assert_eq!(of_span(&outside), None);
+
+            let span = tracing::info_span!("entry", traceparent = %remote, instance_id = 7);
+            let _guard = span.enter();
+            let entry = current().unwrap();
+            let child = tracing::info_span!("child").in_scope(|| current().unwrap());
+
+            // an empty traceparent starts a new trace
+            let root = tracing::info_span!("root", traceparent = "");
+            assert_ne!(of_span(&root).unwrap().trace_id, remote.trace_id);
+            (entry, child)
+        });
+        assert_eq!(entry.trace_id, remote.trace_id);
+        assert_eq!(child.trace_id, remote.trace_id);
+        assert_ne!(entry.parent_id, child.parent_id);
+
+        let spans: Vec<Value> = receiver
+            .try_iter()
+            .filter_map(|message| match message {
+                Message::Span(span) => Some(span),
+                Message::Shutdown => None,
+            })
+            .collect();
+        let span = |name: &str| spans.iter().find(|span| span["name"] == name).unwrap();
+        assert_eq!(spans.len(), 3);
+        assert_eq!(
+            span("entry")["parentSpanId"],
+            format!("{:016x}", remote.parent_id)
+        );
+        assert_eq!(span("entry")["kind"], SPAN_KIND_SERVER);
+        assert_eq!(
+            span("entry")["attributes"],
+            json!([{ "key": "instance_id", "value": { "intValue": "7" } }])
+        );
+        assert_eq!(
+            span("child")["parentSpanId"],
+            format!("{:016x}", entry.parent_id)
+        );
+        assert_eq!(span("child")["kind"], SPAN_KIND_INTERNAL);
+        assert!(span("root").get("parentSpanId").is_none());
+    }
+
+    #[test]
+    fn test_shutdown_exports_queued_spans() {
+        let path = std::env::temp_dir().join(format!("dynamo-trace-{}.jsonl", random_span_id()));
+        let layer = TraceLayer::new(&TraceConfig {
+            file: Some(path.clone()),
+            service_name: "test".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("entry", traceparent = "").in_scope(|| {});
+        });
+
+        // the span is still batched until the exporter shuts down
+        shutdown();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let request: Value = serde_json::from_str(contents.trim()).unwrap();
+        let spans = &request["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["name"], "entry");
+    }
+}
@@ -16,6 +16,7 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::pin::Pin;
 
 use crate::Result;
 
@@ -57,6 +58,9 @@ pub trait EventPublisher {
     // fn publisher_bytes(&self, event_name: impl AsRef<str>) -> &PublisherBytes;
 }
 
+/// The events received by an [EventSubscriber], from NATS or from the bus of a local runtime.
+pub type EventStream = Pin<Box<dyn futures::Stream<Item = async_nats::Message> + Send>>;
+
 /// An [EventSubscriber] is an object that can subscribe to events.
 ///
 /// This trait provides methods to subscribe to events published on specific subjects.
@@ -64,11 +68,8 @@ pub trait EventPublisher {
 pub trait EventSubscriber {
     /// Subscribe to events with the given event name.
     /// The `event_name` will be `.` concatenated with the base subject provided by the implementation.
-    /// Returns a subscriber that can be used to receive events.
-    async fn subscribe(
-        &self,
-        event_name: impl AsRef<str> + Send + Sync,
-    ) -> Result<async_nats::Subscriber>;
+    /// Returns a stream of the received events.
+    async fn subscribe(&self, event_name: impl AsRef<str> + Send + Sync) -> Result<EventStream>;
 
     /// Subscribe to events with the given event name and deserialize them to the specified type.
     /// This is a convenience method that combines subscribe and deserialization.
@@ -19,6 +19,7 @@
 //! These are the low-level building blocks for the distributed system.
 
 pub mod etcd;
+pub mod local;
 pub mod nats;
 pub mod tcp;
 pub mod zmq;
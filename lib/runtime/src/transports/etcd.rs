@@ -19,22 +19,26 @@ use async_nats::jetstream::kv;
 use derive_builder::Builder;
 use derive_getters::Dissolve;
 use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
 use validator::Validate;
 
 use etcd_client::{
-    Certificate, Compare, CompareOp, DeleteOptions, GetOptions, Identity, PutOptions, PutResponse,
-    TlsOptions, Txn, TxnOp, TxnOpResponse, WatchOptions, Watcher,
+    Certificate, Compare, CompareOp, DeleteOptions, GetOptions, Identity, PutOptions, TlsOptions,
+    Txn, TxnOp, TxnOpResponse, WatchOptions, WatchStream, Watcher,
 };
-pub use etcd_client::{ConnectOptions, KeyValue, LeaseClient};
+pub use etcd_client::{ConnectOptions, LeaseClient};
 use tokio::time::{interval, Duration};
 
 mod lease;
+mod memory;
 mod path;
 
+use super::local::LocalKv;
 use lease::*;
+pub use memory::MemoryKv;
 pub use path::*;
 
 //pub use etcd::ConnectOptions as EtcdConnectOptions;
@@ -42,11 +46,18 @@ pub use path::*;
 /// ETCD Client
 #[derive(Clone)]
 pub struct Client {
-    client: etcd_client::Client,
+    backend: Backend,
     primary_lease: i64,
     runtime: Runtime,
 }
 
+/// Where the keys live: an etcd cluster, or the [`LocalKv`] of a local runtime
+#[derive(Clone)]
+enum Backend {
+    Etcd(etcd_client::Client),
+    Local(LocalKv),
+}
+
 #[derive(Debug, Clone)]
 pub struct Lease {
     /// ETCD lease ID
@@ -87,6 +98,79 @@ impl Lease {
     }
 }
 
+/// A key-value pair as stored in etcd
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyValue {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    create_revision: i64,
+    mod_revision: i64,
+    version: i64,
+    lease: i64,
+}
+
+impl KeyValue {
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn key_str(&self) -> std::result::Result<&str, std::str::Utf8Error> {
+        std::str::from_utf8(&self.key)
+    }
+
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    pub fn value_str(&self) -> std::result::Result<&str, std::str::Utf8Error> {
+        std::str::from_utf8(&self.value)
+    }
+
+    /// Revision of the last creation of the key
+    pub fn create_revision(&self) -> i64 {
+        self.create_revision
+    }
+
+    /// Revision of the last modification of the key
+    pub fn mod_revision(&self) -> i64 {
+        self.mod_revision
+    }
+
+    /// Number of modifications since the key was created, 1 for a new key
+    pub fn version(&self) -> i64 {
+        self.version
+    }
+
+    /// The lease attached to the key, 0 if none
+    pub fn lease(&self) -> i64 {
+        self.lease
+    }
+
+    pub fn into_key_value(self) -> (Vec<u8>, Vec<u8>) {
+        (self.key, self.value)
+    }
+}
+
+impl From<etcd_client::KeyValue> for KeyValue {
+    fn from(kv: etcd_client::KeyValue) -> Self {
+        let (create_revision, mod_revision, version, lease) = (
+            kv.create_revision(),
+            kv.mod_revision(),
+            kv.version(),
+            kv.lease(),
+        );
+        let (key, value) = kv.into_key_value();
+        KeyValue {
+            key,
+            value,
+            create_revision,
+            mod_revision,
+            version,
+            lease,
+        }
+    }
+}
+
 impl Client {
     pub fn builder() -> ClientOptionsBuilder {
         ClientOptionsBuilder::default()
@@ -107,6 +191,26 @@ impl Client {
             .await?
     }
 
+    /// Create a discovery client over a [`LocalKv`] instead of an etcd server. The keys are
+    /// only visible to the clients sharing the [`LocalKv`].
+    ///
+    /// As with etcd, the primary lease is revoked when the [`Runtime`] is shutdown.
+    pub async fn local(kv: LocalKv, attach_lease: bool, runtime: Runtime) -> Result<Self> {
+        let primary_lease = if attach_lease {
+            local_lease(&kv, runtime.primary_token(), &runtime)
+                .await
+                .context("creating primary lease")?
+                .id
+        } else {
+            0
+        };
+        Ok(Client {
+            backend: Backend::Local(kv),
+            primary_lease,
+            runtime,
+        })
+    }
+
     /// Create a new etcd client and tie the primary [`CancellationToken`] to the primary etcd lease.
     async fn create(config: ClientOptions, runtime: Runtime) -> Result<Self> {
         let token = runtime.primary_token();
@@ -126,17 +230,12 @@ impl Client {
         };
 
         Ok(Client {
-            client,
+            backend: Backend::Etcd(client),
             primary_lease: lease_id,
             runtime,
         })
     }
 
-    /// Get a reference to the underlying [`etcd_client::Client`] instance.
-    pub(crate) fn etcd_client(&self) -> &etcd_client::Client {
-        &self.client
-    }
-
     /// Get the primary lease ID.
     pub fn lease_id(&self) -> i64 {
         self.primary_lease
@@ -154,20 +253,30 @@ impl Client {
     /// This [`Lease`] will be tied to the [`Runtime`], specifically a child [`CancellationToken`].
     pub async fn create_lease(&self, ttl: i64) -> Result<Lease> {
         let token = self.runtime.child_token();
-        let lease_client = self.client.lease_client();
-        self.runtime
-            .secondary()
-            .spawn(create_lease(lease_client, ttl, token))
-            .await?
+        match &self.backend {
+            Backend::Etcd(client) => {
+                let lease_client = client.lease_client();
+                self.runtime
+                    .secondary()
+                    .spawn(create_lease(lease_client, ttl, token))
+                    .await?
+            }
+            Backend::Local(kv) => local_lease(kv, token, &self.runtime).await,
+        }
     }
 
     // Revoke an etcd lease given its lease id. A wrapper over etcd_client::LeaseClient::revoke
     pub async fn revoke_lease(&self, lease_id: i64) -> Result<()> {
-        let lease_client = self.client.lease_client();
-        self.runtime
-            .secondary()
-            .spawn(revoke_lease(lease_client, lease_id))
-            .await?
+        match &self.backend {
+            Backend::Etcd(client) => {
+                let lease_client = client.lease_client();
+                self.runtime
+                    .secondary()
+                    .spawn(revoke_lease(lease_client, lease_id))
+                    .await?
+            }
+            Backend::Local(kv) => kv.revoke_lease(lease_id).await,
+        }
     }
 
     pub async fn kv_create(
@@ -177,6 +286,17 @@ impl Client {
         lease_id: Option<i64>,
     ) -> Result<()> {
         let id = lease_id.unwrap_or(self.lease_id());
+
+        let client = match &self.backend {
+            Backend::Etcd(client) => client,
+            Backend::Local(kv) => {
+                return match kv.create(key, value, id).await? {
+                    true => Ok(()),
+                    false => Err(error!("failed to create key")),
+                };
+            }
+        };
+
         let put_options = PutOptions::new().with_lease(id);
 
         // Build the transaction
@@ -187,7 +307,7 @@ impl Client {
             ]);
 
         // Execute the transaction
-        let result = self.client.kv_client().txn(txn).await?;
+        let result = client.kv_client().txn(txn).await?;
 
         if result.succeeded() {
             Ok(())
@@ -207,6 +327,17 @@ impl Client {
         lease_id: Option<i64>,
     ) -> Result<()> {
         let id = lease_id.unwrap_or(self.lease_id());
+
+        let client = match &self.backend {
+            Backend::Etcd(client) => client,
+            Backend::Local(kv) => {
+                return match kv.create_or_validate(key, value, id).await? {
+                    true => Ok(()),
+                    false => Err(error!("failed to create or validate key")),
+                };
+            }
+        };
+
         let put_options = PutOptions::new().with_lease(id);
 
         // Build the transaction that either creates the key if it doesn't exist,
@@ -226,7 +357,7 @@ impl Client {
             ]);
 
         // Execute the transaction
-        let result = self.client.kv_client().txn(txn).await?;
+        let result = client.kv_client().txn(txn).await?;
 
         // We have to enumerate the response paths to determine if the transaction succeeded
         if result.succeeded() {
@@ -252,70 +383,112 @@ impl Client {
         lease_id: Option<i64>,
     ) -> Result<()> {
         let id = lease_id.unwrap_or(self.lease_id());
-        let put_options = PutOptions::new().with_lease(id);
-        let _ = self
-            .client
-            .kv_client()
-            .put(key.as_ref(), value.as_ref(), Some(put_options))
-            .await?;
+        match &self.backend {
+            Backend::Etcd(client) => {
+                let put_options = PutOptions::new().with_lease(id);
+                let _ = client
+                    .kv_client()
+                    .put(key.as_ref(), value.as_ref(), Some(put_options))
+                    .await?;
+            }
+            Backend::Local(kv) => {
+                kv.put(key.as_ref(), value.as_ref(), id).await?;
+            }
+        }
         Ok(())
     }
 
-    pub async fn kv_put_with_options(
+    /// Put a key attached to the primary lease, returning the key-value pair it replaced
+    pub async fn kv_put_with_prev_key(
         &self,
         key: impl AsRef<str>,
         value: impl AsRef<[u8]>,
-        options: Option<PutOptions>,
-    ) -> Result<PutResponse> {
-        let options = options
-            .unwrap_or_default()
-            .with_lease(self.primary_lease().id());
-        self.client
-            .kv_client()
-            .put(key.as_ref(), value.as_ref(), Some(options))
-            .await
-            .map_err(|err| err.into())
+    ) -> Result<Option<KeyValue>> {
+        let id = self.primary_lease().id();
+        match &self.backend {
+            Backend::Etcd(client) => {
+                let options = PutOptions::new().with_lease(id).with_prev_key();
+                let mut response = client
+                    .kv_client()
+                    .put(key.as_ref(), value.as_ref(), Some(options))
+                    .await?;
+                Ok(response.take_prev_key().map(KeyValue::from))
+            }
+            Backend::Local(kv) => kv.put(key.as_ref(), value.as_ref(), id).await,
+        }
     }
 
+    /// The local backend does not support `options`
     pub async fn kv_get(
         &self,
         key: impl Into<Vec<u8>>,
         options: Option<GetOptions>,
     ) -> Result<Vec<KeyValue>> {
-        let mut get_response = self.client.kv_client().get(key, options).await?;
-        Ok(get_response.take_kvs())
+        match &self.backend {
+            Backend::Etcd(client) => {
+                let mut get_response = client.kv_client().get(key, options).await?;
+                Ok(into_key_values(get_response.take_kvs()))
+            }
+            Backend::Local(kv) => {
+                if options.is_some() {
+                    return Err(error!("get options are not supported by the local backend"));
+                }
+                Ok(kv.get(key).await?.into_iter().collect())
+            }
+        }
     }
 
+    /// The local backend does not support `options`
     pub async fn kv_delete(
         &self,
         key: impl Into<Vec<u8>>,
         options: Option<DeleteOptions>,
     ) -> Result<i64> {
-        self.client
-            .kv_client()
-            .delete(key, options)
-            .await
-            .map(|del_response| del_response.deleted())
-            .map_err(|err| err.into())
+        match &self.backend {
+            Backend::Etcd(client) => client
+                .kv_client()
+                .delete(key, options)
+                .await
+                .map(|del_response| del_response.deleted())
+                .map_err(|err| err.into()),
+            Backend::Local(kv) => {
+                if options.is_some() {
+                    return Err(error!(
+                        "delete options are not supported by the local backend"
+                    ));
+                }
+                kv.delete(key).await
+            }
+        }
     }
 
     pub async fn kv_get_prefix(&self, prefix: impl AsRef<str>) -> Result<Vec<KeyValue>> {
-        let mut get_response = self
-            .client
-            .kv_client()
-            .get(prefix.as_ref(), Some(GetOptions::new().with_prefix()))
-            .await?;
-
-        Ok(get_response.take_kvs())
+        match &self.backend {
+            Backend::Etcd(client) => {
+                let mut get_response = client
+                    .kv_client()
+                    .get(prefix.as_ref(), Some(GetOptions::new().with_prefix()))
+                    .await?;
+
+                Ok(into_key_values(get_response.take_kvs()))
+            }
+            Backend::Local(kv) => kv.get_prefix(prefix.as_ref()).await,
+        }
     }
 
     pub async fn kv_get_and_watch_prefix(
         &self,
         prefix: impl AsRef<str> + std::fmt::Display,
     ) -> Result<PrefixWatcher> {
-        let mut kv_client = self.client.kv_client();
-        let mut watch_client = self.client.watch_client();
+        let client = match &self.backend {
+            Backend::Etcd(client) => client,
+            Backend::Local(kv) => {
+                let (kvs, events) = kv.get_and_watch_prefix(prefix.as_ref()).await?;
+                return Ok(self.forward_local_watch(prefix.as_ref(), kvs, events));
+            }
+        };
 
+        let mut kv_client = client.kv_client();
         let mut get_response = kv_client
             .get(prefix.as_ref(), Some(GetOptions::new().with_prefix()))
             .await?;
@@ -328,21 +501,64 @@ impl Client {
         tracing::trace!("{prefix}: start_revision: {start_revision}");
         let start_revision = start_revision + 1;
 
-        let (watcher, mut watch_stream) = watch_client
-            .watch(
-                prefix.as_ref(),
-                Some(
-                    WatchOptions::new()
-                        .with_prefix()
-                        .with_start_revision(start_revision)
-                        .with_prev_key(),
-                ),
-            )
-            .await?;
-
-        let kvs = get_response.take_kvs();
+        let kvs = into_key_values(get_response.take_kvs());
         tracing::trace!("initial kv count: {:?}", kvs.len());
 
+        self.forward_etcd_watch(client, prefix.as_ref(), kvs, Some(start_revision))
+            .await
+    }
+
+    /// Watch the changes under `prefix`, without the keys it already holds
+    pub async fn kv_watch_prefix(&self, prefix: impl AsRef<str>) -> Result<PrefixWatcher> {
+        match &self.backend {
+            Backend::Etcd(client) => {
+                self.forward_etcd_watch(client, prefix.as_ref(), vec![], None)
+                    .await
+            }
+            Backend::Local(kv) => {
+                let events = kv.watch_prefix(prefix.as_ref()).await?;
+                Ok(self.forward_local_watch(prefix.as_ref(), vec![], events))
+            }
+        }
+    }
+
+    /// Send `kvs` as [`WatchEvent::Put`], then the events of an etcd watch of `prefix` from
+    /// `start_revision`
+    async fn forward_etcd_watch(
+        &self,
+        client: &etcd_client::Client,
+        prefix: &str,
+        kvs: Vec<KeyValue>,
+        start_revision: Option<i64>,
+    ) -> Result<PrefixWatcher> {
+        let mut watch_client = client.watch_client();
+
+        let mut options = WatchOptions::new().with_prefix().with_prev_key();
+        if let Some(start_revision) = start_revision {
+            options = options.with_start_revision(start_revision);
+        }
+        let (watcher, watch_stream) = watch_client.watch(prefix, Some(options)).await?;
+
+        let (tx, rx) = mpsc::channel(32);
+
+        self.runtime
+            .secondary()
+            .spawn(forward_etcd_events(kvs, watch_stream, tx));
+
+        Ok(PrefixWatcher {
+            prefix: prefix.to_string(),
+            watcher: Some(watcher),
+            rx,
+        })
+    }
+
+    /// Send `kvs` as [`WatchEvent::Put`], then the `events` of a [`LocalKv`] watch
+    fn forward_local_watch(
+        &self,
+        prefix: &str,
+        kvs: Vec<KeyValue>,
+        mut events: mpsc::UnboundedReceiver<WatchEvent>,
+    ) -> PrefixWatcher {
         let (tx, rx) = mpsc::channel(32);
 
         self.runtime.secondary().spawn(async move {
@@ -355,34 +571,12 @@ impl Client {
 
             loop {
                 tokio::select! {
-                    maybe_resp = watch_stream.next() => {
-                        // Early return for None or Err cases
-                        let Some(Ok(response)) = maybe_resp else {
-                            tracing::info!("kv watch stream closed");
+                    event = events.recv() => {
+                        let Some(event) = event else {
                             return;
                         };
-
-                        // Process events
-                        for event in response.events() {
-                            // Extract the KeyValue if it exists
-                            let Some(kv) = event.kv() else {
-                                continue; // Skip events with no KV
-                            };
-
-                            // Handle based on event type
-                            match event.event_type() {
-                                etcd_client::EventType::Put => {
-                                    if let Err(err) = tx.send(WatchEvent::Put(kv.clone())).await {
-                                        tracing::error!("kv watcher error forwarding WatchEvent::Put: {err}");
-                                        return;
-                                    }
-                                }
-                                etcd_client::EventType::Delete => {
-                                    if tx.send(WatchEvent::Delete(kv.clone())).await.is_err() {
-                                        return;
-                                    }
-                                }
-                            }
+                        if tx.send(event).await.is_err() {
+                            return;
                         }
                     }
                     _ = tx.closed() => {
@@ -392,22 +586,99 @@ impl Client {
                 }
             }
         });
-        Ok(PrefixWatcher {
-            prefix: prefix.as_ref().to_string(),
-            watcher,
+
+        PrefixWatcher {
+            prefix: prefix.to_string(),
+            watcher: None,
             rx,
-        })
+        }
+    }
+}
+
+/// A lease of a [`LocalKv`], revoked when `token` is cancelled
+async fn local_lease(kv: &LocalKv, token: CancellationToken, runtime: &Runtime) -> Result<Lease> {
+    let id = kv.grant_lease().await?;
+    let kv = kv.clone();
+    let revoke = token.clone();
+    runtime.secondary().spawn(async move {
+        revoke.cancelled().await;
+        tracing::trace!(
+            lease_id = id,
+            "cancellation token triggered; revoking lease"
+        );
+        let _ = kv.revoke_lease(id).await;
+    });
+    Ok(Lease {
+        id,
+        cancel_token: token,
+    })
+}
+
+fn into_key_values(kvs: Vec<etcd_client::KeyValue>) -> Vec<KeyValue> {
+    kvs.into_iter().map(KeyValue::from).collect()
+}
+
+async fn forward_etcd_events(
+    kvs: Vec<KeyValue>,
+    mut watch_stream: WatchStream,
+    tx: mpsc::Sender<WatchEvent>,
+) {
+    for kv in kvs {
+        if tx.send(WatchEvent::Put(kv)).await.is_err() {
+            // receiver is already closed
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            maybe_resp = watch_stream.next() => {
+                // Early return for None or Err cases
+                let Some(Ok(response)) = maybe_resp else {
+                    tracing::info!("kv watch stream closed");
+                    return;
+                };
+
+                // Process events
+                for event in response.events() {
+                    // Extract the KeyValue if it exists
+                    let Some(kv) = event.kv() else {
+                        continue; // Skip events with no KV
+                    };
+
+                    // Handle based on event type
+                    match event.event_type() {
+                        etcd_client::EventType::Put => {
+                            if let Err(err) = tx.send(WatchEvent::Put(kv.clone().into())).await {
+                                tracing::error!("kv watcher error forwarding WatchEvent::Put: {err}");
+                                return;
+                            }
+                        }
+                        etcd_client::EventType::Delete => {
+                            if tx.send(WatchEvent::Delete(kv.clone().into())).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+            _ = tx.closed() => {
+                tracing::debug!("no more receivers, stopping watcher");
+                return;
+            }
+        }
     }
 }
 
 #[derive(Dissolve)]
 pub struct PrefixWatcher {
     prefix: String,
-    watcher: Watcher,
+    /// The etcd watcher, `None` for a local backend
+    watcher: Option<Watcher>,
     rx: mpsc::Receiver<WatchEvent>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WatchEvent {
     Put(KeyValue),
     Delete(KeyValue),
@@ -745,13 +1016,8 @@ mod tests {
         assert_eq!(external_update, Some(b"external_update".to_vec()));
 
         // Clean up - delete the test keys
-        let etcd_client = client.etcd_client();
-        let _ = etcd_client
-            .kv_client()
-            .delete(
-                prefix,
-                Some(etcd_client::DeleteOptions::new().with_prefix()),
-            )
+        let _ = client
+            .kv_delete(prefix, Some(DeleteOptions::new().with_prefix()))
             .await?;
 
         Ok(())
@@ -0,0 +1,282 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An etcd stand-in kept in memory, the discovery backend of a local
+//! [`DistributedRuntime`](crate::DistributedRuntime).
+//!
+//! It keeps the parts of etcd the runtime relies on: a revision bumped by every change, per key
+//! versions, leases whose revocation deletes their keys, and prefix watches which see every
+//! change made after the read they start with. Leases do not expire, they live until revoked,
+//! which [`Client`](super::Client) does when the token of the lease is cancelled.
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc;
+
+use super::{KeyValue, WatchEvent};
+use crate::{error, Result};
+
+/// The key-value store shared by the [`Client`](super::Client)s of the local runtimes
+#[derive(Clone, Default)]
+pub struct MemoryKv {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    revision: i64,
+    last_lease_id: i64,
+    leases: HashSet<i64>,
+    kvs: BTreeMap<Vec<u8>, KeyValue>,
+    watchers: Vec<(Vec<u8>, mpsc::UnboundedSender<WatchEvent>)>,
+}
+
+impl State {
+    fn check_lease(&self, lease: i64) -> Result<()> {
+        if lease != 0 && !self.leases.contains(&lease) {
+            return Err(error!("lease {lease:x} not found"));
+        }
+        Ok(())
+    }
+
+    fn put(&mut self, key: Vec<u8>, value: Vec<u8>, lease: i64) -> Result<Option<KeyValue>> {
+        self.check_lease(lease)?;
+        self.revision += 1;
+        let prev = self.kvs.get(&key).cloned();
+        let kv = KeyValue {
+            key: key.clone(),
+            value,
+            create_revision: prev
+                .as_ref()
+                .map_or(self.revision, |prev| prev.create_revision),
+            mod_revision: self.revision,
+            version: prev.as_ref().map_or(1, |prev| prev.version + 1),
+            lease,
+        };
+        self.kvs.insert(key, kv.clone());
+        self.notify(WatchEvent::Put(kv));
+        Ok(prev)
+    }
+
+    /// Delete the keys matching `predicate` in a single revision
+    fn delete_where(&mut self, predicate: impl Fn(&KeyValue) -> bool) -> i64 {
+        let keys: Vec<Vec<u8>> = self
+            .kvs
+            .values()
+            .filter(|kv| predicate(kv))
+            .map(|kv| kv.key.clone())
+            .collect();
+        if keys.is_empty() {
+            return 0;
+        }
+        self.revision += 1;
+        for key in &keys {
+            self.kvs.remove(key);
+            // Like etcd, a delete event carries the key without its value
+            self.notify(WatchEvent::Delete(KeyValue {
+                key: key.clone(),
+                mod_revision: self.revision,
+                ..Default::default()
+            }));
+        }
+        keys.len() as i64
+    }
+
+    fn prefix(&self, prefix: &[u8]) -> Vec<KeyValue> {
+        self.kvs
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(_, kv)| kv.clone())
+            .collect()
+    }
+
+    fn watch(&mut self, prefix: &[u8]) -> mpsc::UnboundedReceiver<WatchEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.watchers.push((prefix.to_vec(), tx));
+        rx
+    }
+
+    fn notify(&mut self, event: WatchEvent) {
+        let key = match &event {
+            WatchEvent::Put(kv) | WatchEvent::Delete(kv) => kv.key.clone(),
+        };
+        self.watchers.retain(|(prefix, tx)| {
+            !tx.is_closed() && (!key.starts_with(prefix) || tx.send(event.clone()).is_ok())
+        });
+    }
+}
+
+impl MemoryKv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        // The state stays consistent even if a holder panicked, every change is a single step
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Grant a new lease
+    pub fn grant_lease(&self) -> i64 {
+        let mut state = self.state();
+        state.last_lease_id += 1;
+        let id = state.last_lease_id;
+        state.leases.insert(id);
+        id
+    }
+
+    /// Revoke a lease, deleting all its keys
+    pub fn revoke_lease(&self, lease: i64) -> Result<()> {
+        let mut state = self.state();
+        if !state.leases.remove(&lease) {
+            return Err(error!("lease {lease:x} not found"));
+        }
+        state.delete_where(|kv| kv.lease == lease);
+        Ok(())
+    }
+
+    /// Put a key, returning the key-value pair it replaced
+    pub fn put(
+        &self,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+        lease: i64,
+    ) -> Result<Option<KeyValue>> {
+        self.state().put(key.into(), value.into(), lease)
+    }
+
+    /// Put a key if it does not exist yet. Returns false if it exists.
+    pub fn create(
+        &self,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+        lease: i64,
+    ) -> Result<bool> {
+        let key: Vec<u8> = key.into();
+        let mut state = self.state();
+        if state.kvs.contains_key(&key) {
+            return Ok(false);
+        }
+        state.put(key, value.into(), lease)?;
+        Ok(true)
+    }
+
+    /// Put a key if it does not exist yet. Returns false if it exists with a different value.
+    pub fn create_or_validate(
+        &self,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+        lease: i64,
+    ) -> Result<bool> {
+        let (key, value): (Vec<u8>, Vec<u8>) = (key.into(), value.into());
+        let mut state = self.state();
+        if let Some(kv) = state.kvs.get(&key) {
+            return Ok(kv.value == value);
+        }
+        state.put(key, value, lease)?;
+        Ok(true)
+    }
+
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<KeyValue> {
+        self.state().kvs.get(key.as_ref()).cloned()
+    }
+
+    pub fn get_prefix(&self, prefix: impl AsRef<[u8]>) -> Vec<KeyValue> {
+        self.state().prefix(prefix.as_ref())
+    }
+
+    /// Delete a key, returning the number of deleted keys
+    pub fn delete(&self, key: impl AsRef<[u8]>) -> i64 {
+        let key = key.as_ref();
+        self.state().delete_where(|kv| kv.key == key)
+    }
+
+    /// The changes under `prefix` from now on
+    pub fn watch_prefix(&self, prefix: impl AsRef<[u8]>) -> mpsc::UnboundedReceiver<WatchEvent> {
+        self.state().watch(prefix.as_ref())
+    }
+
+    /// The keys under `prefix`, and the changes made after reading them
+    pub fn get_and_watch_prefix(
+        &self,
+        prefix: impl AsRef<[u8]>,
+    ) -> (Vec<KeyValue>, mpsc::UnboundedReceiver<WatchEvent>) {
+        let mut state = self.state();
+        let kvs = state.prefix(prefix.as_ref());
+        (kvs, state.watch(prefix.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_and_revisions() {
+        let kv = MemoryKv::new();
+        assert!(kv.put("a", "1", 0).unwrap().is_none());
+        let prev = kv.put("a", "2", 0).unwrap().unwrap();
+        assert_eq!(prev.value_str().unwrap(), "1");
+
+        let current = kv.get("a").unwrap();
+        assert_eq!(current.version(), 2);
+        assert_eq!(current.create_revision(), 1);
+        assert_eq!(current.mod_revision(), 2);
+
+        assert!(!kv.create("a", "3", 0).unwrap());
+        assert!(kv.create_or_validate("a", "2", 0).unwrap());
+        assert!(!kv.create_or_validate("a", "3", 0).unwrap());
+        assert_eq!(kv.delete("a"), 1);
+        assert!(kv.get("a").is_none());
+    }
+
+    #[test]
+    fn test_revoking_a_lease_deletes_its_keys() {
+        let kv = MemoryKv::new();
+        assert!(kv.put("x", "1", 42).is_err(), "unknown lease");
+
+        let lease = kv.grant_lease();
+        kv.put("instances/a", "1", lease).unwrap();
+        kv.put("instances/b", "2", 0).unwrap();
+        let (kvs, mut events) = kv.get_and_watch_prefix("instances/");
+        assert_eq!(kvs.len(), 2);
+
+        kv.revoke_lease(lease).unwrap();
+        assert!(kv.revoke_lease(lease).is_err());
+        assert_eq!(kv.get_prefix("instances/").len(), 1);
+        match events.try_recv().unwrap() {
+            WatchEvent::Delete(deleted) => assert_eq!(deleted.key_str().unwrap(), "instances/a"),
+            event => panic!("unexpected event {event:?}"),
+        }
+    }
+
+    #[test]
+    fn test_watch_prefix() {
+        let kv = MemoryKv::new();
+        let mut events = kv.watch_prefix("models/");
+        kv.put("models/a", "1", 0).unwrap();
+        kv.put("other/a", "1", 0).unwrap();
+        kv.put("models/b", "2", 0).unwrap();
+
+        let mut keys = vec![];
+        while let Ok(WatchEvent::Put(put)) = events.try_recv() {
+            keys.push(put.key_str().unwrap().to_string());
+        }
+        assert_eq!(keys, vec!["models/a", "models/b"]);
+    }
+}
@@ -0,0 +1,737 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Host-local transport, standing in for etcd and NATS in a runtime created with
+//! [`DistributedRuntime::local`](crate::DistributedRuntime::local).
+//!
+//! A [`LocalBackend`] pairs the [`LocalKv`] used for discovery with a [`LocalBus`] carrying
+//! the event plane and the request plane. Runtimes sharing a [`LocalBackend`] see each other's
+//! instances, events and endpoints; the response streams still flow over TCP.
+//!
+//! A backend lives in the process which created it. That process can [`LocalBackend::serve`] it
+//! on a unix socket, to which the other processes of the host [`LocalBackend::connect`]: their
+//! runtimes then share the backend as if they ran in the serving process. When a connection
+//! closes, the keys leased by the connected process are deleted and its endpoints removed, like
+//! etcd and NATS do when a worker dies.
+//!
+//! The [`LocalBus`] keeps the NATS semantics the runtime relies on: events reach every
+//! subscription matching their subject, with the `*` and `>` wildcards; a request reaches the
+//! endpoint serving its subject, or fails right away if there is none; and services answer stats
+//! scrapes with the output of their stats handler.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+
+use bytes::Bytes;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use super::etcd::{KeyValue, MemoryKv, WatchEvent};
+use crate::component::service::{EndpointStats, StatsHandler};
+use crate::service::{EndpointInfo, Metrics, ServiceInfo};
+use crate::{error, Result};
+
+mod socket;
+
+pub use socket::LocalServer;
+use socket::{Connection, Op, Peer};
+
+/// The discovery store and the bus shared by local runtimes
+#[derive(Clone, Default)]
+pub struct LocalBackend {
+    kv: LocalKv,
+    bus: LocalBus,
+}
+
+impl LocalBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The backend shared by all the local runtimes of this process
+    pub fn global() -> Self {
+        static GLOBAL: OnceLock<LocalBackend> = OnceLock::new();
+        GLOBAL.get_or_init(LocalBackend::new).clone()
+    }
+
+    /// Serve this backend to the other processes of the host on the unix socket at `path`, until
+    /// the returned [`LocalServer`] is dropped. Only a backend created in this process can be
+    /// served.
+    pub fn serve(&self, path: impl AsRef<Path>) -> Result<LocalServer> {
+        match (&self.kv.inner, &self.bus.inner) {
+            (KvInner::Memory(kv), BusInner::Memory(bus)) => {
+                socket::serve(kv.clone(), bus.clone(), path.as_ref())
+            }
+            _ => Err(error!(
+                "a backend connected to another process cannot be served"
+            )),
+        }
+    }
+
+    /// The backend served by another process on the unix socket at `path`
+    pub async fn connect(path: impl AsRef<Path>) -> Result<Self> {
+        let connection = Connection::connect(path.as_ref()).await?;
+        Ok(Self {
+            kv: LocalKv {
+                inner: KvInner::Socket(connection.clone()),
+            },
+            bus: LocalBus {
+                inner: BusInner::Socket(connection),
+            },
+        })
+    }
+
+    pub fn kv(&self) -> &LocalKv {
+        &self.kv
+    }
+
+    pub fn bus(&self) -> &LocalBus {
+        &self.bus
+    }
+}
+
+/// The discovery store of a [`LocalBackend`]: a [`MemoryKv`], or the one of the process serving
+/// the backend
+#[derive(Clone)]
+pub struct LocalKv {
+    inner: KvInner,
+}
+
+#[derive(Clone)]
+enum KvInner {
+    Memory(MemoryKv),
+    Socket(Arc<Connection>),
+}
+
+impl Default for LocalKv {
+    fn default() -> Self {
+        MemoryKv::new().into()
+    }
+}
+
+impl From<MemoryKv> for LocalKv {
+    fn from(kv: MemoryKv) -> Self {
+        LocalKv {
+            inner: KvInner::Memory(kv),
+        }
+    }
+}
+
+impl LocalKv {
+    /// Grant a new lease
+    pub async fn grant_lease(&self) -> Result<i64> {
+        match &self.inner {
+            KvInner::Memory(kv) => Ok(kv.grant_lease()),
+            KvInner::Socket(connection) => connection.call(Op::GrantLease, Bytes::new()).await,
+        }
+    }
+
+    /// Revoke a lease, deleting all its keys
+    pub async fn revoke_lease(&self, lease: i64) -> Result<()> {
+        match &self.inner {
+            KvInner::Memory(kv) => kv.revoke_lease(lease),
+            KvInner::Socket(connection) => {
+                connection
+                    .call(Op::RevokeLease { lease }, Bytes::new())
+                    .await
+            }
+        }
+    }
+
+    /// Put a key, returning the key-value pair it replaced
+    pub async fn put(
+        &self,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+        lease: i64,
+    ) -> Result<Option<KeyValue>> {
+        let (key, value) = (key.into(), value.into());
+        match &self.inner {
+            KvInner::Memory(kv) => kv.put(key, value, lease),
+            KvInner::Socket(connection) => {
+                let op = Op::Put { key, value, lease };
+                connection.call(op, Bytes::new()).await
+            }
+        }
+    }
+
+    /// Put a key if it does not exist yet. Returns false if it exists.
+    pub async fn create(
+        &self,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+        lease: i64,
+    ) -> Result<bool> {
+        let (key, value) = (key.into(), value.into());
+        match &self.inner {
+            KvInner::Memory(kv) => kv.create(key, value, lease),
+            KvInner::Socket(connection) => {
+                let op = Op::Create { key, value, lease };
+                connection.call(op, Bytes::new()).await
+            }
+        }
+    }
+
+    /// Put a key if it does not exist yet. Returns false if it exists with a different value.
+    pub async fn create_or_validate(
+        &self,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+        lease: i64,
+    ) -> Result<bool> {
+        let (key, value) = (key.into(), value.into());
+        match &self.inner {
+            KvInner::Memory(kv) => kv.create_or_validate(key, value, lease),
+            KvInner::Socket(connection) => {
+                let op = Op::CreateOrValidate { key, value, lease };
+                connection.call(op, Bytes::new()).await
+            }
+        }
+    }
+
+    pub async fn get(&self, key: impl Into<Vec<u8>>) -> Result<Option<KeyValue>> {
+        let key = key.into();
+        match &self.inner {
+            KvInner::Memory(kv) => Ok(kv.get(key)),
+            KvInner::Socket(connection) => connection.call(Op::Get { key }, Bytes::new()).await,
+        }
+    }
+
+    pub async fn get_prefix(&self, prefix: impl Into<Vec<u8>>) -> Result<Vec<KeyValue>> {
+        let prefix = prefix.into();
+        match &self.inner {
+            KvInner::Memory(kv) => Ok(kv.get_prefix(prefix)),
+            KvInner::Socket(connection) => {
+                connection
+                    .call(Op::GetPrefix { prefix }, Bytes::new())
+                    .await
+            }
+        }
+    }
+
+    /// Delete a key, returning the number of deleted keys
+    pub async fn delete(&self, key: impl Into<Vec<u8>>) -> Result<i64> {
+        let key = key.into();
+        match &self.inner {
+            KvInner::Memory(kv) => Ok(kv.delete(key)),
+            KvInner::Socket(connection) => connection.call(Op::Delete { key }, Bytes::new()).await,
+        }
+    }
+
+    /// The changes under `prefix` from now on
+    pub async fn watch_prefix(
+        &self,
+        prefix: impl Into<Vec<u8>>,
+    ) -> Result<mpsc::UnboundedReceiver<WatchEvent>> {
+        let prefix = prefix.into();
+        match &self.inner {
+            KvInner::Memory(kv) => Ok(kv.watch_prefix(prefix)),
+            KvInner::Socket(connection) => {
+                let (_, events) = connection.watch(prefix, false).await?;
+                Ok(events)
+            }
+        }
+    }
+
+    /// The keys under `prefix`, and the changes made after reading them
+    pub async fn get_and_watch_prefix(
+        &self,
+        prefix: impl Into<Vec<u8>>,
+    ) -> Result<(Vec<KeyValue>, mpsc::UnboundedReceiver<WatchEvent>)> {
+        let prefix = prefix.into();
+        match &self.inner {
+            KvInner::Memory(kv) => Ok(kv.get_and_watch_prefix(prefix)),
+            KvInner::Socket(connection) => connection.watch(prefix, true).await,
+        }
+    }
+}
+
+/// Events, requests and service stats between the runtimes sharing a [`LocalBackend`]
+#[derive(Clone, Default)]
+pub struct LocalBus {
+    inner: BusInner,
+}
+
+#[derive(Clone)]
+enum BusInner {
+    Memory(MemoryBus),
+    Socket(Arc<Connection>),
+}
+
+impl Default for BusInner {
+    fn default() -> Self {
+        BusInner::Memory(MemoryBus::default())
+    }
+}
+
+impl LocalBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver an event to the subscriptions matching `subject`
+    pub fn publish(&self, subject: impl Into<String>, payload: Bytes) {
+        match &self.inner {
+            BusInner::Memory(bus) => bus.publish(subject.into(), payload),
+            BusInner::Socket(connection) => connection.publish(subject.into(), payload),
+        }
+    }
+
+    /// The events published on the subjects matching `subject`, which may contain wildcards
+    pub fn subscribe(
+        &self,
+        subject: impl Into<String>,
+    ) -> UnboundedReceiverStream<async_nats::Message> {
+        match &self.inner {
+            BusInner::Memory(bus) => bus.subscribe(subject.into()),
+            BusInner::Socket(connection) => connection.subscribe(subject.into()),
+        }
+    }
+
+    /// Send a request to the endpoint serving `subject`, returning once the endpoint received it
+    pub async fn request(&self, subject: &str, payload: Bytes) -> Result<()> {
+        match &self.inner {
+            BusInner::Memory(bus) => bus.request(subject, payload).await,
+            BusInner::Socket(connection) => {
+                let op = Op::Request {
+                    subject: subject.to_string(),
+                };
+                connection.call(op, payload).await
+            }
+        }
+    }
+
+    /// Start a service, whose endpoints report their stats through `stats_handler`
+    pub fn add_service(
+        &self,
+        name: impl Into<String>,
+        version: impl Into<String>,
+        stats_handler: StatsHandler,
+    ) -> LocalService {
+        let inner = Arc::new(ServiceInner {
+            name: name.into(),
+            id: nuid::next().to_string(),
+            version: version.into(),
+            started: chrono::Utc::now().to_rfc3339(),
+            stats_handler: Mutex::new(stats_handler),
+            endpoints: Mutex::new(Vec::new()),
+        });
+        match &self.inner {
+            BusInner::Memory(bus) => bus.add_service(&inner),
+            BusInner::Socket(connection) => connection.add_service(&inner),
+        }
+        LocalService {
+            bus: self.clone(),
+            inner,
+        }
+    }
+
+    /// The stats of the live services named `name`
+    pub async fn service_infos(&self, name: &str) -> Result<Vec<ServiceInfo>> {
+        match &self.inner {
+            BusInner::Memory(bus) => Ok(bus.service_infos(name).await),
+            BusInner::Socket(connection) => {
+                let op = Op::ServiceInfos {
+                    name: name.to_string(),
+                };
+                connection.call(op, Bytes::new()).await
+            }
+        }
+    }
+
+    fn add_endpoint(&self, subject: String) -> (u64, mpsc::UnboundedReceiver<LocalRequest>) {
+        match &self.inner {
+            BusInner::Memory(bus) => bus.add_endpoint(subject),
+            BusInner::Socket(connection) => connection.add_endpoint(subject),
+        }
+    }
+
+    fn remove_endpoint(&self, subject: &str, id: u64) {
+        match &self.inner {
+            BusInner::Memory(bus) => bus.remove_endpoint(subject, id),
+            BusInner::Socket(connection) => connection.remove_endpoint(id),
+        }
+    }
+}
+
+/// The bus of the process which created the backend
+#[derive(Clone, Default)]
+struct MemoryBus {
+    state: Arc<Mutex<BusState>>,
+}
+
+#[derive(Default)]
+struct BusState {
+    subscriptions: Vec<(String, mpsc::UnboundedSender<async_nats::Message>)>,
+    endpoints: HashMap<String, (u64, mpsc::UnboundedSender<LocalRequest>)>,
+    services: Vec<Weak<ServiceInner>>,
+    remote_services: Vec<RemoteService>,
+    last_endpoint_id: u64,
+}
+
+struct LocalRequest {
+    payload: Bytes,
+    ack: oneshot::Sender<()>,
+}
+
+/// A service of a process connected to the backend, which answers for its stats
+struct RemoteService {
+    name: String,
+    id: String,
+    peer: Weak<Peer>,
+}
+
+impl MemoryBus {
+    fn state(&self) -> MutexGuard<'_, BusState> {
+        // Every change of the state is a single step, it stays consistent if a holder panicked
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn publish(&self, subject: String, payload: Bytes) {
+        let message = message(subject, payload);
+        self.state().subscriptions.retain(|(pattern, tx)| {
+            !tx.is_closed()
+                && (!subject_matches(pattern, &message.subject) || tx.send(message.clone()).is_ok())
+        });
+    }
+
+    fn subscribe(&self, subject: String) -> UnboundedReceiverStream<async_nats::Message> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.state().subscriptions.push((subject, tx));
+        UnboundedReceiverStream::new(rx)
+    }
+
+    async fn request(&self, subject: &str, payload: Bytes) -> Result<()> {
+        let (ack, acked) = oneshot::channel();
+        let sent = self
+            .state()
+            .endpoints
+            .get(subject)
+            .is_some_and(|(_, tx)| tx.send(LocalRequest { payload, ack }).is_ok());
+        if !sent {
+            return Err(error!("no responders on {subject}"));
+        }
+        acked
+            .await
+            .map_err(|_| error!("{subject} stopped before receiving the request"))
+    }
+
+    fn add_service(&self, service: &Arc<ServiceInner>) {
+        let mut state = self.state();
+        state.services.retain(|service| service.strong_count() > 0);
+        state.services.push(Arc::downgrade(service));
+    }
+
+    fn add_remote_service(&self, service: RemoteService) {
+        let mut state = self.state();
+        state
+            .remote_services
+            .retain(|service| service.peer.strong_count() > 0);
+        state.remote_services.push(service);
+    }
+
+    async fn service_infos(&self, name: &str) -> Vec<ServiceInfo> {
+        let (services, remote_services): (Vec<Arc<ServiceInner>>, Vec<(String, Arc<Peer>)>) = {
+            let state = self.state();
+            let services = state
+                .services
+                .iter()
+                .filter_map(Weak::upgrade)
+                .filter(|service| service.name == name)
+                .collect();
+            let remote_services = state
+                .remote_services
+                .iter()
+                .filter(|service| service.name == name)
+                .filter_map(|service| Some((service.id.clone(), service.peer.upgrade()?)))
+                .collect();
+            (services, remote_services)
+        };
+        // The stats handlers run outside of the bus lock
+        let mut infos: Vec<ServiceInfo> = services.iter().map(|service| service.info()).collect();
+
+        // The connected processes run the stats handlers of their services
+        let answers = futures::future::join_all(remote_services.into_iter().map(
+            |(service, peer)| async move {
+                let op = Op::Stats {
+                    service: service.clone(),
+                };
+                (service, peer.call::<ServiceInfo>(op, Bytes::new()).await)
+            },
+        ))
+        .await;
+        let mut stopped = Vec::new();
+        for (id, info) in answers {
+            match info {
+                Ok(info) => infos.push(info),
+                Err(err) => {
+                    tracing::debug!(service = id, %err, "dropping a stopped remote service");
+                    stopped.push(id);
+                }
+            }
+        }
+        if !stopped.is_empty() {
+            self.state()
+                .remote_services
+                .retain(|service| !stopped.contains(&service.id));
+        }
+        infos
+    }
+
+    fn add_endpoint(&self, subject: String) -> (u64, mpsc::UnboundedReceiver<LocalRequest>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut state = self.state();
+        state.last_endpoint_id += 1;
+        let id = state.last_endpoint_id;
+        if state.endpoints.insert(subject.clone(), (id, tx)).is_some() {
+            tracing::warn!(subject, "replacing the endpoint serving the subject");
+        }
+        (id, rx)
+    }
+
+    fn remove_endpoint(&self, subject: &str, id: u64) {
+        let mut state = self.state();
+        if state
+            .endpoints
+            .get(subject)
+            .is_some_and(|(current, _)| *current == id)
+        {
+            state.endpoints.remove(subject);
+        }
+    }
+}
+
+/// An event as received from NATS
+fn message(subject: String, payload: Bytes) -> async_nats::Message {
+    async_nats::Message {
+        length: subject.len() + payload.len(),
+        subject: subject.into(),
+        reply: None,
+        payload,
+        headers: None,
+        status: None,
+        description: None,
+    }
+}
+
+/// A service started on a [`LocalBus`], see [`LocalBus::add_service`]
+#[derive(Clone)]
+pub struct LocalService {
+    bus: LocalBus,
+    inner: Arc<ServiceInner>,
+}
+
+struct ServiceInner {
+    name: String,
+    id: String,
+    version: String,
+    started: String,
+    stats_handler: Mutex<StatsHandler>,
+    endpoints: Mutex<Vec<Arc<EndpointState>>>,
+}
+
+struct EndpointState {
+    name: String,
+    subject: String,
+    requests: AtomicU64,
+}
+
+impl ServiceInner {
+    fn info(&self) -> ServiceInfo {
+        let endpoints: Vec<Arc<EndpointState>> = self.endpoints.lock().unwrap().clone();
+        let mut stats_handler = self.stats_handler.lock().unwrap();
+        let endpoints = endpoints
+            .iter()
+            .map(|endpoint| {
+                let requests = endpoint.requests.load(Ordering::Relaxed);
+                let mut stats = EndpointStats::default();
+                stats.name = endpoint.name.clone();
+                stats.subject = endpoint.subject.clone();
+                stats.requests = requests as usize;
+                let handler: &mut StatsHandler = &mut stats_handler;
+                let data = handler(endpoint.subject.clone(), stats);
+                EndpointInfo {
+                    name: endpoint.name.clone(),
+                    subject: endpoint.subject.clone(),
+                    data: Some(Metrics {
+                        average_processing_time: 0.0,
+                        last_error: String::new(),
+                        num_errors: 0,
+                        num_requests: requests,
+                        processing_time: 0,
+                        queue_group: String::new(),
+                        data,
+                    }),
+                }
+            })
+            .collect();
+        ServiceInfo {
+            name: self.name.clone(),
+            id: self.id.clone(),
+            version: self.version.clone(),
+            started: self.started.clone(),
+            endpoints,
+        }
+    }
+}
+
+impl LocalService {
+    /// Serve the requests sent to `subject`. A later endpoint on the same subject replaces
+    /// this one.
+    pub fn endpoint(&self, name: impl Into<String>, subject: impl Into<String>) -> LocalEndpoint {
+        let subject = subject.into();
+        let (id, rx) = self.bus.add_endpoint(subject.clone());
+        let endpoint = Arc::new(EndpointState {
+            name: name.into(),
+            subject: subject.clone(),
+            requests: AtomicU64::new(0),
+        });
+        self.inner.endpoints.lock().unwrap().push(endpoint.clone());
+        LocalEndpoint {
+            id,
+            service: self.clone(),
+            endpoint,
+            rx,
+        }
+    }
+}
+
+/// The requests sent to an endpoint of a [`LocalService`]
+pub struct LocalEndpoint {
+    id: u64,
+    service: LocalService,
+    endpoint: Arc<EndpointState>,
+    rx: mpsc::UnboundedReceiver<LocalRequest>,
+}
+
+impl LocalEndpoint {
+    /// The payload of the next request, acknowledged to its sender
+    pub async fn next(&mut self) -> Option<Bytes> {
+        let request = self.rx.recv().await?;
+        self.endpoint.requests.fetch_add(1, Ordering::Relaxed);
+        // The sender may have given up waiting, the request is served anyway like on NATS
+        let _ = request.ack.send(());
+        Some(request.payload)
+    }
+
+    /// Stop serving the subject and reporting stats
+    pub fn stop(&mut self) {
+        self.service
+            .bus
+            .remove_endpoint(&self.endpoint.subject, self.id);
+        self.service
+            .inner
+            .endpoints
+            .lock()
+            .unwrap()
+            .retain(|endpoint| !Arc::ptr_eq(endpoint, &self.endpoint));
+        self.rx.close();
+    }
+}
+
+impl Drop for LocalEndpoint {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// NATS subject matching: `*` matches a single token and `>` all the remaining ones
+fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut tokens = subject.split('.');
+    for expected in pattern.split('.') {
+        if expected == ">" {
+            return tokens.next().is_some();
+        }
+        match tokens.next() {
+            Some(token) if expected == "*" || expected == token => {}
+            _ => return false,
+        }
+    }
+    tokens.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[test]
+    fn test_subject_matches() {
+        assert!(subject_matches("a.b.c", "a.b.c"));
+        assert!(!subject_matches("a.b", "a.b.c"));
+        assert!(!subject_matches("a.b.c", "a.b"));
+        assert!(subject_matches("a.*.c", "a.b.c"));
+        assert!(!subject_matches("a.*", "a.b.c"));
+        assert!(subject_matches("a.>", "a.b.c"));
+        assert!(!subject_matches("a.>", "a"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_subscribe() {
+        let bus = LocalBus::new();
+        let mut events = bus.subscribe("ns.component.*.kv_events");
+        let mut unrelated = bus.subscribe("other");
+
+        bus.publish("ns.component.worker.kv_events", Bytes::from_static(b"1"));
+        bus.publish("ns.component.worker.load", Bytes::from_static(b"2"));
+
+        let message = events.next().await.unwrap();
+        assert_eq!(message.subject.as_str(), "ns.component.worker.kv_events");
+        assert_eq!(message.payload, Bytes::from_static(b"1"));
+
+        bus.publish("other", Bytes::from_static(b"3"));
+        assert_eq!(unrelated.next().await.unwrap().payload, "3");
+    }
+
+    #[tokio::test]
+    async fn test_request_and_stats() {
+        let bus = LocalBus::new();
+        assert!(bus.request("svc.generate-1", Bytes::new()).await.is_err());
+
+        let service = bus.add_service(
+            "svc",
+            "0.0.1",
+            Box::new(|subject: String, stats: EndpointStats| {
+                serde_json::json!({ "subject": subject, "requests": stats.requests })
+            }),
+        );
+        let mut endpoint = service.endpoint("generate-1", "svc.generate-1");
+
+        let (sent, received) = tokio::join!(
+            bus.request("svc.generate-1", Bytes::from_static(b"payload")),
+            endpoint.next()
+        );
+        sent.unwrap();
+        assert_eq!(received.unwrap(), "payload");
+
+        let infos = bus.service_infos("svc").await.unwrap();
+        assert_eq!(infos.len(), 1);
+        let endpoints: Vec<EndpointInfo> = infos[0].endpoints.clone();
+        assert_eq!(endpoints[0].id().unwrap(), 1);
+        let metrics = endpoints[0].data.clone().unwrap();
+        assert_eq!(metrics.num_requests, 1);
+        assert_eq!(metrics.data["requests"], 1);
+
+        endpoint.stop();
+        assert!(bus.request("svc.generate-1", Bytes::new()).await.is_err());
+        assert!(bus.service_infos("svc").await.unwrap()[0]
+            .endpoints
+            .is_empty());
+    }
+}
@@ -0,0 +1,723 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The unix socket sharing a [`LocalBackend`](super::LocalBackend) between the processes of a
+//! host.
+//!
+//! The frames are [`TwoPartMessage`]s: the header is a JSON [`Header`], the data carries the
+//! payload of the events and of the requests. Both sides make [`Header::Call`]s answered by a
+//! [`Header::Reply`] with the same id: the connected process calls the discovery store and the
+//! bus of the serving one, which calls back the endpoints and the services of the connected
+//! process. The [`Header::Notice`]s are not answered.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::{AbortHandle, JoinSet};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_util::codec::{FramedRead, FramedWrite};
+use tokio_util::sync::CancellationToken;
+
+use super::{message, LocalRequest, MemoryBus, RemoteService, ServiceInner};
+use crate::pipeline::error::TwoPartCodecError;
+use crate::pipeline::network::codec::{TwoPartCodec, TwoPartMessage};
+use crate::service::ServiceInfo;
+use crate::transports::etcd::{KeyValue, MemoryKv, WatchEvent};
+use crate::{error, ErrorContext, Result};
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Header {
+    /// An operation, answered by a [`Header::Reply`] with the same id
+    Call {
+        id: u64,
+        op: Op,
+    },
+    Reply {
+        id: u64,
+        result: CallResult,
+    },
+    Notice(Notice),
+}
+
+type CallResult = std::result::Result<serde_json::Value, String>;
+
+/// The operations of the connected process on the backend, then those of the serving process on
+/// the endpoints and services of the connected one
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) enum Op {
+    GrantLease,
+    RevokeLease {
+        lease: i64,
+    },
+    Put {
+        key: Vec<u8>,
+        value: Vec<u8>,
+        lease: i64,
+    },
+    Create {
+        key: Vec<u8>,
+        value: Vec<u8>,
+        lease: i64,
+    },
+    CreateOrValidate {
+        key: Vec<u8>,
+        value: Vec<u8>,
+        lease: i64,
+    },
+    Get {
+        key: Vec<u8>,
+    },
+    GetPrefix {
+        prefix: Vec<u8>,
+    },
+    Delete {
+        key: Vec<u8>,
+    },
+    /// Answered with the keys under `prefix` if `with_kvs`, the changes follow as
+    /// [`Notice::Watch`]
+    Watch {
+        id: u64,
+        prefix: Vec<u8>,
+        with_kvs: bool,
+    },
+    /// Answered once the endpoint serving `subject` received the request
+    Request {
+        subject: String,
+    },
+    ServiceInfos {
+        name: String,
+    },
+    EndpointRequest {
+        endpoint: u64,
+    },
+    Stats {
+        service: String,
+    },
+}
+
+/// The frames which are not answered, from the connected process then from the serving one
+#[derive(Debug, Serialize, Deserialize)]
+enum Notice {
+    Publish { subject: String },
+    Subscribe { id: u64, subject: String },
+    Unsubscribe { id: u64 },
+    Unwatch { id: u64 },
+    AddService { id: String, name: String },
+    AddEndpoint { id: u64, subject: String },
+    RemoveEndpoint { id: u64 },
+    Event { subscription: u64, subject: String },
+    Watch { id: u64, event: WatchEvent },
+}
+
+/// One side of a connection: the frames it sends, and its calls waiting for a reply
+pub(super) struct Peer {
+    frames: mpsc::UnboundedSender<TwoPartMessage>,
+    /// `None` once the connection closed
+    calls: Mutex<Option<HashMap<u64, oneshot::Sender<CallResult>>>>,
+    last_id: AtomicU64,
+}
+
+impl Peer {
+    /// Start writing the frames sent to `stream`, returning the frames received from it
+    fn start(stream: UnixStream) -> (Arc<Peer>, FramedRead<OwnedReadHalf, TwoPartCodec>) {
+        let (read_half, write_half) = stream.into_split();
+        let (frames, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut writer = FramedWrite::new(write_half, TwoPartCodec::default());
+            while let Some(frame) = rx.recv().await {
+                if let Err(err) = writer.send(frame).await {
+                    tracing::debug!(%err, "local backend connection closed");
+                    return;
+                }
+            }
+        });
+        let peer = Arc::new(Peer {
+            frames,
+            calls: Mutex::new(Some(HashMap::new())),
+            last_id: AtomicU64::new(0),
+        });
+        (peer, FramedRead::new(read_half, TwoPartCodec::default()))
+    }
+
+    fn next_id(&self) -> u64 {
+        self.last_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn send(&self, header: &Header, data: Bytes) {
+        let header = serde_json::to_vec(header).expect("frame headers are serializable");
+        // Once the connection closed, the frames are dropped and the calls fail
+        let _ = self.frames.send(TwoPartMessage::new(header.into(), data));
+    }
+
+    fn notify(&self, notice: Notice, data: Bytes) {
+        self.send(&Header::Notice(notice), data);
+    }
+
+    pub(super) async fn call<T: DeserializeOwned>(&self, op: Op, data: Bytes) -> Result<T> {
+        let id = self.next_id();
+        let (tx, rx) = oneshot::channel();
+        self.calls
+            .lock()
+            .unwrap()
+            .as_mut()
+            .ok_or_else(|| error!("the local backend connection is closed"))?
+            .insert(id, tx);
+        self.send(&Header::Call { id, op }, data);
+        let value = rx
+            .await
+            .map_err(|_| error!("the local backend connection closed"))?
+            .map_err(|err| error!(err))?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn reply<T: Serialize>(&self, id: u64, result: Result<T>) {
+        let result = result
+            .and_then(|value| Ok(serde_json::to_value(value)?))
+            .map_err(|err| err.to_string());
+        self.send(&Header::Reply { id, result }, Bytes::new());
+    }
+
+    fn resolve(&self, id: u64, result: CallResult) {
+        let call = self
+            .calls
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|calls| calls.remove(&id));
+        if let Some(call) = call {
+            let _ = call.send(result);
+        }
+    }
+
+    /// Fail the calls waiting for a reply, and the later ones
+    fn close(&self) {
+        self.calls.lock().unwrap().take();
+    }
+}
+
+fn decode(
+    frame: std::result::Result<TwoPartMessage, TwoPartCodecError>,
+) -> Result<(Header, Bytes)> {
+    let (header, data) = frame?.into_parts();
+    Ok((serde_json::from_slice(&header)?, data))
+}
+
+/// The connection of a process to the backend served by another one
+pub(super) struct Connection {
+    peer: Arc<Peer>,
+    subscriptions: Mutex<HashMap<u64, mpsc::UnboundedSender<async_nats::Message>>>,
+    watches: Mutex<HashMap<u64, mpsc::UnboundedSender<WatchEvent>>>,
+    endpoints: Mutex<HashMap<u64, mpsc::UnboundedSender<LocalRequest>>>,
+    services: Mutex<HashMap<String, Weak<ServiceInner>>>,
+}
+
+impl Connection {
+    pub(super) async fn connect(path: &Path) -> Result<Arc<Self>> {
+        let stream = UnixStream::connect(path)
+            .await
+            .with_context(|| format!("connecting to the local backend at {}", path.display()))?;
+        let (peer, mut frames) = Peer::start(stream);
+        let connection = Arc::new(Connection {
+            peer,
+            subscriptions: Mutex::new(HashMap::new()),
+            watches: Mutex::new(HashMap::new()),
+            endpoints: Mutex::new(HashMap::new()),
+            services: Mutex::new(HashMap::new()),
+        });
+
+        // The connection closes once its last user dropped it, or when the serving process stops
+        let weak = Arc::downgrade(&connection);
+        tokio::spawn(async move {
+            while let Some(frame) = frames.next().await {
+                let Some(connection) = weak.upgrade() else {
+                    return;
+                };
+                match decode(frame) {
+                    Ok((header, data)) => connection.receive(header, data),
+                    Err(err) => {
+                        tracing::warn!(%err, "invalid frame from the local backend");
+                        break;
+                    }
+                }
+            }
+            if let Some(connection) = weak.upgrade() {
+                connection.close();
+            }
+        });
+        Ok(connection)
+    }
+
+    pub(super) async fn call<T: DeserializeOwned>(&self, op: Op, data: Bytes) -> Result<T> {
+        self.peer.call(op, data).await
+    }
+
+    pub(super) fn publish(&self, subject: String, payload: Bytes) {
+        self.peer.notify(Notice::Publish { subject }, payload);
+    }
+
+    pub(super) fn subscribe(
+        &self,
+        subject: String,
+    ) -> UnboundedReceiverStream<async_nats::Message> {
+        let id = self.peer.next_id();
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscriptions.lock().unwrap().insert(id, tx);
+        self.peer
+            .notify(Notice::Subscribe { id, subject }, Bytes::new());
+        UnboundedReceiverStream::new(rx)
+    }
+
+    pub(super) async fn watch(
+        &self,
+        prefix: Vec<u8>,
+        with_kvs: bool,
+    ) -> Result<(Vec<KeyValue>, mpsc::UnboundedReceiver<WatchEvent>)> {
+        let id = self.peer.next_id();
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.watches.lock().unwrap().insert(id, tx);
+        let op = Op::Watch {
+            id,
+            prefix,
+            with_kvs,
+        };
+        match self.peer.call(op, Bytes::new()).await {
+            Ok(kvs) => Ok((kvs, rx)),
+            Err(err) => {
+                self.watches.lock().unwrap().remove(&id);
+                Err(err)
+            }
+        }
+    }
+
+    pub(super) fn add_service(&self, service: &Arc<ServiceInner>) {
+        self.services
+            .lock()
+            .unwrap()
+            .insert(service.id.clone(), Arc::downgrade(service));
+        let notice = Notice::AddService {
+            id: service.id.clone(),
+            name: service.name.clone(),
+        };
+        self.peer.notify(notice, Bytes::new());
+    }
+
+    pub(super) fn add_endpoint(
+        &self,
+        subject: String,
+    ) -> (u64, mpsc::UnboundedReceiver<LocalRequest>) {
+        let id = self.peer.next_id();
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.endpoints.lock().unwrap().insert(id, tx);
+        self.peer
+            .notify(Notice::AddEndpoint { id, subject }, Bytes::new());
+        (id, rx)
+    }
+
+    pub(super) fn remove_endpoint(&self, id: u64) {
+        if self.endpoints.lock().unwrap().remove(&id).is_some() {
+            self.peer
+                .notify(Notice::RemoveEndpoint { id }, Bytes::new());
+        }
+    }
+
+    fn receive(&self, header: Header, data: Bytes) {
+        match header {
+            Header::Reply { id, result } => self.peer.resolve(id, result),
+            Header::Call {
+                id,
+                op: Op::EndpointRequest { endpoint },
+            } => {
+                let (ack, acked) = oneshot::channel();
+                let request = LocalRequest { payload: data, ack };
+                let sent = self
+                    .endpoints
+                    .lock()
+                    .unwrap()
+                    .get(&endpoint)
+                    .is_some_and(|tx| tx.send(request).is_ok());
+                if !sent {
+                    self.peer
+                        .reply::<()>(id, Err(error!("endpoint {endpoint} stopped")));
+                    return;
+                }
+                let peer = self.peer.clone();
+                tokio::spawn(async move {
+                    let received = acked
+                        .await
+                        .map_err(|_| error!("the endpoint stopped before receiving the request"));
+                    peer.reply(id, received);
+                });
+            }
+            Header::Call {
+                id,
+                op: Op::Stats { service },
+            } => {
+                let inner = self
+                    .services
+                    .lock()
+                    .unwrap()
+                    .get(&service)
+                    .and_then(Weak::upgrade);
+                // The stats handlers run outside of the lock
+                let info = inner
+                    .map(|inner| inner.info())
+                    .ok_or_else(|| error!("service {service} stopped"));
+                self.peer.reply::<ServiceInfo>(id, info);
+            }
+            Header::Call { id, op } => {
+                self.peer
+                    .reply::<()>(id, Err(error!("unexpected call {op:?}")));
+            }
+            Header::Notice(Notice::Event {
+                subscription,
+                subject,
+            }) => {
+                let mut subscriptions = self.subscriptions.lock().unwrap();
+                let Some(tx) = subscriptions.get(&subscription) else {
+                    return;
+                };
+                if tx.send(message(subject, data)).is_err() {
+                    subscriptions.remove(&subscription);
+                    let notice = Notice::Unsubscribe { id: subscription };
+                    self.peer.notify(notice, Bytes::new());
+                }
+            }
+            Header::Notice(Notice::Watch { id, event }) => {
+                let mut watches = self.watches.lock().unwrap();
+                let Some(tx) = watches.get(&id) else {
+                    return;
+                };
+                if tx.send(event).is_err() {
+                    watches.remove(&id);
+                    self.peer.notify(Notice::Unwatch { id }, Bytes::new());
+                }
+            }
+            Header::Notice(notice) => {
+                tracing::warn!(?notice, "unexpected notice from the local backend");
+            }
+        }
+    }
+
+    /// The serving process is gone: the calls fail, and the subscriptions, watches and
+    /// endpoints end
+    fn close(&self) {
+        self.peer.close();
+        self.subscriptions.lock().unwrap().clear();
+        self.watches.lock().unwrap().clear();
+        self.endpoints.lock().unwrap().clear();
+    }
+}
+
+/// The unix socket on which a [`LocalBackend`](super::LocalBackend) is served, see
+/// [`LocalBackend::serve`](super::LocalBackend::serve). Dropping it closes the connections and
+/// removes the socket.
+pub struct LocalServer {
+    path: PathBuf,
+    cancel_token: CancellationToken,
+}
+
+impl LocalServer {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        self.cancel_token.cancel();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+pub(super) fn serve(kv: MemoryKv, bus: MemoryBus, path: &Path) -> Result<LocalServer> {
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("serving the local backend at {}", path.display()))?;
+    let cancel_token = CancellationToken::new();
+    let token = cancel_token.clone();
+    tokio::spawn(async move {
+        loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = token.cancelled() => return,
+            };
+            match accepted {
+                Ok((stream, _)) => {
+                    let (peer, frames) = Peer::start(stream);
+                    let session = Session::new(kv.clone(), bus.clone(), peer);
+                    tokio::spawn(session.run(frames, token.child_token()));
+                }
+                Err(err) => tracing::warn!(%err, "failed to accept a local backend connection"),
+            }
+        }
+    });
+    Ok(LocalServer {
+        path: path.to_path_buf(),
+        cancel_token,
+    })
+}
+
+/// A connected process, as seen by the serving one. Dropping the session removes the keys
+/// leased by the process, its endpoints and its subscriptions.
+struct Session {
+    kv: MemoryKv,
+    bus: MemoryBus,
+    peer: Arc<Peer>,
+    leases: HashSet<i64>,
+    /// The tasks forwarding the events of the subscriptions and of the watches, by id
+    forwarders: HashMap<u64, AbortHandle>,
+    /// The subject and the id on the bus of the endpoints, by their id in the connected process
+    endpoints: HashMap<u64, (String, u64)>,
+    tasks: JoinSet<()>,
+}
+
+impl Session {
+    fn new(kv: MemoryKv, bus: MemoryBus, peer: Arc<Peer>) -> Self {
+        Session {
+            kv,
+            bus,
+            peer,
+            leases: HashSet::new(),
+            forwarders: HashMap::new(),
+            endpoints: HashMap::new(),
+            tasks: JoinSet::new(),
+        }
+    }
+
+    async fn run(
+        mut self,
+        mut frames: FramedRead<OwnedReadHalf, TwoPartCodec>,
+        token: CancellationToken,
+    ) {
+        loop {
+            let frame = tokio::select! {
+                frame = frames.next() => frame,
+                _ = token.cancelled() => return,
+            };
+            let Some(frame) = frame else {
+                tracing::debug!("local backend connection closed");
+                return;
+            };
+            match decode(frame) {
+                Ok((Header::Call { id, op }, data)) => self.call(id, op, data),
+                Ok((Header::Reply { id, result }, _)) => self.peer.resolve(id, result),
+                Ok((Header::Notice(notice), data)) => self.notice(notice, data),
+                Err(err) => {
+                    tracing::warn!(%err, "invalid frame from a local backend connection");
+                    return;
+                }
+            }
+            while self.tasks.try_join_next().is_some() {}
+        }
+    }
+
+    fn call(&mut self, id: u64, op: Op, data: Bytes) {
+        match op {
+            Op::GrantLease => {
+                let lease = self.kv.grant_lease();
+                self.leases.insert(lease);
+                self.peer.reply(id, Ok(lease));
+            }
+            Op::RevokeLease { lease } => {
+                self.leases.remove(&lease);
+                self.peer.reply(id, self.kv.revoke_lease(lease));
+            }
+            Op::Put { key, value, lease } => self.peer.reply(id, self.kv.put(key, value, lease)),
+            Op::Create { key, value, lease } => {
+                self.peer.reply(id, self.kv.create(key, value, lease));
+            }
+            Op::CreateOrValidate { key, value, lease } => {
+                let created = self.kv.create_or_validate(key, value, lease);
+                self.peer.reply(id, created);
+            }
+            Op::Get { key } => self.peer.reply(id, Ok(self.kv.get(key))),
+            Op::GetPrefix { prefix } => self.peer.reply(id, Ok(self.kv.get_prefix(prefix))),
+            Op::Delete { key } => self.peer.reply(id, Ok(self.kv.delete(key))),
+            Op::Watch {
+                id: watch,
+                prefix,
+                with_kvs,
+            } => {
+                let (kvs, mut events) = match with_kvs {
+                    true => self.kv.get_and_watch_prefix(prefix),
+                    false => (vec![], self.kv.watch_prefix(prefix)),
+                };
+                // The keys are sent before the changes made after reading them
+                self.peer.reply(id, Ok(kvs));
+                let peer = self.peer.clone();
+                let forwarder = self.tasks.spawn(async move {
+                    while let Some(event) = events.recv().await {
+                        peer.notify(Notice::Watch { id: watch, event }, Bytes::new());
+                    }
+                });
+                self.forwarders.insert(watch, forwarder);
+            }
+            Op::Request { subject } => {
+                let (bus, peer) = (self.bus.clone(), self.peer.clone());
+                self.tasks.spawn(async move {
+                    peer.reply(id, bus.request(&subject, data).await);
+                });
+            }
+            Op::ServiceInfos { name } => {
+                let (bus, peer) = (self.bus.clone(), self.peer.clone());
+                self.tasks.spawn(async move {
+                    peer.reply(id, Ok(bus.service_infos(&name).await));
+                });
+            }
+            op => self
+                .peer
+                .reply::<()>(id, Err(error!("unexpected call {op:?}"))),
+        }
+    }
+
+    fn notice(&mut self, notice: Notice, data: Bytes) {
+        match notice {
+            Notice::Publish { subject } => self.bus.publish(subject, data),
+            Notice::Subscribe { id, subject } => {
+                let mut events = self.bus.subscribe(subject);
+                let peer = self.peer.clone();
+                let forwarder = self.tasks.spawn(async move {
+                    while let Some(event) = events.next().await {
+                        let notice = Notice::Event {
+                            subscription: id,
+                            subject: event.subject.to_string(),
+                        };
+                        peer.notify(notice, event.payload);
+                    }
+                });
+                self.forwarders.insert(id, forwarder);
+            }
+            Notice::Unsubscribe { id } | Notice::Unwatch { id } => {
+                if let Some(forwarder) = self.forwarders.remove(&id) {
+                    forwarder.abort();
+                }
+            }
+            Notice::AddService { id, name } => self.bus.add_remote_service(RemoteService {
+                name,
+                id,
+                peer: Arc::downgrade(&self.peer),
+            }),
+            Notice::AddEndpoint { id, subject } => {
+                let (bus_id, mut requests) = self.bus.add_endpoint(subject.clone());
+                self.endpoints.insert(id, (subject, bus_id));
+                let peer = self.peer.clone();
+                self.tasks.spawn(async move {
+                    while let Some(request) = requests.recv().await {
+                        let peer = peer.clone();
+                        // The request is acknowledged once the endpoint received it
+                        tokio::spawn(async move {
+                            let op = Op::EndpointRequest { endpoint: id };
+                            if peer.call::<()>(op, request.payload).await.is_ok() {
+                                let _ = request.ack.send(());
+                            }
+                        });
+                    }
+                });
+            }
+            Notice::RemoveEndpoint { id } => {
+                if let Some((subject, bus_id)) = self.endpoints.remove(&id) {
+                    self.bus.remove_endpoint(&subject, bus_id);
+                }
+            }
+            notice @ (Notice::Event { .. } | Notice::Watch { .. }) => {
+                tracing::warn!(?notice, "unexpected notice from a local backend connection");
+            }
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // Like etcd and NATS when a worker dies, its keys and endpoints go away with its
+        // connection; the forwarders are aborted with the tasks
+        self.peer.close();
+        for (subject, bus_id) in self.endpoints.values() {
+            self.bus.remove_endpoint(subject, *bus_id);
+        }
+        for lease in self.leases.drain() {
+            let _ = self.kv.revoke_lease(lease);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transports::local::LocalBackend;
+
+    #[tokio::test]
+    async fn test_connected_backend() {
+        let path = std::env::temp_dir().join(format!("dynamo-{}.sock", uuid::Uuid::new_v4()));
+        let backend = LocalBackend::new();
+        let server = backend.serve(&path).unwrap();
+        assert!(LocalBackend::connect(&path)
+            .await
+            .unwrap()
+            .serve(path.with_extension("other"))
+            .is_err());
+
+        let (kvs, mut events) = backend
+            .kv()
+            .get_and_watch_prefix("instances/")
+            .await
+            .unwrap();
+        assert!(kvs.is_empty());
+        let mut load = backend.bus().subscribe("ns.*.load");
+
+        let connected = LocalBackend::connect(&path).await.unwrap();
+        let lease = connected.kv().grant_lease().await.unwrap();
+        assert!(connected
+            .kv()
+            .create("instances/a", "1", lease)
+            .await
+            .unwrap());
+        assert!(!connected
+            .kv()
+            .create("instances/a", "2", lease)
+            .await
+            .unwrap());
+        assert!(
+            connected.kv().put("x", "1", 42).await.is_err(),
+            "unknown lease"
+        );
+        let WatchEvent::Put(kv) = events.recv().await.unwrap() else {
+            panic!("expected a put");
+        };
+        assert_eq!(kv.value_str().unwrap(), "1");
+
+        connected
+            .bus()
+            .publish("ns.worker.load", Bytes::from_static(b"7"));
+        assert_eq!(load.next().await.unwrap().payload, "7");
+
+        // Dropping the connection revokes its lease
+        drop(connected);
+        let WatchEvent::Delete(kv) = events.recv().await.unwrap() else {
+            panic!("expected a delete");
+        };
+        assert_eq!(kv.key_str().unwrap(), "instances/a");
+
+        drop(server);
+        assert!(!path.exists());
+        assert!(LocalBackend::connect(&path).await.is_err());
+    }
+}
@@ -101,7 +101,9 @@ impl Worker {
         Fut: Future<Output = Result<()>> + Send + 'static,
     {
         let runtime = self.runtime.clone();
-        runtime.secondary().block_on(self.execute_internal(f))??;
+        let result = runtime.secondary().block_on(self.execute_internal(f));
+        crate::trace::shutdown();
+        result??;
         runtime.shutdown();
         Ok(())
     }
@@ -113,7 +115,9 @@ impl Worker {
     {
         let runtime = self.runtime.clone();
         let task = self.execute_internal(f);
-        task.await??;
+        let result = task.await;
+        tokio::task::spawn_blocking(crate::trace::shutdown).await?;
+        result??;
         runtime.shutdown();
         Ok(())
     }
@@ -170,6 +174,7 @@ impl Worker {
 
                 _ = tokio::time::sleep(tokio::time::Duration::from_secs(timeout)) => {
                     tracing::debug!("Application did not shutdown in time; terminating");
+                    crate::trace::shutdown();
                     std::process::exit(911);
                 }
             }?;
@@ -0,0 +1,151 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A frontend and a backend runtime talking through a shared [`LocalBackend`], without etcd or
+//! NATS servers.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use dynamo_runtime::{
+    pipeline::{
+        async_trait, network::Ingress, AsyncEngine, AsyncEngineContextProvider, Error, ManyOut,
+        PushRouter, ResponseStream, RouterMode, SingleIn,
+    },
+    protocols::annotated::Annotated,
+    stream::{self, StreamExt},
+    traits::events::{EventPublisher, EventSubscriber},
+    transports::local::LocalBackend,
+    DistributedRuntime, Result, Runtime,
+};
+
+struct Echo;
+
+#[async_trait]
+impl AsyncEngine<SingleIn<String>, ManyOut<Annotated<String>>, Error> for Echo {
+    async fn generate(&self, input: SingleIn<String>) -> Result<ManyOut<Annotated<String>>> {
+        let (data, ctx) = input.into_parts();
+        let chars = data
+            .chars()
+            .map(|c| Annotated::from_data(c.to_string()))
+            .collect::<Vec<_>>();
+        Ok(ResponseStream::new(
+            Box::pin(stream::iter(chars)),
+            ctx.context(),
+        ))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_in_process_request_and_events() -> Result<()> {
+    let runtime = Runtime::from_current()?;
+    let backend_local = LocalBackend::new();
+    let backend = DistributedRuntime::local(runtime.clone(), backend_local.clone()).await?;
+    let frontend = DistributedRuntime::local(runtime.clone(), backend_local).await?;
+    assert!(frontend.try_nats_client().is_none());
+
+    let component = backend.namespace("test")?.component("backend")?;
+    let service = component.service_builder().create().await?;
+    let endpoint = service
+        .endpoint("generate")
+        .endpoint_builder()
+        .handler(Ingress::for_engine(Arc::new(Echo))?);
+    tokio::spawn(endpoint.start());
+
+    // Discovery
+    let client = frontend
+        .namespace("test")?
+        .component("backend")?
+        .endpoint("generate")
+        .client()
+        .await?;
+    let instances =
+        tokio::time::timeout(Duration::from_secs(5), client.wait_for_instances()).await??;
+    assert_eq!(instances.len(), 1);
+
+    // Request plane
+    let router =
+        PushRouter::<String, Annotated<String>>::from_client(client, Default::default()).await?;
+    let responses: Vec<String> = router
+        .random("hello".to_string().into())
+        .await?
+        .filter_map(|response| async move { response.data })
+        .collect()
+        .await;
+    assert_eq!(responses.concat(), "hello");
+
+    // Event plane
+    let mut events = frontend
+        .namespace("test")?
+        .component("backend")?
+        .subscribe("load")
+        .await?;
+    component.publish("load", &42).await?;
+    let event = tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await?
+        .expect("the subscription is open");
+    assert_eq!(event.payload, "42");
+
+    runtime.shutdown();
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_routers_share_inflight_requests() -> Result<()> {
+    let runtime = Runtime::from_current()?;
+    let backend_local = LocalBackend::new();
+    let backend = DistributedRuntime::local(runtime.clone(), backend_local.clone()).await?;
+    let frontend = DistributedRuntime::local(runtime.clone(), backend_local).await?;
+
+    let service = backend
+        .namespace("test")?
+        .component("backend")?
+        .service_builder()
+        .create()
+        .await?;
+    let endpoint = service
+        .endpoint("generate")
+        .endpoint_builder()
+        .handler(Ingress::for_engine(Arc::new(Echo))?);
+    tokio::spawn(endpoint.start());
+
+    let client = frontend
+        .namespace("test")?
+        .component("backend")?
+        .endpoint("generate")
+        .client()
+        .await?;
+    let instances =
+        tokio::time::timeout(Duration::from_secs(5), client.wait_for_instances()).await??;
+    let instance_id = instances[0].id();
+
+    // Two routers over the same client, like those of the chat and completions engines of a
+    // model, see the requests of each other
+    let chat = PushRouter::<String, Annotated<String>>::from_client(
+        client.clone(),
+        RouterMode::LeastLoaded,
+    )
+    .await?;
+    let completions =
+        PushRouter::<String, Annotated<String>>::from_client(client, RouterMode::LeastLoaded)
+            .await?;
+    let stream = chat.least_loaded("hello".to_string().into()).await?;
+    assert_eq!(completions.inflight_requests(instance_id), 1);
+    drop(stream);
+    assert_eq!(completions.inflight_requests(instance_id), 0);
+
+    runtime.shutdown();
+    Ok(())
+}
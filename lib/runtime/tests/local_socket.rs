@@ -0,0 +1,143 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A frontend runtime and a backend runtime in another process, sharing the [`LocalBackend`]
+//! the frontend serves on a unix socket, without etcd or NATS servers.
+
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use dynamo_runtime::{
+    pipeline::{
+        async_trait, network::Ingress, AsyncEngine, AsyncEngineContextProvider, Error, ManyOut,
+        PushRouter, ResponseStream, SingleIn,
+    },
+    protocols::annotated::Annotated,
+    stream::{self, StreamExt},
+    traits::events::{EventPublisher, EventSubscriber},
+    transports::local::LocalBackend,
+    DistributedRuntime, Result, Runtime,
+};
+
+/// The socket the backend process connects to
+const SOCKET_ENV: &str = "DYN_TEST_LOCAL_BACKEND_SOCKET";
+
+struct Echo;
+
+#[async_trait]
+impl AsyncEngine<SingleIn<String>, ManyOut<Annotated<String>>, Error> for Echo {
+    async fn generate(&self, input: SingleIn<String>) -> Result<ManyOut<Annotated<String>>> {
+        let (data, ctx) = input.into_parts();
+        let chars = data
+            .chars()
+            .map(|c| Annotated::from_data(c.to_string()))
+            .collect::<Vec<_>>();
+        Ok(ResponseStream::new(
+            Box::pin(stream::iter(chars)),
+            ctx.context(),
+        ))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_backend_in_another_process() -> Result<()> {
+    let runtime = Runtime::from_current()?;
+    let path = std::env::temp_dir().join(format!("dynamo-test-{}.sock", uuid::Uuid::new_v4()));
+    let backend = LocalBackend::new();
+    let _server = backend.serve(&path)?;
+    let frontend = DistributedRuntime::local(runtime.clone(), backend).await?;
+    let component = frontend.namespace("test")?.component("backend")?;
+    let mut events = component.subscribe("load").await?;
+    let client = component.endpoint("generate").client().await?;
+
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(["--exact", "backend_process", "--ignored", "--nocapture"])
+        .env(SOCKET_ENV, &path)
+        .spawn()?;
+
+    // Discovery
+    let instances =
+        tokio::time::timeout(Duration::from_secs(30), client.wait_for_instances()).await??;
+    assert_eq!(instances.len(), 1);
+
+    // Request plane
+    let router =
+        PushRouter::<String, Annotated<String>>::from_client(client.clone(), Default::default())
+            .await?;
+    let responses: Vec<String> = router
+        .random("hello".to_string().into())
+        .await?
+        .filter_map(|response| async move { response.data })
+        .collect()
+        .await;
+    assert_eq!(responses.concat(), "hello");
+
+    // Event plane
+    let event = tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await?
+        .expect("the subscription is open");
+    assert_eq!(event.payload, "42");
+
+    // Stats, from the stats handlers of the backend process
+    let stats = component.scrape_stats(Duration::from_secs(5)).await?;
+    let endpoints: Vec<_> = stats.into_endpoints().collect();
+    assert_eq!(endpoints.len(), 1);
+    assert_eq!(endpoints[0].data.as_ref().unwrap().num_requests, 1);
+
+    // The lease of the backend process is revoked when its connection closes
+    child.kill()?;
+    child.wait()?;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !client.instances().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    runtime.shutdown();
+    Ok(())
+}
+
+/// The backend process of [`test_backend_in_another_process`], which kills it once done
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[ignore = "started by test_backend_in_another_process"]
+async fn backend_process() -> Result<()> {
+    let path = std::env::var(SOCKET_ENV)?;
+    let runtime = Runtime::from_current()?;
+    let backend = LocalBackend::connect(path).await?;
+    let drt = DistributedRuntime::local(runtime.clone(), backend).await?;
+
+    let component = drt.namespace("test")?.component("backend")?;
+    let service = component.service_builder().create().await?;
+    let endpoint = service
+        .endpoint("generate")
+        .endpoint_builder()
+        .handler(Ingress::for_engine(Arc::new(Echo))?);
+    tokio::spawn(endpoint.start());
+
+    // The subscription ends with the connection, if the test process exits without killing
+    // this one
+    let mut connection = component.subscribe("unused").await?;
+    loop {
+        component.publish("load", &42).await?;
+        tokio::select! {
+            event = connection.next() => if event.is_none() {
+                return Ok(());
+            },
+            _ = tokio::time::sleep(Duration::from_millis(50)) => {}
+        }
+    }
+}
@@ -0,0 +1,153 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Retries of the PushRouter, against in-process workers of which some close their response
+//! streams without responding.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use dynamo_runtime::{
+    component::Client,
+    pipeline::{
+        async_trait,
+        network::{
+            egress::retry::{
+                RetryConfig, RetryMetrics, RetryPolicy, RETRY_NO_INSTANCE, RETRY_STREAM,
+            },
+            Ingress,
+        },
+        AsyncEngine, AsyncEngineContextProvider, Error, ManyOut, PushRouter, ResponseStream,
+        RouterMode, SingleIn,
+    },
+    protocols::annotated::Annotated,
+    stream::{self, StreamExt},
+    transports::local::LocalBackend,
+    DistributedRuntime, Result, Runtime,
+};
+
+/// Echoes the request one character at a time, or closes the stream at once if `broken`
+struct Echo {
+    broken: bool,
+}
+
+#[async_trait]
+impl AsyncEngine<SingleIn<String>, ManyOut<Annotated<String>>, Error> for Echo {
+    async fn generate(&self, input: SingleIn<String>) -> Result<ManyOut<Annotated<String>>> {
+        let (data, ctx) = input.into_parts();
+        let chars = match self.broken {
+            true => vec![],
+            false => data
+                .chars()
+                .map(|c| Annotated::from_data(c.to_string()))
+                .collect::<Vec<_>>(),
+        };
+        Ok(ResponseStream::new(
+            Box::pin(stream::iter(chars)),
+            ctx.context(),
+        ))
+    }
+}
+
+async fn serve(
+    runtime: &Runtime,
+    backend: &LocalBackend,
+    endpoint: &str,
+    broken: bool,
+) -> Result<()> {
+    let drt = DistributedRuntime::local(runtime.clone(), backend.clone()).await?;
+    let service = drt
+        .namespace("test")?
+        .component("worker")?
+        .service_builder()
+        .create()
+        .await?;
+    let endpoint = service
+        .endpoint(endpoint)
+        .endpoint_builder()
+        .handler(Ingress::for_engine(Arc::new(Echo { broken }))?);
+    tokio::spawn(endpoint.start());
+    Ok(())
+}
+
+async fn wait_for_instance_count(client: &Client, count: usize) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while client.instances().len() < count {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("instances registered");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_retry_and_failover() -> Result<()> {
+    let runtime = Runtime::from_current()?;
+    let backend = LocalBackend::new();
+    serve(&runtime, &backend, "generate", false).await?;
+    serve(&runtime, &backend, "generate", true).await?;
+    serve(&runtime, &backend, "broken", true).await?;
+
+    let frontend = DistributedRuntime::local(runtime.clone(), backend).await?;
+    let component = frontend.namespace("test")?.component("worker")?;
+    let metrics = Arc::new(RetryMetrics::default());
+    let policy = |name: &str| {
+        Some(Arc::new(RetryPolicy::new(
+            name,
+            RetryConfig {
+                max_retries: 2,
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(10),
+            },
+            metrics.clone(),
+        )))
+    };
+
+    // One of the first two requests goes to the broken worker and fails over to the other one,
+    // which then serves all the requests
+    let client = component.endpoint("generate").client().await?;
+    wait_for_instance_count(&client, 2).await;
+    let router =
+        PushRouter::<String, Annotated<String>>::from_client(client, RouterMode::RoundRobin)
+            .await?
+            .with_retry_policy(policy("generate"));
+    for _ in 0..4 {
+        let responses: Vec<String> = router
+            .round_robin("hello".to_string().into())
+            .await?
+            .filter_map(|response| async move { response.data })
+            .collect()
+            .await;
+        assert_eq!(responses.concat(), "hello");
+    }
+    assert_eq!(metrics.get_retries("generate", RETRY_STREAM), 1);
+    assert_eq!(metrics.get_retried_requests("generate"), 1);
+    assert_eq!(metrics.get_failed_over_requests("generate"), 1);
+    assert_eq!(metrics.get_exhausted_requests("generate"), 0);
+
+    // Without a working instance the request fails once its retries are used up
+    let client = component.endpoint("broken").client().await?;
+    wait_for_instance_count(&client, 1).await;
+    let router = PushRouter::<String, Annotated<String>>::from_client(client, RouterMode::Random)
+        .await?
+        .with_retry_policy(policy("broken"));
+    assert!(router.random("hello".to_string().into()).await.is_err());
+    assert_eq!(metrics.get_retries("broken", RETRY_STREAM), 1);
+    assert_eq!(metrics.get_retries("broken", RETRY_NO_INSTANCE), 1);
+    assert_eq!(metrics.get_exhausted_requests("broken"), 1);
+
+    runtime.shutdown();
+    Ok(())
+}